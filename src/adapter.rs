@@ -0,0 +1,117 @@
+use crate::engine::{Engine, EngineBuilder, SearchInfo, SearchLimits};
+use crate::game::Game;
+use crate::variant::{Variant, VariantResult};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Size of the transposition table, in MB
+pub const DEFAULT_HASH: usize = 32;
+pub const MAX_HASH: usize = 4096;
+pub const DEFAULT_MOBILITY: i32 = 4;
+/// Approximate size of a transposition table entry, in bytes
+const ENTRY_SIZE: usize = 128;
+/// How often a finished search checks whether it may give its best move
+const RELEASE_POLL: Duration = Duration::from_millis(5);
+
+/// A search running on its own thread for one of the engine's adapters, which gives the engine back once over
+#[derive(Debug)]
+pub struct Search {
+    handle: JoinHandle<(Engine, SearchInfo)>,
+    stop: Arc<AtomicBool>,
+    ponder_hit: Arc<AtomicBool>,
+    /// Set when the search may give its best move, which infinite and pondering searches wait for
+    released: Arc<AtomicBool>,
+}
+
+impl Search {
+    /**
+    Starts searching `game` within `limits` with `engine`, on another thread.
+    `report` is called after each iteration, and `finish` once the search is over and released, with its final state;
+    an unreleased search waits for `ponder_hit` or `stop` before calling `finish`.
+    **/
+    pub fn start<R, F>(
+        mut engine: Engine,
+        game: Game,
+        limits: SearchLimits,
+        released: bool,
+        mut report: R,
+        finish: F,
+    ) -> Self
+    where
+        R: FnMut(&Game, &SearchInfo) + Send + 'static,
+        F: FnOnce(&Game, &SearchInfo) + Send + 'static,
+    {
+        let stop = engine.stop_handle();
+        let ponder_hit = engine.ponder_handle();
+        let released = Arc::new(AtomicBool::new(released));
+        let wait = released.clone();
+        let handle = thread::spawn(move || {
+            let info = engine.search_with(&game, &limits, |info: &SearchInfo| report(&game, info));
+            while !wait.load(Ordering::Relaxed) {
+                thread::sleep(RELEASE_POLL);
+            }
+            finish(&game, &info);
+            (engine, info)
+        });
+        Search {
+            handle,
+            stop,
+            ponder_hit,
+            released,
+        }
+    }
+
+    /// Lets a pondering search go on as a normal one, which spends its time from now and then gives its best move
+    pub fn ponder_hit(&self) {
+        self.ponder_hit.store(true, Ordering::Relaxed);
+        self.released.store(true, Ordering::Relaxed);
+    }
+
+    /// Asks the search to end now and give its best move
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.released.store(true, Ordering::Relaxed);
+    }
+
+    /// Waits for the search to end, and gives back the engine with the search's final state
+    pub fn join(self) -> Option<(Engine, SearchInfo)> {
+        let result = self.handle.join().ok();
+        // the flags may have been set after the search returned
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponder_hit.store(false, Ordering::Relaxed);
+        result
+    }
+}
+
+/// Builds an engine for the variant of `game`, with a transposition table of about `hash` MB
+pub fn build_engine(game: &Game, hash: usize, mobility: i32) -> Engine {
+    EngineBuilder::new()
+        .hash_size(hash * 1024 * 1024 / ENTRY_SIZE)
+        .mobility(mobility)
+        .build(game)
+}
+
+/// Loads the variant with the given name from `dir`, set up in its starting position
+pub fn load_variant(dir: &Path, name: &str) -> VariantResult<Game> {
+    Variant::load(dir.join(format!("{}.toml", name)))?.game()
+}
+
+/// Names of the variants found in `dir`, sorted
+pub fn variant_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
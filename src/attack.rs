@@ -0,0 +1,116 @@
+use crate::bitboard::{color_index, Bitboard};
+use crate::game::Game;
+use crate::player::PlayerColor;
+
+/// A piece which could capture on a square
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Attacker {
+    /// Square that the attacking piece stands on
+    pub from: (usize, usize),
+    pub piece: usize,
+    pub color: PlayerColor,
+}
+
+/// The squares controlled by each piece of a position
+#[derive(Clone, Debug)]
+pub struct AttackMap {
+    width: usize,
+    attackers: Vec<Vec<Attacker>>,
+    attacked: [Bitboard; 2],
+    quiet: [Bitboard; 2],
+}
+
+impl AttackMap {
+    /// Pieces of both colors which could capture on (x, y)
+    pub fn attackers(&self, x: usize, y: usize) -> &[Attacker] {
+        self.attackers
+            .get(y * self.width + x)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Pieces of `color` which could capture on (x, y)
+    pub fn attackers_by(
+        &self,
+        x: usize,
+        y: usize,
+        color: PlayerColor,
+    ) -> impl Iterator<Item = &Attacker> {
+        self.attackers(x, y)
+            .iter()
+            .filter(move |attacker| attacker.color == color)
+    }
+
+    /// Number of `color`'s pieces which could capture on (x, y)
+    pub fn count(&self, x: usize, y: usize, color: PlayerColor) -> usize {
+        self.attackers_by(x, y, color).count()
+    }
+
+    pub fn is_attacked(&self, x: usize, y: usize, by: PlayerColor) -> bool {
+        self.attackers_by(x, y, by).next().is_some()
+    }
+
+    /// Squares that `color`'s pieces could capture on, including those of their own pieces; empty on boards without bitboards
+    pub fn attacked_by(&self, color: PlayerColor) -> Bitboard {
+        self.attacked[color_index(color)]
+    }
+
+    /// Empty squares that `color`'s pieces may move to, but not capture on, like a pawn's push; empty on boards without bitboards
+    pub fn quiet_targets(&self, color: PlayerColor) -> Bitboard {
+        self.quiet[color_index(color)]
+    }
+}
+
+impl Game {
+    /**
+    Computes the squares attacked by every piece on the board.
+    Pieces with custom movement conditions attack the squares they may currently move to.
+    **/
+    pub fn attack_map(&self) -> AttackMap {
+        let board = self.board();
+        let width = board.width.get();
+        let occupied = board.occupied();
+        let mut map = AttackMap {
+            width,
+            attackers: vec![vec![]; width * board.height.get()],
+            attacked: [Bitboard::EMPTY; 2],
+            quiet: [Bitboard::EMPTY; 2],
+        };
+
+        for &color in &[PlayerColor::White, PlayerColor::Black] {
+            for (x, y) in board.squares_of(color) {
+                let (piece, _) = board.get(x, y).unwrap().unwrap();
+                let (attacks, quiet) = match self.table(board, piece, color) {
+                    Some(table) => (
+                        table.attacks(x, y, occupied).squares().collect(),
+                        table.quiet_targets(x, y, occupied),
+                    ),
+                    None => match self.player(color) {
+                        Some(player) => (
+                            self.piece_movements(board, player, piece, x, y)
+                                .into_iter()
+                                .map(|(dx, dy)| {
+                                    ((x as isize + dx) as usize, (y as isize + dy) as usize)
+                                })
+                                .collect::<Vec<_>>(),
+                            Bitboard::EMPTY,
+                        ),
+                        None => continue,
+                    },
+                };
+                map.quiet[color_index(color)] |= quiet;
+                for (tx, ty) in attacks {
+                    if board.has_bitboards() {
+                        map.attacked[color_index(color)].set(tx, ty);
+                    }
+                    map.attackers[ty * width + tx].push(Attacker {
+                        from: (x, y),
+                        piece,
+                        color,
+                    });
+                }
+            }
+        }
+        map
+    }
+}
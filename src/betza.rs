@@ -0,0 +1,444 @@
+use self::BetzaError::*;
+use crate::movement::{MovementCondition, MovementType};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// Leapers of Betza's notation, with the (dx, dy) of their step
+const ATOMS: [(char, (isize, isize)); 9] = [
+    ('W', (1, 0)),
+    ('F', (1, 1)),
+    ('D', (2, 0)),
+    ('N', (2, 1)),
+    ('A', (2, 2)),
+    ('H', (3, 0)),
+    ('C', (3, 1)),
+    ('Z', (3, 2)),
+    ('G', (3, 3)),
+];
+
+/// Direction modifiers, from the most to the least specific
+const DIRECTIONS: [&str; 22] = [
+    "ffr", "ffl", "bbr", "bbl", "rrf", "rrb", "llf", "llb", "ff", "bb", "rr", "ll", "fr", "fl",
+    "br", "bl", "f", "b", "l", "r", "v", "s",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BetzaError {
+    Empty,
+    UnknownAtom(char),
+    UnsupportedModifier(char),
+    /// The direction modifier selects none of the atom's steps
+    InvalidDirection(String),
+}
+
+pub type BetzaResult<T> = Result<T, BetzaError>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Mode {
+    Any,
+    Move,
+    Capture,
+}
+
+/// Steps of the same atom that share their range and conditions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Group {
+    initial: bool,
+    mode: Mode,
+    atom: usize,
+    /// Maximum number of steps, or `None` for riders
+    range: Option<usize>,
+}
+
+impl MovementType {
+    /**
+    Parses a piece's movement written in Betza's funny notation, like `fmWfcFifmW2` for the chess pawn.
+    Supports the leapers W, F, D, N, A, H, C, Z and G, the shorthands K, Q, R and B,
+    riders (`NN`) and limited ranges (`W3`, `R0` for unlimited), the `m` and `c` modifiers,
+    and directions from `f`, `b`, `l`, `r`, `v` and `s`, doubled for the narrow steps of oblique atoms.
+    Directions are given for white and mirrored for black.
+    `i`, for initial moves, is taken as standing on the second rank.
+    **/
+    pub fn from_betza(text: &str) -> BetzaResult<MovementType> {
+        let mut parts = vec![];
+        let mut chars = text.chars().filter(|c| !c.is_whitespace()).peekable();
+        while chars.peek().is_some() {
+            let mut modifiers = String::new();
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_lowercase() {
+                    break;
+                }
+                modifiers.push(c);
+                chars.next();
+            }
+            let letter = chars.next().ok_or(Empty)?;
+            let (atoms, mut range): (Vec<usize>, Option<usize>) = match letter {
+                'K' => (vec![0, 1], Some(1)),
+                'Q' => (vec![0, 1], None),
+                'R' => (vec![0], None),
+                'B' => (vec![1], None),
+                _ => {
+                    let atom = atom_index(letter).ok_or(UnknownAtom(letter))?;
+                    if chars.peek() == Some(&letter) {
+                        chars.next();
+                        (vec![atom], None)
+                    } else {
+                        (vec![atom], Some(1))
+                    }
+                }
+            };
+            let mut digits = String::new();
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_digit() {
+                    break;
+                }
+                digits.push(c);
+                chars.next();
+            }
+            if let Ok(n) = digits.parse::<usize>() {
+                range = if n == 0 { None } else { Some(n) };
+            }
+
+            let (mode, initial, directions) = parse_modifiers(&modifiers)?;
+            for atom in atoms {
+                let steps = match &directions {
+                    Some(directions) => {
+                        let mut steps = vec![];
+                        for direction in directions {
+                            let selected = select(atom, direction);
+                            if selected.is_empty() {
+                                return Err(InvalidDirection(direction.clone()));
+                            }
+                            steps.extend(selected);
+                        }
+                        steps.sort_unstable();
+                        steps.dedup();
+                        steps
+                    }
+                    None => steps(atom),
+                };
+                parts.push(movement(atom, &steps, range, mode, initial));
+            }
+        }
+        match parts.len() {
+            0 => Err(Empty),
+            1 => Ok(parts.pop().unwrap()),
+            _ => Ok(MovementType::Union(parts)),
+        }
+    }
+
+    /**
+    Writes the movement in Betza's notation, as seen by white.
+    Steps that the notation can't describe, and custom conditions, are left out.
+    **/
+    pub fn betza(&self) -> String {
+        let mut groups: BTreeMap<Group, Vec<(isize, isize)>> = BTreeMap::new();
+        collect(self, Mode::Any, false, None, &mut groups);
+
+        let mut res = String::new();
+        let mut written = vec![];
+        for (group, steps) in groups.iter() {
+            if written.contains(group) {
+                continue;
+            }
+            // a wazir and a ferz moving alike make a king or a queen
+            if group.atom == 0 && steps.len() == 4 {
+                let ferz = Group { atom: 1, ..*group };
+                if groups.get(&ferz).is_some_and(|steps| steps.len() == 4) {
+                    res.push_str(&prefix(group));
+                    res.push(if group.range == Some(1) { 'K' } else { 'Q' });
+                    if let Some(range) = group.range.filter(|&range| range > 1) {
+                        res.push_str(&range.to_string());
+                    }
+                    written.push(ferz);
+                    continue;
+                }
+            }
+            res.push_str(&prefix(group));
+            if steps.len() < self::steps(group.atom).len() {
+                res.push_str(&directions(group.atom, steps));
+            }
+            let letter = ATOMS[group.atom].0;
+            match (group.range, group.atom) {
+                (Some(1), _) => res.push(letter),
+                (None, 0) => res.push('R'),
+                (None, 1) => res.push('B'),
+                (None, _) => {
+                    res.push(letter);
+                    res.push(letter);
+                }
+                (Some(range), _) => {
+                    res.push(letter);
+                    res.push_str(&range.to_string());
+                }
+            }
+        }
+        res
+    }
+}
+
+fn atom_index(letter: char) -> Option<usize> {
+    ATOMS.iter().position(|&(c, _)| c == letter)
+}
+
+/// Every step of an atom
+fn steps(atom: usize) -> Vec<(isize, isize)> {
+    let (a, b) = ATOMS[atom].1;
+    let mut res = vec![];
+    for &(dx, dy) in &[(a, b), (b, a)] {
+        for &(sx, sy) in &[(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+            res.push((dx * sx, dy * sy));
+        }
+    }
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+/// Steps of an atom that go in the given direction
+fn select(atom: usize, direction: &str) -> Vec<(isize, isize)> {
+    steps(atom)
+        .into_iter()
+        .filter(|&(dx, dy)| {
+            let vertical = dy.abs() > dx.abs();
+            let sideways = dx.abs() > dy.abs();
+            let mut chars = direction.chars();
+            let first = chars.next();
+            let doubled = direction.len() > 1 && direction[1..].starts_with(first.unwrap());
+            if doubled {
+                chars.next();
+            }
+            let along = |c: char| match c {
+                'f' => dy > 0,
+                'b' => dy < 0,
+                'l' => dx < 0,
+                'r' => dx > 0,
+                'v' => vertical,
+                's' => sideways,
+                _ => false,
+            };
+            let narrow = match first {
+                Some('f') | Some('b') => vertical || !doubled,
+                Some('l') | Some('r') => sideways || !doubled,
+                _ => true,
+            };
+            first.is_some_and(along) && narrow && chars.all(along)
+        })
+        .collect()
+}
+
+/// Direction modifiers selecting exactly `steps` out of an atom's steps
+fn directions(atom: usize, steps: &[(isize, isize)]) -> String {
+    let mut candidates: Vec<(&str, Vec<(isize, isize)>)> = DIRECTIONS
+        .iter()
+        .map(|&direction| (direction, select(atom, direction)))
+        .filter(|(_, selected)| !selected.is_empty())
+        .collect();
+    // the widest and then the shortest directions first
+    candidates.sort_by_key(|(direction, selected)| (Reverse(selected.len()), direction.len()));
+
+    let mut res = String::new();
+    let mut covered: Vec<(isize, isize)> = vec![];
+    for (direction, selected) in candidates {
+        if selected.iter().all(|step| steps.contains(step))
+            && selected.iter().any(|step| !covered.contains(step))
+        {
+            res.push_str(direction);
+            covered.extend(selected);
+        }
+    }
+    res
+}
+
+fn prefix(group: &Group) -> String {
+    let mut res = String::new();
+    if group.initial {
+        res.push('i');
+    }
+    match group.mode {
+        Mode::Any => {}
+        Mode::Move => res.push('m'),
+        Mode::Capture => res.push('c'),
+    }
+    res
+}
+
+/// Splits modifiers into a mode, whether the move is initial, and the directions if any
+fn parse_modifiers(modifiers: &str) -> BetzaResult<(Mode, bool, Option<Vec<String>>)> {
+    let mut mode = Mode::Any;
+    let mut initial = false;
+    let mut directions = vec![];
+    let mut rest = modifiers;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            'm' => {
+                mode = if mode == Mode::Capture {
+                    Mode::Any
+                } else {
+                    Mode::Move
+                }
+            }
+            'c' => {
+                mode = if mode == Mode::Move {
+                    Mode::Any
+                } else {
+                    Mode::Capture
+                }
+            }
+            'i' => initial = true,
+            _ => {
+                let direction = DIRECTIONS
+                    .iter()
+                    .find(|direction| rest.starts_with(*direction))
+                    .ok_or(UnsupportedModifier(c))?;
+                directions.push(direction.to_string());
+                rest = &rest[direction.len()..];
+                continue;
+            }
+        }
+        rest = &rest[1..];
+    }
+    let directions = if directions.is_empty() {
+        None
+    } else {
+        Some(directions)
+    };
+    Ok((mode, initial, directions))
+}
+
+/// Builds the movement of white along `steps`, and of black along their mirror image
+fn movement(
+    atom: usize,
+    steps: &[(isize, isize)],
+    range: Option<usize>,
+    mode: Mode,
+    initial: bool,
+) -> MovementType {
+    let leaps = |steps: &[(isize, isize)]| {
+        let leap = if steps.len() == self::steps(atom).len() {
+            let (a, b) = ATOMS[atom].1;
+            MovementType::Undirected(a as usize, b as usize)
+        } else if steps.len() == 1 {
+            MovementType::Directed(steps[0].0, steps[0].1)
+        } else {
+            MovementType::Union(
+                steps
+                    .iter()
+                    .map(|&(dx, dy)| MovementType::Directed(dx, dy))
+                    .collect(),
+            )
+        };
+        match range {
+            Some(1) => leap,
+            Some(range) => MovementType::Range(Box::new(leap), range),
+            None => MovementType::RangeAny(Box::new(leap)),
+        }
+    };
+    let mut conditions = vec![];
+    match mode {
+        Mode::Any => {}
+        Mode::Move => conditions.push(MovementCondition::NoCapture),
+        Mode::Capture => conditions.push(MovementCondition::Capture),
+    }
+    if initial {
+        conditions.push(MovementCondition::Rank(1));
+    }
+
+    let mut mirrored: Vec<(isize, isize)> = steps.iter().map(|&(dx, dy)| (dx, -dy)).collect();
+    mirrored.sort_unstable();
+    if mirrored == steps {
+        return with_conditions(leaps(steps), conditions);
+    }
+    let as_color = |condition: MovementCondition| {
+        let mut conditions = conditions.clone();
+        conditions.insert(0, condition);
+        conditions
+    };
+    MovementType::Union(vec![
+        with_conditions(leaps(steps), as_color(MovementCondition::AsWhite)),
+        with_conditions(leaps(&mirrored), as_color(MovementCondition::AsBlack)),
+    ])
+}
+
+fn with_conditions(movement: MovementType, conditions: Vec<MovementCondition>) -> MovementType {
+    if conditions.is_empty() {
+        movement
+    } else {
+        MovementType::Condition(Box::new(movement), conditions)
+    }
+}
+
+/// Gathers the steps of white's movement by atom, range and conditions
+fn collect(
+    movement: &MovementType,
+    mode: Mode,
+    initial: bool,
+    range: Option<Option<usize>>,
+    groups: &mut BTreeMap<Group, Vec<(isize, isize)>>,
+) {
+    let mut add = |dx: isize, dy: isize| {
+        let canonical = (dx.abs().max(dy.abs()), dx.abs().min(dy.abs()));
+        if let Some(atom) = ATOMS.iter().position(|&(_, step)| step == canonical) {
+            let group = Group {
+                initial,
+                mode,
+                atom,
+                range: range.unwrap_or(Some(1)),
+            };
+            let steps = groups.entry(group).or_default();
+            if !steps.contains(&(dx, dy)) {
+                steps.push((dx, dy));
+                steps.sort_unstable();
+            }
+        }
+    };
+    match movement {
+        MovementType::Stay => {}
+        MovementType::Undirected(a, b) => {
+            for &(dx, dy) in &[(*a, *b), (*b, *a)] {
+                for &(sx, sy) in &[(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+                    add(dx as isize * sx, dy as isize * sy);
+                }
+            }
+        }
+        MovementType::Directed(dx, dy) => add(*dx, *dy),
+        MovementType::Range(inner, max) => collect(inner, mode, initial, Some(Some(*max)), groups),
+        MovementType::RangeAny(inner) => collect(inner, mode, initial, Some(None), groups),
+        MovementType::Union(movements) => {
+            for movement in movements {
+                collect(movement, mode, initial, range, groups);
+            }
+        }
+        MovementType::Condition(inner, conditions) => {
+            let mut mode = mode;
+            let mut initial = initial;
+            for condition in conditions {
+                match condition {
+                    MovementCondition::AsBlack | MovementCondition::Custom(_) => return,
+                    MovementCondition::AsWhite => {}
+                    MovementCondition::Capture if mode == Mode::Move => return,
+                    MovementCondition::Capture => mode = Mode::Capture,
+                    MovementCondition::NoCapture if mode == Mode::Capture => return,
+                    MovementCondition::NoCapture => mode = Mode::Move,
+                    MovementCondition::Rank(_) => initial = true,
+                }
+            }
+            collect(inner, mode, initial, range, groups);
+        }
+    }
+}
+
+impl fmt::Display for BetzaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Empty => write!(f, "no movement given"),
+            UnknownAtom(c) => write!(f, "unknown atom {}", c),
+            UnsupportedModifier(c) => write!(f, "unsupported modifier {}", c),
+            InvalidDirection(direction) => write!(f, "no step goes in direction {}", direction),
+        }
+    }
+}
+
+impl Error for BetzaError {}
@@ -1,22 +1,30 @@
 // Rendering main logic
 
+use super::online::{Online, OnlineMessage};
 use super::style::SharmatStyleSheet;
 use super::settings::*;
+use super::timer::Every;
 
-use sharmat::{game::*, player::PlayerColor};
+use sharmat::driver::{DriverEvent, DriverInfo, EngineDriver, Score};
+use sharmat::engine::SearchLimits;
+use sharmat::movement::Move;
+use sharmat::{bitboard::Bitboard, game::*, player::PlayerColor};
 
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::rc::Rc;
+use std::time::Duration;
 
 use iced::{
-    executor, Application, Background, Color, Command, Container, Element, Length, Point, Row, Size,
+    executor, Application, Background, Color, Column, Command, Container, Element, Font,
+    HorizontalAlignment, Length, Point, Row, Size, Subscription, Text, VerticalAlignment,
 };
 use iced_native::{
+    input::{mouse, ButtonState},
     layout,
     widget::{svg::Handle, Widget},
-    MouseCursor, Rectangle,
+    Clipboard, Event, MouseCursor, Rectangle,
 };
 use iced_wgpu::{Defaults, Primitive, Renderer};
 
@@ -27,11 +35,42 @@ pub struct Sharmat {
     pub stylesheet: SharmatStyleSheet,
     pub settings: SharmatSettings,
     pub piece_assets: Rc<HashMap<String, Handle>>,
+    /// External engine, whose output is polled while it searches
+    pub engine: Option<EngineDriver>,
+    pub engine_mode: EngineMode,
+    pub engine_name: Option<String>,
+    /// Whether the running search was stopped for a new one, which starts once the old one's move is in
+    restart_engine: bool,
+    /// The engine's last report, ready to be displayed
+    pub analysis: Option<String>,
+    pub online: Online,
+    /// The square of the piece about to be moved
+    pub selected: Option<(usize, usize)>,
 }
 
+/// How the external engine is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineMode {
+    /// The engine plays the given side
+    Opponent(PlayerColor),
+    /// The engine analyses the position without playing
+    Analysis,
+}
+
+/// Time the engine spends on each of its moves
+const ENGINE_MOVETIME: Duration = Duration::from_secs(2);
+/// How often the engine's output is read while it searches
+const ENGINE_POLL: Duration = Duration::from_millis(50);
+
 /// Message enum for user interaction
-#[derive(Debug)]
-pub enum SharmatMessage {}
+#[derive(Debug, Clone)]
+pub enum SharmatMessage {
+    /// Time to read what the external engine said
+    PollEngine,
+    Online(OnlineMessage),
+    /// A square of the board was clicked
+    Square(usize, usize),
+}
 type Message = SharmatMessage;
 
 /// Graphical board (visible representation of the board)
@@ -43,28 +82,49 @@ pub struct GBoard {
     pub fill_light: Color,
     pub fill_dark_hl: Color,
     pub fill_light_hl: Color,
+    /// Tint of the squares of the pieces attacked by the opponent
+    pub fill_threat: Color,
     pub highlight_border_ratio: f32,
     pub settings: SharmatSettings,
     pub piece_assets: Rc<HashMap<String, Handle>>,
     pub flip_board: bool,
+    pub selected: Option<(usize, usize)>,
 }
 
 impl Application for Sharmat {
-    type Executor = executor::Null;
+    type Executor = executor::Default;
     type Message = Message;
-    /// SVG handles, Game structure, whether or not to show hints, whether or not to also show hints for the opponent's pieces
-    type Flags = (HashMap<String, Handle>, Game, HashMap<String, SharmatSettingType>);
+    /// SVG handles, Game structure, settings, and the external engine with how to use it
+    type Flags = (
+        HashMap<String, Handle>,
+        Game,
+        HashMap<String, SharmatSettingType>,
+        Option<(EngineDriver, EngineMode)>,
+    );
 
     fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        (
-            Self {
-                game: Rc::new(RefCell::new(flags.1)),
-                stylesheet: SharmatStyleSheet::default(),
-                piece_assets: Rc::new(flags.0),
-                settings: SharmatSettings::new(flags.2),
-            },
-            Command::none(),
-        )
+        let (engine, engine_mode, engine_name) = match flags.3 {
+            Some((driver, mode)) => {
+                let name = driver.name().unwrap_or("Engine").to_string();
+                (Some(driver), mode, Some(name))
+            }
+            None => (None, EngineMode::Analysis, None),
+        };
+        let mut sharmat = Self {
+            game: Rc::new(RefCell::new(flags.1)),
+            stylesheet: SharmatStyleSheet::default(),
+            piece_assets: Rc::new(flags.0),
+            settings: SharmatSettings::new(flags.2),
+            engine,
+            engine_mode,
+            engine_name,
+            restart_engine: false,
+            analysis: None,
+            online: Online::new(),
+            selected: None,
+        };
+        sharmat.start_engine();
+        (sharmat, Command::none())
     }
 
     fn title(&self) -> String {
@@ -72,21 +132,33 @@ impl Application for Sharmat {
     }
 
     fn view(&mut self) -> Element<Self::Message> {
+        let mut side = Column::new().padding(10).spacing(10);
+        if let Some(name) = &self.engine_name {
+            side = side.push(Text::new(name.clone()).size(24));
+        }
+        if let Some(analysis) = &self.analysis {
+            side = side.push(Text::new(analysis.clone()).size(18));
+        }
+        side = side.push(self.online.view(&self.game));
+
         Container::new(
-            Row::new().push(
-                Container::new::<iced_native::Element<_, _>>(
-                    GBoard::new(
-                        self.game.clone(),
-                        self.piece_assets.clone(),
-                        self.settings.clone(),
-                        true,
+            Row::new()
+                .push(
+                    Container::new::<iced_native::Element<_, _>>(
+                        GBoard::new(
+                            self.game.clone(),
+                            self.piece_assets.clone(),
+                            self.settings.clone(),
+                            true,
+                            self.selected,
+                        )
+                        .into(),
                     )
-                    .into(),
+                    .width(Length::Units(600))
+                    .height(Length::Units(600))
+                    .padding(10),
                 )
-                .width(Length::Units(600))
-                .height(Length::Units(600))
-                .padding(10),
-            ),
+                .push(side.width(Length::Units(300))),
         )
         .padding(10)
         .width(Length::Fill)
@@ -97,8 +169,187 @@ impl Application for Sharmat {
         .into()
     }
 
-    fn update(&mut self, _message: Self::Message) -> Command<Self::Message> {
-        Command::none()
+    fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
+        match message {
+            SharmatMessage::PollEngine => {
+                self.poll_engine();
+                Command::none()
+            }
+            SharmatMessage::Online(message) => {
+                let position = self.game.borrow().position_key();
+                let command = self.online.update(message, &self.game);
+                if self.game.borrow().position_key() == position {
+                    return command;
+                }
+                // a move or a new game came from the server
+                self.selected = None;
+                self.start_engine();
+                command
+            }
+            SharmatMessage::Square(x, y) => self.click((x, y)),
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        let mut subscriptions = vec![self.online.subscription()];
+        if let Some(driver) = &self.engine {
+            if driver.is_searching() {
+                subscriptions
+                    .push(Subscription::from_recipe(Every(ENGINE_POLL)).map(|_| Message::PollEngine));
+            }
+        }
+        Subscription::batch(subscriptions)
+    }
+}
+
+impl Sharmat {
+    /**
+    Selects a piece of the player to move, or moves the selected piece to the clicked square.
+    Online, the move is sent to the server, and played once it comes back.
+    **/
+    fn click(&mut self, square: (usize, usize)) -> Command<Message> {
+        let online = self.online.game.is_some();
+        let to_move = self
+            .game
+            .borrow()
+            .current_player()
+            .map(|player| player.color);
+        let engine_to_move = match self.engine_mode {
+            EngineMode::Opponent(color) => self.engine.is_some() && to_move == Some(color),
+            EngineMode::Analysis => false,
+        };
+        if (online && !self.online.my_turn(&self.game.borrow())) || (!online && engine_to_move) {
+            self.selected = None;
+            return Command::none();
+        }
+
+        let mv = self.selected.take().and_then(|from| {
+            let moves: Vec<Move> = self
+                .game
+                .borrow()
+                .legal_moves()
+                .into_iter()
+                .filter(|mv| mv.from == Some(from) && mv.to == square)
+                .collect();
+            // promoting is nearly always the better choice
+            moves
+                .iter()
+                .find(|mv| mv.promotion.is_some())
+                .or_else(|| moves.first())
+                .cloned()
+        });
+        match mv {
+            Some(mv) if online => {
+                let text = self.game.borrow().uci_move(&mv);
+                self.online.send_move(text);
+                Command::none()
+            }
+            Some(mv) => {
+                self.game.borrow_mut().play(&mv);
+                self.start_engine();
+                Command::none()
+            }
+            None => {
+                let piece = self.game.borrow().board().get(square.0, square.1);
+                if let Ok(Some((_, color))) = piece {
+                    if Some(color) == to_move {
+                        self.selected = Some(square);
+                    }
+                }
+                Command::none()
+            }
+        }
+    }
+
+    /**
+    Starts the engine's search if it has something to do: its turn to play, or a position to analyse.
+    A running search is stopped first, and the new one only starts once the engine gave its move.
+    **/
+    fn start_engine(&mut self) {
+        let driver = match &mut self.engine {
+            Some(driver) => driver,
+            None => return,
+        };
+        if driver.is_searching() {
+            self.restart_engine = true;
+            if let Err(e) = driver.stop() {
+                self.engine_stopped(e.to_string());
+            }
+            return;
+        }
+        let game = self.game.borrow().clone();
+        let limits = match self.engine_mode {
+            EngineMode::Opponent(color) => {
+                let to_move = game.current_player().map(|player| player.color);
+                // online, the opponent is someone else
+                if to_move != Some(color)
+                    || game.legal_moves().is_empty()
+                    || self.online.game.is_some()
+                {
+                    return;
+                }
+                SearchLimits {
+                    movetime: Some(ENGINE_MOVETIME),
+                    ..SearchLimits::default()
+                }
+            }
+            // a search without limits runs until stopped
+            EngineMode::Analysis => SearchLimits::default(),
+        };
+        if let Err(e) = driver.go(&game, &limits) {
+            self.engine_stopped(e.to_string());
+        }
+    }
+
+    /// Reads what the engine said since the last poll, without waiting
+    fn poll_engine(&mut self) {
+        loop {
+            let event = match self.engine.as_mut().map(|driver| driver.poll()) {
+                Some(Ok(Some(event))) => event,
+                Some(Ok(None)) | None => return,
+                Some(Err(e)) => return self.engine_stopped(e.to_string()),
+            };
+            match event {
+                DriverEvent::Info(info) => self.analysis = Some(self.format_analysis(&info)),
+                // the move of a search stopped for a new one is from another position
+                DriverEvent::BestMove(_) if self.restart_engine => {
+                    self.restart_engine = false;
+                    self.start_engine();
+                }
+                DriverEvent::BestMove(mv) => {
+                    if let (EngineMode::Opponent(_), Some(mv)) = (self.engine_mode, mv) {
+                        self.game.borrow_mut().play(&mv);
+                        self.start_engine();
+                    }
+                }
+            }
+        }
+    }
+
+    fn engine_stopped(&mut self, e: String) {
+        eprintln!("Engine stopped: {}", e);
+        self.engine = None;
+        self.restart_engine = false;
+    }
+
+    /// Writes the depth, score and principal variation of an engine's report
+    fn format_analysis(&self, info: &DriverInfo) -> String {
+        let mut res = String::new();
+        if let Some(depth) = info.depth {
+            res.push_str(&format!("depth {}  ", depth));
+        }
+        match info.score {
+            Some(Score::Centipawns(cp)) => res.push_str(&format!("{:+.2}", cp as f32 / 100.0)),
+            Some(Score::Mate(moves)) => res.push_str(&format!("mate {}", moves)),
+            None => {}
+        }
+        let mut game = self.game.borrow().clone();
+        for (i, mv) in info.pv.iter().enumerate() {
+            res.push_str(if i == 0 { "\n" } else { " " });
+            res.push_str(&game.move_name(mv));
+            game.play(mv);
+        }
+        res
     }
 }
 
@@ -108,6 +359,7 @@ impl GBoard {
         piece_assets: Rc<HashMap<String, Handle>>,
         settings: SharmatSettings,
         flip_board: bool,
+        selected: Option<(usize, usize)>,
     ) -> GBoard {
         GBoard {
             game,
@@ -115,10 +367,12 @@ impl GBoard {
             fill_light: Color::from_rgb8(255, 221, 210),
             fill_dark_hl: Color::from_rgb8(113, 129, 120),
             fill_light_hl: Color::from_rgb8(128, 165, 165),
+            fill_threat: Color::from_rgba8(200, 30, 30, 0.35),
             piece_assets,
             settings: settings.clone(),
             highlight_border_ratio: 0.15,
             flip_board,
+            selected,
         }
     }
 
@@ -165,26 +419,29 @@ impl GBoard {
                 || self.render_hints_opponent())
             && self.render_hints()
         {
-            let raw = &hovered_piece_raw.unwrap();
-            let game = self.game.borrow();
-            let hovered_piece = game
-                .pieces()
-                .get(raw.0)
-                .expect(&format!("Couldn't find piece {}", raw.0));
-            let hovered_player = game
-                .player(raw.1)
-                .expect(&format!("Couldn't find player {:?}", raw.1));
-            hovered_piece.movement_type()[0]
-                .flatten(self.game.borrow().board(), hovered_player, m_x, m_y)
-                .unwrap()
+            self.game
+                .borrow()
+                .piece_moves(m_x, m_y)
                 .into_iter()
-                .map(|(dx, dy)| ((m_x as isize + dx) as usize, (m_y as isize + dy) as usize))
+                .map(|mv| mv.to)
                 .collect()
         } else {
             vec![]
         }
     }
 
+    /// Squares of the current player's pieces which the opponent attacks
+    fn get_threats(&self) -> Bitboard {
+        let game = self.game.borrow();
+        match game.current_player() {
+            Some(player) if self.render_threats() => {
+                game.attack_map().attacked_by(player.color.opponent())
+                    & game.board().occupied_by(player.color)
+            }
+            _ => Bitboard::EMPTY,
+        }
+    }
+
     fn get_mouse_pos(&self, bounds: Rectangle, mouse: Point, tile_size: f32) -> (usize, usize) {
         if bounds.contains(mouse) {
             (
@@ -200,12 +457,20 @@ impl GBoard {
         self.settings.get_bool("render_hints").unwrap_or(true)
     }
 
+    pub fn render_coordinates(&self) -> bool {
+        self.settings.get_bool("render_coordinates").unwrap_or(true)
+    }
+
+    pub fn render_threats(&self) -> bool {
+        self.settings.get_bool("render_threats").unwrap_or(false)
+    }
+
     pub fn render_hints_opponent(&self) -> bool {
         self.settings.get_bool("render_hints_opponent").unwrap_or(false)
     }
 }
 
-impl<'a, Message> Widget<Message, Renderer> for GBoard {
+impl Widget<Message, Renderer> for GBoard {
     fn width(&self) -> Length {
         Length::Fill
     }
@@ -226,6 +491,29 @@ impl<'a, Message> Widget<Message, Renderer> for GBoard {
         self.game.borrow().board().hash(hasher);
     }
 
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: layout::Layout<'_>,
+        cursor_position: Point,
+        messages: &mut Vec<Message>,
+        _renderer: &Renderer,
+        _clipboard: Option<&dyn Clipboard>,
+    ) {
+        if let Event::Mouse(mouse::Event::Input {
+            button: mouse::Button::Left,
+            state: ButtonState::Pressed,
+        }) = event
+        {
+            let bounds = layout.bounds();
+            let tile_size = self.tile_size(bounds.width, bounds.height);
+            let (x, y) = self.get_mouse_pos(bounds, cursor_position, tile_size);
+            if x < self.get_board_width() && y < self.get_board_height() {
+                messages.push(SharmatMessage::Square(x, y));
+            }
+        }
+    }
+
     fn draw(
         &self,
         _renderer: &mut Renderer,
@@ -239,7 +527,16 @@ impl<'a, Message> Widget<Message, Renderer> for GBoard {
 
         let (m_x, m_y) = self.get_mouse_pos(layout.bounds(), mouse, tile_size);
 
-        let hints = self.get_hints(m_x, m_y);
+        // the moves of the selected piece, or else of the hovered one
+        let hints = match self.selected {
+            Some((x, y)) => {
+                let mut hints = self.get_hints(x, y);
+                hints.push((x, y));
+                hints
+            }
+            None => self.get_hints(m_x, m_y),
+        };
+        let threats = self.get_threats();
 
         for y in 0..self.get_board_height() {
             for x in 0..self.get_board_width() {
@@ -317,6 +614,61 @@ impl<'a, Message> Widget<Message, Renderer> for GBoard {
                         border_color: Color::TRANSPARENT,
                     });
                 }
+
+                if threats.get(x, y) {
+                    res.push(Primitive::Quad {
+                        bounds,
+                        background: Background::Color(self.fill_threat),
+                        border_radius: 0,
+                        border_width: 0,
+                        border_color: Color::TRANSPARENT,
+                    });
+                }
+
+                // file and rank names, along the left and bottom edges
+                if self.render_coordinates() {
+                    let game = self.game.borrow();
+                    let coordinates = game.coordinates();
+                    let color = if (x + y) % 2 == 0 {
+                        self.fill_dark
+                    } else {
+                        self.fill_light
+                    };
+                    if x == 0 {
+                        res.push(Primitive::Text {
+                            content: coordinates.rank_name(
+                                y,
+                                self.get_board_height(),
+                                PlayerColor::White,
+                            ),
+                            bounds: sub_bounds,
+                            color,
+                            size: tile_size * 0.2,
+                            font: Font::Default,
+                            horizontal_alignment: HorizontalAlignment::Left,
+                            vertical_alignment: VerticalAlignment::Top,
+                        });
+                    }
+                    if y + 1 == self.get_board_height() {
+                        res.push(Primitive::Text {
+                            content: coordinates.file_name(
+                                x,
+                                self.get_board_width(),
+                                PlayerColor::White,
+                            ),
+                            bounds: Rectangle {
+                                x: v_x + tile_size - hl_width,
+                                y: v_y + tile_size - hl_width,
+                                ..sub_bounds
+                            },
+                            color,
+                            size: tile_size * 0.2,
+                            font: Font::Default,
+                            horizontal_alignment: HorizontalAlignment::Right,
+                            vertical_alignment: VerticalAlignment::Bottom,
+                        });
+                    }
+                }
             }
         }
 
@@ -331,8 +683,8 @@ impl<'a, Message> Widget<Message, Renderer> for GBoard {
     }
 }
 
-impl<'a, Message> Into<iced_native::Element<'a, Message, Renderer>> for GBoard {
-    fn into(self) -> iced_native::Element<'a, Message, Renderer> {
-        iced_native::Element::new(self)
+impl<'a> From<GBoard> for iced_native::Element<'a, Message, Renderer> {
+    fn from(board: GBoard) -> Self {
+        iced_native::Element::new(board)
     }
 }
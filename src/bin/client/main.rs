@@ -4,159 +4,60 @@ extern crate iced;
 extern crate iced_native;
 extern crate iced_wgpu;
 
+use gui::EngineMode;
 use iced::{Application, Settings};
-use sharmat::board::Board;
-use sharmat::game::*;
-use sharmat::movement::*;
-use sharmat::piece::*;
-use sharmat::player::*;
-use std::num::NonZeroUsize;
+use sharmat::driver::{DriverBuilder, EngineDriver, Protocol};
+use sharmat::player::PlayerColor;
+use sharmat::variant::Variant;
 use std::collections::HashMap;
+use std::env;
+use std::process;
 
 pub mod gui;
+pub mod online;
 pub mod pieces;
 pub mod style;
 pub mod settings;
+pub mod timer;
 
 fn main() {
     // TODO: do this in another thread or idk
-    let board = Board::new(NonZeroUsize::new(8).unwrap(), NonZeroUsize::new(8).unwrap());
-    let mut game = GameBuilder::new()
-        .board(board)
-        .piece(
-            PieceBuilder::new()
-                .id("standard.bishop")
-                .alias("bishop")
-                .display_white("standard.w_bishop")
-                .display_black("standard.b_bishop")
-                .movement(vec![MovementType::RangeAny(Box::new(
-                    MovementType::Undirected(1, 1),
-                ))])
-                .build(),
-        )
-        .piece(
-            PieceBuilder::new()
-                .id("standard.rook")
-                .alias("rook")
-                .display_white("standard.w_rook")
-                .display_black("standard.b_rook")
-                .movement(vec![MovementType::RangeAny(Box::new(
-                    MovementType::Undirected(1, 0),
-                ))])
-                .build(),
-        )
-        .piece(
-            PieceBuilder::new()
-                .id("standard.king")
-                .alias("king")
-                .display_white("standard.w_king")
-                .display_black("standard.b_king")
-                .movement(vec![MovementType::Union(vec![
-                    MovementType::Undirected(1, 0),
-                    MovementType::Undirected(1, 1),
-                ])])
-                .build(),
-        )
-        .piece(
-            PieceBuilder::new()
-                .id("standard.queen")
-                .alias("queen")
-                .display_white("standard.w_queen")
-                .display_black("standard.b_queen")
-                .movement(vec![MovementType::RangeAny(Box::new(MovementType::Union(
-                    vec![
-                        MovementType::Undirected(1, 0),
-                        MovementType::Undirected(1, 1),
-                    ],
-                )))])
-                .build(),
-        )
-        .piece(
-            PieceBuilder::new()
-                .id("standard.knight")
-                .alias("knight")
-                .display_white("standard.w_knight")
-                .display_black("standard.b_knight")
-                .movement(vec![MovementType::Undirected(2, 1)])
-                .build(),
-        )
-        .piece(
-            PieceBuilder::new()
-                .id("standard.pawn")
-                .alias("pawn")
-                .display_white("standard.w_pawn")
-                .display_black("standard.b_pawn")
-                .movement(vec![MovementType::Union(vec![
-                    MovementType::Condition(
-                        Box::new(MovementType::Directed(0, 1)),
-                        vec![MovementCondition::AsWhite, MovementCondition::NoCapture],
-                    ),
-                    MovementType::Condition(
-                        Box::new(MovementType::Directed(0, -1)),
-                        vec![MovementCondition::AsBlack, MovementCondition::NoCapture],
-                    ),
-                    MovementType::Condition(
-                        Box::new(MovementType::Union(vec![
-                            MovementType::Directed(1, 1),
-                            MovementType::Directed(-1, 1),
-                        ])),
-                        vec![MovementCondition::AsWhite, MovementCondition::Capture],
-                    ),
-                    MovementType::Condition(
-                        Box::new(MovementType::Union(vec![
-                            MovementType::Directed(1, -1),
-                            MovementType::Directed(-1, -1),
-                        ])),
-                        vec![MovementCondition::AsBlack, MovementCondition::Capture],
-                    ),
-                    MovementType::Condition(
-                        Box::new(MovementType::Directed(0, 2)),
-                        vec![
-                            MovementCondition::AsWhite,
-                            MovementCondition::NoCapture,
-                            MovementCondition::Custom(&|_b, _p, _x, y, _dx, _dy| y == 1),
-                        ],
-                    ),
-                    MovementType::Condition(
-                        Box::new(MovementType::Directed(0, -2)),
-                        vec![
-                            MovementCondition::AsBlack,
-                            MovementCondition::NoCapture,
-                            MovementCondition::Custom(&|b, _p, _x, y, _dx, _dy| {
-                                y == b.height.get() - 2
-                            }),
-                        ],
-                    ),
-                    // TODO: en-passant :(
-                ])])
-                .build(),
-        )
-        .player(Player::new(PlayerColor::White))
-        .player(Player::new(PlayerColor::Black))
-        .build();
-
-    game.set(0, 0, "rook", PlayerColor::White).unwrap();
-    game.set(1, 0, "knight", PlayerColor::White).unwrap();
-    game.set(2, 0, "bishop", PlayerColor::White).unwrap();
-    game.set(3, 0, "king", PlayerColor::White).unwrap();
-    game.set(4, 0, "queen", PlayerColor::White).unwrap();
-    game.set(5, 0, "bishop", PlayerColor::White).unwrap();
-    game.set(6, 0, "knight", PlayerColor::White).unwrap();
-    game.set(7, 0, "rook", PlayerColor::White).unwrap();
+    let variant_path = env::args().nth(1).unwrap_or_else(|| {
+        format!("{}/variants/standard.toml", env!("CARGO_MANIFEST_DIR"))
+    });
+    let game = Variant::load(&variant_path)
+        .and_then(|variant| variant.game())
+        .unwrap_or_else(|e| {
+            eprintln!("Couldn't load variant {}: {}", variant_path, e);
+            process::exit(1);
+        });
+    let engine = load_engine();
+    let piece_assets = pieces::load_assets(format!("{}/assets/", env!("CARGO_MANIFEST_DIR")));
+    gui::Sharmat::run(Settings::with_flags((
+        piece_assets,
+        game,
+        HashMap::new(),
+        engine,
+    )))
+}
 
-    for x in 0..8 {
-        game.set(x, 6, "pawn", PlayerColor::Black).unwrap();
-        game.set(x, 1, "pawn", PlayerColor::White).unwrap();
+/// Starts the external engine given after the variant, as `ENGINE [uci|usi] [white|black|analysis]`
+fn load_engine() -> Option<(EngineDriver, EngineMode)> {
+    let command = env::args().nth(2)?;
+    let protocol = match env::args().nth(3).as_deref() {
+        Some("usi") => Protocol::Usi,
+        _ => Protocol::Uci,
+    };
+    let mode = match env::args().nth(4).as_deref() {
+        Some("white") => EngineMode::Opponent(PlayerColor::White),
+        Some("analysis") => EngineMode::Analysis,
+        _ => EngineMode::Opponent(PlayerColor::Black),
+    };
+    match DriverBuilder::new().command(&command).protocol(protocol).build() {
+        Ok(driver) => Some((driver, mode)),
+        Err(e) => {
+            eprintln!("Couldn't start engine {}: {}", command, e);
+            None
+        }
     }
-
-    game.set(0, 7, "rook", PlayerColor::Black).unwrap();
-    game.set(1, 7, "knight", PlayerColor::Black).unwrap();
-    game.set(2, 7, "bishop", PlayerColor::Black).unwrap();
-    game.set(3, 7, "king", PlayerColor::Black).unwrap();
-    game.set(4, 7, "queen", PlayerColor::Black).unwrap();
-    game.set(5, 7, "bishop", PlayerColor::Black).unwrap();
-    game.set(6, 7, "knight", PlayerColor::Black).unwrap();
-    game.set(7, 7, "rook", PlayerColor::Black).unwrap();
-    let piece_assets = pieces::load_assets(format!("{}/assets/", env!("CARGO_MANIFEST_DIR")));
-    gui::Sharmat::run(Settings::with_flags((piece_assets, game, HashMap::new())))
 }
@@ -0,0 +1,1017 @@
+// Online play: connecting to a server, finding an opponent and playing against them
+
+use super::gui::SharmatMessage;
+use super::timer::Every;
+
+use sharmat::game::Game;
+use sharmat::player::PlayerColor;
+use sharmat::protocol::*;
+
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::env;
+use std::hash::{Hash, Hasher};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use iced::{
+    button, scrollable, text_input, Button, Column, Command, Element, Length, Row, Scrollable,
+    Subscription, Text, TextInput,
+};
+use iced_native::futures::channel::{mpsc, oneshot};
+use iced_native::futures::stream::{BoxStream, StreamExt};
+use iced_native::subscription::Recipe;
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: &str = "7070";
+const DEFAULT_VARIANT: &str = "standard";
+const DEFAULT_NAME: &str = "player";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Attempts to get back to the server after losing the connection during a game
+const RECONNECT_ATTEMPTS: usize = 5;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// How often running clocks are redrawn
+const CLOCK_TICK: Duration = Duration::from_millis(100);
+/// Chat messages kept, over all channels
+const CHAT_KEPT: usize = 200;
+/// Chat messages shown, from the current channel
+const CHAT_LINES: usize = 8;
+
+/// Messages of the online panel
+#[derive(Debug, Clone)]
+pub enum OnlineMessage {
+    HostChanged(String),
+    PortChanged(String),
+    NameChanged(String),
+    VariantChanged(String),
+    TimeChanged(String),
+    /// Switches to the next color choice for new games
+    CycleColor,
+    Connect,
+    /// The handshake with the server ended, for the connection with the given number
+    Connected(usize, Result<Connection, String>),
+    Disconnect,
+    Refresh,
+    /// Opens a game for anyone to join
+    Seek,
+    Join(u64),
+    /// Challenges the user with the given number
+    Challenge(u64),
+    Accept(u64),
+    /// Declines a challenge received, or withdraws one sent
+    Decline(u64),
+    /// Watches the running game with the given number
+    Watch(u64),
+    /// Resigns the online game, stops watching it, or cancels the game waiting for an opponent
+    Resign,
+    ChatChanged(String),
+    /// Says the chat text in the current channel, or mutes or unmutes someone with `/mute name` or `/unmute name`
+    SendChat,
+    /// A message from the server, or the error that closed the connection with the given number
+    Received(usize, Result<Box<ServerMessage>, String>),
+    /// Time to redraw the running clock
+    Tick,
+}
+
+/// A connection to a server, shared with the thread reading its messages
+#[derive(Debug, Clone)]
+pub struct Connection {
+    /// Number given by the server to this client
+    pub client: u64,
+    /// Address of the server, to reconnect to
+    address: String,
+    /// Secret given by the server to resume the session
+    token: String,
+    writer: Arc<Mutex<TcpStream>>,
+    reader: Arc<TcpStream>,
+}
+
+/// Messages read from a connection on a dedicated thread, until it is closed
+#[derive(Debug)]
+struct Frames {
+    /// Number of the connection
+    session: usize,
+    reader: Arc<TcpStream>,
+}
+
+/// The game played online
+#[derive(Debug)]
+pub struct OnlineGame {
+    pub id: u64,
+    /// The color played, or `None` when watching the game
+    pub color: Option<PlayerColor>,
+    /// Names of white and black
+    pub players: [Option<String>; 2],
+    pub spectators: u32,
+    /// The last clocks sent by the server, and when they arrived
+    pub clock: Option<(ClockUpdate, Instant)>,
+}
+
+impl OnlineGame {
+    /// Time left to white and black, with the running clock counted down since the last update
+    pub fn clocks(&self) -> Option<[Duration; 2]> {
+        let (clock, received) = self.clock.as_ref()?;
+        let mut left = [clock.white, clock.black];
+        if let Some(color) = clock.running {
+            let running = &mut left[if color.white() { 0 } else { 1 }];
+            *running = running.saturating_sub(received.elapsed());
+        }
+        Some(left)
+    }
+}
+
+/// State of the online panel
+#[derive(Debug)]
+pub struct Online {
+    pub host: String,
+    pub port: String,
+    pub name: String,
+    pub variant: String,
+    /// Time control of new games, like `5+3`, or nothing for untimed games
+    pub time: String,
+    pub color: ColorChoice,
+    pub connection: Option<Connection>,
+    /// Number of the current connection attempt, so that messages from older connections are ignored
+    session: usize,
+    connecting: bool,
+    pub lobby: Lobby,
+    /// The game created and waiting for an opponent
+    pub waiting: Option<u64>,
+    pub game: Option<OnlineGame>,
+    /// Last thing worth telling the player: errors, rejected moves, results
+    pub status: Option<String>,
+    /// Chat messages heard, oldest first
+    pub chat: Vec<ChatMessage>,
+    pub chat_text: String,
+
+    host_input: text_input::State,
+    port_input: text_input::State,
+    name_input: text_input::State,
+    variant_input: text_input::State,
+    time_input: text_input::State,
+    connect_button: button::State,
+    disconnect_button: button::State,
+    refresh_button: button::State,
+    color_button: button::State,
+    seek_button: button::State,
+    join_buttons: Vec<button::State>,
+    watch_buttons: Vec<button::State>,
+    challenge_buttons: Vec<button::State>,
+    /// Accept and decline buttons of each challenge
+    answer_buttons: Vec<(button::State, button::State)>,
+    resign_button: button::State,
+    games_scroll: scrollable::State,
+    chat_input: text_input::State,
+    chat_button: button::State,
+}
+
+impl Connection {
+    /// Connects to a server and introduces the client
+    fn open(address: String, name: String) -> Result<Connection, String> {
+        let stream = Connection::connect(&address)?;
+        let hello = ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name,
+        };
+        Connection::handshake(address, stream, &hello)
+    }
+
+    /// Connects again to the server of a lost connection, waiting a bit between attempts
+    fn reopen(address: String, token: String) -> Result<Connection, String> {
+        let mut stream = Connection::connect(&address);
+        for _ in 1..RECONNECT_ATTEMPTS {
+            if stream.is_ok() {
+                break;
+            }
+            thread::sleep(RECONNECT_DELAY);
+            stream = Connection::connect(&address);
+        }
+        let resume = ClientMessage::Resume {
+            version: PROTOCOL_VERSION,
+            token,
+        };
+        Connection::handshake(address, stream?, &resume)
+    }
+
+    fn connect(address: &str) -> Result<TcpStream, String> {
+        let socket = address
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("unknown address {}", address))?;
+        let stream =
+            TcpStream::connect_timeout(&socket, CONNECT_TIMEOUT).map_err(|e| e.to_string())?;
+        let _ = stream.set_nodelay(true);
+        Ok(stream)
+    }
+
+    /// Says hello, or resumes a session, and waits for the server's welcome
+    fn handshake(
+        address: String,
+        mut stream: TcpStream,
+        hello: &ClientMessage,
+    ) -> Result<Connection, String> {
+        write_frame(&mut stream, hello).map_err(|e| e.to_string())?;
+        let (client, token) = match read_frame(&mut stream).map_err(|e| e.to_string())? {
+            ServerMessage::Welcome { client, token, .. } => (client, token),
+            ServerMessage::Error(e) => return Err(e),
+            message => return Err(format!("unexpected answer {:?}", message)),
+        };
+        let reader = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Connection {
+            client,
+            address,
+            token,
+            writer: Arc::new(Mutex::new(stream)),
+            reader: Arc::new(reader),
+        })
+    }
+
+    fn send(&self, message: &ClientMessage) -> Result<(), String> {
+        let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+        write_frame(&mut *writer, message).map_err(|e| e.to_string())
+    }
+
+    /// Closes the connection, which ends the thread reading its messages
+    fn close(&self) {
+        if let Ok(writer) = self.writer.lock() {
+            let _ = writer.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Default for Online {
+    fn default() -> Self {
+        Online {
+            host: DEFAULT_HOST.to_string(),
+            port: DEFAULT_PORT.to_string(),
+            name: env::var("USER").unwrap_or_else(|_| DEFAULT_NAME.to_string()),
+            variant: DEFAULT_VARIANT.to_string(),
+            time: String::new(),
+            color: ColorChoice::Random,
+            connection: None,
+            session: 0,
+            connecting: false,
+            lobby: Lobby::default(),
+            waiting: None,
+            game: None,
+            status: None,
+            chat: Vec::new(),
+            chat_text: String::new(),
+            host_input: text_input::State::new(),
+            port_input: text_input::State::new(),
+            name_input: text_input::State::new(),
+            variant_input: text_input::State::new(),
+            time_input: text_input::State::new(),
+            connect_button: button::State::new(),
+            disconnect_button: button::State::new(),
+            refresh_button: button::State::new(),
+            color_button: button::State::new(),
+            seek_button: button::State::new(),
+            join_buttons: Vec::new(),
+            watch_buttons: Vec::new(),
+            challenge_buttons: Vec::new(),
+            answer_buttons: Vec::new(),
+            resign_button: button::State::new(),
+            games_scroll: scrollable::State::new(),
+            chat_input: text_input::State::new(),
+            chat_button: button::State::new(),
+        }
+    }
+}
+
+impl Online {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the local player is to move in the online game
+    pub fn my_turn(&self, game: &Game) -> bool {
+        match &self.game {
+            Some(OnlineGame {
+                color: Some(color), ..
+            }) => game.current_player().map(|player| player.color) == Some(*color),
+            _ => false,
+        }
+    }
+
+    /// Where chat messages are said: with the opponent when playing, with the other spectators when watching, or in the lobby
+    fn chat_channel(&self) -> ChatChannel {
+        match &self.game {
+            Some(OnlineGame {
+                id, color: Some(_), ..
+            }) => ChatChannel::Players(*id),
+            Some(OnlineGame {
+                id, color: None, ..
+            }) => ChatChannel::Spectators(*id),
+            None => ChatChannel::Lobby,
+        }
+    }
+
+    fn clock_running(&self) -> bool {
+        matches!(&self.game, Some(OnlineGame { clock: Some((clock, _)), .. }) if clock.running.is_some())
+    }
+
+    /// Listens to the server, and redraws the running clock
+    pub fn subscription(&self) -> Subscription<SharmatMessage> {
+        let mut subscriptions = Vec::new();
+        if let Some(connection) = &self.connection {
+            let frames = Frames {
+                session: self.session,
+                reader: connection.reader.clone(),
+            };
+            subscriptions.push(Subscription::from_recipe(frames).map(SharmatMessage::Online));
+        }
+        if self.clock_running() {
+            subscriptions.push(
+                Subscription::from_recipe(Every(CLOCK_TICK))
+                    .map(|_| SharmatMessage::Online(OnlineMessage::Tick)),
+            );
+        }
+        Subscription::batch(subscriptions)
+    }
+
+    /// Sends a move of the local player, which is played once the server accepts it
+    pub fn send_move(&mut self, mv: String) {
+        if let Some(game) = &self.game {
+            let message = ClientMessage::Move { game: game.id, mv };
+            self.send(&message);
+        }
+    }
+
+    /// Handles a message of the online panel; `game` is the game displayed by the board
+    pub fn update(
+        &mut self,
+        message: OnlineMessage,
+        game: &Rc<RefCell<Game>>,
+    ) -> Command<SharmatMessage> {
+        match message {
+            OnlineMessage::HostChanged(host) => self.host = host,
+            OnlineMessage::PortChanged(port) => self.port = port,
+            OnlineMessage::NameChanged(name) => self.name = name,
+            OnlineMessage::VariantChanged(variant) => self.variant = variant,
+            OnlineMessage::TimeChanged(time) => self.time = time,
+            OnlineMessage::CycleColor => {
+                self.color = match self.color {
+                    ColorChoice::Random => ColorChoice::White,
+                    ColorChoice::White => ColorChoice::Black,
+                    ColorChoice::Black => ColorChoice::Random,
+                }
+            }
+            OnlineMessage::Connect => {
+                if self.connecting || self.connection.is_some() {
+                    return Command::none();
+                }
+                self.connecting = true;
+                self.status = None;
+                self.session += 1;
+                let session = self.session;
+                let address = format!("{}:{}", self.host.trim(), self.port.trim());
+                let name = self.name.trim().to_string();
+                return Command::perform(
+                    on_thread(move || Connection::open(address, name)),
+                    move |result| SharmatMessage::Online(OnlineMessage::Connected(session, result)),
+                );
+            }
+            OnlineMessage::Connected(session, result) => {
+                if session != self.session {
+                    return Command::none();
+                }
+                self.connecting = false;
+                match result {
+                    Ok(connection) => {
+                        self.connection = Some(connection);
+                        self.send(&ClientMessage::Lobby);
+                    }
+                    // a failed reconnection gives up on the game too
+                    Err(e) => self.disconnect(Some(format!("Couldn't connect: {}", e))),
+                }
+            }
+            OnlineMessage::Disconnect => {
+                self.disconnect(None);
+            }
+            OnlineMessage::Refresh => self.send(&ClientMessage::Lobby),
+            OnlineMessage::Seek => {
+                if let Some(time) = self.time_control() {
+                    let message = ClientMessage::CreateGame {
+                        variant: self.variant.trim().to_string(),
+                        color: self.color,
+                        time,
+                    };
+                    self.send(&message);
+                }
+            }
+            OnlineMessage::Join(id) => self.send(&ClientMessage::JoinGame { game: id }),
+            OnlineMessage::Challenge(user) => {
+                if let Some(time) = self.time_control() {
+                    let message = ClientMessage::Challenge {
+                        to: user,
+                        variant: self.variant.trim().to_string(),
+                        color: self.color,
+                        time,
+                    };
+                    self.send(&message);
+                }
+            }
+            OnlineMessage::Accept(challenge) => {
+                self.send(&ClientMessage::AcceptChallenge { challenge })
+            }
+            OnlineMessage::Decline(challenge) => {
+                self.send(&ClientMessage::DeclineChallenge { challenge })
+            }
+            OnlineMessage::Watch(id) => self.send(&ClientMessage::Watch { game: id }),
+            OnlineMessage::Resign => match &self.game {
+                Some(OnlineGame {
+                    id, color: None, ..
+                }) => {
+                    let id = *id;
+                    self.send(&ClientMessage::StopWatching { game: id });
+                    self.game = None;
+                    self.send(&ClientMessage::Lobby);
+                }
+                _ => {
+                    if let Some(id) = self.game.as_ref().map(|game| game.id).or(self.waiting) {
+                        self.send(&ClientMessage::Resign { game: id });
+                        self.waiting = None;
+                        self.send(&ClientMessage::Lobby);
+                    }
+                }
+            },
+            OnlineMessage::ChatChanged(text) => self.chat_text = text,
+            OnlineMessage::SendChat => {
+                let text = self.chat_text.trim().to_string();
+                let message = if let Some(name) = text.strip_prefix("/mute ") {
+                    ClientMessage::Mute {
+                        name: name.trim().to_string(),
+                    }
+                } else if let Some(name) = text.strip_prefix("/unmute ") {
+                    ClientMessage::Unmute {
+                        name: name.trim().to_string(),
+                    }
+                } else if text.is_empty() {
+                    return Command::none();
+                } else {
+                    ClientMessage::Chat {
+                        channel: self.chat_channel(),
+                        text,
+                    }
+                };
+                self.send(&message);
+                self.chat_text.clear();
+            }
+            OnlineMessage::Received(session, result) => {
+                if session != self.session || self.connection.is_none() {
+                    return Command::none();
+                }
+                match result {
+                    Ok(message) => self.receive(*message, game),
+                    Err(e) => return self.reconnect(e),
+                }
+            }
+            // the clock is redrawn with the rest of the view
+            OnlineMessage::Tick => {}
+        }
+        Command::none()
+    }
+
+    fn receive(&mut self, message: ServerMessage, game: &Rc<RefCell<Game>>) {
+        match message {
+            ServerMessage::Welcome { .. } => {}
+            ServerMessage::GameList(games) => {
+                self.lobby.games = games;
+                self.reset_buttons();
+            }
+            ServerMessage::Lobby(lobby) => {
+                self.lobby = lobby;
+                self.reset_buttons();
+            }
+            ServerMessage::Challenge(challenge) => {
+                if Some(challenge.to) == self.connection.as_ref().map(|c| c.client) {
+                    self.status = Some(format!("{} challenges you", challenge.from_name));
+                }
+                self.send(&ClientMessage::Lobby);
+            }
+            ServerMessage::ChallengeClosed { reason, .. } => {
+                self.status = Some(format!("Challenge {}", reason));
+                self.send(&ClientMessage::Lobby);
+            }
+            ServerMessage::GameCreated { game } => {
+                self.waiting = Some(game);
+                self.status = None;
+            }
+            ServerMessage::Snapshot(snapshot) => match snapshot.game() {
+                Ok(new_game) => {
+                    *game.borrow_mut() = new_game;
+                    self.waiting = None;
+                    self.status = None;
+                    self.game = Some(OnlineGame {
+                        id: snapshot.game,
+                        color: snapshot.color,
+                        players: snapshot.players,
+                        spectators: 0,
+                        clock: snapshot.clock.map(|clock| (clock, Instant::now())),
+                    });
+                }
+                Err(e) => self.disconnect(Some(format!("Couldn't start the game: {}", e))),
+            },
+            ServerMessage::Spectators { game, count } => {
+                if let Some(online_game) = self.game.as_mut().filter(|online| online.id == game) {
+                    online_game.spectators = count;
+                }
+            }
+            ServerMessage::Move {
+                game: id,
+                mv,
+                checksum: expected,
+            } => {
+                if self.game.as_ref().map(|game| game.id) != Some(id) {
+                    return;
+                }
+                let mut game = game.borrow_mut();
+                let played = match game.parse_uci_move(&mv) {
+                    Ok(mv) => game.play(&mv).is_some(),
+                    Err(_) => false,
+                };
+                if !played || checksum(&game) != expected {
+                    drop(game);
+                    self.disconnect(Some(String::from("Out of sync with the server")));
+                }
+            }
+            ServerMessage::MoveRejected { mv, reason, .. } => {
+                self.status = Some(format!("Move {} rejected: {}", mv, reason));
+            }
+            ServerMessage::Clock(clock) => {
+                if let Some(online_game) = self.game.as_mut().filter(|game| game.id == clock.game) {
+                    online_game.clock = Some((clock, Instant::now()));
+                }
+            }
+            ServerMessage::Ping { ping } => self.send(&ClientMessage::Pong { ping }),
+            ServerMessage::GameOver {
+                game,
+                result,
+                reason,
+            } => {
+                if self.game.as_ref().map(|game| game.id) == Some(game) {
+                    self.game = None;
+                    self.status = Some(format!("Game over: {} ({})", result, reason));
+                    self.send(&ClientMessage::Lobby);
+                }
+            }
+            ServerMessage::PlayerAway { game, color, grace } => {
+                if let Some(name) = self.player_name(game, color) {
+                    self.status = Some(format!(
+                        "{} lost their connection, and forfeits unless back within {} seconds",
+                        name,
+                        grace.as_secs()
+                    ));
+                }
+            }
+            ServerMessage::PlayerBack { game, color } => {
+                if let Some(name) = self.player_name(game, color) {
+                    self.status = Some(format!("{} is back", name));
+                }
+            }
+            ServerMessage::Chat(message) => {
+                self.chat.push(message);
+                if self.chat.len() > CHAT_KEPT {
+                    self.chat.remove(0);
+                }
+            }
+            ServerMessage::ChatHistory { channel, messages } => {
+                self.chat.retain(|message| message.channel != channel);
+                self.chat.extend(messages);
+                let excess = self.chat.len().saturating_sub(CHAT_KEPT);
+                self.chat.drain(..excess);
+            }
+            ServerMessage::Muted(names) => {
+                self.status = Some(if names.is_empty() {
+                    String::from("No one is muted")
+                } else {
+                    format!("Muted: {}", names.join(", "))
+                });
+            }
+            // stored games aren't browsed from the client yet
+            ServerMessage::History(_) | ServerMessage::Export { .. } => {}
+            ServerMessage::Error(e) => self.status = Some(e),
+        }
+    }
+
+    /// The time control of new games, or `None` if it can't be read
+    fn time_control(&mut self) -> Option<Option<TimeControl>> {
+        if self.time.trim().is_empty() {
+            return Some(None);
+        }
+        match self.time.parse() {
+            Ok(time) => Some(Some(time)),
+            Err(e) => {
+                self.status = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    /// Name of a player of the online game, if it is the given one
+    fn player_name(&self, id: u64, color: PlayerColor) -> Option<String> {
+        let online_game = self.game.as_ref().filter(|game| game.id == id)?;
+        let name = online_game.players[if color.white() { 0 } else { 1 }].clone();
+        Some(name.unwrap_or_else(|| String::from(if color.white() { "White" } else { "Black" })))
+    }
+
+    /// Makes a button for each game, user and challenge of the lobby
+    fn reset_buttons(&mut self) {
+        let lobby = &self.lobby;
+        self.join_buttons = lobby.games.iter().map(|_| button::State::new()).collect();
+        self.watch_buttons = lobby.running.iter().map(|_| button::State::new()).collect();
+        self.challenge_buttons = lobby.users.iter().map(|_| button::State::new()).collect();
+        self.answer_buttons = lobby
+            .challenges
+            .iter()
+            .map(|_| (button::State::new(), button::State::new()))
+            .collect();
+    }
+
+    fn send(&mut self, message: &ClientMessage) {
+        if let Some(connection) = &self.connection {
+            // the wait for the next message then fails, and the connection is handled as lost
+            if connection.send(message).is_err() {
+                connection.close();
+            }
+        }
+    }
+
+    /// Gets back to the game on a new connection after losing the previous one, or gives up if not playing
+    fn reconnect(&mut self, e: String) -> Command<SharmatMessage> {
+        let playing = matches!(self.game, Some(OnlineGame { color: Some(_), .. }));
+        let connection = match self.connection.take() {
+            Some(connection) if playing => connection,
+            connection => {
+                self.connection = connection;
+                self.disconnect(Some(format!("Connection lost: {}", e)));
+                return Command::none();
+            }
+        };
+        connection.close();
+        self.session += 1;
+        self.connecting = true;
+        self.status = Some(format!("Connection lost ({}), reconnecting...", e));
+        let session = self.session;
+        let (address, token) = (connection.address.clone(), connection.token.clone());
+        Command::perform(
+            on_thread(move || Connection::reopen(address, token)),
+            move |result| SharmatMessage::Online(OnlineMessage::Connected(session, result)),
+        )
+    }
+
+    /// Closes the connection, keeping the board as it was
+    fn disconnect(&mut self, status: Option<String>) {
+        if let Some(connection) = self.connection.take() {
+            connection.close();
+        }
+        self.session += 1;
+        self.connecting = false;
+        self.lobby = Lobby::default();
+        self.reset_buttons();
+        self.waiting = None;
+        self.game = None;
+        self.chat.clear();
+        self.status = status;
+    }
+
+    pub fn view(&mut self, game: &Rc<RefCell<Game>>) -> Element<'_, SharmatMessage> {
+        let mut column = Column::new().spacing(10).push(Text::new("Online").size(24));
+        let message = |message: OnlineMessage| SharmatMessage::Online(message);
+        let channel = self.chat_channel();
+
+        if self.connection.is_none() {
+            column = column
+                .push(
+                    TextInput::new(&mut self.host_input, "Host", &self.host, move |host| {
+                        message(OnlineMessage::HostChanged(host))
+                    })
+                    .padding(5),
+                )
+                .push(
+                    TextInput::new(&mut self.port_input, "Port", &self.port, move |port| {
+                        message(OnlineMessage::PortChanged(port))
+                    })
+                    .padding(5),
+                )
+                .push(
+                    TextInput::new(&mut self.name_input, "Name", &self.name, move |name| {
+                        message(OnlineMessage::NameChanged(name))
+                    })
+                    .padding(5)
+                    .on_submit(message(OnlineMessage::Connect)),
+                );
+            column = if self.connecting {
+                column.push(Text::new("Connecting..."))
+            } else {
+                column.push(
+                    Button::new(&mut self.connect_button, Text::new("Connect"))
+                        .on_press(message(OnlineMessage::Connect)),
+                )
+            };
+        } else if let Some(online_game) = &self.game {
+            let names = [PlayerColor::White, PlayerColor::Black]
+                .iter()
+                .map(|&color| {
+                    let name = online_game.players[if color.white() { 0 } else { 1 }]
+                        .clone()
+                        .unwrap_or_default();
+                    if Some(color) == online_game.color {
+                        format!("{} (you)", name)
+                    } else {
+                        name
+                    }
+                });
+            let clocks = online_game.clocks();
+            for (index, (side, name)) in ["White", "Black"].iter().zip(names).enumerate() {
+                let line = match clocks {
+                    Some(clocks) => {
+                        format!("{}: {} - {}", side, name, describe_clock(clocks[index]))
+                    }
+                    None => format!("{}: {}", side, name),
+                };
+                column = column.push(Text::new(line));
+            }
+            let (turn, leave) = if online_game.color.is_none() {
+                ("Watching", "Stop watching")
+            } else if self.my_turn(&game.borrow()) {
+                ("Your turn", "Resign")
+            } else {
+                ("Waiting for your opponent", "Resign")
+            };
+            column = column
+                .push(Text::new(turn))
+                .push(Text::new(format!("{} watching", online_game.spectators)).size(16))
+                .push(
+                    Button::new(&mut self.resign_button, Text::new(leave))
+                        .on_press(message(OnlineMessage::Resign)),
+                );
+        } else if let Some(id) = self.waiting {
+            column = column
+                .push(Text::new(format!("Waiting for an opponent in game {}", id)))
+                .push(
+                    Button::new(&mut self.resign_button, Text::new("Cancel"))
+                        .on_press(message(OnlineMessage::Resign)),
+                );
+        } else {
+            let me = self.connection.as_ref().map(|connection| connection.client);
+            column = column
+                .push(
+                    TextInput::new(
+                        &mut self.variant_input,
+                        "Variant",
+                        &self.variant,
+                        move |variant| message(OnlineMessage::VariantChanged(variant)),
+                    )
+                    .padding(5),
+                )
+                .push(
+                    TextInput::new(
+                        &mut self.time_input,
+                        "Time, like 5+3, or untimed",
+                        &self.time,
+                        move |time| message(OnlineMessage::TimeChanged(time)),
+                    )
+                    .padding(5),
+                );
+            let color = match self.color {
+                ColorChoice::White => "As white",
+                ColorChoice::Black => "As black",
+                ColorChoice::Random => "Random color",
+            };
+            column = column.push(
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        Button::new(&mut self.color_button, Text::new(color).size(16))
+                            .on_press(message(OnlineMessage::CycleColor)),
+                    )
+                    .push(
+                        Button::new(&mut self.seek_button, Text::new("Seek").size(16))
+                            .on_press(message(OnlineMessage::Seek)),
+                    ),
+            );
+
+            let mut lobby = Scrollable::new(&mut self.games_scroll)
+                .spacing(5)
+                .height(Length::Units(250));
+            for ((accept, decline), challenge) in
+                self.answer_buttons.iter_mut().zip(&self.lobby.challenges)
+            {
+                let time = describe_time(challenge.time);
+                if Some(challenge.to) == me {
+                    let color = match challenge.color {
+                        ColorChoice::White => "black",
+                        ColorChoice::Black => "white",
+                        ColorChoice::Random => "a random color",
+                    };
+                    lobby = lobby
+                        .push(
+                            Text::new(format!(
+                                "{} challenges you: {}, {}, you play {}",
+                                challenge.from_name, challenge.variant, time, color
+                            ))
+                            .size(16),
+                        )
+                        .push(
+                            Row::new()
+                                .spacing(5)
+                                .push(
+                                    Button::new(accept, Text::new("Accept").size(16))
+                                        .on_press(message(OnlineMessage::Accept(challenge.id))),
+                                )
+                                .push(
+                                    Button::new(decline, Text::new("Decline").size(16))
+                                        .on_press(message(OnlineMessage::Decline(challenge.id))),
+                                ),
+                        );
+                } else {
+                    lobby = lobby
+                        .push(
+                            Text::new(format!(
+                                "You challenge {}: {}, {}",
+                                challenge.to_name, challenge.variant, time
+                            ))
+                            .size(16),
+                        )
+                        .push(
+                            Button::new(decline, Text::new("Withdraw").size(16))
+                                .on_press(message(OnlineMessage::Decline(challenge.id))),
+                        );
+                }
+            }
+
+            lobby = lobby.push(Text::new("Open games").size(20));
+            if self.lobby.games.is_empty() {
+                lobby = lobby.push(Text::new("None").size(16));
+            }
+            for (state, open) in self.join_buttons.iter_mut().zip(&self.lobby.games) {
+                let color = if open.color.white() { "white" } else { "black" };
+                lobby = lobby.push(
+                    Button::new(
+                        state,
+                        Text::new(format!(
+                            "{} by {}, {}, as {}",
+                            open.variant,
+                            open.host,
+                            describe_time(open.time),
+                            color
+                        ))
+                        .size(16),
+                    )
+                    .width(Length::Fill)
+                    .on_press(message(OnlineMessage::Join(open.game))),
+                );
+            }
+
+            lobby = lobby.push(Text::new("Running games").size(20));
+            if self.lobby.running.is_empty() {
+                lobby = lobby.push(Text::new("None").size(16));
+            }
+            for (state, running) in self.watch_buttons.iter_mut().zip(&self.lobby.running) {
+                lobby = lobby.push(
+                    Button::new(
+                        state,
+                        Text::new(format!(
+                            "{} vs {} ({}, {} watching)",
+                            running.players[0],
+                            running.players[1],
+                            running.variant,
+                            running.spectators
+                        ))
+                        .size(16),
+                    )
+                    .width(Length::Fill)
+                    .on_press(message(OnlineMessage::Watch(running.game))),
+                );
+            }
+
+            lobby = lobby.push(Text::new("Players").size(20));
+            for (state, user) in self.challenge_buttons.iter_mut().zip(&self.lobby.users) {
+                if Some(user.id) == me {
+                    continue;
+                }
+                let mut button = Button::new(
+                    state,
+                    Text::new(match user.game {
+                        Some(_) => format!("{} (busy)", user.name),
+                        None => format!("Challenge {}", user.name),
+                    })
+                    .size(16),
+                )
+                .width(Length::Fill);
+                if user.game.is_none() {
+                    button = button.on_press(message(OnlineMessage::Challenge(user.id)));
+                }
+                lobby = lobby.push(button);
+            }
+            column = column.push(lobby).push(
+                Button::new(&mut self.refresh_button, Text::new("Refresh"))
+                    .on_press(message(OnlineMessage::Refresh)),
+            );
+        }
+
+        if self.connection.is_some() {
+            let heard = self
+                .chat
+                .iter()
+                .filter(|message| message.channel == channel)
+                .collect::<Vec<_>>();
+            for said in &heard[heard.len().saturating_sub(CHAT_LINES)..] {
+                column =
+                    column.push(Text::new(format!("{}: {}", said.from_name, said.text)).size(16));
+            }
+            column = column.push(
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        TextInput::new(
+                            &mut self.chat_input,
+                            "Chat",
+                            &self.chat_text,
+                            move |text| message(OnlineMessage::ChatChanged(text)),
+                        )
+                        .padding(5)
+                        .size(16)
+                        .on_submit(message(OnlineMessage::SendChat)),
+                    )
+                    .push(
+                        Button::new(&mut self.chat_button, Text::new("Send").size(16))
+                            .on_press(message(OnlineMessage::SendChat)),
+                    ),
+            );
+            column = column.push(
+                Button::new(&mut self.disconnect_button, Text::new("Disconnect"))
+                    .on_press(message(OnlineMessage::Disconnect)),
+            );
+        }
+        if let Some(status) = &self.status {
+            column = column.push(Text::new(status.clone()).size(16));
+        }
+        column.into()
+    }
+}
+
+impl<H: Hasher, E> Recipe<H, E> for Frames {
+    type Output = OnlineMessage;
+
+    fn hash(&self, state: &mut H) {
+        TypeId::of::<Self>().hash(state);
+        self.session.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _input: BoxStream<'static, E>) -> BoxStream<'static, OnlineMessage> {
+        let (sender, receiver) = mpsc::unbounded();
+        let session = self.session;
+        thread::spawn(move || loop {
+            let mut reader = &*self.reader;
+            let message = read_frame(&mut reader)
+                .map(Box::new)
+                .map_err(|e| e.to_string());
+            // a read error ends the connection
+            let closed = message.is_err();
+            let sent = sender.unbounded_send(OnlineMessage::Received(session, message));
+            if closed || sent.is_err() {
+                break;
+            }
+        });
+        receiver.boxed()
+    }
+}
+
+/// Runs `f` on its own thread, so that waiting for the network doesn't hold up the executor
+async fn on_thread<T, F>(f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+    receiver
+        .await
+        .unwrap_or_else(|_| Err(String::from("the connection attempt stopped")))
+}
+
+/// Writes the time left on a clock, with tenths of seconds when it runs low
+fn describe_clock(left: Duration) -> String {
+    let seconds = left.as_secs();
+    if seconds < 10 {
+        format!("{}.{}", seconds, left.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+fn describe_time(time: Option<TimeControl>) -> String {
+    match time {
+        Some(time) => time.to_string(),
+        None => String::from("untimed"),
+    }
+}
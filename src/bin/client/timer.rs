@@ -0,0 +1,38 @@
+// A subscription ticking at a fixed interval, to poll things without blocking the UI
+
+use iced_native::futures::channel::mpsc;
+use iced_native::futures::stream::{BoxStream, StreamExt};
+use iced_native::subscription::Recipe;
+
+use std::any::TypeId;
+use std::hash::{Hash, Hasher};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Gives the current time at the given interval, for as long as the subscription is kept
+#[derive(Debug, Clone, Copy)]
+pub struct Every(pub Duration);
+
+impl<H: Hasher, E> Recipe<H, E> for Every {
+    type Output = Instant;
+
+    fn hash(&self, state: &mut H) {
+        TypeId::of::<Self>().hash(state);
+        self.0.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _input: BoxStream<'static, E>) -> BoxStream<'static, Instant> {
+        let (mut sender, receiver) = mpsc::channel(1);
+        let interval = self.0;
+        // ticks are skipped while the UI is busy, and the thread ends once the subscription is dropped
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if let Err(e) = sender.try_send(Instant::now()) {
+                if e.is_disconnected() {
+                    break;
+                }
+            }
+        });
+        receiver.boxed()
+    }
+}
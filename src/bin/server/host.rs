@@ -0,0 +1,1237 @@
+// Game hosting: the one place where the server's state lives and changes
+
+use sharmat::game::{Game, Outcome};
+use sharmat::player::PlayerColor;
+use sharmat::protocol::*;
+use sharmat::storage::*;
+use sharmat::variant::Variant;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::io::Write;
+use std::net::{Shutdown, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Something that happened on a connection, sent by the connection threads to the host
+#[derive(Debug)]
+pub enum Event {
+    Connected(u64, TcpStream),
+    Message(u64, ClientMessage),
+    /// A frame that couldn't be decoded
+    Invalid(u64, String),
+    Disconnected(u64),
+}
+
+#[derive(Debug)]
+struct Client {
+    stream: TcpStream,
+    /// Frames waiting to be written to the client by its writer thread
+    outbox: SyncSender<Vec<u8>>,
+    name: Option<String>,
+    /// The game the client plays in, if any
+    game: Option<u64>,
+    /// Secret with which the client can resume its session on another connection
+    token: String,
+    /// When the connection was lost, if the client may still come back to its game
+    away: Option<Instant>,
+    /// Round trips of the last answered pings, oldest first
+    round_trips: Vec<Duration>,
+    /// The ping waiting for an answer, and when it was sent
+    ping: Option<(u64, Instant)>,
+    /// Names of the users whose chat messages the client doesn't get
+    muted: Vec<String>,
+}
+
+#[derive(Debug)]
+struct HostedGame {
+    variant: String,
+    /// The variant's definition, sent to the players along with the game
+    definition: String,
+    game: Game,
+    /// Moves played, written like in UCI
+    moves: Vec<String>,
+    time: Option<TimeControl>,
+    /// Clients playing white and black
+    players: [Option<u64>; 2],
+    /// Clients watching the game
+    spectators: Vec<u64>,
+    /// When both seats were taken, or `None` if the game hasn't started
+    started: Option<SystemTime>,
+    /// Names of white and black, kept once the game starts so that it can be stored after they leave
+    names: [String; 2],
+    /// The players' clocks, once a timed game starts
+    clock: Option<Clock>,
+}
+
+/// The clocks of a timed game
+#[derive(Debug)]
+struct Clock {
+    /// Time left to white and black, not counting the time since the running clock started
+    left: [Duration; 2],
+    /// The player whose clock is running, if any
+    running: Option<PlayerColor>,
+    /// When the running clock started
+    since: Instant,
+    /// Latency taken off white's and black's clocks so far
+    compensated: [Duration; 2],
+}
+
+impl Clock {
+    /// The part of `compensation` that may still be taken off a player's clock in a game with the given time
+    fn allowance(&self, color: PlayerColor, time: TimeControl, compensation: Duration) -> Duration {
+        let most = time.base / MAX_COMPENSATION_SHARE;
+        compensation.min(most.saturating_sub(self.compensated[seat(color)]))
+    }
+
+    /// The clocks as sent to clients, with the running one counted down to now
+    fn update(&self, game: u64) -> ClockUpdate {
+        let mut left = self.left;
+        if let Some(color) = self.running {
+            left[seat(color)] = left[seat(color)].saturating_sub(self.since.elapsed());
+        }
+        ClockUpdate {
+            game,
+            white: left[0],
+            black: left[1],
+            running: self.running,
+        }
+    }
+}
+
+impl HostedGame {
+    /// The clients to tell about the game: its players and spectators
+    fn audience(&self) -> Vec<u64> {
+        self.players
+            .iter()
+            .flatten()
+            .chain(&self.spectators)
+            .cloned()
+            .collect()
+    }
+
+    /// The game as stored, with its result if it is over
+    fn stored(&self, id: u64, result: Option<(GameResult, &str)>) -> Option<StoredGame> {
+        Some(StoredGame {
+            id,
+            variant: self.variant.clone(),
+            definition: self.definition.clone(),
+            players: self.names.clone(),
+            moves: self.moves.clone(),
+            result: result.map(|(result, _)| result),
+            reason: result.map(|(_, reason)| reason.to_string()),
+            started: self.started?,
+            ended: result.map(|_| SystemTime::now()),
+            time: self.time,
+        })
+    }
+}
+
+/// Number of frames waiting to be written to a client before it is cut off
+const OUTBOX_SIZE: usize = 256;
+/// How often clients are pinged to measure their latency
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+/// Number of round trips from which the latency of a client is estimated
+const ROUND_TRIPS_KEPT: usize = 8;
+/// Most latency taken off a player's clock over a game, as a share of the game's base time: a tenth
+const MAX_COMPENSATION_SHARE: u32 = 10;
+/// Number of messages kept by each chat channel for the clients who join it
+const CHAT_HISTORY: usize = 50;
+
+/// The server's state: connected clients and the games they play
+#[derive(Debug)]
+pub struct Host {
+    variants_dir: PathBuf,
+    /// Where games are kept once they start
+    store: GameStore,
+    /// How long a player who lost their connection has to come back before forfeiting
+    grace: Duration,
+    /// Most latency taken off the clock of a player for a move
+    max_lag: Duration,
+    next_ping: u64,
+    /// When clients were last pinged
+    pinged: Instant,
+    clients: HashMap<u64, Client>,
+    games: BTreeMap<u64, HostedGame>,
+    next_game: u64,
+    challenges: BTreeMap<u64, Challenge>,
+    next_challenge: u64,
+    /// The last messages of each chat channel
+    chats: HashMap<ChatChannel, VecDeque<ChatMessage>>,
+}
+
+impl Host {
+    pub fn new(
+        variants_dir: PathBuf,
+        store: GameStore,
+        grace: Duration,
+        max_lag: Duration,
+    ) -> StorageResult<Self> {
+        for (id, e) in store.unreadable() {
+            eprintln!("Couldn't read stored game {}: {}", id, e);
+        }
+        // ids of stored games aren't given again, even unreadable ones
+        let next_game = store.ids()?.last().map_or(1, |id| id + 1);
+        Ok(Host {
+            variants_dir,
+            store,
+            grace,
+            max_lag,
+            next_ping: 1,
+            pinged: Instant::now(),
+            clients: HashMap::new(),
+            games: BTreeMap::new(),
+            next_game,
+            challenges: BTreeMap::new(),
+            next_challenge: 1,
+            chats: HashMap::new(),
+        })
+    }
+
+    pub fn handle(&mut self, event: Event) {
+        match event {
+            Event::Connected(id, stream) => {
+                let outbox = match stream.try_clone() {
+                    Ok(writer) => spawn_writer(writer),
+                    Err(_) => return,
+                };
+                self.clients.insert(
+                    id,
+                    Client {
+                        stream,
+                        outbox,
+                        name: None,
+                        game: None,
+                        token: format!("{:032x}", rand::random::<u128>()),
+                        away: None,
+                        round_trips: Vec::new(),
+                        ping: None,
+                        muted: Vec::new(),
+                    },
+                );
+            }
+            Event::Message(id, message) => {
+                if let Err(e) = self.command(id, message) {
+                    self.send(id, &ServerMessage::Error(e));
+                }
+            }
+            Event::Invalid(id, e) => self.send(id, &ServerMessage::Error(e)),
+            Event::Disconnected(id) => self.disconnect(id),
+        }
+    }
+
+    /// Ends the games of the players out of time or who didn't come back in time, and pings clients now and then
+    pub fn tick(&mut self) {
+        let flagged: Vec<(u64, PlayerColor)> = self
+            .games
+            .iter()
+            .filter_map(|(&game_id, hosted)| {
+                let clock = hosted.clock.as_ref()?;
+                let color = clock.running?;
+                let compensation = self.compensation(hosted.players[seat(color)]?);
+                let allowance = clock.allowance(color, hosted.time?, compensation);
+                let spent = clock.since.elapsed().saturating_sub(allowance);
+                if spent >= clock.left[seat(color)] {
+                    Some((game_id, color))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for (game_id, color) in flagged {
+            self.end(game_id, loss(color), "time forfeit");
+        }
+
+        if self.pinged.elapsed() >= PING_INTERVAL {
+            self.pinged = Instant::now();
+            let clients: Vec<u64> = self
+                .clients
+                .iter()
+                .filter(|(_, client)| client.name.is_some() && client.away.is_none())
+                .map(|(&id, _)| id)
+                .collect();
+            for id in clients {
+                self.ping(id);
+            }
+        }
+
+        let grace = self.grace;
+        let expired: Vec<u64> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| matches!(client.away, Some(since) if since.elapsed() >= grace))
+            .map(|(&id, _)| id)
+            .collect();
+        for id in expired {
+            if let Some(client) = self.clients.remove(&id) {
+                if let Some(game_id) = client.game {
+                    self.leave(game_id, id);
+                }
+            }
+        }
+    }
+
+    fn command(&mut self, id: u64, message: ClientMessage) -> Result<(), String> {
+        let hello = matches!(
+            message,
+            ClientMessage::Hello { .. } | ClientMessage::Resume { .. }
+        );
+        if !hello && self.client(id)?.name.is_none() {
+            return Err(String::from("say hello first"));
+        }
+        match message {
+            ClientMessage::Hello { version, name } => {
+                check_version(version)?;
+                let client = self.client_mut(id)?;
+                client.name = Some(name);
+                let token = client.token.clone();
+                self.send(
+                    id,
+                    &ServerMessage::Welcome {
+                        version: PROTOCOL_VERSION,
+                        client: id,
+                        token,
+                    },
+                );
+                self.send_history(id, ChatChannel::Lobby);
+                self.ping(id);
+            }
+            ClientMessage::Resume { version, token } => {
+                check_version(version)?;
+                self.resume(id, &token)?;
+                self.ping(id);
+            }
+            ClientMessage::Pong { ping } => {
+                let client = self.client_mut(id)?;
+                if let Some((sent, at)) = client.ping {
+                    if sent == ping {
+                        client.round_trips.push(at.elapsed());
+                        if client.round_trips.len() > ROUND_TRIPS_KEPT {
+                            client.round_trips.remove(0);
+                        }
+                        client.ping = None;
+                    }
+                }
+            }
+            ClientMessage::Chat { channel, text } => self.chat(id, channel, &text)?,
+            ClientMessage::Mute { name } => {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(String::from("no one to mute"));
+                }
+                let client = self.client_mut(id)?;
+                if !client.muted.iter().any(|muted| muted == name) {
+                    client.muted.push(name.to_string());
+                }
+                let muted = client.muted.clone();
+                self.send(id, &ServerMessage::Muted(muted));
+            }
+            ClientMessage::Unmute { name } => {
+                let client = self.client_mut(id)?;
+                client.muted.retain(|muted| muted != name.trim());
+                let muted = client.muted.clone();
+                self.send(id, &ServerMessage::Muted(muted));
+            }
+            ClientMessage::CreateGame {
+                variant,
+                color,
+                time,
+            } => self.create(id, &variant, color, time)?,
+            ClientMessage::JoinGame { game } => self.join(id, game)?,
+            ClientMessage::Move { game, mv } => {
+                let watching = self
+                    .games
+                    .get(&game)
+                    .map(|hosted| hosted.spectators.contains(&id));
+                if watching == Some(true) {
+                    return Err(String::from("spectators can't move"));
+                }
+                self.playing(id, game)?;
+                self.play(id, game, &mv)?;
+            }
+            ClientMessage::Resign { game } => {
+                self.playing(id, game)?;
+                self.leave(game, id);
+            }
+            ClientMessage::ListGames => {
+                let games = self.open_games();
+                self.send(id, &ServerMessage::GameList(games));
+            }
+            ClientMessage::Lobby => {
+                let lobby = self.lobby(id);
+                self.send(id, &ServerMessage::Lobby(lobby));
+            }
+            ClientMessage::Challenge {
+                to,
+                variant,
+                color,
+                time,
+            } => self.challenge(id, to, variant, color, time)?,
+            ClientMessage::AcceptChallenge { challenge } => self.accept(id, challenge)?,
+            ClientMessage::DeclineChallenge { challenge } => {
+                let reason = match self.challenges.get(&challenge) {
+                    Some(found) if found.to == id => "declined",
+                    Some(found) if found.from == id => "withdrawn",
+                    _ => return Err(format!("unknown challenge {}", challenge)),
+                };
+                self.close_challenge(challenge, reason);
+            }
+            ClientMessage::Watch { game } => self.watch(id, game)?,
+            ClientMessage::StopWatching { game } => {
+                let watching = self
+                    .games
+                    .get(&game)
+                    .map(|hosted| hosted.spectators.contains(&id));
+                if watching != Some(true) {
+                    return Err(String::from("not watching this game"));
+                }
+                self.unwatch(game, id);
+            }
+            ClientMessage::History(query) => {
+                let games = self.store.find(&query);
+                self.send(id, &ServerMessage::History(games));
+            }
+            ClientMessage::Export { game, format } => {
+                let text = self
+                    .store
+                    .load(game)
+                    .and_then(|stored| stored.export(format))
+                    .map_err(|e| e.to_string())?;
+                self.send(id, &ServerMessage::Export { game, format, text });
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a lost connection: a player of a running game may come back, other clients are gone
+    fn disconnect(&mut self, id: u64) {
+        let challenges: Vec<u64> = self
+            .challenges
+            .values()
+            .filter(|challenge| challenge.from == id || challenge.to == id)
+            .map(|challenge| challenge.id)
+            .collect();
+        for challenge in challenges {
+            self.close_challenge(challenge, "the player left");
+        }
+        let watched: Vec<u64> = self
+            .games
+            .iter()
+            .filter(|(_, hosted)| hosted.spectators.contains(&id))
+            .map(|(&game_id, _)| game_id)
+            .collect();
+        for game_id in watched {
+            self.unwatch(game_id, id);
+        }
+
+        let game_id = match self.clients.get(&id) {
+            Some(client) => client.game,
+            None => return,
+        };
+        let running = game_id.and_then(|game_id| {
+            let hosted = self.games.get(&game_id)?;
+            hosted.started?;
+            let color = if hosted.players[0] == Some(id) {
+                PlayerColor::White
+            } else {
+                PlayerColor::Black
+            };
+            Some((game_id, color, hosted.audience()))
+        });
+        match running {
+            Some((game_id, color, audience)) => {
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.away = Some(Instant::now());
+                }
+                let message = ServerMessage::PlayerAway {
+                    game: game_id,
+                    color,
+                    grace: self.grace,
+                };
+                for client in audience.into_iter().filter(|&client| client != id) {
+                    self.send(client, &message);
+                }
+            }
+            None => {
+                self.clients.remove(&id);
+                if let Some(game_id) = game_id {
+                    self.leave(game_id, id);
+                }
+            }
+        }
+    }
+
+    /// Moves the session with the given token onto the connection of a new client
+    fn resume(&mut self, id: u64, token: &str) -> Result<(), String> {
+        if self.client(id)?.name.is_some() {
+            return Err(String::from("already introduced"));
+        }
+        let old = self
+            .clients
+            .iter()
+            .find(|(&other, client)| other != id && client.token == token)
+            .map(|(&other, _)| other)
+            .ok_or_else(|| String::from("unknown session"))?;
+        let old_client = match self.clients.remove(&old) {
+            Some(client) => client,
+            None => return Err(String::from("unknown session")),
+        };
+        // the old connection may not have noticed it was replaced
+        let _ = old_client.stream.shutdown(Shutdown::Both);
+        let client = self.client_mut(id)?;
+        client.name = old_client.name;
+        client.game = old_client.game;
+        client.token = old_client.token;
+        client.muted = old_client.muted;
+        for hosted in self.games.values_mut() {
+            for player in hosted
+                .players
+                .iter_mut()
+                .filter(|player| **player == Some(old))
+            {
+                *player = Some(id);
+            }
+            for spectator in hosted
+                .spectators
+                .iter_mut()
+                .filter(|spectator| **spectator == old)
+            {
+                *spectator = id;
+            }
+        }
+        for challenge in self.challenges.values_mut() {
+            if challenge.from == old {
+                challenge.from = id;
+            }
+            if challenge.to == old {
+                challenge.to = id;
+            }
+        }
+
+        self.send(
+            id,
+            &ServerMessage::Welcome {
+                version: PROTOCOL_VERSION,
+                client: id,
+                token: token.to_string(),
+            },
+        );
+        self.send_history(id, ChatChannel::Lobby);
+        let game_id = match old_client.game {
+            Some(game_id) => game_id,
+            None => return Ok(()),
+        };
+        let (color, audience) = match self.games.get(&game_id) {
+            Some(hosted) if hosted.players[0] == Some(id) => {
+                (PlayerColor::White, hosted.audience())
+            }
+            Some(hosted) => (PlayerColor::Black, hosted.audience()),
+            None => return Ok(()),
+        };
+        if let Some(snapshot) = self.snapshot(game_id, Some(color)) {
+            self.send(id, &ServerMessage::Snapshot(snapshot));
+        }
+        self.send_history(id, ChatChannel::Players(game_id));
+        if old_client.away.is_some() {
+            let message = ServerMessage::PlayerBack {
+                game: game_id,
+                color,
+            };
+            for client in audience.into_iter().filter(|&client| client != id) {
+                self.send(client, &message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a ping to a client, whose answer measures its latency
+    fn ping(&mut self, id: u64) {
+        let ping = self.next_ping;
+        self.next_ping += 1;
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.ping = Some((ping, Instant::now()));
+        }
+        self.send(id, &ServerMessage::Ping { ping });
+    }
+
+    /**
+    Time taken off the clock of a client for each move, for the latency of its connection:
+    half its fastest recent round trip, so that answering pings slowly doesn't buy much time.
+    **/
+    fn compensation(&self, id: u64) -> Duration {
+        self.clients
+            .get(&id)
+            .and_then(|client| client.round_trips.iter().min())
+            .map(|round_trip| *round_trip / 2)
+            .unwrap_or_default()
+            .min(self.max_lag)
+    }
+
+    /// Says a chat message, if the client is in the channel and the message isn't too long
+    fn chat(&mut self, id: u64, channel: ChatChannel, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(String::from("empty message"));
+        }
+        if text.chars().count() > MAX_CHAT_LENGTH {
+            return Err(format!(
+                "messages are limited to {} characters",
+                MAX_CHAT_LENGTH
+            ));
+        }
+        let audience = self.chat_audience(channel);
+        if !audience.contains(&id) {
+            return Err(String::from("not in this chat"));
+        }
+        let message = ChatMessage {
+            channel,
+            from: id,
+            from_name: self.client(id)?.name.clone().unwrap_or_default(),
+            text: text.to_string(),
+            sent: SystemTime::now(),
+        };
+        let history = self.chats.entry(channel).or_default();
+        history.push_back(message.clone());
+        if history.len() > CHAT_HISTORY {
+            history.pop_front();
+        }
+        for client in audience {
+            if !self.mutes(client, &message.from_name) {
+                self.send(client, &ServerMessage::Chat(message.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The clients who hear a chat channel, and may speak on it
+    fn chat_audience(&self, channel: ChatChannel) -> Vec<u64> {
+        match channel {
+            ChatChannel::Lobby => self
+                .clients
+                .iter()
+                .filter(|(_, client)| client.name.is_some() && client.away.is_none())
+                .map(|(&id, _)| id)
+                .collect(),
+            ChatChannel::Players(game_id) => self
+                .games
+                .get(&game_id)
+                .map(|hosted| hosted.players.iter().flatten().cloned().collect())
+                .unwrap_or_default(),
+            ChatChannel::Spectators(game_id) => self
+                .games
+                .get(&game_id)
+                .map(|hosted| hosted.spectators.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Sends the last messages of a chat channel, but those of the users muted by the client
+    fn send_history(&mut self, id: u64, channel: ChatChannel) {
+        let messages = match self.chats.get(&channel) {
+            Some(history) => history
+                .iter()
+                .filter(|message| !self.mutes(id, &message.from_name))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        self.send(id, &ServerMessage::ChatHistory { channel, messages });
+    }
+
+    /// Whether a client muted the given name
+    fn mutes(&self, id: u64, name: &str) -> bool {
+        matches!(self.clients.get(&id), Some(client) if client.muted.iter().any(|muted| muted == name))
+    }
+
+    /// Checks that a client plays in the given game
+    fn playing(&self, id: u64, game_id: u64) -> Result<(), String> {
+        match self.client(id)?.game {
+            Some(game) if game == game_id => Ok(()),
+            _ => Err(String::from("not in this game")),
+        }
+    }
+
+    fn create(
+        &mut self,
+        id: u64,
+        variant: &str,
+        color: ColorChoice,
+        time: Option<TimeControl>,
+    ) -> Result<(), String> {
+        if self.client(id)?.game.is_some() {
+            return Err(String::from("already in a game"));
+        }
+        validate_time(time)?;
+        let (definition, game) = self.load_variant(variant)?;
+        let mut players = [None, None];
+        players[seat(pick(color))] = Some(id);
+        let game_id = self.add_game(variant, definition, game, time, players);
+        self.client_mut(id)?.game = Some(game_id);
+        self.send(id, &ServerMessage::GameCreated { game: game_id });
+        Ok(())
+    }
+
+    fn join(&mut self, id: u64, game_id: u64) -> Result<(), String> {
+        if self.client(id)?.game.is_some() {
+            return Err(String::from("already in a game"));
+        }
+        let hosted = self
+            .games
+            .get_mut(&game_id)
+            .ok_or_else(|| format!("unknown game {}", game_id))?;
+        let free = hosted
+            .players
+            .iter()
+            .position(|player| player.is_none())
+            .ok_or("the game is full")?;
+        hosted.players[free] = Some(id);
+        self.client_mut(id)?.game = Some(game_id);
+        self.start(game_id);
+        Ok(())
+    }
+
+    fn challenge(
+        &mut self,
+        id: u64,
+        to: u64,
+        variant: String,
+        color: ColorChoice,
+        time: Option<TimeControl>,
+    ) -> Result<(), String> {
+        if self.client(id)?.game.is_some() {
+            return Err(String::from("already in a game"));
+        }
+        validate_time(time)?;
+        let to_name = match self.clients.get(&to) {
+            Some(Client {
+                name: Some(name),
+                away: None,
+                ..
+            }) if to != id => name.clone(),
+            _ => return Err(format!("unknown user {}", to)),
+        };
+        // the variant is checked now rather than when the game starts
+        self.load_variant(&variant)?;
+        let challenge = Challenge {
+            id: self.next_challenge,
+            from: id,
+            from_name: self.client(id)?.name.clone().unwrap_or_default(),
+            to,
+            to_name,
+            variant,
+            color,
+            time,
+        };
+        self.next_challenge += 1;
+        let message = ServerMessage::Challenge(challenge.clone());
+        self.challenges.insert(challenge.id, challenge);
+        self.send(id, &message);
+        self.send(to, &message);
+        Ok(())
+    }
+
+    /// Starts the game of a challenge, if both players are free
+    fn accept(&mut self, id: u64, challenge_id: u64) -> Result<(), String> {
+        let challenge = match self.challenges.get(&challenge_id) {
+            Some(challenge) if challenge.to == id => challenge.clone(),
+            _ => return Err(format!("unknown challenge {}", challenge_id)),
+        };
+        if self.client(id)?.game.is_some() {
+            return Err(String::from("already in a game"));
+        }
+        if self.client(challenge.from)?.game.is_some() {
+            return Err(format!("{} is already in a game", challenge.from_name));
+        }
+        let (definition, game) = self.load_variant(&challenge.variant)?;
+        self.challenges.remove(&challenge_id);
+
+        let mut players = [Some(id), Some(id)];
+        players[seat(pick(challenge.color))] = Some(challenge.from);
+        let game_id = self.add_game(
+            &challenge.variant,
+            definition,
+            game,
+            challenge.time,
+            players,
+        );
+        for player in &[id, challenge.from] {
+            self.client_mut(*player)?.game = Some(game_id);
+        }
+        self.start(game_id);
+        Ok(())
+    }
+
+    /// Removes a challenge, telling both players why
+    fn close_challenge(&mut self, challenge_id: u64, reason: &str) {
+        if let Some(challenge) = self.challenges.remove(&challenge_id) {
+            let message = ServerMessage::ChallengeClosed {
+                challenge: challenge_id,
+                reason: reason.to_string(),
+            };
+            self.send(challenge.from, &message);
+            self.send(challenge.to, &message);
+        }
+    }
+
+    /// Reads a variant's definition from the variants directory, and builds its game
+    fn load_variant(&self, variant: &str) -> Result<(String, Game), String> {
+        // variant names are file names, which must not lead out of the variants directory
+        if !variant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!("unknown variant {}", variant));
+        }
+        let path = self.variants_dir.join(format!("{}.toml", variant));
+        let definition =
+            fs::read_to_string(&path).map_err(|_| format!("unknown variant {}", variant))?;
+        let game = definition
+            .parse::<Variant>()
+            .and_then(|variant| variant.game())
+            .map_err(|_| format!("unknown variant {}", variant))?;
+        Ok((definition, game))
+    }
+
+    fn add_game(
+        &mut self,
+        variant: &str,
+        definition: String,
+        game: Game,
+        time: Option<TimeControl>,
+        players: [Option<u64>; 2],
+    ) -> u64 {
+        let game_id = self.next_game;
+        self.next_game += 1;
+        self.games.insert(
+            game_id,
+            HostedGame {
+                variant: variant.to_string(),
+                definition,
+                game,
+                moves: Vec::new(),
+                time,
+                players,
+                spectators: Vec::new(),
+                started: None,
+                names: Default::default(),
+                clock: None,
+            },
+        );
+        game_id
+    }
+
+    /// Sends a game, whose seats are all taken, to its players
+    fn start(&mut self, game_id: u64) {
+        let names = match self.games.get(&game_id) {
+            Some(hosted) => hosted.players.map(|player| {
+                player
+                    .and_then(|player| self.clients.get(&player)?.name.clone())
+                    .unwrap_or_default()
+            }),
+            None => return,
+        };
+        if let Some(hosted) = self.games.get_mut(&game_id) {
+            hosted.started = Some(SystemTime::now());
+            hosted.names = names;
+            hosted.clock = hosted.time.map(|time| Clock {
+                left: [time.base; 2],
+                running: hosted.game.current_player().map(|player| player.color),
+                since: Instant::now(),
+                compensated: [Duration::default(); 2],
+            });
+        }
+        self.save(game_id, None);
+        let messages: Vec<(u64, ServerMessage)> = match self.games.get(&game_id) {
+            Some(hosted) => [PlayerColor::White, PlayerColor::Black]
+                .iter()
+                .filter_map(|&color| {
+                    let snapshot = self.snapshot(game_id, Some(color))?;
+                    Some((
+                        hosted.players[seat(color)]?,
+                        ServerMessage::Snapshot(snapshot),
+                    ))
+                })
+                .collect(),
+            None => return,
+        };
+        for (player, message) in messages {
+            self.send(player, &message);
+        }
+    }
+
+    /// The state of a game, for one of its players or, without a color, for a spectator
+    fn snapshot(&self, game_id: u64, color: Option<PlayerColor>) -> Option<Snapshot> {
+        let hosted = self.games.get(&game_id)?;
+        let names = [0, 1].map(|seat| {
+            hosted.players[seat].and_then(|player| self.clients.get(&player)?.name.clone())
+        });
+        let clock = hosted.clock.as_ref().map(|clock| clock.update(game_id));
+        Some(Snapshot {
+            game: game_id,
+            variant: hosted.variant.clone(),
+            definition: hosted.definition.clone(),
+            players: names,
+            color,
+            moves: hosted.moves.clone(),
+            time: hosted.time,
+            clock,
+            checksum: checksum(&hosted.game),
+        })
+    }
+
+    /// Adds a spectator to a running game, sending them its state
+    fn watch(&mut self, id: u64, game_id: u64) -> Result<(), String> {
+        let hosted = self
+            .games
+            .get_mut(&game_id)
+            .ok_or_else(|| format!("unknown game {}", game_id))?;
+        if hosted.players.contains(&None) {
+            return Err(String::from("the game hasn't started"));
+        }
+        if hosted.players.contains(&Some(id)) {
+            return Err(String::from("already playing this game"));
+        }
+        if hosted.spectators.contains(&id) {
+            return Err(String::from("already watching this game"));
+        }
+        hosted.spectators.push(id);
+        if let Some(snapshot) = self.snapshot(game_id, None) {
+            self.send(id, &ServerMessage::Snapshot(snapshot));
+        }
+        self.send_history(id, ChatChannel::Spectators(game_id));
+        self.send_spectators(game_id);
+        Ok(())
+    }
+
+    fn unwatch(&mut self, game_id: u64, id: u64) {
+        if let Some(hosted) = self.games.get_mut(&game_id) {
+            hosted.spectators.retain(|&spectator| spectator != id);
+            self.send_spectators(game_id);
+        }
+    }
+
+    /// Tells the players and spectators of a game how many spectators it has
+    fn send_spectators(&mut self, game_id: u64) {
+        let hosted = match self.games.get(&game_id) {
+            Some(hosted) => hosted,
+            None => return,
+        };
+        let message = ServerMessage::Spectators {
+            game: game_id,
+            count: hosted.spectators.len() as u32,
+        };
+        for client in hosted.audience() {
+            self.send(client, &message);
+        }
+    }
+
+    /// The users, open games, and the challenges sent to or by the given client
+    fn lobby(&self, id: u64) -> Lobby {
+        let mut users: Vec<LobbyUser> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.away.is_none())
+            .filter_map(|(&user, client)| {
+                Some(LobbyUser {
+                    id: user,
+                    name: client.name.clone()?,
+                    game: client.game,
+                })
+            })
+            .collect();
+        users.sort_by_key(|user| user.id);
+        Lobby {
+            users,
+            games: self.open_games(),
+            running: self.running_games(),
+            challenges: self
+                .challenges
+                .values()
+                .filter(|challenge| challenge.from == id || challenge.to == id)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Games with a free seat, oldest first
+    fn open_games(&self) -> Vec<OpenGame> {
+        self.games
+            .iter()
+            .filter_map(|(&game, hosted)| {
+                let (host, color) = match hosted.players {
+                    [Some(host), None] => (host, PlayerColor::Black),
+                    [None, Some(host)] => (host, PlayerColor::White),
+                    _ => return None,
+                };
+                Some(OpenGame {
+                    game,
+                    variant: hosted.variant.clone(),
+                    host: self.clients.get(&host)?.name.clone()?,
+                    color,
+                    time: hosted.time,
+                })
+            })
+            .collect()
+    }
+
+    /// Games being played, oldest first
+    fn running_games(&self) -> Vec<RunningGame> {
+        self.games
+            .iter()
+            .filter_map(|(&game, hosted)| {
+                let name = |player: Option<u64>| self.clients.get(&player?)?.name.clone();
+                Some(RunningGame {
+                    game,
+                    variant: hosted.variant.clone(),
+                    players: [name(hosted.players[0])?, name(hosted.players[1])?],
+                    spectators: hosted.spectators.len() as u32,
+                })
+            })
+            .collect()
+    }
+
+    /// Plays a move submitted by a player, if it is legal and the player's turn
+    fn play(&mut self, id: u64, game_id: u64, text: &str) -> Result<(), String> {
+        let compensation = self.compensation(id);
+        let hosted = self
+            .games
+            .get_mut(&game_id)
+            .ok_or_else(|| format!("unknown game {}", game_id))?;
+        let reason = if hosted.players.contains(&None) {
+            Some("the game hasn't started")
+        } else {
+            match hosted.game.current_player() {
+                Some(player) if hosted.players[seat(player.color)] == Some(id) => None,
+                _ => Some("not your turn"),
+            }
+        };
+        let mv = match reason {
+            Some(reason) => Err(reason),
+            None => hosted.game.parse_uci_move(text).map_err(|_| "illegal move"),
+        };
+        let mv = match mv {
+            Ok(mv) => mv,
+            Err(reason) => {
+                let message = ServerMessage::MoveRejected {
+                    game: game_id,
+                    mv: text.to_string(),
+                    reason: reason.to_string(),
+                };
+                self.send(id, &message);
+                return Ok(());
+            }
+        };
+
+        // the move's time is counted from when the clock started to when the move arrived, less the latency
+        let color = hosted.game.current_player().map(|player| player.color);
+        if let (Some(clock), Some(time), Some(color)) = (hosted.clock.as_mut(), hosted.time, color)
+        {
+            let elapsed = clock.since.elapsed();
+            let spent = elapsed.saturating_sub(clock.allowance(color, time, compensation));
+            if spent >= clock.left[seat(color)] {
+                self.end(game_id, loss(color), "time forfeit");
+                return Ok(());
+            }
+            clock.compensated[seat(color)] += elapsed - spent;
+            let left = &mut clock.left[seat(color)];
+            *left = (*left - spent).saturating_add(time.increment);
+        }
+
+        hosted.game.play(&mv);
+        hosted.moves.push(text.to_string());
+        if let Some(clock) = hosted.clock.as_mut() {
+            clock.running = hosted.game.current_player().map(|player| player.color);
+            clock.since = Instant::now();
+        }
+        let result = game_result(&hosted.game);
+        let audience = hosted.audience();
+        let message = ServerMessage::Move {
+            game: game_id,
+            mv: text.to_string(),
+            checksum: checksum(&hosted.game),
+        };
+        let clock = hosted
+            .clock
+            .as_ref()
+            .map(|clock| ServerMessage::Clock(clock.update(game_id)));
+        self.save(game_id, None);
+        for client in audience {
+            self.send(client, &message);
+            if let Some(clock) = &clock {
+                self.send(client, clock);
+            }
+        }
+        if let Some((result, reason)) = result {
+            self.end(game_id, result, reason);
+        }
+        Ok(())
+    }
+
+    /// Removes a player from a game, who loses it if it had started
+    fn leave(&mut self, game_id: u64, id: u64) {
+        let players = match self.games.get(&game_id) {
+            Some(hosted) => hosted.players,
+            None => return,
+        };
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.game = None;
+        }
+        if players.contains(&None) {
+            // the game hasn't started, and nobody else is there to be told
+            self.remove_game(game_id);
+            return;
+        }
+        let result = if players[0] == Some(id) {
+            GameResult::BlackWins
+        } else {
+            GameResult::WhiteWins
+        };
+        self.end(game_id, result, "forfeit");
+    }
+
+    /// Ends a game, telling its players and spectators the result
+    fn end(&mut self, game_id: u64, result: GameResult, reason: &str) {
+        self.save(game_id, Some((result, reason)));
+        let hosted = match self.remove_game(game_id) {
+            Some(hosted) => hosted,
+            None => return,
+        };
+        let message = ServerMessage::GameOver {
+            game: game_id,
+            result,
+            reason: reason.to_string(),
+        };
+        for client in hosted.audience() {
+            self.send(client, &message);
+        }
+        for player in hosted.players.iter().flatten() {
+            if let Some(client) = self.clients.get_mut(player) {
+                client.game = None;
+            }
+        }
+    }
+
+    /// Stores a game that has started; the server goes on if it can't
+    fn save(&mut self, game_id: u64, result: Option<(GameResult, &str)>) {
+        let stored = match self.games.get(&game_id) {
+            Some(hosted) => hosted.stored(game_id, result),
+            None => None,
+        };
+        if let Some(stored) = stored {
+            if let Err(e) = self.store.save(&stored) {
+                eprintln!("Couldn't store game {}: {}", game_id, e);
+            }
+        }
+    }
+
+    /// Removes a game along with its chat channels
+    fn remove_game(&mut self, game_id: u64) -> Option<HostedGame> {
+        self.chats.remove(&ChatChannel::Players(game_id));
+        self.chats.remove(&ChatChannel::Spectators(game_id));
+        self.games.remove(&game_id)
+    }
+
+    fn client(&self, id: u64) -> Result<&Client, String> {
+        self.clients
+            .get(&id)
+            .ok_or_else(|| String::from("unknown client"))
+    }
+
+    fn client_mut(&mut self, id: u64) -> Result<&mut Client, String> {
+        self.clients
+            .get_mut(&id)
+            .ok_or_else(|| String::from("unknown client"))
+    }
+
+    /**
+    Sends a message to a client, without waiting for it to be written.
+    A client that doesn't keep up with its messages is cut off, and then handled like a lost connection.
+    **/
+    fn send(&mut self, id: u64, message: &ServerMessage) {
+        let client = match self.clients.get(&id) {
+            Some(client) => client,
+            None => return,
+        };
+        let frame = match encode(message) {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("Couldn't send a message to client {}: {}", id, e);
+                return;
+            }
+        };
+        if client.outbox.try_send(frame).is_err() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Writes the frames sent to a connection on their own thread, until it closes
+fn spawn_writer(mut stream: TcpStream) -> SyncSender<Vec<u8>> {
+    let (outbox, frames) = mpsc::sync_channel::<Vec<u8>>(OUTBOX_SIZE);
+    thread::spawn(move || {
+        for frame in frames {
+            if stream
+                .write_all(&frame)
+                .and_then(|()| stream.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    outbox
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version != PROTOCOL_VERSION {
+        return Err(format!(
+            "unsupported protocol version {}, expected {}",
+            version, PROTOCOL_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the time control of a new game; untimed games are fine
+fn validate_time(time: Option<TimeControl>) -> Result<(), String> {
+    match time {
+        Some(time) => time.validate().map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+/// The color given by a choice, drawing it if random
+fn pick(color: ColorChoice) -> PlayerColor {
+    match color {
+        ColorChoice::White => PlayerColor::White,
+        ColorChoice::Black => PlayerColor::Black,
+        ColorChoice::Random if rand::random() => PlayerColor::White,
+        ColorChoice::Random => PlayerColor::Black,
+    }
+}
+
+/// The result of a game lost by the given player
+fn loss(color: PlayerColor) -> GameResult {
+    match color {
+        PlayerColor::White => GameResult::BlackWins,
+        PlayerColor::Black => GameResult::WhiteWins,
+    }
+}
+
+/// Index of a color's seat in a game
+fn seat(color: PlayerColor) -> usize {
+    match color {
+        PlayerColor::White => 0,
+        PlayerColor::Black => 1,
+    }
+}
+
+/// The result of a game and its reason, if it is over
+fn game_result(game: &Game) -> Option<(GameResult, &'static str)> {
+    Some(match game.result()? {
+        Outcome::Win(PlayerColor::White) => (GameResult::WhiteWins, "checkmate"),
+        Outcome::Win(PlayerColor::Black) => (GameResult::BlackWins, "checkmate"),
+        Outcome::Draw => (GameResult::Draw, "stalemate"),
+    })
+}
@@ -1,5 +1,110 @@
 // The ~server~ (the thing that sends packets & stuff)
 
+use host::{Event, Host};
+use sharmat::protocol::{read_frame, ClientMessage, ProtocolError};
+use sharmat::storage::GameStore;
+use std::env;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+pub mod host;
+
+const DEFAULT_ADDRESS: &str = "0.0.0.0:7070";
+const DEFAULT_GAMES_DIR: &str = "games";
+/// Seconds a player who lost their connection has to come back
+const DEFAULT_GRACE: u64 = 60;
+/// Milliseconds of network latency taken off a player's clock for each move, at most
+const DEFAULT_MAX_LAG: u64 = 500;
+/// How often the host checks for players who didn't come back
+const TICK: Duration = Duration::from_millis(100);
+
 fn main() {
-    println!("Hello, world! I am the server!");
+    let variants_dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("{}/variants", env!("CARGO_MANIFEST_DIR"))));
+    let address = env::args()
+        .nth(2)
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let games_dir = env::args()
+        .nth(3)
+        .unwrap_or_else(|| DEFAULT_GAMES_DIR.to_string());
+    let grace = Duration::from_secs(number(4, DEFAULT_GRACE, "The grace period in seconds"));
+    let max_lag = Duration::from_millis(number(
+        5,
+        DEFAULT_MAX_LAG,
+        "The latency compensation in milliseconds",
+    ));
+    let mut host = GameStore::open(&games_dir)
+        .and_then(|store| Host::new(variants_dir, store, grace, max_lag))
+        .unwrap_or_else(|e| {
+            eprintln!("Couldn't open the games in {}: {}", games_dir, e);
+            process::exit(1);
+        });
+    let listener = TcpListener::bind(&address).unwrap_or_else(|e| {
+        eprintln!("Couldn't listen on {}: {}", address, e);
+        process::exit(1);
+    });
+    if let Ok(address) = listener.local_addr() {
+        println!("Listening on {}", address);
+    }
+
+    // connections are read on their own threads, and handled one event at a time by the host
+    let (events, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for (stream, id) in listener
+            .incoming()
+            .filter_map(|stream| stream.ok())
+            .zip(0..)
+        {
+            let mut reader = match stream.try_clone() {
+                Ok(reader) => reader,
+                Err(_) => continue,
+            };
+            let _ = stream.set_nodelay(true);
+            if events.send(Event::Connected(id, stream)).is_err() {
+                break;
+            }
+            let events = events.clone();
+            thread::spawn(move || {
+                loop {
+                    let event = match read_frame::<_, ClientMessage>(&mut reader) {
+                        Ok(message) => Event::Message(id, message),
+                        // the frame was read whole, so the next one can still be
+                        Err(ProtocolError::Encoding(e)) => Event::Invalid(id, e),
+                        Err(_) => break,
+                    };
+                    if events.send(event).is_err() {
+                        return;
+                    }
+                }
+                let _ = events.send(Event::Disconnected(id));
+            });
+        }
+    });
+
+    loop {
+        match receiver.recv_timeout(TICK) {
+            Ok(event) => host.handle(event),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        host.tick();
+    }
+}
+
+/// The number given as the argument at the given position, or its default
+fn number(position: usize, default: u64, description: &str) -> u64 {
+    match env::args().nth(position).map(|arg| arg.parse()) {
+        None => default,
+        Some(Ok(number)) => number,
+        Some(Err(_)) => {
+            eprintln!("{} must be a number", description);
+            process::exit(1);
+        }
+    }
 }
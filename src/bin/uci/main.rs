@@ -0,0 +1,201 @@
+// The ~UCI adapter~ (the thing that lets chess GUIs talk to the engine)
+
+use sharmat::adapter::*;
+use sharmat::engine::{Engine, SearchInfo};
+use sharmat::game::Game;
+use sharmat::uci::*;
+use std::env;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const DEFAULT_VARIANT: &str = "standard";
+
+struct Adapter {
+    variants_dir: PathBuf,
+    start: Game,
+    game: Game,
+    hash: usize,
+    mobility: i32,
+    /// The engine, or `None` while it is searching
+    engine: Option<Engine>,
+    search: Option<Search>,
+}
+
+fn main() {
+    let variants_dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("{}/variants", env!("CARGO_MANIFEST_DIR"))));
+    let mut adapter = Adapter::new(variants_dir).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match line.parse::<UciCommand>() {
+            Ok(UciCommand::Quit) => break,
+            Ok(command) => adapter.handle(command),
+            Err(UciError::Empty) | Err(UciError::UnknownCommand(_)) => {}
+            Err(e) => println!("info string {}", e),
+        }
+    }
+    adapter.stop();
+}
+
+impl Adapter {
+    fn new(variants_dir: PathBuf) -> Result<Self, String> {
+        let start = named_variant(&variants_dir, DEFAULT_VARIANT)?;
+        let engine = build_engine(&start, DEFAULT_HASH, DEFAULT_MOBILITY);
+        Ok(Adapter {
+            variants_dir,
+            game: start.clone(),
+            start,
+            hash: DEFAULT_HASH,
+            mobility: DEFAULT_MOBILITY,
+            engine: Some(engine),
+            search: None,
+        })
+    }
+
+    fn handle(&mut self, command: UciCommand) {
+        match command {
+            UciCommand::Uci => {
+                println!("id name Sharmat {}", env!("CARGO_PKG_VERSION"));
+                println!("id author {}", env!("CARGO_PKG_AUTHORS"));
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH, MAX_HASH
+                );
+                println!(
+                    "option name Mobility type spin default {} min 0 max 100",
+                    DEFAULT_MOBILITY
+                );
+                let vars: Vec<String> = variant_names(&self.variants_dir)
+                    .iter()
+                    .map(|name| format!(" var {}", name))
+                    .collect();
+                println!(
+                    "option name UCI_Variant type combo default {}{}",
+                    DEFAULT_VARIANT,
+                    vars.concat()
+                );
+                println!("uciok");
+            }
+            UciCommand::IsReady => println!("readyok"),
+            UciCommand::SetOption { name, value } => {
+                self.stop();
+                if let Err(e) = self.set_option(&name, value.as_deref().unwrap_or("")) {
+                    println!("info string {}", e);
+                }
+            }
+            UciCommand::UciNewGame => {
+                self.stop();
+                if let Some(engine) = self.engine.as_mut() {
+                    engine.clear();
+                }
+                self.game = self.start.clone();
+            }
+            UciCommand::Position { fen, moves } => {
+                self.stop();
+                if let Err(e) = self.set_position(fen.as_deref(), &moves) {
+                    println!("info string {}", e);
+                }
+            }
+            UciCommand::Go(go) => self.go(go),
+            UciCommand::Stop => self.stop(),
+            UciCommand::PonderHit => {
+                if let Some(search) = &self.search {
+                    search.ponder_hit();
+                }
+            }
+            UciCommand::Debug(_) | UciCommand::Quit => {}
+        }
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("invalid value {}", value))
+        };
+        match name.to_lowercase().as_str() {
+            "hash" => self.hash = number()?.clamp(1, MAX_HASH),
+            "mobility" => self.mobility = number()?.min(100) as i32,
+            "uci_variant" => {
+                let name = if value == "chess" {
+                    DEFAULT_VARIANT
+                } else {
+                    value
+                };
+                self.start = named_variant(&self.variants_dir, name)?;
+                self.game = self.start.clone();
+            }
+            _ => return Err(format!("unknown option {}", name)),
+        }
+        self.engine = Some(build_engine(&self.start, self.hash, self.mobility));
+        Ok(())
+    }
+
+    fn set_position(&mut self, fen: Option<&str>, moves: &[String]) -> Result<(), String> {
+        self.game = self.start.clone();
+        if let Some(fen) = fen {
+            // variants with drops are set up from SFEN
+            if let Err(e) = self.game.load_fen(fen) {
+                self.game
+                    .load_sfen(fen)
+                    .map_err(|_| format!("invalid position {}: {}", fen, e))?;
+            }
+        }
+        for text in moves {
+            let mv = self.game.parse_uci_move(text).map_err(|e| e.to_string())?;
+            self.game.play(&mv);
+        }
+        Ok(())
+    }
+
+    fn go(&mut self, go: Go) {
+        self.stop();
+        let color = match self.game.current_player() {
+            Some(player) => player.color,
+            None => return,
+        };
+        let engine = match self.engine.take() {
+            Some(engine) => engine,
+            None => return,
+        };
+        // infinite and pondering searches only give their best move once told to stop, or of a ponder hit
+        self.search = Some(Search::start(
+            engine,
+            self.game.clone(),
+            go.limits(color),
+            !go.infinite && !go.ponder,
+            |game: &Game, info: &SearchInfo| println!("{}", info_line(game, info)),
+            |game: &Game, info: &SearchInfo| match info.best_move() {
+                Some(mv) => println!("bestmove {}", game.uci_move(mv)),
+                None => println!("bestmove 0000"),
+            },
+        ));
+    }
+
+    /// Stops the current search, waiting for it to send its best move
+    fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop();
+            if let Some((engine, _)) = search.join() {
+                self.engine = Some(engine);
+            }
+        }
+        if self.engine.is_none() {
+            self.engine = Some(build_engine(&self.start, self.hash, self.mobility));
+        }
+    }
+}
+
+fn named_variant(variants_dir: &Path, name: &str) -> Result<Game, String> {
+    load_variant(variants_dir, name).map_err(|e| format!("couldn't load variant {}: {}", name, e))
+}
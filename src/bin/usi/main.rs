@@ -0,0 +1,170 @@
+// The ~USI adapter~ (the thing that lets shogi GUIs talk to the engine)
+
+use sharmat::adapter::*;
+use sharmat::engine::{Engine, SearchInfo};
+use sharmat::game::Game;
+use sharmat::usi::*;
+use sharmat::variant::Variant;
+use std::env;
+use std::io::{self, BufRead};
+use std::process;
+
+struct Adapter {
+    start: Game,
+    game: Game,
+    hash: usize,
+    mobility: i32,
+    /// The engine, or `None` while it is searching
+    engine: Option<Engine>,
+    search: Option<Search>,
+}
+
+fn main() {
+    let variant_path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| format!("{}/variants/shogi.toml", env!("CARGO_MANIFEST_DIR")));
+    let start = Variant::load(&variant_path)
+        .and_then(|variant| variant.game())
+        .unwrap_or_else(|e| {
+            eprintln!("Couldn't load variant {}: {}", variant_path, e);
+            process::exit(1);
+        });
+    let mut adapter = Adapter {
+        game: start.clone(),
+        start,
+        hash: DEFAULT_HASH,
+        mobility: DEFAULT_MOBILITY,
+        engine: None,
+        search: None,
+    };
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match line.parse::<UsiCommand>() {
+            Ok(UsiCommand::Quit) => break,
+            Ok(command) => adapter.handle(command),
+            Err(UsiError::Empty) | Err(UsiError::UnknownCommand(_)) => {}
+            Err(e) => println!("info string {}", e),
+        }
+    }
+    adapter.stop();
+}
+
+impl Adapter {
+    fn handle(&mut self, command: UsiCommand) {
+        match command {
+            UsiCommand::Usi => {
+                println!("id name Sharmat {}", env!("CARGO_PKG_VERSION"));
+                println!("id author {}", env!("CARGO_PKG_AUTHORS"));
+                println!(
+                    "option name USI_Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH, MAX_HASH
+                );
+                println!(
+                    "option name Mobility type spin default {} min 0 max 100",
+                    DEFAULT_MOBILITY
+                );
+                println!("usiok");
+            }
+            UsiCommand::IsReady => {
+                self.stop();
+                println!("readyok");
+            }
+            UsiCommand::SetOption { name, value } => {
+                self.stop();
+                let value = value.unwrap_or_default();
+                let number = value.parse::<usize>();
+                match (name.as_str(), number) {
+                    ("USI_Hash", Ok(hash)) => self.hash = hash.clamp(1, MAX_HASH),
+                    ("Mobility", Ok(mobility)) => self.mobility = mobility.min(100) as i32,
+                    ("USI_Ponder", _) => return,
+                    _ => {
+                        println!("info string invalid option {} {}", name, value);
+                        return;
+                    }
+                }
+                self.engine = None;
+            }
+            UsiCommand::UsiNewGame => {
+                self.stop();
+                if let Some(engine) = self.engine.as_mut() {
+                    engine.clear();
+                }
+                self.game = self.start.clone();
+            }
+            UsiCommand::Position { sfen, moves } => {
+                self.stop();
+                if let Err(e) = self.set_position(sfen.as_deref(), &moves) {
+                    println!("info string {}", e);
+                }
+            }
+            UsiCommand::Go(go) => self.go(go),
+            // the pondering search goes on, now on the engine's own time
+            UsiCommand::PonderHit => {
+                if let Some(search) = &self.search {
+                    search.ponder_hit();
+                }
+            }
+            UsiCommand::Stop | UsiCommand::GameOver(_) => self.stop(),
+            UsiCommand::Quit => {}
+        }
+    }
+
+    fn set_position(&mut self, sfen: Option<&str>, moves: &[String]) -> Result<(), String> {
+        self.game = self.start.clone();
+        if let Some(sfen) = sfen {
+            self.game
+                .load_sfen(sfen)
+                .map_err(|e| format!("invalid position {}: {}", sfen, e))?;
+        }
+        for text in moves {
+            let mv = self.game.parse_usi_move(text).map_err(|e| e.to_string())?;
+            self.game.play(&mv);
+        }
+        Ok(())
+    }
+
+    fn go(&mut self, go: Go) {
+        self.stop();
+        if go.mate {
+            println!("checkmate notimplemented");
+            return;
+        }
+        let color = match self.game.current_player() {
+            Some(player) => player.color,
+            None => return,
+        };
+        let engine = match self.engine.take() {
+            Some(engine) => engine,
+            None => return,
+        };
+        // infinite and pondering searches only give their best move once told to stop, or of a ponder hit
+        self.search = Some(Search::start(
+            engine,
+            self.game.clone(),
+            go.limits(color),
+            !go.infinite && !go.ponder,
+            |game: &Game, info: &SearchInfo| println!("{}", info_line(game, info)),
+            |game: &Game, info: &SearchInfo| match info.best_move() {
+                Some(mv) => println!("bestmove {}", game.usi_move(mv)),
+                None => println!("bestmove resign"),
+            },
+        ));
+    }
+
+    /// Stops the current search, waiting for it to send its best move, and makes the engine ready
+    fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop();
+            if let Some((engine, _)) = search.join() {
+                self.engine = Some(engine);
+            }
+        }
+        if self.engine.is_none() {
+            self.engine = Some(build_engine(&self.start, self.hash, self.mobility));
+        }
+    }
+}
@@ -0,0 +1,308 @@
+// The ~XBoard adapter~ (the thing that lets fairy chess GUIs talk to the engine)
+
+use sharmat::adapter::*;
+use sharmat::engine::{Engine, SearchInfo};
+use sharmat::game::Game;
+use sharmat::movement::MovementType;
+use sharmat::player::PlayerColor;
+use sharmat::xboard::*;
+use std::env;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+const DEFAULT_VARIANT: &str = "standard";
+/// XBoard's name for orthodox chess
+const NORMAL: &str = "normal";
+/// Commands that need no answer
+const IGNORED: [&str; 10] = [
+    "accepted", "rejected", "random", "computer", "name", "rating", "ics", "hard", "easy", "draw",
+];
+
+struct Adapter {
+    variants_dir: PathBuf,
+    start: Game,
+    game: Game,
+    /// Side played by the engine, or `None` in force mode
+    engine_color: Option<PlayerColor>,
+    time: TimeControl,
+    post: bool,
+    hash: usize,
+    /// The engine, or `None` while it is searching
+    engine: Option<Engine>,
+    search: Option<Search>,
+}
+
+fn main() {
+    let variants_dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("{}/variants", env!("CARGO_MANIFEST_DIR"))));
+    let mut adapter = Adapter::new(variants_dir).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match line.parse::<XboardCommand>() {
+            Ok(XboardCommand::Quit) => break,
+            Ok(command) => adapter.handle(command),
+            Err(XboardError::Empty) => {}
+            Err(XboardError::UnknownCommand(ref command))
+                if IGNORED.contains(&command.as_str()) => {}
+            Err(XboardError::UnknownCommand(_)) => println!("Error (unknown command): {}", line),
+            Err(e) => println!("Error ({}): {}", e, line),
+        }
+    }
+    adapter.stop();
+}
+
+impl Adapter {
+    fn new(variants_dir: PathBuf) -> Result<Self, String> {
+        let start = named_variant(&variants_dir, DEFAULT_VARIANT)?;
+        let engine = build_engine(&start, DEFAULT_HASH, DEFAULT_MOBILITY);
+        Ok(Adapter {
+            variants_dir,
+            game: start.clone(),
+            start,
+            engine_color: Some(PlayerColor::Black),
+            // XBoard's own default, until the GUI sends a `level` command
+            time: TimeControl {
+                moves: 40,
+                base: Duration::from_secs(300),
+                ..TimeControl::default()
+            },
+            post: false,
+            hash: DEFAULT_HASH,
+            engine: Some(engine),
+            search: None,
+        })
+    }
+
+    fn handle(&mut self, command: XboardCommand) {
+        match command {
+            // a ping is answered once the current search, if any, played its move; searches always have a limit
+            XboardCommand::Ping(number) => {
+                self.wait();
+                println!("pong {}", number);
+                return;
+            }
+            XboardCommand::Post(post) => {
+                self.post = post;
+                return;
+            }
+            _ => self.stop(),
+        }
+        match command {
+            XboardCommand::Protover(_) => {
+                let variants: Vec<String> = variant_names(&self.variants_dir)
+                    .into_iter()
+                    .map(|name| {
+                        if name == DEFAULT_VARIANT {
+                            NORMAL.to_string()
+                        } else {
+                            name
+                        }
+                    })
+                    .collect();
+                println!(
+                    "feature myname=\"Sharmat {}\" variants=\"{}\"",
+                    env!("CARGO_PKG_VERSION"),
+                    variants.join(",")
+                );
+                println!(
+                    "feature setboard=1 usermove=1 ping=1 playother=1 colors=0 time=1 memory=1 \
+                     sigint=0 sigterm=0 reuse=1 analyze=0 done=1"
+                );
+            }
+            XboardCommand::New => {
+                if let Err(e) = self.set_variant(NORMAL) {
+                    println!("tellusererror {}", e);
+                }
+                self.engine_color = Some(PlayerColor::Black);
+                self.time.depth = None;
+            }
+            XboardCommand::Variant(name) => {
+                if let Err(e) = self.set_variant(&name) {
+                    println!("Error (unsupported variant): {}", name);
+                    println!("tellusererror {}", e);
+                    return;
+                }
+                if name != NORMAL {
+                    println!("{}", setup_line(&self.start));
+                    for line in piece_lines(&self.start) {
+                        println!("{}", line);
+                    }
+                }
+            }
+            XboardCommand::Force | XboardCommand::Result(_) => self.engine_color = None,
+            XboardCommand::Go => {
+                self.engine_color = self.game.current_player().map(|player| player.color);
+                self.go();
+            }
+            XboardCommand::PlayOther => {
+                self.engine_color = self
+                    .game
+                    .current_player()
+                    .map(|player| player.color.opponent());
+            }
+            XboardCommand::UserMove(text) => match self.game.parse_uci_move(&text) {
+                Ok(mv) => {
+                    self.game.play(&mv);
+                    if let Some(result) = result_line(&self.game) {
+                        println!("{}", result);
+                    } else if self.engine_to_move() {
+                        self.go();
+                    }
+                }
+                Err(_) => println!("Illegal move: {}", text),
+            },
+            XboardCommand::Level {
+                moves,
+                base,
+                increment,
+            } => {
+                self.time.moves = moves;
+                self.time.base = base;
+                self.time.increment = increment;
+                self.time.move_time = None;
+            }
+            XboardCommand::St(time) => self.time.move_time = Some(time),
+            XboardCommand::Sd(depth) => self.time.depth = Some(depth),
+            XboardCommand::Time(time) => self.time.remaining = Some(time),
+            XboardCommand::SetBoard(fen) => {
+                let mut game = self.start.clone();
+                // variants with drops are set up from SFEN
+                let loaded = game.load_fen(&fen).is_ok() || game.load_sfen(&fen).is_ok();
+                if loaded {
+                    self.game = game;
+                } else {
+                    println!("tellusererror Illegal position");
+                }
+            }
+            XboardCommand::Undo => {
+                self.game.undo();
+            }
+            XboardCommand::Remove => {
+                self.game.undo();
+                self.game.undo();
+            }
+            XboardCommand::Memory(size) => {
+                self.hash = size.clamp(1, MAX_HASH);
+                self.engine = Some(build_engine(&self.start, self.hash, DEFAULT_MOBILITY));
+            }
+            XboardCommand::Piece { letter, betza } => {
+                if let Err(e) = self.set_piece(letter, &betza) {
+                    println!("Error ({}): piece {} {}", e, letter, betza);
+                }
+            }
+            XboardCommand::Xboard
+            | XboardCommand::Otim(_)
+            | XboardCommand::MoveNow
+            | XboardCommand::Ping(_)
+            | XboardCommand::Post(_)
+            | XboardCommand::Quit => {}
+        }
+    }
+
+    fn set_variant(&mut self, name: &str) -> Result<(), String> {
+        let name = if name == NORMAL {
+            DEFAULT_VARIANT
+        } else {
+            name
+        };
+        self.start = named_variant(&self.variants_dir, name)?;
+        self.game = self.start.clone();
+        self.engine = Some(build_engine(&self.start, self.hash, DEFAULT_MOBILITY));
+        Ok(())
+    }
+
+    /// Redefines how the piece with the given letter moves, in both the starting and the current position
+    fn set_piece(&mut self, letter: char, betza: &str) -> Result<(), String> {
+        let letter = letter.to_ascii_uppercase();
+        let piece = self
+            .start
+            .pieces()
+            .iter()
+            .position(|piece| piece.letter() == Some(letter))
+            .ok_or_else(|| "unknown piece".to_string())?;
+        let movement = MovementType::from_betza(betza).map_err(|e| e.to_string())?;
+        self.start.set_movement(piece, vec![movement.clone()]);
+        self.game.set_movement(piece, vec![movement]);
+        // the pieces' values depend on their movement
+        self.engine = Some(build_engine(&self.start, self.hash, DEFAULT_MOBILITY));
+        Ok(())
+    }
+
+    fn engine_to_move(&self) -> bool {
+        self.engine_color.is_some()
+            && self.game.current_player().map(|player| player.color) == self.engine_color
+    }
+
+    fn go(&mut self) {
+        if !self.engine_to_move() {
+            return;
+        }
+        let engine = match self.engine.take() {
+            Some(engine) => engine,
+            None => return,
+        };
+        let limits = self
+            .time
+            .limits(self.game.ply_number().saturating_sub(1) / 2);
+        let post = self.post;
+        self.search = Some(Search::start(
+            engine,
+            self.game.clone(),
+            limits,
+            true,
+            move |game: &Game, info: &SearchInfo| {
+                if post {
+                    println!("{}", thinking_line(game, info));
+                }
+            },
+            |game: &Game, info: &SearchInfo| {
+                if let Some(mv) = info.best_move() {
+                    println!("move {}", game.uci_move(mv));
+                    let mut game = game.clone();
+                    game.play(mv);
+                    if let Some(result) = result_line(&game) {
+                        println!("{}", result);
+                    }
+                }
+            },
+        ));
+    }
+
+    /// Waits for the current search to play its move, and makes the engine ready
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            if let Some((engine, info)) = search.join() {
+                self.engine = Some(engine);
+                if let Some(mv) = info.best_move() {
+                    self.game.play(mv);
+                }
+            }
+        }
+        if self.engine.is_none() {
+            self.engine = Some(build_engine(&self.start, self.hash, DEFAULT_MOBILITY));
+        }
+    }
+
+    /// Makes the current search play its move now
+    fn stop(&mut self) {
+        if let Some(search) = &self.search {
+            search.stop();
+        }
+        self.wait();
+    }
+}
+
+fn named_variant(variants_dir: &Path, name: &str) -> Result<Game, String> {
+    load_variant(variants_dir, name).map_err(|e| format!("couldn't load variant {}: {}", name, e))
+}
@@ -0,0 +1,478 @@
+use crate::movement::{MovementCondition, MovementType};
+use crate::player::PlayerColor;
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+/// Largest width and height of a board with bitboards; larger boards only use their array of squares
+pub const MAX_SIZE: usize = 16;
+
+/// A set of squares of a board of up to 16x16 squares, one bit per square.
+/// Square (x, y) is bit `y * 16 + x`, whatever the board's width.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Bitboard([u64; 4]);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard([0; 4]);
+
+    pub fn index(x: usize, y: usize) -> usize {
+        y * MAX_SIZE + x
+    }
+
+    pub fn square(index: usize) -> (usize, usize) {
+        (index % MAX_SIZE, index / MAX_SIZE)
+    }
+
+    pub fn from_square(x: usize, y: usize) -> Self {
+        let mut res = Bitboard::EMPTY;
+        res.set(x, y);
+        res
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        let index = Bitboard::index(x, y);
+        self.0[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize) {
+        let index = Bitboard::index(x, y);
+        self.0[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn clear(&mut self, x: usize, y: usize) {
+        let index = Bitboard::index(x, y);
+        self.0[index / 64] &= !(1 << (index % 64));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+
+    /// Index of the lowest square of the set
+    pub fn first(&self) -> Option<usize> {
+        self.0
+            .iter()
+            .enumerate()
+            .find(|(_, &word)| word != 0)
+            .map(|(i, word)| i * 64 + word.trailing_zeros() as usize)
+    }
+
+    /// Index of the highest square of the set
+    pub fn last(&self) -> Option<usize> {
+        self.0
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, &word)| word != 0)
+            .map(|(i, word)| i * 64 + 63 - word.leading_zeros() as usize)
+    }
+
+    /// Iterates over the squares of the set, as (x, y)
+    pub fn squares(self) -> Squares {
+        Squares(self)
+    }
+
+    /// The squares whose index is lower than `index`
+    fn below(index: usize) -> Self {
+        let mut res = Bitboard::EMPTY;
+        for (i, word) in res.0.iter_mut().enumerate() {
+            if index >= (i + 1) * 64 {
+                *word = !0;
+            } else if index > i * 64 {
+                *word = (1 << (index - i * 64)) - 1;
+            }
+        }
+        res
+    }
+}
+
+pub struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        let index = self.0.first()?;
+        self.0 .0[index / 64] &= self.0 .0[index / 64] - 1;
+        Some(Bitboard::square(index))
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+
+    fn bitand(mut self, other: Bitboard) -> Bitboard {
+        self &= other;
+        self
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, other: Bitboard) {
+        for (word, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *word &= other;
+        }
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+
+    fn bitor(mut self, other: Bitboard) -> Bitboard {
+        self |= other;
+        self
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, other: Bitboard) {
+        for (word, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *word |= other;
+        }
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+    }
+}
+
+impl fmt::Debug for Bitboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.squares()).finish()
+    }
+}
+
+/// Index of a color in the per-color arrays of bitboards and tables
+pub(crate) fn color_index(color: PlayerColor) -> usize {
+    match color {
+        PlayerColor::White => 0,
+        PlayerColor::Black => 1,
+    }
+}
+
+/// Which target squares a movement may reach
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Any,
+    /// Only empty squares, from the `NoCapture` condition
+    Quiet,
+    /// Only occupied squares, from the `Capture` condition
+    Capture,
+}
+
+impl Mode {
+    fn and(self, other: Mode) -> Option<Mode> {
+        match (self, other) {
+            (Mode::Any, mode) | (mode, Mode::Any) => Some(mode),
+            (a, b) if a == b => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// The squares along one direction of a ranging movement, up to its range
+#[derive(Clone, Debug)]
+struct Ray {
+    squares: Bitboard,
+    /// Whether the square indices increase along the ray
+    forward: bool,
+    mode: Mode,
+}
+
+impl Ray {
+    /// The squares of the ray up to its first blocker, included
+    fn reach(&self, occupied: Bitboard) -> Bitboard {
+        let blockers = self.squares & occupied;
+        if self.forward {
+            match blockers.first() {
+                Some(blocker) => self.squares & Bitboard::below(blocker + 1),
+                None => self.squares,
+            }
+        } else {
+            match blockers.last() {
+                Some(blocker) => self.squares & !Bitboard::below(blocker),
+                None => self.squares,
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct SquareAttacks {
+    /// Leaper targets, for each `Mode`
+    leaps: [Bitboard; 3],
+    rays: Vec<Ray>,
+}
+
+/**
+The target squares of a piece of one color, precomputed for every square of a board from the piece's movement types.
+Leaps are stored as bitboards, and ranging movements as rays which are cut at their first blocker.
+**/
+#[derive(Clone, Debug)]
+pub struct AttackTable {
+    width: usize,
+    height: usize,
+    squares: Vec<SquareAttacks>,
+    exact: bool,
+}
+
+impl AttackTable {
+    pub fn new(
+        movements: &[MovementType],
+        color: PlayerColor,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        let mut table = AttackTable {
+            width,
+            height,
+            squares: vec![SquareAttacks::default(); width * height],
+            exact: true,
+        };
+        if width > MAX_SIZE || height > MAX_SIZE {
+            // left inexact, so that movements are computed from the board instead
+            table.squares.clear();
+            table.exact = false;
+            return table;
+        }
+        for y in 0..height {
+            for x in 0..width {
+                for movement in movements {
+                    if !table.add(movement, color, x, y, Mode::Any) {
+                        table.exact = false;
+                    }
+                }
+            }
+        }
+        table
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether the table gives the same targets as `MovementType::flatten`; custom conditions can't be precomputed
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    /// Target squares of the piece standing on (x, y), given the occupied squares and those of the piece's owner
+    pub fn targets(&self, x: usize, y: usize, occupied: Bitboard, own: Bitboard) -> Bitboard {
+        let square = &self.squares[y * self.width + x];
+        let mut res = square.leaps[Mode::Any as usize]
+            | (square.leaps[Mode::Quiet as usize] & !occupied)
+            | (square.leaps[Mode::Capture as usize] & occupied);
+        for ray in &square.rays {
+            res |= match ray.mode {
+                Mode::Any => ray.reach(occupied),
+                Mode::Quiet => ray.reach(occupied) & !occupied,
+                Mode::Capture => ray.reach(occupied) & occupied,
+            };
+        }
+        res & !own
+    }
+
+    /// Squares that the piece standing on (x, y) could capture on, whoever stands there.
+    /// Movements restricted to empty squares are left out.
+    pub fn attacks(&self, x: usize, y: usize, occupied: Bitboard) -> Bitboard {
+        let square = &self.squares[y * self.width + x];
+        let mut res = square.leaps[Mode::Any as usize] | square.leaps[Mode::Capture as usize];
+        for ray in &square.rays {
+            if ray.mode != Mode::Quiet {
+                res |= ray.reach(occupied);
+            }
+        }
+        res
+    }
+
+    /// Empty squares that the piece standing on (x, y) may only move to without capturing
+    pub fn quiet_targets(&self, x: usize, y: usize, occupied: Bitboard) -> Bitboard {
+        let square = &self.squares[y * self.width + x];
+        let mut res = square.leaps[Mode::Quiet as usize];
+        for ray in &square.rays {
+            if ray.mode == Mode::Quiet {
+                res |= ray.reach(occupied);
+            }
+        }
+        res & !occupied
+    }
+
+    /// Adds the targets of `movement` from (x, y); returns false if it can't be precomputed
+    fn add(
+        &mut self,
+        movement: &MovementType,
+        color: PlayerColor,
+        x: usize,
+        y: usize,
+        mode: Mode,
+    ) -> bool {
+        match movement {
+            MovementType::Stay => true,
+            MovementType::Undirected(_, _) | MovementType::Directed(_, _) => {
+                for (dx, dy) in self.steps(movement, color, x, y).unwrap() {
+                    let (tx, ty) = ((x as isize + dx) as usize, (y as isize + dy) as usize);
+                    self.squares[y * self.width + x].leaps[mode as usize].set(tx, ty);
+                }
+                true
+            }
+            MovementType::RangeAny(inner) => {
+                let range = self.width.max(self.height);
+                self.add_rays(inner, color, x, y, mode, range)
+            }
+            MovementType::Range(inner, range) => self.add_rays(inner, color, x, y, mode, *range),
+            MovementType::Union(movements) => {
+                let mut exact = true;
+                for movement in movements {
+                    exact &= self.add(movement, color, x, y, mode);
+                }
+                exact
+            }
+            MovementType::Condition(inner, conditions) => {
+                let mut mode = Some(mode);
+                for condition in conditions {
+                    match condition {
+                        MovementCondition::Capture => {
+                            mode = mode.and_then(|m| m.and(Mode::Capture))
+                        }
+                        MovementCondition::NoCapture => {
+                            mode = mode.and_then(|m| m.and(Mode::Quiet))
+                        }
+                        MovementCondition::Custom(_) => return false,
+                        _ => {
+                            if !self.validate(condition, color, y) {
+                                mode = None;
+                            }
+                        }
+                    }
+                }
+                match mode {
+                    Some(mode) => self.add(inner, color, x, y, mode),
+                    None => true,
+                }
+            }
+        }
+    }
+
+    fn add_rays(
+        &mut self,
+        inner: &MovementType,
+        color: PlayerColor,
+        x: usize,
+        y: usize,
+        mode: Mode,
+        range: usize,
+    ) -> bool {
+        let mut steps = match self.steps(inner, color, x, y) {
+            Some(steps) => steps,
+            None => return false,
+        };
+        steps.sort_unstable();
+        steps.dedup();
+        for (dx, dy) in steps {
+            if dx == 0 && dy == 0 {
+                continue;
+            }
+            let mut squares = Bitboard::EMPTY;
+            for mult in 1..=range as isize {
+                let (tx, ty) = (x as isize + dx * mult, y as isize + dy * mult);
+                if !self.in_bounds(tx, ty) {
+                    break;
+                }
+                squares.set(tx as usize, ty as usize);
+            }
+            self.squares[y * self.width + x].rays.push(Ray {
+                squares,
+                forward: dy > 0 || (dy == 0 && dx > 0),
+                mode,
+            });
+        }
+        true
+    }
+
+    /// The single steps of a movement from (x, y), which stay on the board; `None` if they depend on the position
+    fn steps(
+        &self,
+        movement: &MovementType,
+        color: PlayerColor,
+        x: usize,
+        y: usize,
+    ) -> Option<Vec<(isize, isize)>> {
+        let candidates = match movement {
+            MovementType::Stay => vec![],
+            MovementType::Undirected(dx, dy) => {
+                let (dx, dy) = (*dx as isize, *dy as isize);
+                vec![
+                    (dx, dy),
+                    (-dx, dy),
+                    (dx, -dy),
+                    (-dx, -dy),
+                    (dy, dx),
+                    (-dy, dx),
+                    (dy, -dx),
+                    (-dy, -dx),
+                ]
+            }
+            MovementType::Directed(dx, dy) => vec![(*dx, *dy)],
+            MovementType::RangeAny(_) | MovementType::Range(_, _) => return None,
+            MovementType::Union(movements) => {
+                let mut res = vec![];
+                for movement in movements {
+                    res.append(&mut self.steps(movement, color, x, y)?);
+                }
+                return Some(res);
+            }
+            MovementType::Condition(inner, conditions) => {
+                for condition in conditions {
+                    match condition {
+                        MovementCondition::Capture
+                        | MovementCondition::NoCapture
+                        | MovementCondition::Custom(_) => return None,
+                        _ => {
+                            if !self.validate(condition, color, y) {
+                                return Some(vec![]);
+                            }
+                        }
+                    }
+                }
+                return self.steps(inner, color, x, y);
+            }
+        };
+        Some(
+            candidates
+                .into_iter()
+                .filter(|&(dx, dy)| self.in_bounds(x as isize + dx, y as isize + dy))
+                .collect(),
+        )
+    }
+
+    /// Evaluates the conditions which only depend on the piece's color and square
+    fn validate(&self, condition: &MovementCondition, color: PlayerColor, y: usize) -> bool {
+        match condition {
+            MovementCondition::AsWhite => color.white(),
+            MovementCondition::AsBlack => color.black(),
+            MovementCondition::Rank(rank) => {
+                if color.white() {
+                    y == *rank
+                } else {
+                    self.height - 1 - y == *rank
+                }
+            }
+            _ => true,
+        }
+    }
+
+    fn in_bounds(&self, x: isize, y: isize) -> bool {
+        x >= 0 && x < self.width as isize && y >= 0 && y < self.height as isize
+    }
+}
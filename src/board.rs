@@ -1,5 +1,9 @@
 use self::BoardError::*;
+use super::bitboard::{color_index, Bitboard, MAX_SIZE};
+use super::coordinates::Coordinates;
 use super::player::PlayerColor;
+use std::error::Error;
+use std::fmt;
 use std::num::NonZeroUsize;
 
 type RawPiece = Option<(usize, PlayerColor)>;
@@ -8,7 +12,9 @@ type RawPiece = Option<(usize, PlayerColor)>;
 pub struct Board {
     pub width: NonZeroUsize,
     pub height: NonZeroUsize,
-    board: Vec<Vec<RawPiece>>,
+    board: Vec<RawPiece>,
+    /// Squares occupied by each color's pieces, white first
+    occupied: [Bitboard; 2],
     name: String,
 }
 
@@ -20,53 +26,80 @@ pub enum BoardError {
 pub type BoardResult<T> = Result<T, BoardError>;
 
 impl Board {
+    /// Creates an empty board; boards larger than 16x16 have no bitboards, see `has_bitboards`
     pub fn new(width: NonZeroUsize, height: NonZeroUsize) -> Self {
-        let mut board = Vec::with_capacity(width.get());
-        for x in 0..width.get() {
-            board.push(Vec::with_capacity(height.get()));
-            for _y in 0..height.get() {
-                board[x].push(None);
-            }
-        }
         Board {
             width,
             height,
-            board,
+            board: vec![None; width.get() * height.get()],
+            occupied: [Bitboard::EMPTY; 2],
             name: String::from("Board"),
         }
     }
 
     pub fn set(&mut self, x: usize, y: usize, piece: RawPiece) -> BoardResult<()> {
         self.check_pos(x, y)?;
-        self.board[x][y] = piece;
+        let index = y * self.width.get() + x;
+        if self.has_bitboards() {
+            if let Some((_, color)) = self.board[index] {
+                self.occupied[color_index(color)].clear(x, y);
+            }
+            if let Some((_, color)) = piece {
+                self.occupied[color_index(color)].set(x, y);
+            }
+        }
+        self.board[index] = piece;
         Ok(())
     }
 
     pub fn get(&self, x: usize, y: usize) -> BoardResult<RawPiece> {
         self.check_pos(x, y)?;
-        Ok(self.board[x][y])
+        Ok(self.board[y * self.width.get() + x])
     }
 
     pub fn move_piece(&mut self, x: usize, y: usize, dx: usize, dy: usize) -> BoardResult<()> {
-        self.check_pos(x, y)?;
+        let piece = self.get(x, y)?;
         self.check_pos(dx, dy)?;
-        self.board[dx][dy] = self.board[x][y];
-        self.board[x][y] = None;
-        Ok(())
+        self.set(x, y, None)?;
+        self.set(dx, dy, piece)
     }
 
     pub fn clear_pos(&mut self, x: usize, y: usize) -> BoardResult<()> {
-        self.check_pos(x, y)?;
-        self.board[x][y] = None;
-        Ok(())
+        self.set(x, y, None)
     }
 
     pub fn clear(&mut self) {
-        self.board.iter_mut().for_each(|column| {
-            column.iter_mut().for_each(|cell| {
-                *cell = None;
-            });
-        });
+        self.board.iter_mut().for_each(|cell| *cell = None);
+        self.occupied = [Bitboard::EMPTY; 2];
+    }
+
+    /// Whether the board fits in a bitboard; `occupied` and `occupied_by` are always empty otherwise
+    pub fn has_bitboards(&self) -> bool {
+        self.width.get() <= MAX_SIZE && self.height.get() <= MAX_SIZE
+    }
+
+    /// Squares holding a piece of `color`, rank by rank from the first, on boards of any size
+    pub fn squares_of(&self, color: PlayerColor) -> Vec<(usize, usize)> {
+        if self.has_bitboards() {
+            return self.occupied_by(color).squares().collect();
+        }
+        let width = self.width.get();
+        self.board
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| matches!(cell, Some((_, c)) if *c == color))
+            .map(|(index, _)| (index % width, index / width))
+            .collect()
+    }
+
+    /// Squares occupied by any piece
+    pub fn occupied(&self) -> Bitboard {
+        self.occupied[0] | self.occupied[1]
+    }
+
+    /// Squares occupied by the pieces of `color`
+    pub fn occupied_by(&self, color: PlayerColor) -> Bitboard {
+        self.occupied[color_index(color)]
     }
 
     fn check_pos(&self, x: usize, y: usize) -> BoardResult<()> {
@@ -85,3 +118,17 @@ impl Board {
         self.name.clone()
     }
 }
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutOfBounds(x, y) => write!(
+                f,
+                "square {} is out of the board",
+                Coordinates::Algebraic.square_name((*x, *y), 0, 0, PlayerColor::White)
+            ),
+        }
+    }
+}
+
+impl Error for BoardError {}
@@ -0,0 +1,225 @@
+use crate::player::PlayerColor;
+use serde::Deserialize;
+
+const KANJI_DIGITS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+/// How the files and ranks of a board are named
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Coordinates {
+    /// Files `a` to `z`, then `aa`, `ab`... from the left, and ranks numbered from the bottom: `e4`
+    #[default]
+    Algebraic,
+    /// Files numbered from the right in full-width digits, and ranks in kanji from the top: `７六`
+    Kanji,
+    /// Files numbered from the right, and ranks lettered from the top: `7f`
+    Shogi,
+    /// Files numbered from the player's right, and ranks from the player's side: `5-1`
+    Xiangqi,
+}
+
+impl Coordinates {
+    /// Name of the file `x`, as seen by the player `color`
+    pub fn file_name(&self, x: usize, width: usize, color: PlayerColor) -> String {
+        match self {
+            Coordinates::Algebraic => letters(x),
+            Coordinates::Kanji => width
+                .saturating_sub(x)
+                .to_string()
+                .chars()
+                .map(|c| std::char::from_u32('０' as u32 + c.to_digit(10).unwrap()).unwrap())
+                .collect(),
+            Coordinates::Shogi => width.saturating_sub(x).to_string(),
+            Coordinates::Xiangqi => {
+                if color.white() {
+                    width.saturating_sub(x).to_string()
+                } else {
+                    (x + 1).to_string()
+                }
+            }
+        }
+    }
+
+    /// Name of the rank `y`, as seen by the player `color`
+    pub fn rank_name(&self, y: usize, height: usize, color: PlayerColor) -> String {
+        match self {
+            Coordinates::Algebraic => (y + 1).to_string(),
+            Coordinates::Kanji => kanji_number(height.saturating_sub(y)),
+            Coordinates::Shogi => letters(height.saturating_sub(y + 1)),
+            Coordinates::Xiangqi => {
+                if color.white() {
+                    (y + 1).to_string()
+                } else {
+                    height.saturating_sub(y).to_string()
+                }
+            }
+        }
+    }
+
+    /// Name of a square, as seen by the player `color`
+    pub fn square_name(
+        &self,
+        (x, y): (usize, usize),
+        width: usize,
+        height: usize,
+        color: PlayerColor,
+    ) -> String {
+        let separator = if *self == Coordinates::Xiangqi {
+            "-"
+        } else {
+            ""
+        };
+        format!(
+            "{}{}{}",
+            self.file_name(x, width, color),
+            separator,
+            self.rank_name(y, height, color)
+        )
+    }
+
+    pub fn parse_file(&self, raw: &str, width: usize, color: PlayerColor) -> Option<usize> {
+        let x = match self {
+            Coordinates::Algebraic => parse_letters(raw)?,
+            Coordinates::Kanji | Coordinates::Shogi => width.checked_sub(parse_digits(raw)?)?,
+            Coordinates::Xiangqi => {
+                let file: usize = raw.parse().ok().filter(|&f| f > 0)?;
+                if color.white() {
+                    width.checked_sub(file)?
+                } else {
+                    file - 1
+                }
+            }
+        };
+        if x < width {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    pub fn parse_rank(&self, raw: &str, height: usize, color: PlayerColor) -> Option<usize> {
+        let y = match self {
+            Coordinates::Algebraic => raw.parse::<usize>().ok()?.checked_sub(1)?,
+            Coordinates::Kanji => height.checked_sub(parse_kanji_number(raw)?)?,
+            Coordinates::Shogi => height.checked_sub(parse_letters(raw)? + 1)?,
+            Coordinates::Xiangqi => {
+                let rank: usize = raw.parse().ok().filter(|&r| r > 0)?;
+                if color.white() {
+                    rank - 1
+                } else {
+                    height.checked_sub(rank)?
+                }
+            }
+        };
+        if y < height {
+            Some(y)
+        } else {
+            None
+        }
+    }
+
+    /// Parses the name of a square, as seen by the player `color`
+    pub fn parse_square(
+        &self,
+        raw: &str,
+        width: usize,
+        height: usize,
+        color: PlayerColor,
+    ) -> Option<(usize, usize)> {
+        let split = match self {
+            Coordinates::Algebraic => raw.find(|c: char| !c.is_ascii_lowercase())?,
+            Coordinates::Kanji | Coordinates::Shogi => raw.find(|c| !is_digit(c))?,
+            Coordinates::Xiangqi => {
+                let split = raw.find('-')?;
+                return Some((
+                    self.parse_file(&raw[..split], width, color)?,
+                    self.parse_rank(&raw[split + 1..], height, color)?,
+                ));
+            }
+        };
+        Some((
+            self.parse_file(&raw[..split], width, color)?,
+            self.parse_rank(&raw[split..], height, color)?,
+        ))
+    }
+}
+
+/// Bijective base-26 numbering: `a` for 0, `z` for 25, `aa` for 26...
+fn letters(mut n: usize) -> String {
+    let mut res = vec![];
+    loop {
+        res.push((b'a' + (n % 26) as u8) as char);
+        if n < 26 {
+            break;
+        }
+        n = n / 26 - 1;
+    }
+    res.into_iter().rev().collect()
+}
+
+fn parse_letters(raw: &str) -> Option<usize> {
+    if raw.is_empty() {
+        return None;
+    }
+    let mut res = 0usize;
+    for c in raw.chars() {
+        if !c.is_ascii_lowercase() {
+            return None;
+        }
+        res = res
+            .checked_mul(26)?
+            .checked_add((c as u8 - b'a') as usize + 1)?;
+    }
+    Some(res - 1)
+}
+
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || ('０'..='９').contains(&c)
+}
+
+/// Parses a number written with ASCII or full-width digits
+fn parse_digits(raw: &str) -> Option<usize> {
+    if raw.is_empty() || !raw.chars().all(is_digit) {
+        return None;
+    }
+    raw.chars()
+        .map(|c| match c.to_digit(10) {
+            Some(d) => d,
+            None => c as u32 - '０' as u32,
+        })
+        .try_fold(0usize, |acc, d| {
+            acc.checked_mul(10)?.checked_add(d as usize)
+        })
+}
+
+/// Writes a number in kanji, up to 99
+pub(crate) fn kanji_number(n: usize) -> String {
+    if n >= 100 {
+        return n.to_string();
+    }
+    let mut res = String::new();
+    if n >= 10 {
+        if n >= 20 {
+            res.push(KANJI_DIGITS[n / 10 - 1]);
+        }
+        res.push('十');
+    }
+    if !n.is_multiple_of(10) {
+        res.push(KANJI_DIGITS[n % 10 - 1]);
+    }
+    res
+}
+
+/// Parses a number written in kanji, up to 99
+pub(crate) fn parse_kanji_number(raw: &str) -> Option<usize> {
+    let digit = |c: char| KANJI_DIGITS.iter().position(|&n| n == c).map(|d| d + 1);
+    let chars: Vec<char> = raw.chars().collect();
+    match chars.as_slice() {
+        ['十'] => Some(10),
+        [c] => digit(*c),
+        ['十', c] => digit(*c).map(|d| 10 + d),
+        [c, '十'] => digit(*c).map(|d| d * 10),
+        [c, '十', d] => Some(digit(*c)? * 10 + digit(*d)?),
+        _ => None,
+    }
+}
@@ -0,0 +1,340 @@
+use self::CsaError::*;
+use crate::game::Game;
+use crate::movement::Move;
+use crate::player::PlayerColor;
+use crate::record::*;
+use crate::sfen::SfenError;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CsaError {
+    InvalidLine(String),
+    InvalidMove(String),
+    IllegalMove(String),
+    UnknownPiece(String),
+    /// The piece with the given id has no two-letter CSA code
+    NoCode(String),
+    Sfen(SfenError),
+}
+
+pub type CsaResult<T> = Result<T, CsaError>;
+
+const ENDS: &[(&str, GameEnd)] = &[
+    ("%TORYO", GameEnd::Resignation),
+    ("%TSUMI", GameEnd::Checkmate),
+    ("%SENNICHITE", GameEnd::Sennichite),
+    ("%JISHOGI", GameEnd::Jishogi),
+    ("%KACHI", GameEnd::EnteringKing),
+    ("%TIME_UP", GameEnd::TimeUp),
+    ("%ILLEGAL_MOVE", GameEnd::IllegalMove),
+    ("%CHUDAN", GameEnd::Interruption),
+];
+
+impl Record {
+    /**
+    Reads a record in the CSA format, for a game of the variant of `game`.
+    Pieces are found through their two-letter aliases, e.g. `FU` or `NG`.
+    **/
+    pub fn from_csa(game: &Game, csa: &str) -> CsaResult<Record> {
+        let mut record = Record::default();
+        // the position being set up, until the side to move is given
+        let mut setup: Option<Game> = None;
+        let mut position: Option<Game> = None;
+
+        let statements = csa.lines().flat_map(|line| {
+            if line.starts_with('\'') {
+                vec![]
+            } else {
+                line.split(',').map(str::trim).collect()
+            }
+        });
+        for statement in statements {
+            let invalid = || InvalidLine(statement.to_string());
+            if statement.is_empty() || statement.starts_with('V') {
+                continue;
+            }
+            if let Some(name) = statement.strip_prefix("N+") {
+                record.sente = Some(name.to_string());
+            } else if let Some(name) = statement.strip_prefix("N-") {
+                record.gote = Some(name.to_string());
+            } else if let Some(header) = statement.strip_prefix('$') {
+                let index = header.find(':').ok_or_else(invalid)?;
+                record
+                    .headers
+                    .push((header[..index].to_string(), header[index + 1..].to_string()));
+            } else if !statement.is_ascii() {
+                return Err(invalid());
+            } else if let Some(removed) = statement.strip_prefix("PI") {
+                let mut start = game.clone();
+                start.load_sfen(HIRATE_SFEN).map_err(Sfen)?;
+                for (square, _) in pieces(&start, removed).ok_or_else(invalid)? {
+                    let square = square.ok_or_else(invalid)?;
+                    start.board_mut().clear_pos(square.0, square.1).unwrap();
+                }
+                setup = Some(start);
+            } else if statement.starts_with("P+") || statement.starts_with("P-") {
+                let color = color(&statement[1..2]).unwrap();
+                let start = setup.get_or_insert_with(|| empty_position(game, PlayerColor::White));
+                for (square, piece) in pieces(start, &statement[2..]).ok_or_else(invalid)? {
+                    match square {
+                        Some((x, y)) => start.board_mut().set(x, y, Some((piece, color))).unwrap(),
+                        None => {
+                            let player = start.players.iter_mut().find(|p| p.color == color);
+                            player.ok_or_else(invalid)?.hand.push(piece);
+                        }
+                    }
+                }
+            } else if statement.starts_with('P') {
+                let rank = statement.get(1..2).ok_or_else(invalid)?;
+                let rank: usize = rank.parse().map_err(|_| invalid())?;
+                let start = setup.get_or_insert_with(|| empty_position(game, PlayerColor::White));
+                let width = start.board().width.get();
+                let mut cells: Vec<char> = statement[2..].chars().collect();
+                // the trailing spaces of an empty square may have been trimmed
+                if cells.len() < width * 3 {
+                    cells.resize(width * 3, ' ');
+                }
+                if cells.len() != width * 3 {
+                    return Err(invalid());
+                }
+                for (i, cell) in cells.chunks(3).enumerate() {
+                    let (x, y) = board_square(start, width - i, rank).ok_or_else(invalid)?;
+                    let cell: String = cell.iter().collect();
+                    if cell == " * " {
+                        continue;
+                    }
+                    let color = cell.get(..1).and_then(color).ok_or_else(invalid)?;
+                    let code = &cell[1..];
+                    let piece =
+                        piece_coded(start, code).ok_or_else(|| UnknownPiece(code.to_string()))?;
+                    start.board_mut().set(x, y, Some((piece, color))).unwrap();
+                }
+            } else if statement == "+" || statement == "-" {
+                let mut start = setup
+                    .take()
+                    .map(Ok)
+                    .unwrap_or_else(|| {
+                        let mut start = game.clone();
+                        start.load_sfen(HIRATE_SFEN).map(|_| start)
+                    })
+                    .map_err(Sfen)?;
+                start.set_current_player(color(statement).unwrap());
+                let sfen = start.sfen().map_err(Sfen)?;
+                record.sfen = if sfen == HIRATE_SFEN {
+                    None
+                } else {
+                    Some(sfen)
+                };
+                position = Some(start);
+            } else if statement.starts_with('+') || statement.starts_with('-') {
+                let position = position.as_mut().ok_or_else(invalid)?;
+                let mv = parse_move(position, statement)?;
+                position.play(&mv).unwrap();
+                record.moves.push(RecordMove { mv, time: None });
+            } else if let Some(time) = statement.strip_prefix('T') {
+                let time = time.parse().map_err(|_| invalid())?;
+                let last = record.moves.last_mut().ok_or_else(invalid)?;
+                last.time = Some(Duration::from_secs(time));
+            } else if statement.starts_with('%') {
+                if let Some((_, end)) = ENDS.iter().find(|(name, _)| *name == statement) {
+                    record.end = Some(*end);
+                }
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(record)
+    }
+
+    /// Writes the record in the CSA format; `game` gives the variant's pieces
+    pub fn csa(&self, game: &Game) -> CsaResult<String> {
+        let mut position = self
+            .start(game)
+            .ok_or_else(|| InvalidLine(self.sfen.clone().unwrap_or_default()))?;
+        let mut res = String::from("V2.2\n");
+        if let Some(sente) = &self.sente {
+            res.push_str(&format!("N+{}\n", sente));
+        }
+        if let Some(gote) = &self.gote {
+            res.push_str(&format!("N-{}\n", gote));
+        }
+        for (key, value) in &self.headers {
+            res.push_str(&format!("${}:{}\n", key, value));
+        }
+
+        if self.sfen.is_none() {
+            res.push_str("PI\n");
+        } else {
+            let width = position.board().width.get();
+            let height = position.board().height.get();
+            for rank in 1..=height {
+                res.push_str(&format!("P{}", rank));
+                for file in (1..=width).rev() {
+                    let (x, y) = board_square(&position, file, rank).unwrap();
+                    match position.board().get(x, y).unwrap() {
+                        Some((piece, color)) => {
+                            res.push_str(sign(color));
+                            res.push_str(&code(&position, piece)?);
+                        }
+                        None => res.push_str(" * "),
+                    }
+                }
+                res.push('\n');
+            }
+            for color in &[PlayerColor::White, PlayerColor::Black] {
+                if let Some(player) = position.player(*color) {
+                    if !player.hand.is_empty() {
+                        let mut hand = player.hand.clone();
+                        hand.sort();
+                        res.push_str(&format!("P{}", sign(*color)));
+                        for piece in hand {
+                            res.push_str(&format!("00{}", code(&position, piece)?));
+                        }
+                        res.push('\n');
+                    }
+                }
+            }
+        }
+        let color = position
+            .current_player()
+            .map(|p| p.color)
+            .unwrap_or(PlayerColor::White);
+        res.push_str(&format!("{}\n", sign(color)));
+
+        for mv in &self.moves {
+            let color = position
+                .current_player()
+                .map(|p| p.color)
+                .unwrap_or(PlayerColor::White);
+            let (from_file, from_rank) = match mv.mv.from {
+                Some(from) => shogi_square(&position, from),
+                None => (0, 0),
+            };
+            let (to_file, to_rank) = shogi_square(&position, mv.mv.to);
+            res.push_str(&format!(
+                "{}{}{}{}{}{}\n",
+                sign(color),
+                from_file,
+                from_rank,
+                to_file,
+                to_rank,
+                code(&position, mv.mv.final_piece())?
+            ));
+            if let Some(time) = mv.time {
+                res.push_str(&format!("T{}\n", time.as_secs()));
+            }
+            position
+                .play(&mv.mv)
+                .ok_or_else(|| IllegalMove(position.move_name(&mv.mv)))?;
+        }
+        if let Some(end) = self.end {
+            let (name, _) = ENDS.iter().find(|(_, e)| *e == end).unwrap();
+            res.push_str(&format!("{}\n", name));
+        }
+        Ok(res)
+    }
+}
+
+/// Parses a move such as `+7776FU`, where the piece is the one standing on the destination square after the move
+fn parse_move(position: &Game, statement: &str) -> CsaResult<Move> {
+    let invalid = || InvalidMove(statement.to_string());
+    if statement.len() != 7 || !statement.is_char_boundary(5) {
+        return Err(invalid());
+    }
+    let digits: Vec<usize> = statement[1..5]
+        .chars()
+        .map(|c| c.to_digit(10).map(|d| d as usize))
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+    let piece = piece_coded(position, &statement[5..])
+        .ok_or_else(|| UnknownPiece(statement[5..].to_string()))?;
+    let from = match (digits[0], digits[1]) {
+        (0, 0) => None,
+        (file, rank) => Some(board_square(position, file, rank).ok_or_else(invalid)?),
+    };
+    let to = board_square(position, digits[2], digits[3]).ok_or_else(invalid)?;
+
+    position
+        .legal_moves()
+        .into_iter()
+        .find(|mv| mv.from == from && mv.to == to && mv.final_piece() == piece)
+        .ok_or_else(|| IllegalMove(statement.to_string()))
+}
+
+/// A square and the piece standing on it
+type Placement = (Option<(usize, usize)>, usize);
+
+/// Parses a list of pieces such as `82HI22KA` or `00FU`; squares are `None` for pieces in hand
+fn pieces(position: &Game, raw: &str) -> Option<Vec<Placement>> {
+    let chars: Vec<char> = raw.chars().collect();
+    if !chars.len().is_multiple_of(4) {
+        return None;
+    }
+    chars
+        .chunks(4)
+        .map(|chunk| {
+            let file = chunk[0].to_digit(10)? as usize;
+            let rank = chunk[1].to_digit(10)? as usize;
+            let piece = piece_coded(position, &chunk[2..].iter().collect::<String>())?;
+            let square = match (file, rank) {
+                (0, 0) => None,
+                _ => Some(board_square(position, file, rank)?),
+            };
+            Some((square, piece))
+        })
+        .collect()
+}
+
+/// Finds the piece with the given two-letter alias
+fn piece_coded(position: &Game, code: &str) -> Option<usize> {
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    position
+        .pieces()
+        .iter()
+        .position(|p| p.alias_list().iter().any(|alias| alias == code))
+}
+
+fn code(position: &Game, piece: usize) -> CsaResult<String> {
+    let piece = &position.pieces()[piece];
+    piece
+        .alias_list()
+        .iter()
+        .find(|alias| alias.len() == 2 && alias.chars().all(|c| c.is_ascii_uppercase()))
+        .cloned()
+        .ok_or_else(|| NoCode(piece.id().to_string()))
+}
+
+fn color(sign: &str) -> Option<PlayerColor> {
+    match sign {
+        "+" => Some(PlayerColor::White),
+        "-" => Some(PlayerColor::Black),
+        _ => None,
+    }
+}
+
+fn sign(color: PlayerColor) -> &'static str {
+    if color.white() {
+        "+"
+    } else {
+        "-"
+    }
+}
+
+impl fmt::Display for CsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidLine(l) => write!(f, "invalid line {}", l),
+            InvalidMove(m) => write!(f, "invalid move {}", m),
+            IllegalMove(m) => write!(f, "illegal move {}", m),
+            UnknownPiece(p) => write!(f, "unknown piece {}", p),
+            NoCode(id) => write!(f, "piece {} has no CSA code", id),
+            Sfen(e) => write!(f, "invalid position: {}", e),
+        }
+    }
+}
+
+impl Error for CsaError {}
@@ -0,0 +1,539 @@
+use self::DriverError::*;
+use crate::engine::SearchLimits;
+use crate::game::Game;
+use crate::movement::Move;
+use crate::player::PlayerColor;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time given to an engine to answer `uci`, `usi` or `isready`
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time given to an engine to quit before it is killed
+const QUIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Protocol spoken by an external engine
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Uci,
+    Usi,
+}
+
+/// Score of an `info` line, for the player to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Moves until mate for UCI and plies for USI, negative if the player to move gets mated
+    Mate(i32),
+}
+
+/// What an engine reported about its search in an `info` line
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DriverInfo {
+    pub depth: Option<usize>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    /// Principal variation, cut at its first move that isn't legal
+    pub pv: Vec<Move>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DriverEvent {
+    Info(DriverInfo),
+    /// The search ended, with the engine's move or `None` if it has none or resigns
+    BestMove(Option<Move>),
+}
+
+#[derive(Debug)]
+pub enum DriverError {
+    Spawn(io::Error),
+    Io(io::Error),
+    /// The engine didn't answer in time
+    Timeout,
+    /// The engine quit
+    Closed,
+    /// There is no search running
+    NotSearching,
+    /// The position can't be written in FEN or SFEN
+    InvalidPosition,
+    IllegalMove(String),
+}
+
+pub type DriverResult<T> = Result<T, DriverError>;
+
+/**
+An external engine, run as a subprocess speaking UCI or USI.
+Each search sends the whole game, from its starting position and through its move history,
+so that the engine stays in sync with the `Game` it is given.
+**/
+#[derive(Debug)]
+pub struct EngineDriver {
+    protocol: Protocol,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    name: Option<String>,
+    author: Option<String>,
+    timeout: Duration,
+    /// Position of the current search, in which `info` and `bestmove` lines are read
+    searching: Option<Game>,
+    /// When the engine must have answered the current search by, if its limits or a `stop` bound it
+    deadline: Option<Instant>,
+    info: Option<DriverInfo>,
+}
+
+impl EngineDriver {
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Name given by the engine in its `id name` line
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// Whether a search is running
+    pub fn is_searching(&self) -> bool {
+        self.searching.is_some()
+    }
+
+    /// The last `info` line with a score or a principal variation, of the current or last search
+    pub fn info(&self) -> Option<&DriverInfo> {
+        self.info.as_ref()
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> DriverResult<()> {
+        self.send(&format!("setoption name {} value {}", name, value))
+    }
+
+    /// Waits for the engine to be ready
+    pub fn sync(&mut self) -> DriverResult<()> {
+        self.send("isready")?;
+        self.wait_for("readyok")
+    }
+
+    /// Tells the engine that the next search is from another game
+    pub fn new_game(&mut self) -> DriverResult<()> {
+        self.send(match self.protocol {
+            Protocol::Uci => "ucinewgame",
+            Protocol::Usi => "usinewgame",
+        })?;
+        self.sync()
+    }
+
+    /**
+    Starts searching `game` within `limits`; a search without limits goes on until `stop` is called.
+    The clock of the limits is taken as the one of the player to move.
+    A search with a clock or a move time must end within that time and the driver's timeout, or `next_event` fails with `Timeout`.
+    **/
+    pub fn go(&mut self, game: &Game, limits: &SearchLimits) -> DriverResult<()> {
+        if self.searching.is_some() {
+            self.stop()?;
+            while self.searching.is_some() {
+                self.next_event()?;
+            }
+        }
+        let position = self.position_command(game)?;
+        self.send(&position)?;
+        self.send(&self.go_command(game, limits))?;
+        self.searching = Some(game.clone());
+        // the engine may use all of its clock, and USI engines their byoyomi on top of it
+        let clock = limits.clock.as_ref().map(|clock| clock.remaining);
+        self.deadline = match (clock, limits.movetime) {
+            (None, None) => None,
+            (clock, movetime) => Some(
+                Instant::now()
+                    + clock.unwrap_or_default()
+                    + movetime.unwrap_or_default()
+                    + self.timeout,
+            ),
+        };
+        self.info = None;
+        Ok(())
+    }
+
+    /// Asks the engine to end its search; its best move is still reported by `next_event`, within the driver's timeout
+    pub fn stop(&mut self) -> DriverResult<()> {
+        if self.searching.is_none() {
+            return Ok(());
+        }
+        let deadline = Instant::now() + self.timeout;
+        self.deadline = Some(self.deadline.map_or(deadline, |d| d.min(deadline)));
+        self.send("stop")
+    }
+
+    /// Reads the engine's output until its next `info` or `bestmove` line
+    pub fn next_event(&mut self) -> DriverResult<DriverEvent> {
+        loop {
+            if self.searching.is_none() {
+                return Err(NotSearching);
+            }
+            let line = self.recv(self.deadline)?;
+            if let Some(event) = self.read_event(&line)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Reads the engine's output without waiting, returning `None` if it has nothing new to report
+    pub fn poll(&mut self) -> DriverResult<Option<DriverEvent>> {
+        while self.searching.is_some() {
+            let line = match self.lines.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(Closed),
+            };
+            if let Some(event) = self.read_event(&line)? {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Searches `game` within `limits` and waits for the engine's move
+    pub fn best_move(&mut self, game: &Game, limits: &SearchLimits) -> DriverResult<Option<Move>> {
+        self.go(game, limits)?;
+        loop {
+            if let DriverEvent::BestMove(mv) = self.next_event()? {
+                return Ok(mv);
+            }
+        }
+    }
+
+    fn read_event(&mut self, line: &str) -> DriverResult<Option<DriverEvent>> {
+        let mut words = line.split_whitespace();
+        match (words.next(), &self.searching) {
+            (Some("info"), Some(game)) => {
+                let info = parse_info(game, self.protocol, line);
+                if let Some(info) = &info {
+                    self.info = Some(info.clone());
+                }
+                Ok(info.map(DriverEvent::Info))
+            }
+            (Some("bestmove"), Some(_)) => {
+                // the search is over, even if its move is illegal
+                let game = self.searching.take().ok_or(NotSearching)?;
+                let mv = match words.next() {
+                    None | Some("0000") | Some("(none)") | Some("resign") | Some("win") => None,
+                    Some(text) => Some(parse_move(&game, self.protocol, text)?),
+                };
+                Ok(Some(DriverEvent::BestMove(mv)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The `position` command setting up the game's starting position, followed by its moves
+    fn position_command(&self, game: &Game) -> DriverResult<String> {
+        let mut start = game.clone();
+        while start.undo().is_some() {}
+        let setup = match self.protocol {
+            // variants with drops are sent in SFEN, which our own UCI adapter accepts as well
+            Protocol::Uci => start
+                .fen()
+                .ok()
+                .or_else(|| start.sfen().ok())
+                .map(|fen| format!("fen {}", fen)),
+            Protocol::Usi => start.sfen().ok().map(|sfen| format!("sfen {}", sfen)),
+        }
+        .ok_or(InvalidPosition)?;
+
+        let mut res = format!("position {}", setup);
+        if !game.history().is_empty() {
+            res.push_str(" moves");
+            for mv in game.history() {
+                res.push(' ');
+                res.push_str(&write_move(&start, self.protocol, mv));
+                start.play(mv).ok_or(InvalidPosition)?;
+            }
+        }
+        Ok(res)
+    }
+
+    fn go_command(&self, game: &Game, limits: &SearchLimits) -> String {
+        let mut res = String::from("go");
+        let color = game.current_player().map(|player| player.color);
+        if let Some(clock) = &limits.clock {
+            // USI's `b` stands for sente, who moves first like white
+            let (time, increment) = match (self.protocol, color) {
+                (Protocol::Uci, Some(PlayerColor::Black)) => ("btime", "binc"),
+                (Protocol::Uci, _) => ("wtime", "winc"),
+                (Protocol::Usi, Some(PlayerColor::Black)) => ("wtime", "winc"),
+                (Protocol::Usi, _) => ("btime", "binc"),
+            };
+            res.push_str(&format!(" {} {}", time, clock.remaining.as_millis()));
+            res.push_str(&format!(" {} {}", increment, clock.increment.as_millis()));
+            if let (Protocol::Uci, Some(moves)) = (self.protocol, clock.moves_to_go) {
+                res.push_str(&format!(" movestogo {}", moves));
+            }
+        }
+        if let Some(movetime) = limits.movetime {
+            match self.protocol {
+                Protocol::Uci => res.push_str(&format!(" movetime {}", movetime.as_millis())),
+                // a byoyomi without main time makes USI engines spend about that much
+                Protocol::Usi => {
+                    if limits.clock.is_none() {
+                        res.push_str(" btime 0 wtime 0");
+                    }
+                    res.push_str(&format!(" byoyomi {}", movetime.as_millis()));
+                }
+            }
+        }
+        if let Some(depth) = limits.depth {
+            res.push_str(&format!(" depth {}", depth));
+        }
+        if let Some(nodes) = limits.nodes {
+            res.push_str(&format!(" nodes {}", nodes));
+        }
+        if *limits == SearchLimits::default() {
+            res.push_str(" infinite");
+        }
+        res
+    }
+
+    fn send(&mut self, line: &str) -> DriverResult<()> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::BrokenPipe => Closed,
+                _ => Io(e),
+            })
+    }
+
+    /// Reads the engine's next line, failing with `Timeout` once `deadline` has passed
+    fn recv(&self, deadline: Option<Instant>) -> DriverResult<String> {
+        match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                self.lines.recv_timeout(left).map_err(|e| match e {
+                    RecvTimeoutError::Timeout => Timeout,
+                    RecvTimeoutError::Disconnected => Closed,
+                })
+            }
+            None => self.lines.recv().map_err(|_| Closed),
+        }
+    }
+
+    /// Reads lines until `expected`, noting the engine's name and author on the way
+    fn wait_for(&mut self, expected: &str) -> DriverResult<()> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let line = self.recv(Some(deadline))?;
+            let line = line.trim();
+            if line == expected {
+                return Ok(());
+            }
+            if let Some(name) = line.strip_prefix("id name ") {
+                self.name = Some(name.to_string());
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                self.author = Some(author.to_string());
+            }
+        }
+    }
+}
+
+impl Drop for EngineDriver {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Reads an `info` line in the position `game`, returning `None` if it has neither a score nor a principal variation
+pub fn parse_info(game: &Game, protocol: Protocol, line: &str) -> Option<DriverInfo> {
+    let mut info = DriverInfo::default();
+    let mut words = line.split_whitespace().skip(1);
+    while let Some(word) = words.next() {
+        match word {
+            "depth" => info.depth = words.next().and_then(|n| n.parse().ok()),
+            "nodes" => info.nodes = words.next().and_then(|n| n.parse().ok()),
+            "score" => {
+                let kind = words.next();
+                let value = words.next();
+                info.score = match (kind, value) {
+                    (Some("cp"), Some(n)) => n.parse().ok().map(Score::Centipawns),
+                    // USI engines may only give the sign of a mate
+                    (Some("mate"), Some("+")) => Some(Score::Mate(1)),
+                    (Some("mate"), Some("-")) => Some(Score::Mate(-1)),
+                    (Some("mate"), Some(n)) => n.parse().ok().map(Score::Mate),
+                    _ => None,
+                };
+            }
+            "pv" => {
+                let mut game = game.clone();
+                for text in words.by_ref() {
+                    match parse_move(&game, protocol, text) {
+                        Ok(mv) => {
+                            game.play(&mv);
+                            info.pv.push(mv);
+                        }
+                        Err(_) => break,
+                    }
+                }
+            }
+            // the rest of the line is free text
+            "string" => break,
+            _ => {}
+        }
+    }
+    if info.score.is_none() && info.pv.is_empty() {
+        None
+    } else {
+        Some(info)
+    }
+}
+
+fn parse_move(game: &Game, protocol: Protocol, text: &str) -> DriverResult<Move> {
+    match protocol {
+        Protocol::Uci => game.parse_uci_move(text).ok(),
+        Protocol::Usi => game.parse_usi_move(text).ok(),
+    }
+    .ok_or_else(|| IllegalMove(text.to_string()))
+}
+
+fn write_move(game: &Game, protocol: Protocol, mv: &Move) -> String {
+    match protocol {
+        Protocol::Uci => game.uci_move(mv),
+        Protocol::Usi => game.usi_move(mv),
+    }
+}
+
+pub struct DriverBuilder {
+    driver_command: String,
+    driver_args: Vec<String>,
+    driver_protocol: Protocol,
+    driver_options: Vec<(String, String)>,
+    driver_timeout: Duration,
+}
+
+impl Default for DriverBuilder {
+    fn default() -> Self {
+        DriverBuilder {
+            driver_command: String::new(),
+            driver_args: vec![],
+            driver_protocol: Protocol::Uci,
+            driver_options: vec![],
+            driver_timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl DriverBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Path of the engine's executable
+    pub fn command(mut self, command: &str) -> Self {
+        self.driver_command = command.to_string();
+        self
+    }
+
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.driver_args.push(arg.to_string());
+        self
+    }
+
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.driver_protocol = protocol;
+        self
+    }
+
+    /// Sets an option of the engine once it started
+    pub fn option(mut self, name: &str, value: &str) -> Self {
+        self.driver_options
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Time given to the engine to start, to answer `isready`, and to end a search past its limits or a `stop`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.driver_timeout = timeout;
+        self
+    }
+
+    /// Starts the engine, and waits for it to be ready
+    pub fn build(self) -> DriverResult<EngineDriver> {
+        let mut child = Command::new(&self.driver_command)
+            .args(&self.driver_args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(Spawn)?;
+        let stdin = child.stdin.take().ok_or(Closed)?;
+        let stdout = child.stdout.take().ok_or(Closed)?;
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut driver = EngineDriver {
+            protocol: self.driver_protocol,
+            child,
+            stdin,
+            lines,
+            name: None,
+            author: None,
+            timeout: self.driver_timeout,
+            searching: None,
+            deadline: None,
+            info: None,
+        };
+        match driver.protocol {
+            Protocol::Uci => {
+                driver.send("uci")?;
+                driver.wait_for("uciok")?;
+            }
+            Protocol::Usi => {
+                driver.send("usi")?;
+                driver.wait_for("usiok")?;
+            }
+        }
+        for (name, value) in &self.driver_options {
+            driver.set_option(name, value)?;
+        }
+        driver.sync()?;
+        Ok(driver)
+    }
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Spawn(e) => write!(f, "couldn't start the engine: {}", e),
+            Io(e) => write!(f, "couldn't talk to the engine: {}", e),
+            Timeout => write!(f, "the engine didn't answer in time"),
+            Closed => write!(f, "the engine quit"),
+            NotSearching => write!(f, "the engine isn't searching"),
+            InvalidPosition => write!(f, "the position can't be sent to the engine"),
+            IllegalMove(mv) => write!(f, "the engine played an illegal move {}", mv),
+        }
+    }
+}
+
+impl Error for DriverError {}
@@ -0,0 +1,597 @@
+use crate::game::{Game, Outcome};
+use crate::movement::Move;
+use crate::player::PlayerColor;
+use crate::value::EstimatorBuilder;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Score of a checkmate for the player who mates; every ply until the mate lowers it by one
+pub const MATE: i32 = 1_000_000;
+/// Scores beyond this bound are mates
+const MATE_BOUND: i32 = MATE - 1000;
+const INFINITY: i32 = MATE + 1;
+const MAX_PLY: usize = 128;
+const DEFAULT_MOBILITY: i32 = 4;
+const DEFAULT_HASH_SIZE: usize = 1 << 18;
+/// Moves assumed to be left in the game when the clock has no time control
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// Time kept on the clock to answer in time
+const CLOCK_MARGIN: Duration = Duration::from_millis(50);
+
+/// The time left on the clock of the player to move
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Clock {
+    pub remaining: Duration,
+    /// Time added after each move
+    pub increment: Duration,
+    /// Moves until the next time control, if any
+    pub moves_to_go: Option<u32>,
+}
+
+/// Limits of a search, which stops as soon as one of them is reached
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    /// Time to spend on the move
+    pub movetime: Option<Duration>,
+    /// Clock from which the time to spend on the move is derived
+    pub clock: Option<Clock>,
+    /// Searches on the opponent's time: the time to spend only counts from the ponder hit, see `Engine::ponder_handle`
+    pub ponder: bool,
+}
+
+/// State of a search after a completed iteration
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchInfo {
+    pub depth: usize,
+    /// Score in centipawns for the player to move, see `MATE`
+    pub score: i32,
+    pub nodes: u64,
+    pub time: Duration,
+    /// Principal variation, the best line found
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> Option<&Move> {
+        self.pv.first()
+    }
+
+    /// Number of moves until mate, negative if the player to move gets mated
+    pub fn mate(&self) -> Option<i32> {
+        if self.score > MATE_BOUND {
+            Some((MATE - self.score + 1) / 2)
+        } else if self.score < -MATE_BOUND {
+            Some(-(MATE + self.score) / 2)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    key: u64,
+    depth: usize,
+    score: i32,
+    bound: Bound,
+    best: Option<Move>,
+}
+
+/// An alpha-beta searcher, evaluating positions from piece values and mobility
+#[derive(Debug)]
+pub struct Engine {
+    values: Vec<i32>,
+    mobility: i32,
+    table: Vec<Option<Entry>>,
+    killers: Vec<[Option<Move>; 2]>,
+    history: HashMap<(usize, (usize, usize)), i32>,
+    stop: Arc<AtomicBool>,
+    ponder_hit: Arc<AtomicBool>,
+    stopped: bool,
+    nodes: u64,
+    node_limit: Option<u64>,
+    /// Time to spend on the search, from its start or from the ponder hit
+    budget: Option<Duration>,
+    /// Whether the search is pondering and waits for the ponder hit to spend its time
+    pondering: bool,
+    deadline: Option<Instant>,
+    /// Keys of the positions leading to the one being searched
+    keys: Vec<u64>,
+}
+
+impl Engine {
+    /// Value of a piece, in centipawns
+    pub fn piece_value(&self, piece: usize) -> i32 {
+        self.values.get(piece).copied().unwrap_or(0)
+    }
+
+    /// A flag stopping the current or next search when set, from any thread; searches clear it when they return
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// A flag turning the current pondering search into a normal one when set, from any thread; searches clear it when they return
+    pub fn ponder_handle(&self) -> Arc<AtomicBool> {
+        self.ponder_hit.clone()
+    }
+
+    /// Forgets what was learnt from previous searches
+    pub fn clear(&mut self) {
+        for entry in self.table.iter_mut() {
+            *entry = None;
+        }
+        self.history.clear();
+    }
+
+    /// Static evaluation of a position, in centipawns for the player to move
+    pub fn evaluate(&self, game: &Game) -> i32 {
+        let board = game.board();
+        let occupied = board.occupied();
+        let mut score = 0;
+        for &color in &[PlayerColor::White, PlayerColor::Black] {
+            let sign = if color == PlayerColor::White { 1 } else { -1 };
+            let own = board.occupied_by(color);
+            for (x, y) in board.squares_of(color) {
+                let (piece, _) = board.get(x, y).unwrap().unwrap();
+                score += sign * self.piece_value(piece);
+                if let Some(table) = game.table(board, piece, color) {
+                    let moves = table.targets(x, y, occupied, own).count() as i32;
+                    score += sign * self.mobility * moves;
+                }
+            }
+            if let Some(player) = game.player(color) {
+                score += sign
+                    * player
+                        .hand
+                        .iter()
+                        .map(|&p| self.piece_value(p))
+                        .sum::<i32>();
+            }
+        }
+        match game.current_player() {
+            Some(player) if player.color == PlayerColor::Black => -score,
+            _ => score,
+        }
+    }
+
+    /// Searches the best move, within `limits`
+    pub fn search(&mut self, game: &Game, limits: &SearchLimits) -> SearchInfo {
+        self.search_with(game, limits, |_| {})
+    }
+
+    /// Searches the best move, within `limits`, calling `report` after each iteration
+    pub fn search_with<F: FnMut(&SearchInfo)>(
+        &mut self,
+        game: &Game,
+        limits: &SearchLimits,
+        mut report: F,
+    ) -> SearchInfo {
+        let start = Instant::now();
+        self.budget = time_budget(limits);
+        self.pondering = limits.ponder;
+        self.stopped = false;
+        self.nodes = 0;
+        self.node_limit = limits.nodes;
+        self.deadline = None;
+        self.check_ponder_hit();
+        self.killers = vec![[None, None]; MAX_PLY + 1];
+
+        let mut game = game.clone();
+        self.keys.clear();
+        let mut previous = game.clone();
+        while previous.undo().is_some() {
+            self.keys.push(previous.position_key());
+        }
+
+        let mut info = SearchInfo {
+            depth: 0,
+            score: self.evaluate(&game),
+            nodes: 0,
+            time: Duration::default(),
+            pv: game.legal_moves().into_iter().take(1).collect(),
+        };
+        if info.pv.is_empty() {
+            info.score = terminal_score(&game, 0);
+            self.stop.store(false, Ordering::Relaxed);
+            self.ponder_hit.store(false, Ordering::Relaxed);
+            return info;
+        }
+        let max_depth = limits.depth.unwrap_or(MAX_PLY).clamp(1, MAX_PLY);
+        for depth in 1..=max_depth {
+            let score = self.negamax(&mut game, depth, 0, -INFINITY, INFINITY);
+            // an interrupted iteration is discarded, unless there is no other
+            if self.stopped && depth > 1 {
+                break;
+            }
+            let pv = self.principal_variation(&mut game, depth);
+            if !pv.is_empty() {
+                info.pv = pv;
+            }
+            if !self.stopped {
+                info.depth = depth;
+                info.score = score;
+            }
+            info.nodes = self.nodes;
+            info.time = start.elapsed();
+            report(&info);
+            // the next iteration would most likely not finish in time
+            self.check_ponder_hit();
+            let out_of_time = match (self.deadline, self.budget) {
+                (Some(deadline), Some(budget)) => {
+                    deadline.saturating_duration_since(Instant::now()) < budget / 2
+                }
+                _ => false,
+            };
+            if self.stopped || out_of_time {
+                break;
+            }
+        }
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponder_hit.store(false, Ordering::Relaxed);
+        info.nodes = self.nodes;
+        info.time = start.elapsed();
+        info
+    }
+
+    fn negamax(
+        &mut self,
+        game: &mut Game,
+        depth: usize,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+        let key = game.position_key();
+        if ply > 0 && self.keys.contains(&key) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(game, ply, alpha, beta);
+        }
+        self.nodes += 1;
+
+        let mut tt_move = None;
+        if let Some(entry) = self.probe(key) {
+            let score = score_from_table(entry.score, ply);
+            if ply > 0 && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+            tt_move = entry.best.clone();
+        }
+
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            return terminal_score(game, ply);
+        }
+        let moves = self.order(moves, tt_move.as_ref(), ply);
+
+        let alpha_start = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        self.keys.push(key);
+        for mv in moves {
+            game.make_move(mv.clone());
+            let score = -self.negamax(game, depth - 1, ply + 1, -beta, -alpha);
+            game.undo();
+            if self.stopped {
+                break;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv.clone());
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                if !mv.is_capture() {
+                    self.add_killer(mv.clone(), ply);
+                    *self.history.entry((mv.piece, mv.to)).or_insert(0) += (depth * depth) as i32;
+                }
+                break;
+            }
+        }
+        self.keys.pop();
+        if self.stopped {
+            // the root keeps the best move of the moves searched so far
+            if ply == 0 && best_move.is_some() {
+                self.store(key, 0, best_score, Bound::Lower, best_move);
+            }
+            return 0;
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > alpha_start {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.store(
+            key,
+            depth,
+            score_to_table(best_score, ply),
+            bound,
+            best_move,
+        );
+        best_score
+    }
+
+    /// Searches captures and promotions until the position is quiet
+    fn quiescence(&mut self, game: &mut Game, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+        let stand_pat = self.evaluate(game);
+        if stand_pat >= beta || ply >= MAX_PLY {
+            return stand_pat;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let moves = game
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.is_capture() || mv.promotion.is_some())
+            .collect();
+        for mv in self.order(moves, None, ply) {
+            game.make_move(mv);
+            let score = -self.quiescence(game, ply + 1, -beta, -alpha);
+            game.undo();
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+        alpha
+    }
+
+    /// Sorts moves from the most to the least promising: the move of the transposition table,
+    /// captures of valuable pieces by cheap ones, promotions, killer moves, then by history
+    fn order(&self, moves: Vec<Move>, tt_move: Option<&Move>, ply: usize) -> Vec<Move> {
+        let mut scored: Vec<(i32, Move)> = moves
+            .into_iter()
+            .map(|mv| {
+                let score = if Some(&mv) == tt_move {
+                    1 << 30
+                } else if mv.is_capture() || mv.promotion.is_some() {
+                    let captured = mv.captured.map_or(0, |piece| self.piece_value(piece));
+                    let promoted = mv.promotion.map_or(0, |piece| {
+                        self.piece_value(piece) - self.piece_value(mv.piece)
+                    });
+                    (1 << 24) + 16 * (captured + promoted) - self.piece_value(mv.piece)
+                } else if self
+                    .killers
+                    .get(ply)
+                    .is_some_and(|k| k.contains(&Some(mv.clone())))
+                {
+                    1 << 20
+                } else {
+                    self.history
+                        .get(&(mv.piece, mv.to))
+                        .copied()
+                        .unwrap_or(0)
+                        .min((1 << 20) - 1)
+                };
+                (score, mv)
+            })
+            .collect();
+        scored.sort_by_key(|&(score, _)| Reverse(score));
+        scored.into_iter().map(|(_, mv)| mv).collect()
+    }
+
+    fn add_killer(&mut self, mv: Move, ply: usize) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0].as_ref() != Some(&mv) {
+                killers[1] = killers[0].take();
+                killers[0] = Some(mv);
+            }
+        }
+    }
+
+    /// Follows the best moves of the transposition table from the current position
+    fn principal_variation(&mut self, game: &mut Game, depth: usize) -> Vec<Move> {
+        let mut pv = vec![];
+        let mut seen = vec![];
+        while pv.len() < depth {
+            let key = game.position_key();
+            if seen.contains(&key) {
+                break;
+            }
+            seen.push(key);
+            let mv = match self.probe(key).and_then(|entry| entry.best.clone()) {
+                Some(mv) if game.legal_moves().contains(&mv) => mv,
+                _ => break,
+            };
+            game.make_move(mv.clone());
+            pv.push(mv);
+        }
+        for _ in 0..pv.len() {
+            game.undo();
+        }
+        pv
+    }
+
+    /// Starts spending the time budget, unless still pondering
+    fn check_ponder_hit(&mut self) {
+        if self.pondering && self.ponder_hit.load(Ordering::Relaxed) {
+            self.pondering = false;
+        }
+        if !self.pondering && self.deadline.is_none() {
+            self.deadline = self.budget.map(|budget| Instant::now() + budget);
+        }
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped {
+            let checkpoint = self.nodes & 1023 == 0;
+            if checkpoint {
+                self.check_ponder_hit();
+            }
+            self.stopped = self.node_limit.is_some_and(|limit| self.nodes >= limit)
+                || (checkpoint && self.stop.load(Ordering::Relaxed))
+                || (checkpoint
+                    && self
+                        .deadline
+                        .is_some_and(|deadline| Instant::now() >= deadline));
+        }
+        self.stopped
+    }
+
+    fn probe(&self, key: u64) -> Option<&Entry> {
+        let index = key as usize & (self.table.len() - 1);
+        self.table[index].as_ref().filter(|entry| entry.key == key)
+    }
+
+    fn store(&mut self, key: u64, depth: usize, score: i32, bound: Bound, best: Option<Move>) {
+        let index = key as usize & (self.table.len() - 1);
+        self.table[index] = Some(Entry {
+            key,
+            depth,
+            score,
+            bound,
+            best,
+        });
+    }
+}
+
+/// Score of a position without legal moves; the variant's rules decide whether it is lost, as in drop variants, or drawn
+fn terminal_score(game: &Game, ply: usize) -> i32 {
+    match (game.result(), game.current_player()) {
+        (Some(Outcome::Win(winner)), Some(player)) if winner == player.color => MATE - ply as i32,
+        (Some(Outcome::Win(_)), _) => ply as i32 - MATE,
+        _ => 0,
+    }
+}
+
+/// Mate scores are stored relative to the position rather than to the root
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score + ply as i32
+    } else if score < -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score - ply as i32
+    } else if score < -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// Time to spend on the move
+fn time_budget(limits: &SearchLimits) -> Option<Duration> {
+    let from_clock = limits.clock.map(|clock| {
+        let moves_to_go = clock.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let budget = clock.remaining / moves_to_go + clock.increment * 3 / 4;
+        budget.min(
+            clock
+                .remaining
+                .checked_sub(CLOCK_MARGIN)
+                .unwrap_or_default(),
+        )
+    });
+    match (limits.movetime, from_clock) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+pub struct EngineBuilder {
+    engine_values: Vec<(String, i32)>,
+    engine_mobility: i32,
+    engine_hash_size: usize,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        EngineBuilder {
+            engine_values: vec![],
+            engine_mobility: DEFAULT_MOBILITY,
+            engine_hash_size: DEFAULT_HASH_SIZE,
+        }
+    }
+}
+
+impl EngineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value, in centipawns, of the piece with the given id or alias
+    pub fn piece_value(mut self, piece: &str, value: i32) -> Self {
+        self.engine_values.push((piece.to_string(), value));
+        self
+    }
+
+    /// Value of each square a piece may move to, in centipawns
+    pub fn mobility(mut self, weight: i32) -> Self {
+        self.engine_mobility = weight;
+        self
+    }
+
+    /// Number of entries of the transposition table, rounded up to a power of two
+    pub fn hash_size(mut self, entries: usize) -> Self {
+        self.engine_hash_size = entries;
+        self
+    }
+
+    /// Builds an engine for the variant of `game`
+    pub fn build(self, game: &Game) -> Engine {
+        let mut values = game.estimate_values(&EstimatorBuilder::new().build());
+        for (name, value) in self.engine_values {
+            if let Some(piece) = game.piece_index(&name) {
+                values[piece] = value;
+            }
+        }
+        Engine {
+            values,
+            mobility: self.engine_mobility,
+            table: vec![None; self.engine_hash_size.max(1).next_power_of_two()],
+            killers: vec![],
+            history: HashMap::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder_hit: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+            nodes: 0,
+            node_limit: None,
+            budget: None,
+            pondering: false,
+            deadline: None,
+            keys: vec![],
+        }
+    }
+}
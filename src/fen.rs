@@ -0,0 +1,321 @@
+use self::FenError::*;
+use crate::board::Board;
+use crate::coordinates::Coordinates;
+use crate::game::Game;
+use crate::player::PlayerColor;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    InvalidPlacement(String),
+    UnknownPiece(char),
+    /// The piece with the given id has no single-letter alias and can't be written in FEN
+    NoLetter(String),
+    InvalidColor(String),
+    InvalidCastling(char),
+    InvalidSquare(String),
+    InvalidNumber(String),
+}
+
+pub type FenResult<T> = Result<T, FenError>;
+
+impl Game {
+    /**
+    Loads the position described by a FEN string, replacing the current one.
+    Boards of any size are supported, with multi-digit counts of empty squares.
+    Pieces are found through their letter (see `Piece::letter`): uppercase for white, lowercase for black.
+    Castling rights may be given as `KQkq` or as rook files (X-FEN and Shredder-FEN), which requires a `Rule::Castling`.
+    The halfmove clock and fullmove number may be omitted.
+    **/
+    pub fn load_fen(&mut self, fen: &str) -> FenResult<()> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or(MissingField("placement"))?;
+        let color = fields.next().ok_or(MissingField("color"))?;
+        let castling = fields.next().unwrap_or("-");
+        let en_passant = fields.next().unwrap_or("-");
+        let halfmove_clock = parse_number(fields.next().unwrap_or("0"))?;
+        let fullmove = fields.next().unwrap_or("1");
+        let fullmove_number = parse_number(fullmove)?;
+
+        let mut board = self.board().clone();
+        board.clear();
+        let width = board.width.get();
+        let height = board.height.get();
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != height {
+            return Err(InvalidPlacement(format!(
+                "expected {} ranks, got {}",
+                height,
+                ranks.len()
+            )));
+        }
+        for (i, rank) in ranks.into_iter().enumerate() {
+            let y = height - 1 - i;
+            let mut x = 0;
+            let mut empty = 0;
+            for c in rank.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    empty = empty * 10 + digit as usize;
+                    if x + empty > width {
+                        return Err(InvalidPlacement(format!("rank {} is too long", rank)));
+                    }
+                    continue;
+                }
+                x += empty;
+                empty = 0;
+                if c == '~' {
+                    // promoted piece marker, as used by some crazyhouse tools
+                    continue;
+                }
+                let piece = self
+                    .pieces()
+                    .iter()
+                    .position(|p| p.letter() == Some(c.to_ascii_uppercase()))
+                    .ok_or(UnknownPiece(c))?;
+                let color = if c.is_ascii_uppercase() {
+                    PlayerColor::White
+                } else {
+                    PlayerColor::Black
+                };
+                if x >= width {
+                    return Err(InvalidPlacement(format!("rank {} is too long", rank)));
+                }
+                board.set(x, y, Some((piece, color))).unwrap();
+                x += 1;
+            }
+            x += empty;
+            if x != width {
+                return Err(InvalidPlacement(format!(
+                    "rank {} has {} squares instead of {}",
+                    rank, x, width
+                )));
+            }
+        }
+
+        let color = match color {
+            "w" => PlayerColor::White,
+            "b" => PlayerColor::Black,
+            _ => return Err(InvalidColor(color.to_string())),
+        };
+        if self.player(color).is_none() {
+            return Err(InvalidColor(color_char(color).to_string()));
+        }
+        let ply_number = fullmove_number
+            .max(1)
+            .checked_mul(2)
+            .map(|ply| ply - if color.white() { 1 } else { 0 })
+            .ok_or_else(|| InvalidNumber(fullmove.to_string()))?;
+
+        let mut castling_rights = vec![];
+        if castling != "-" {
+            let (king, rook) = self
+                .castling_pieces()
+                .ok_or_else(|| InvalidCastling(castling.chars().next().unwrap_or_default()))?;
+            for c in castling.chars() {
+                let color = if c.is_ascii_uppercase() {
+                    PlayerColor::White
+                } else if c.is_ascii_lowercase() {
+                    PlayerColor::Black
+                } else {
+                    return Err(InvalidCastling(c));
+                };
+                let file = match c.to_ascii_uppercase() {
+                    'K' | 'Q' => {
+                        let (king_x, king_y) =
+                            find_piece(&board, king, color).ok_or(InvalidCastling(c))?;
+                        let mut rooks = (0..width)
+                            .filter(|&x| board.get(x, king_y).unwrap() == Some((rook, color)));
+                        let outermost = if c.eq_ignore_ascii_case(&'K') {
+                            rooks.rev().find(|&x| x > king_x)
+                        } else {
+                            rooks.find(|&x| x < king_x)
+                        };
+                        outermost.ok_or(InvalidCastling(c))?
+                    }
+                    file => {
+                        let file = (file as u8 - b'A') as usize;
+                        if file >= width {
+                            return Err(InvalidCastling(c));
+                        }
+                        file
+                    }
+                };
+                castling_rights.push((color, file));
+            }
+        }
+
+        let en_passant = if en_passant == "-" {
+            None
+        } else {
+            Some(
+                Coordinates::Algebraic
+                    .parse_square(en_passant, width, height, PlayerColor::White)
+                    .ok_or_else(|| InvalidSquare(en_passant.to_string()))?,
+            )
+        };
+
+        *self.board_mut() = board;
+        self.clear_history();
+        for player in self.players.iter_mut() {
+            player.hand.clear();
+        }
+        self.set_current_player(color).unwrap();
+        self.set_castling(castling_rights);
+        self.set_en_passant(en_passant);
+        self.set_halfmove_clock(halfmove_clock);
+        self.set_fullmove_number(fullmove_number);
+        self.set_ply_number(ply_number);
+        Ok(())
+    }
+
+    /// Returns the current position as a FEN string, with X-FEN castling rights
+    pub fn fen(&self) -> FenResult<String> {
+        self.write_fen(false)
+    }
+
+    /// Returns the current position as a Shredder-FEN string, where castling rights are always given as rook files
+    pub fn shredder_fen(&self) -> FenResult<String> {
+        self.write_fen(true)
+    }
+
+    fn write_fen(&self, shredder: bool) -> FenResult<String> {
+        let board = self.board();
+        let width = board.width.get();
+        let height = board.height.get();
+        let mut res = String::new();
+
+        for y in (0..height).rev() {
+            let mut empty = 0;
+            for x in 0..width {
+                match board.get(x, y).unwrap() {
+                    Some((piece, color)) => {
+                        if empty > 0 {
+                            res.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let piece = &self.pieces()[piece];
+                        let letter = piece
+                            .letter()
+                            .ok_or_else(|| NoLetter(piece.id().to_string()))?;
+                        res.push(if color.white() {
+                            letter
+                        } else {
+                            letter.to_ascii_lowercase()
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                res.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                res.push('/');
+            }
+        }
+
+        res.push(' ');
+        res.push(color_char(
+            self.current_player()
+                .map(|p| p.color)
+                .unwrap_or(PlayerColor::White),
+        ));
+
+        res.push(' ');
+        let mut rights = self.castling().clone();
+        rights.sort_by(|a, b| b.0.white().cmp(&a.0.white()).then(b.1.cmp(&a.1)));
+        if rights.is_empty() {
+            res.push('-');
+        }
+        for (color, file) in rights {
+            let letter = if shredder {
+                None
+            } else {
+                self.castling_letter(color, file)
+            }
+            .unwrap_or((b'A' + file as u8) as char);
+            res.push(if color.white() {
+                letter
+            } else {
+                letter.to_ascii_lowercase()
+            });
+        }
+
+        res.push(' ');
+        match self.en_passant() {
+            Some(square) => res.push_str(&Coordinates::Algebraic.square_name(
+                square,
+                width,
+                height,
+                PlayerColor::White,
+            )),
+            None => res.push('-'),
+        }
+
+        res.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock(),
+            self.fullmove_number()
+        ));
+        Ok(res)
+    }
+
+    /// Returns `K` or `Q` if the castling right with the rook on `file` can be written as such
+    fn castling_letter(&self, color: PlayerColor, file: usize) -> Option<char> {
+        let (king, rook) = self.castling_pieces()?;
+        let board = self.board();
+        let (king_x, king_y) = find_piece(board, king, color)?;
+        let rooks: Vec<usize> = (0..board.width.get())
+            .filter(|&x| board.get(x, king_y).unwrap() == Some((rook, color)))
+            .collect();
+        if file > king_x && rooks.iter().rev().find(|&&x| x > king_x) == Some(&file) {
+            Some('K')
+        } else if file < king_x && rooks.iter().find(|&&x| x < king_x) == Some(&file) {
+            Some('Q')
+        } else {
+            None
+        }
+    }
+}
+
+fn find_piece(board: &Board, piece: usize, color: PlayerColor) -> Option<(usize, usize)> {
+    for y in 0..board.height.get() {
+        for x in 0..board.width.get() {
+            if board.get(x, y).unwrap() == Some((piece, color)) {
+                return Some((x, y));
+            }
+        }
+    }
+    None
+}
+
+fn color_char(color: PlayerColor) -> char {
+    if color.white() {
+        'w'
+    } else {
+        'b'
+    }
+}
+
+fn parse_number(raw: &str) -> FenResult<usize> {
+    raw.parse().map_err(|_| InvalidNumber(raw.to_string()))
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingField(field) => write!(f, "missing {} field", field),
+            InvalidPlacement(e) => write!(f, "invalid piece placement: {}", e),
+            UnknownPiece(c) => write!(f, "unknown piece letter {}", c),
+            NoLetter(id) => write!(f, "piece {} has no letter", id),
+            InvalidColor(c) => write!(f, "invalid color {}", c),
+            InvalidCastling(c) => write!(f, "invalid castling right {}", c),
+            InvalidSquare(s) => write!(f, "invalid square {}", s),
+            InvalidNumber(n) => write!(f, "invalid number {}", n),
+        }
+    }
+}
+
+impl Error for FenError {}
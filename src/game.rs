@@ -1,15 +1,50 @@
+use crate::bitboard::{color_index, AttackTable, Bitboard};
 use crate::board::Board;
+use crate::coordinates::Coordinates;
+use crate::movement::{moves_from, Move, MoveKind, MovementType, RawMovement};
 use crate::piece::Piece;
 use crate::player::{Player, PlayerColor};
+use crate::rule::Rule;
 use std::default::Default;
 use std::num::NonZeroUsize;
+use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Game {
     pieces: Vec<Piece>,
+    /// Attack tables of each piece, for each color
+    tables: Arc<Vec<[AttackTable; 2]>>,
     board: Board,
     pub players: Vec<Player>,
     current_player: usize,
+    rules: Vec<Rule>,
+    coordinates: Coordinates,
+    castling: Vec<(PlayerColor, usize)>,
+    en_passant: Option<(usize, usize)>,
+    halfmove_clock: usize,
+    fullmove_number: usize,
+    ply_number: usize,
+    history: Vec<Move>,
+    undo_stack: Vec<Undo>,
+}
+
+/// How a game ends once the player to move has no legal moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The given player wins, by checkmate or, in variants with drops, by leaving the opponent without moves
+    Win(PlayerColor),
+    /// Stalemate
+    Draw,
+}
+
+/// What a move changed, besides the pieces' positions, so that it can be undone
+#[derive(Clone, Debug)]
+struct Undo {
+    captured: Option<((usize, usize), usize, PlayerColor)>,
+    hand_piece: Option<usize>,
+    castling: Vec<(PlayerColor, usize)>,
+    en_passant: Option<(usize, usize)>,
+    halfmove_clock: usize,
 }
 
 impl Game {
@@ -17,10 +52,54 @@ impl Game {
         &self.pieces
     }
 
+    /// Replaces the movement of a piece, and rebuilds its attack tables
+    pub fn set_movement(&mut self, piece: usize, movement_type: Vec<MovementType>) -> Option<()> {
+        let width = self.board.width.get();
+        let height = self.board.height.get();
+        let piece_ref = self.pieces.get_mut(piece)?;
+        piece_ref.set_movement_type(movement_type);
+        let table = |color| AttackTable::new(piece_ref.movement_type(), color, width, height);
+        let tables = [table(PlayerColor::White), table(PlayerColor::Black)];
+        Arc::make_mut(&mut self.tables)[piece] = tables;
+        Some(())
+    }
+
     pub fn board(&self) -> &Board {
         &self.board
     }
 
+    pub fn board_mut(&mut self) -> &mut Board {
+        &mut self.board
+    }
+
+    pub fn rules(&self) -> &Vec<Rule> {
+        &self.rules
+    }
+
+    pub fn coordinates(&self) -> Coordinates {
+        self.coordinates
+    }
+
+    /// Names a square with the game's coordinates, as seen by the white player
+    pub fn square_name(&self, square: (usize, usize)) -> String {
+        self.coordinates.square_name(
+            square,
+            self.board.width.get(),
+            self.board.height.get(),
+            PlayerColor::White,
+        )
+    }
+
+    /// Parses the name of a square in the game's coordinates, as seen by the white player
+    pub fn parse_square(&self, raw: &str) -> Option<(usize, usize)> {
+        self.coordinates.parse_square(
+            raw,
+            self.board.width.get(),
+            self.board.height.get(),
+            PlayerColor::White,
+        )
+    }
+
     pub fn search_piece<'a>(&'a self, id: &str) -> Option<&'a Piece> {
         self.pieces.iter().find(|x| x.id() == id)
     }
@@ -32,13 +111,15 @@ impl Game {
             .collect()
     }
 
-    pub fn set(&mut self, x: usize, y: usize, piece: &str, color: PlayerColor) -> Option<()> {
-        let piece_index = self
-            .pieces
+    /// Returns the index of the piece with the given id or alias
+    pub fn piece_index(&self, piece: &str) -> Option<usize> {
+        self.pieces
             .iter()
-            .enumerate()
-            .find(|(_k, x)| x.id() == piece || x.alias_list().contains(&piece.to_string()))?
-            .0;
+            .position(|x| x.id() == piece || x.alias_list().contains(&piece.to_string()))
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, piece: &str, color: PlayerColor) -> Option<()> {
+        let piece_index = self.piece_index(piece)?;
         self.board.set(x, y, Some((piece_index, color))).ok()
     }
 
@@ -56,12 +137,712 @@ impl Game {
             self.current_player = 0;
         }
     }
+
+    pub fn set_current_player(&mut self, color: PlayerColor) -> Option<()> {
+        self.current_player = self.players.iter().position(|p| p.color == color)?;
+        Some(())
+    }
+
+    /// Castling rights, as the color of the player and the file of the rook they may castle with
+    pub fn castling(&self) -> &Vec<(PlayerColor, usize)> {
+        &self.castling
+    }
+
+    pub fn set_castling(&mut self, castling: Vec<(PlayerColor, usize)>) {
+        self.castling = castling;
+    }
+
+    /// The square that a pawn may capture en passant on, if any
+    pub fn en_passant(&self) -> Option<(usize, usize)> {
+        self.en_passant
+    }
+
+    pub fn set_en_passant(&mut self, en_passant: Option<(usize, usize)>) {
+        self.en_passant = en_passant;
+    }
+
+    /// Number of half-moves since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> usize {
+        self.halfmove_clock
+    }
+
+    pub fn set_halfmove_clock(&mut self, halfmove_clock: usize) {
+        self.halfmove_clock = halfmove_clock;
+    }
+
+    /// Number of the current move, starting at 1 and incremented once every player has played
+    pub fn fullmove_number(&self) -> usize {
+        self.fullmove_number
+    }
+
+    pub fn set_fullmove_number(&mut self, fullmove_number: usize) {
+        self.fullmove_number = fullmove_number;
+    }
+
+    /// Number of the current half-move, starting at 1 and incremented after every move
+    pub fn ply_number(&self) -> usize {
+        self.ply_number
+    }
+
+    pub fn set_ply_number(&mut self, ply_number: usize) {
+        self.ply_number = ply_number;
+    }
+
+    /// Describes a move with the game's coordinates, like `e2-e4`, or `P*e4` for a drop
+    pub fn move_name(&self, mv: &Move) -> String {
+        let name = |piece: usize| {
+            let piece = &self.pieces[piece];
+            piece
+                .letter()
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| piece.id().to_string())
+        };
+        let mut res = match mv.from {
+            Some(from) => format!("{}-{}", self.square_name(from), self.square_name(mv.to)),
+            None => format!("{}*{}", name(mv.piece), self.square_name(mv.to)),
+        };
+        if let Some(promotion) = mv.promotion {
+            res.push('=');
+            res.push_str(&name(promotion));
+        }
+        res
+    }
+
+    /// Moves played so far, since the game started or its position was last loaded
+    pub fn history(&self) -> &Vec<Move> {
+        &self.history
+    }
+
+    /// Forgets the moves played so far, which can then no longer be undone
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.undo_stack.clear();
+    }
+
+    /// Returns whether the current player's royal pieces are attacked
+    pub fn is_check(&self) -> bool {
+        match self.current_player() {
+            Some(player) => self.royal_attacked(&self.board, player.color),
+            None => false,
+        }
+    }
+
+    /**
+    Returns the outcome of the game if it is over.
+    Without legal moves, the player to move loses if in check or if the variant has drops, and draws otherwise.
+    **/
+    pub fn result(&self) -> Option<Outcome> {
+        if !self.legal_moves().is_empty() {
+            return None;
+        }
+        let color = self.current_player()?.color;
+        if self.is_check() || self.rules.contains(&Rule::Drops) {
+            Some(Outcome::Win(color.opponent()))
+        } else {
+            Some(Outcome::Draw)
+        }
+    }
+
+    /// A hash of the position: the pieces on the board and in hand, the player to move, the castling rights and the en passant square
+    pub fn position_key(&self) -> u64 {
+        let mut key = zobrist(0, self.current_player as u64);
+        for &color in &[PlayerColor::White, PlayerColor::Black] {
+            let color_index = color_index(color) as u64;
+            for (x, y) in self.board.squares_of(color) {
+                let (piece, _) = self.board.get(x, y).unwrap().unwrap();
+                let square = self.square_index(x, y);
+                key ^= zobrist(1, (piece as u64 * 2 + color_index) << 32 | square);
+            }
+        }
+        for player in &self.players {
+            let color_index = color_index(player.color) as u64;
+            let mut hand = player.hand.clone();
+            hand.sort_unstable();
+            // each copy of a piece in hand has its own key
+            for (i, &piece) in hand.iter().enumerate() {
+                let copy = hand[..i].iter().filter(|&&p| p == piece).count() as u64;
+                key ^= zobrist(2, (piece as u64 * 2 + color_index) << 8 | copy);
+            }
+        }
+        for &(color, file) in &self.castling {
+            key ^= zobrist(3, (color_index(color) as u64) << 8 | file as u64);
+        }
+        if let Some((x, y)) = self.en_passant {
+            key ^= zobrist(4, self.square_index(x, y));
+        }
+        key
+    }
+
+    fn square_index(&self, x: usize, y: usize) -> u64 {
+        (y * self.board.width.get() + x) as u64
+    }
+
+    /// Returns whether the square (x, y) is attacked by any of `by`'s pieces.
+    /// Empty squares count as attacked if one of `by`'s pieces could capture there.
+    pub fn is_attacked(&self, x: usize, y: usize, by: PlayerColor) -> bool {
+        if self.board.get(x, y).ok().flatten().is_none() {
+            let mut board = self.board.clone();
+            if board.set(x, y, Some((0, by.opponent()))).is_err() {
+                return false;
+            }
+            self.attacked_on(&board, x, y, by)
+        } else {
+            self.attacked_on(&self.board, x, y, by)
+        }
+    }
+
+    /// Returns the moves that the current player may legally play
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let color = match self.current_player() {
+            Some(player) => player.color,
+            None => return vec![],
+        };
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|mv| {
+                let mut board = self.board.clone();
+                self.apply_to_board(&mut board, mv, color);
+                !self.royal_attacked(&board, color) && !self.is_drop_mate(mv)
+            })
+            .collect()
+    }
+
+    /// Plays a move, if it is legal
+    pub fn play(&mut self, mv: &Move) -> Option<()> {
+        if self.legal_moves().contains(mv) {
+            self.make_move(mv.clone());
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Undoes the last move played, returning it
+    pub fn undo(&mut self) -> Option<Move> {
+        let mv = self.history.pop()?;
+        let undo = self.undo_stack.pop()?;
+        self.previous_player();
+        let color = self.players[self.current_player].color;
+
+        match mv.from {
+            None => {
+                self.board.clear_pos(mv.to.0, mv.to.1).ok()?;
+                self.players[self.current_player].hand.push(mv.piece);
+            }
+            Some((x, y)) => {
+                if let Some(rook_file) = mv.castling() {
+                    let rook = self.board.get(self.castling_rook_to(&mv), y).ok()??;
+                    self.board.clear_pos(mv.to.0, y).ok()?;
+                    self.board.clear_pos(self.castling_rook_to(&mv), y).ok()?;
+                    self.board.set(x, y, Some((mv.piece, color))).ok()?;
+                    self.board.set(rook_file, y, Some(rook)).ok()?;
+                } else {
+                    self.board.clear_pos(mv.to.0, mv.to.1).ok()?;
+                    self.board.set(x, y, Some((mv.piece, color))).ok()?;
+                }
+            }
+        }
+        if let Some(((x, y), piece, captured_color)) = undo.captured {
+            self.board.set(x, y, Some((piece, captured_color))).ok()?;
+        }
+        if let Some(piece) = undo.hand_piece {
+            let hand = &mut self.players[self.current_player].hand;
+            if let Some(index) = hand.iter().rposition(|&p| p == piece) {
+                hand.remove(index);
+            }
+        }
+
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.ply_number -= 1;
+        if self.current_player + 1 == self.players.len() {
+            self.fullmove_number -= 1;
+        }
+        Some(mv)
+    }
+
+    /// Plays a move without checking that it is legal
+    pub(crate) fn make_move(&mut self, mv: Move) {
+        let color = self.players[self.current_player].color;
+        let mut undo = Undo {
+            captured: None,
+            hand_piece: None,
+            castling: self.castling.clone(),
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+        };
+
+        let mut board = self.board.clone();
+        undo.captured = self.apply_to_board(&mut board, &mv, color);
+        self.board = board;
+
+        match mv.from {
+            None => {
+                let hand = &mut self.players[self.current_player].hand;
+                if let Some(index) = hand.iter().position(|&p| p == mv.piece) {
+                    hand.remove(index);
+                }
+            }
+            Some((x, y)) => {
+                if self.is_castling_king(mv.piece) {
+                    self.castling.retain(|(c, _)| *c != color);
+                } else if self.is_castling_rook(mv.piece) && y == self.home_rank(color) {
+                    self.castling.retain(|&(c, file)| c != color || file != x);
+                }
+            }
+        }
+        if let Some(((x, y), piece, captured_color)) = undo.captured {
+            if self.is_castling_rook(piece) && y == self.home_rank(captured_color) {
+                self.castling
+                    .retain(|&(c, file)| c != captured_color || file != x);
+            }
+            if self.rules.contains(&Rule::Drops) {
+                let piece = self.demoted(piece);
+                self.players[self.current_player].hand.push(piece);
+                undo.hand_piece = Some(piece);
+            }
+        }
+
+        self.en_passant = match mv.from {
+            Some((x, y))
+                if self.is_en_passant_piece(mv.piece)
+                    && x == mv.to.0
+                    && (y as isize - mv.to.1 as isize).abs() == 2 =>
+            {
+                Some((x, (y + mv.to.1) / 2))
+            }
+            _ => None,
+        };
+        if undo.captured.is_some() || self.is_pawn(mv.piece) {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        self.ply_number += 1;
+        self.next_player();
+        if self.current_player == 0 {
+            self.fullmove_number += 1;
+        }
+        self.history.push(mv);
+        self.undo_stack.push(undo);
+    }
+
+    /// Moves that the current player's pieces may make, without taking care of their royal pieces' safety
+    fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let player = match self.current_player() {
+            Some(player) => player,
+            None => return vec![],
+        };
+        let color = player.color;
+        let width = self.board.width.get();
+        let height = self.board.height.get();
+        let mut res = vec![];
+
+        for y in 0..height {
+            for x in 0..width {
+                let piece = match self.board.get(x, y).unwrap() {
+                    Some((piece, c)) if c == color => piece,
+                    _ => continue,
+                };
+                for mv in self.piece_moves(x, y) {
+                    self.push_promotions(&mut res, mv, color);
+                }
+                if let Some((ep_x, ep_y)) = self.en_passant {
+                    if self.is_en_passant_piece(piece)
+                        && self.board.get(ep_x, ep_y).unwrap().is_none()
+                    {
+                        // a pawn standing on the en passant square could be captured there
+                        let mut board = self.board.clone();
+                        board
+                            .set(ep_x, ep_y, Some((piece, color.opponent())))
+                            .unwrap();
+                        let captures = self.piece_movements(&board, player, piece, x, y);
+                        if captures.iter().any(|&(dx, dy)| {
+                            x as isize + dx == ep_x as isize && y as isize + dy == ep_y as isize
+                        }) && !res
+                            .iter()
+                            .any(|mv: &Move| mv.from == Some((x, y)) && mv.to == (ep_x, ep_y))
+                        {
+                            let captured = self.board.get(ep_x, y).unwrap().map(|(p, _)| p);
+                            let mv = Move {
+                                kind: MoveKind::EnPassant((ep_x, y)),
+                                ..Move::new((x, y), (ep_x, ep_y), piece, captured)
+                            };
+                            self.push_promotions(&mut res, mv, color);
+                        }
+                    }
+                }
+            }
+        }
+
+        res.append(&mut self.castling_moves(color));
+        res.append(&mut self.drop_moves(player));
+        res
+    }
+
+    /// Moves of the piece standing on (x, y), without promotions or special moves
+    pub fn piece_moves(&self, x: usize, y: usize) -> Vec<Move> {
+        let (piece, color) = match self.board.get(x, y) {
+            Ok(Some(piece)) => piece,
+            _ => return vec![],
+        };
+        let player = match self.player(color) {
+            Some(player) => player,
+            None => return vec![],
+        };
+        let raw = self.piece_movements(&self.board, player, piece, x, y);
+        moves_from(&self.board, player, piece, x, y, raw)
+    }
+
+    /// Raw movements of a piece standing on (x, y), without duplicates
+    pub(crate) fn piece_movements(
+        &self,
+        board: &Board,
+        player: &Player,
+        piece: usize,
+        x: usize,
+        y: usize,
+    ) -> Vec<RawMovement> {
+        if let Some(table) = self.table(board, piece, player.color) {
+            let targets = table.targets(x, y, board.occupied(), board.occupied_by(player.color));
+            return targets
+                .squares()
+                .map(|(tx, ty)| (tx as isize - x as isize, ty as isize - y as isize))
+                .collect();
+        }
+        let mut res: Vec<RawMovement> = self.pieces[piece]
+            .movement_type()
+            .iter()
+            .filter_map(|mt| mt.flatten(board, player, x, y))
+            .flatten()
+            .collect();
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+
+    fn castling_moves(&self, color: PlayerColor) -> Vec<Move> {
+        let mut res = vec![];
+        let (king, rook) = match self.castling_pieces() {
+            Some(pieces) => pieces,
+            None => return res,
+        };
+        let y = self.home_rank(color);
+        let width = self.board.width.get();
+        let king_x =
+            match (0..width).find(|&x| self.board.get(x, y).unwrap() == Some((king, color))) {
+                Some(x) => x,
+                None => return res,
+            };
+
+        for &(c, rook_x) in &self.castling {
+            if c != color || self.board.get(rook_x, y).ok().flatten() != Some((rook, color)) {
+                continue;
+            }
+            let mv = Move {
+                kind: MoveKind::Castling(rook_x),
+                ..Move::new(
+                    (king_x, y),
+                    (if rook_x > king_x { width - 2 } else { 2 }, y),
+                    king,
+                    None,
+                )
+            };
+            let rook_to = self.castling_rook_to(&mv);
+            let king_to = mv.to.0;
+            let min = king_x.min(rook_x).min(king_to).min(rook_to);
+            let max = king_x.max(rook_x).max(king_to).max(rook_to);
+            let path_clear = (min..=max)
+                .all(|x| x == king_x || x == rook_x || self.board.get(x, y).unwrap().is_none());
+            let path_safe = (king_x.min(king_to)..=king_x.max(king_to))
+                .all(|x| !self.is_attacked(x, y, color.opponent()));
+            if path_clear && path_safe {
+                res.push(mv);
+            }
+        }
+        res
+    }
+
+    fn drop_moves(&self, player: &Player) -> Vec<Move> {
+        let mut res = vec![];
+        if !self.rules.contains(&Rule::Drops) {
+            return res;
+        }
+        let mut pieces = player.hand.clone();
+        pieces.sort_unstable();
+        pieces.dedup();
+
+        for piece in pieces {
+            let one_per_file = self.rules.iter().any(|rule| match rule {
+                Rule::OnePerFile(id) => id == self.pieces[piece].id(),
+                _ => false,
+            });
+            for x in 0..self.board.width.get() {
+                if one_per_file
+                    && (0..self.board.height.get())
+                        .any(|y| self.board.get(x, y).unwrap() == Some((piece, player.color)))
+                {
+                    continue;
+                }
+                for y in 0..self.board.height.get() {
+                    if self.board.get(x, y).unwrap().is_none()
+                        && !self.is_dead(piece, player.color, x, y)
+                    {
+                        res.push(Move::drop(piece, (x, y)));
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Pushes a move, along with its promotions
+    fn push_promotions(&self, res: &mut Vec<Move>, mv: Move, color: PlayerColor) {
+        let (from, to, piece) = (mv.from.unwrap(), mv.to, mv.piece);
+        for rule in &self.rules {
+            if let Rule::Promotion {
+                piece: id,
+                to: targets,
+                zone,
+                optional,
+            } = rule
+            {
+                if id != self.pieces[piece].id()
+                    || !(self.in_zone(from.1, *zone, color) || self.in_zone(to.1, *zone, color))
+                {
+                    continue;
+                }
+                if *optional && !self.is_dead(piece, color, to.0, to.1) {
+                    res.push(mv.clone());
+                }
+                for target in targets {
+                    if let Some(target) = self.piece_index(target) {
+                        res.push(Move {
+                            promotion: Some(target),
+                            ..mv.clone()
+                        });
+                    }
+                }
+                return;
+            }
+        }
+        res.push(mv);
+    }
+
+    /// Applies the effects of a move on a board, returning the captured piece, if any
+    fn apply_to_board(
+        &self,
+        board: &mut Board,
+        mv: &Move,
+        color: PlayerColor,
+    ) -> Option<((usize, usize), usize, PlayerColor)> {
+        let (x, y) = match mv.from {
+            Some(from) => from,
+            None => {
+                board.set(mv.to.0, mv.to.1, Some((mv.piece, color))).ok()?;
+                return None;
+            }
+        };
+        if let Some(rook_file) = mv.castling() {
+            let rook = board.get(rook_file, y).ok()?;
+            board.clear_pos(x, y).ok()?;
+            board.clear_pos(rook_file, y).ok()?;
+            board.set(mv.to.0, y, Some((mv.piece, color))).ok()?;
+            board.set(self.castling_rook_to(mv), y, rook).ok()?;
+            return None;
+        }
+
+        let captured_pos = mv.en_passant().unwrap_or(mv.to);
+        let captured = board
+            .get(captured_pos.0, captured_pos.1)
+            .ok()?
+            .map(|(piece, c)| (captured_pos, piece, c));
+        board.clear_pos(captured_pos.0, captured_pos.1).ok()?;
+        board.clear_pos(x, y).ok()?;
+        board
+            .set(mv.to.0, mv.to.1, Some((mv.final_piece(), color)))
+            .ok()?;
+        captured
+    }
+
+    fn attacked_on(&self, board: &Board, x: usize, y: usize, by: PlayerColor) -> bool {
+        let player = match self.player(by) {
+            Some(player) => player,
+            None => return false,
+        };
+        let occupied = board.occupied();
+        board.squares_of(by).into_iter().any(|(px, py)| {
+            let (piece, _) = board.get(px, py).unwrap().unwrap();
+            match self.table(board, piece, by) {
+                Some(table) => table.attacks(px, py, occupied).get(x, y),
+                None => self
+                    .piece_movements(board, player, piece, px, py)
+                    .into_iter()
+                    .any(|(dx, dy)| {
+                        px as isize + dx == x as isize && py as isize + dy == y as isize
+                    }),
+            }
+        })
+    }
+
+    fn royal_attacked(&self, board: &Board, color: PlayerColor) -> bool {
+        board.squares_of(color).into_iter().any(|(x, y)| {
+            let (piece, _) = board.get(x, y).unwrap().unwrap();
+            self.is_royal(piece) && self.attacked_on(board, x, y, color.opponent())
+        })
+    }
+
+    /// The attack table of a piece, if it describes the piece's movement on `board` exactly
+    pub(crate) fn table(
+        &self,
+        board: &Board,
+        piece: usize,
+        color: PlayerColor,
+    ) -> Option<&AttackTable> {
+        let table = &self.tables.get(piece)?[color_index(color)];
+        if table.is_exact()
+            && table.width() == board.width.get()
+            && table.height() == board.height.get()
+        {
+            Some(table)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether `mv` drops a piece that may not checkmate, and checkmates
+    fn is_drop_mate(&self, mv: &Move) -> bool {
+        if mv.from.is_some()
+            || !self.rules.iter().any(|rule| match rule {
+                Rule::NoDropMate(id) => id == self.pieces[mv.piece].id(),
+                _ => false,
+            })
+        {
+            return false;
+        }
+        let mut game = self.clone();
+        game.make_move(mv.clone());
+        game.is_check() && game.legal_moves().is_empty()
+    }
+
+    /// Returns whether a piece standing on (x, y) would have no movement at all, even on an empty board
+    fn is_dead(&self, piece: usize, color: PlayerColor, x: usize, y: usize) -> bool {
+        if let Some(table) = self.table(&self.board, piece, color) {
+            return table
+                .targets(x, y, Bitboard::EMPTY, Bitboard::EMPTY)
+                .is_empty();
+        }
+        let mut board = self.board.clone();
+        board.clear();
+        let player = Player::new(color);
+        self.piece_movements(&board, &player, piece, x, y)
+            .is_empty()
+    }
+
+    fn in_zone(&self, y: usize, zone: usize, color: PlayerColor) -> bool {
+        if color.white() {
+            y + zone >= self.board.height.get()
+        } else {
+            y < zone
+        }
+    }
+
+    fn home_rank(&self, color: PlayerColor) -> usize {
+        if color.white() {
+            0
+        } else {
+            self.board.height.get() - 1
+        }
+    }
+
+    /// Destination of the rook of a castling move
+    fn castling_rook_to(&self, mv: &Move) -> usize {
+        if mv.castling().unwrap_or(0) > mv.from.map(|(x, _)| x).unwrap_or(0) {
+            mv.to.0 - 1
+        } else {
+            mv.to.0 + 1
+        }
+    }
+
+    /// Indices of the king and rook pieces of the castling rule, if any
+    pub(crate) fn castling_pieces(&self) -> Option<(usize, usize)> {
+        self.rules.iter().find_map(|rule| match rule {
+            Rule::Castling { king, rook } => {
+                Some((self.piece_index(king)?, self.piece_index(rook)?))
+            }
+            _ => None,
+        })
+    }
+
+    fn is_castling_king(&self, piece: usize) -> bool {
+        self.castling_pieces().map(|(king, _)| king) == Some(piece)
+    }
+
+    fn is_castling_rook(&self, piece: usize) -> bool {
+        self.castling_pieces().map(|(_, rook)| rook) == Some(piece)
+    }
+
+    pub(crate) fn is_royal(&self, piece: usize) -> bool {
+        self.rules.iter().any(|rule| match rule {
+            Rule::Royal(id) => id == self.pieces[piece].id(),
+            _ => false,
+        })
+    }
+
+    fn is_en_passant_piece(&self, piece: usize) -> bool {
+        self.rules.iter().any(|rule| match rule {
+            Rule::EnPassant(id) => id == self.pieces[piece].id(),
+            _ => false,
+        })
+    }
+
+    /// Pawns are the pieces whose letter is `P`
+    pub fn is_pawn(&self, piece: usize) -> bool {
+        self.pieces
+            .get(piece)
+            .and_then(|p| p.letter())
+            .map(|c| c == 'P')
+            .unwrap_or(false)
+    }
+
+    /// Returns the piece that `piece` promoted from, or `piece` itself if it isn't a promoted piece
+    pub fn demoted(&self, piece: usize) -> usize {
+        for rule in &self.rules {
+            if let Rule::Promotion { piece: id, to, .. } = rule {
+                if to.iter().any(|p| p == self.pieces[piece].id()) {
+                    if let Some(index) = self.piece_index(id) {
+                        return index;
+                    }
+                }
+            }
+        }
+        piece
+    }
+
+    fn previous_player(&mut self) {
+        if self.current_player == 0 {
+            self.current_player = self.players.len();
+        }
+        self.current_player -= 1;
+    }
+}
+
+/// A pseudo-random key for the feature `n` of the given kind, from the SplitMix64 generator
+fn zobrist(kind: u64, n: u64) -> u64 {
+    let mut z = (kind << 56 ^ n).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
 
 pub struct GameBuilder {
     game_pieces: Vec<Piece>,
     game_board: Board,
     game_players: Vec<Player>,
+    game_rules: Vec<Rule>,
+    game_coordinates: Coordinates,
 }
 
 impl Default for GameBuilder {
@@ -70,6 +851,8 @@ impl Default for GameBuilder {
             game_pieces: vec![],
             game_board: Board::new(NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(1).unwrap()),
             game_players: vec![],
+            game_rules: vec![],
+            game_coordinates: Coordinates::default(),
         }
     }
 }
@@ -99,12 +882,42 @@ impl GameBuilder {
         self
     }
 
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.game_rules.push(rule);
+        self
+    }
+
+    pub fn coordinates(mut self, coordinates: Coordinates) -> Self {
+        self.game_coordinates = coordinates;
+        self
+    }
+
     pub fn build(self) -> Game {
+        let width = self.game_board.width.get();
+        let height = self.game_board.height.get();
+        let tables = self
+            .game_pieces
+            .iter()
+            .map(|piece| {
+                let table = |color| AttackTable::new(piece.movement_type(), color, width, height);
+                [table(PlayerColor::White), table(PlayerColor::Black)]
+            })
+            .collect();
         Game {
+            tables: Arc::new(tables),
             board: self.game_board,
             pieces: self.game_pieces,
             players: self.game_players,
             current_player: 0,
+            rules: self.game_rules,
+            coordinates: self.game_coordinates,
+            castling: vec![],
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            ply_number: 1,
+            history: vec![],
+            undo_stack: vec![],
         }
     }
 }
@@ -0,0 +1,573 @@
+use self::KifError::*;
+use crate::coordinates::{kanji_number, parse_kanji_number, Coordinates};
+use crate::game::Game;
+use crate::movement::Move;
+use crate::player::PlayerColor;
+use crate::record::*;
+use crate::sfen::SfenError;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KifError {
+    UnknownHandicap(String),
+    InvalidBoard(String),
+    InvalidHand(String),
+    InvalidMove(String),
+    IllegalMove(String),
+    /// The piece with the given id has no kanji name
+    NoName(String),
+    Sfen(SfenError),
+}
+
+pub type KifResult<T> = Result<T, KifError>;
+
+/// Starting positions of the usual handicaps, named as in the `手合割` field
+const HANDICAPS: &[(&str, &str)] = &[
+    ("平手", HIRATE_SFEN),
+    (
+        "香落ち",
+        "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "右香落ち",
+        "1nsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "角落ち",
+        "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "飛車落ち",
+        "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "飛香落ち",
+        "lnsgkgsn1/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "二枚落ち",
+        "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "四枚落ち",
+        "1nsgkgsn1/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "六枚落ち",
+        "2sgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "八枚落ち",
+        "3gkg3/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "十枚落ち",
+        "4k4/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+];
+
+const ENDS: &[(&str, GameEnd)] = &[
+    ("投了", GameEnd::Resignation),
+    ("詰み", GameEnd::Checkmate),
+    ("千日手", GameEnd::Sennichite),
+    ("持将棋", GameEnd::Jishogi),
+    ("入玉勝ち", GameEnd::EnteringKing),
+    ("切れ負け", GameEnd::TimeUp),
+    ("反則負け", GameEnd::IllegalMove),
+    ("中断", GameEnd::Interruption),
+];
+
+/// The position described by the header, before the first move
+#[derive(Default)]
+struct Setup {
+    handicap: Option<String>,
+    rows: Vec<String>,
+    hands: Vec<(PlayerColor, String)>,
+    gote_to_move: bool,
+}
+
+impl Record {
+    /**
+    Reads a record in the KIF format, for a game of the variant of `game`.
+    Pieces are found through their kanji aliases, e.g. `歩` or `成銀`; single-character aliases are used in board diagrams.
+    Only the main line is read: variations (`変化`) are skipped.
+    **/
+    pub fn from_kif(game: &Game, kif: &str) -> KifResult<Record> {
+        let mut record = Record::default();
+        let mut setup = Setup::default();
+        let mut position: Option<Game> = None;
+
+        for line in kif.lines() {
+            let line = line.trim_end();
+            let trimmed = line.trim_start();
+            if trimmed.is_empty()
+                || trimmed.starts_with('#')
+                || trimmed.starts_with('*')
+                || trimmed.starts_with('&')
+                || trimmed.starts_with('+')
+                || trimmed
+                    .chars()
+                    .all(|c| c == ' ' || ('０'..='９').contains(&c))
+                || trimmed.starts_with("手数")
+                || trimmed.starts_with("まで")
+            {
+                continue;
+            }
+            if trimmed.starts_with("変化") {
+                break;
+            }
+            if trimmed.starts_with('|') {
+                setup.rows.push(trimmed.to_string());
+                continue;
+            }
+            if trimmed == "後手番" || trimmed == "上手番" {
+                setup.gote_to_move = true;
+                continue;
+            }
+            if trimmed == "先手番" || trimmed == "下手番" {
+                continue;
+            }
+
+            if trimmed.starts_with(|c: char| c.is_ascii_digit()) {
+                if record.end.is_some() {
+                    continue;
+                }
+                if position.is_none() {
+                    let start = setup.position(game)?;
+                    let sfen = start.sfen().map_err(Sfen)?;
+                    if sfen != HIRATE_SFEN {
+                        record.sfen = Some(sfen);
+                    }
+                    position = Some(start);
+                }
+                let position = position.as_mut().unwrap();
+                let previous = record.moves.last().map(|mv| mv.mv.to);
+                match parse_move_line(position, trimmed, previous)? {
+                    Ok(mv) => {
+                        position.play(&mv.mv).unwrap();
+                        record.moves.push(mv);
+                    }
+                    Err(end) => record.end = Some(end),
+                }
+                continue;
+            }
+
+            if let Some(index) = trimmed.find('：') {
+                let key = &trimmed[..index];
+                let value = trimmed[index + '：'.len_utf8()..].trim();
+                match key {
+                    "先手" | "下手" => record.sente = Some(value.to_string()),
+                    "後手" | "上手" => record.gote = Some(value.to_string()),
+                    "手合割" => setup.handicap = Some(value.to_string()),
+                    "先手の持駒" | "下手の持駒" => {
+                        setup.hands.push((PlayerColor::White, value.to_string()))
+                    }
+                    "後手の持駒" | "上手の持駒" => {
+                        setup.hands.push((PlayerColor::Black, value.to_string()))
+                    }
+                    _ => record.headers.push((key.to_string(), value.to_string())),
+                }
+            }
+        }
+
+        if position.is_none() {
+            let sfen = setup.position(game)?.sfen().map_err(Sfen)?;
+            if sfen != HIRATE_SFEN {
+                record.sfen = Some(sfen);
+            }
+        }
+        Ok(record)
+    }
+
+    /// Writes the record in the KIF format; `game` gives the variant's pieces
+    pub fn kif(&self, game: &Game) -> KifResult<String> {
+        let mut position = self
+            .start(game)
+            .ok_or_else(|| InvalidBoard(self.sfen.clone().unwrap_or_default()))?;
+        let mut res = String::from("# ---- Kifu ----\n");
+        for (key, value) in &self.headers {
+            res.push_str(&format!("{}：{}\n", key, value));
+        }
+
+        let sfen = position.sfen().map_err(Sfen)?;
+        match HANDICAPS.iter().find(|(_, handicap)| *handicap == sfen) {
+            Some((name, _)) => res.push_str(&format!("手合割：{}\n", name)),
+            None => res.push_str(&board_diagram(&position)?),
+        }
+        if let Some(sente) = &self.sente {
+            res.push_str(&format!("先手：{}\n", sente));
+        }
+        if let Some(gote) = &self.gote {
+            res.push_str(&format!("後手：{}\n", gote));
+        }
+        res.push_str("手数----指手---------消費時間--\n");
+
+        let mut totals = [Duration::default(); 2];
+        let mut previous = None;
+        for (i, mv) in self.moves.iter().enumerate() {
+            res.push_str(&format!(
+                "{:>4} {}",
+                i + 1,
+                move_name(&position, &mv.mv, previous)?
+            ));
+            if let Some(time) = mv.time {
+                totals[i % 2] += time;
+                let total = totals[i % 2].as_secs();
+                res.push_str(&format!(
+                    "   ({:>2}:{:02}/{:02}:{:02}:{:02})",
+                    time.as_secs() / 60,
+                    time.as_secs() % 60,
+                    total / 3600,
+                    total / 60 % 60,
+                    total % 60
+                ));
+            }
+            res.push('\n');
+            position
+                .play(&mv.mv)
+                .ok_or_else(|| IllegalMove(position.move_name(&mv.mv)))?;
+            previous = Some(mv.mv.to);
+        }
+        if let Some(end) = self.end {
+            let (name, _) = ENDS.iter().find(|(_, e)| *e == end).unwrap();
+            res.push_str(&format!("{:>4} {}\n", self.moves.len() + 1, name));
+        }
+        Ok(res)
+    }
+}
+
+impl Setup {
+    fn position(&self, game: &Game) -> KifResult<Game> {
+        if self.rows.is_empty() {
+            let handicap = self.handicap.as_deref().unwrap_or("平手");
+            let sfen = HANDICAPS
+                .iter()
+                .find(|(name, _)| *name == handicap)
+                .map(|(_, sfen)| sfen)
+                .ok_or_else(|| UnknownHandicap(handicap.to_string()))?;
+            let mut position = game.clone();
+            position.load_sfen(sfen).map_err(Sfen)?;
+            return Ok(position);
+        }
+
+        let color = if self.gote_to_move {
+            PlayerColor::Black
+        } else {
+            PlayerColor::White
+        };
+        let mut position = empty_position(game, color);
+        let width = game.board().width.get();
+        let height = game.board().height.get();
+        if self.rows.len() != height {
+            return Err(InvalidBoard(format!(
+                "expected {} ranks, got {}",
+                height,
+                self.rows.len()
+            )));
+        }
+        for (i, row) in self.rows.iter().enumerate() {
+            let y = height - 1 - i;
+            let mut cells = row.trim_start_matches('|').chars();
+            for x in 0..width {
+                let color = match cells.next() {
+                    Some(' ') => PlayerColor::White,
+                    Some('v') => PlayerColor::Black,
+                    _ => return Err(InvalidBoard(row.clone())),
+                };
+                let name = cells.next().ok_or_else(|| InvalidBoard(row.clone()))?;
+                if name == '・' {
+                    continue;
+                }
+                let piece = piece_named(&position, &name.to_string())
+                    .ok_or_else(|| InvalidBoard(row.clone()))?;
+                position
+                    .board_mut()
+                    .set(x, y, Some((piece, color)))
+                    .unwrap();
+            }
+            if cells.next() != Some('|') {
+                return Err(InvalidBoard(row.clone()));
+            }
+        }
+
+        for (color, hand) in &self.hands {
+            let pieces = parse_hand(&position, hand)?;
+            if let Some(player) = position.players.iter_mut().find(|p| p.color == *color) {
+                player.hand = pieces;
+            }
+        }
+        Ok(position)
+    }
+}
+
+/// Parses a move line: the move or the game's end, then the time spent
+fn parse_move_line(
+    position: &Game,
+    line: &str,
+    previous: Option<(usize, usize)>,
+) -> KifResult<Result<RecordMove, GameEnd>> {
+    let invalid = || InvalidMove(line.to_string());
+    let rest = line
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim_start();
+    if let Some((_, end)) = ENDS.iter().find(|(name, _)| rest.starts_with(name)) {
+        return Ok(Err(*end));
+    }
+
+    let mut chars = rest.chars();
+    let to = match chars.next() {
+        Some('同') => {
+            if chars.clone().next() == Some('　') {
+                chars.next();
+            }
+            previous.ok_or_else(invalid)?
+        }
+        Some(_) => {
+            // the square's name is made of digits for the file, then a kanji number for the rank
+            let end = rest
+                .find(|c: char| {
+                    !c.is_ascii_digit()
+                        && !('０'..='９').contains(&c)
+                        && !"一二三四五六七八九十".contains(c)
+                })
+                .unwrap_or(rest.len());
+            let square = Coordinates::Kanji
+                .parse_square(
+                    &rest[..end],
+                    position.board().width.get(),
+                    position.board().height.get(),
+                    PlayerColor::White,
+                )
+                .ok_or_else(invalid)?;
+            chars = rest[end..].chars();
+            square
+        }
+        None => return Err(invalid()),
+    };
+
+    let rest: String = chars.collect();
+    let mut parts = rest.splitn(2, char::is_whitespace);
+    let body = parts.next().unwrap_or("");
+    let time = parts.next().and_then(parse_time);
+
+    let (body, from) = match body.find('(') {
+        Some(index) => {
+            let origin = body[index + 1..].trim_end_matches(')');
+            let digits: Vec<usize> = origin
+                .chars()
+                .filter_map(|c| c.to_digit(10).map(|d| d as usize))
+                .collect();
+            if digits.len() != 2 {
+                return Err(invalid());
+            }
+            let from = board_square(position, digits[0], digits[1]).ok_or_else(invalid)?;
+            (&body[..index], Some(from))
+        }
+        None => (body, None),
+    };
+
+    let (name, promotion, drop) = if let Some(name) = body.strip_suffix('打') {
+        (name, false, true)
+    } else if let Some(name) = body.strip_suffix("不成") {
+        (name, false, false)
+    } else {
+        match body.strip_suffix('成') {
+            Some(name) if piece_named(position, name).is_some() => (name, true, false),
+            _ => (body, false, false),
+        }
+    };
+    let piece = piece_named(position, name).ok_or_else(invalid)?;
+    if drop != from.is_none() {
+        return Err(invalid());
+    }
+
+    let candidates: Vec<Move> = position
+        .legal_moves()
+        .into_iter()
+        .filter(|mv| {
+            mv.from == from
+                && mv.to == to
+                && mv.piece == piece
+                && mv.promotion.is_some() == promotion
+        })
+        .collect();
+    match candidates.len() {
+        1 => Ok(Ok(RecordMove {
+            mv: candidates.into_iter().next().unwrap(),
+            time,
+        })),
+        _ => Err(IllegalMove(line.to_string())),
+    }
+}
+
+/// Parses the time spent on a move, as in `( 0:12/00:01:30)`
+fn parse_time(raw: &str) -> Option<Duration> {
+    let raw = raw.trim().trim_start_matches('(').trim_start();
+    let spent = raw.split('/').next()?;
+    let mut fields = spent.split(':');
+    let minutes: u64 = fields.next()?.trim().parse().ok()?;
+    let seconds: u64 = fields.next()?.trim().parse().ok()?;
+    let total = minutes.checked_mul(60)?.checked_add(seconds)?;
+    Some(Duration::from_secs(total))
+}
+
+fn parse_hand(position: &Game, hand: &str) -> KifResult<Vec<usize>> {
+    let mut res = vec![];
+    if hand == "なし" {
+        return Ok(res);
+    }
+    for item in hand.split(|c: char| c == '　' || c.is_whitespace()) {
+        let mut chars = item.chars();
+        let name = match chars.next() {
+            Some(name) => name,
+            None => continue,
+        };
+        let piece = piece_named(position, &name.to_string())
+            .ok_or_else(|| InvalidHand(hand.to_string()))?;
+        let count: String = chars.collect();
+        let count = if count.is_empty() {
+            1
+        } else {
+            parse_kanji_number(&count).ok_or_else(|| InvalidHand(hand.to_string()))?
+        };
+        for _ in 0..count {
+            res.push(piece);
+        }
+    }
+    Ok(res)
+}
+
+/// Finds the piece with the given kanji alias
+fn piece_named(position: &Game, name: &str) -> Option<usize> {
+    if name.is_empty() || name.is_ascii() {
+        return None;
+    }
+    position
+        .pieces()
+        .iter()
+        .position(|p| p.alias_list().iter().any(|alias| alias == name))
+}
+
+/// The piece's first kanji alias; with `single`, the first one made of a single character
+fn kanji(position: &Game, piece: usize, single: bool) -> KifResult<String> {
+    let piece = &position.pieces()[piece];
+    piece
+        .alias_list()
+        .iter()
+        .find(|alias| !alias.is_ascii() && (!single || alias.chars().count() == 1))
+        .cloned()
+        .ok_or_else(|| NoName(piece.id().to_string()))
+}
+
+fn move_name(position: &Game, mv: &Move, previous: Option<(usize, usize)>) -> KifResult<String> {
+    let mut res = String::new();
+    let name = kanji(position, mv.piece, false)?;
+    if previous == Some(mv.to) {
+        res.push('同');
+        if name.chars().count() == 1 {
+            res.push('　');
+        }
+    } else {
+        res.push_str(&Coordinates::Kanji.square_name(
+            mv.to,
+            position.board().width.get(),
+            position.board().height.get(),
+            PlayerColor::White,
+        ));
+    }
+    res.push_str(&name);
+    match mv.from {
+        None => res.push('打'),
+        Some(from) => {
+            if mv.promotion.is_some() {
+                res.push('成');
+            }
+            let (file, rank) = shogi_square(position, from);
+            res.push_str(&format!("({}{})", file, rank));
+        }
+    }
+    Ok(res)
+}
+
+/// Writes the position as a board diagram, along with the pieces in hand
+fn board_diagram(position: &Game) -> KifResult<String> {
+    let width = position.board().width.get();
+    let height = position.board().height.get();
+    let mut res = format!(
+        "後手の持駒：{}\n",
+        hand_names(position, PlayerColor::Black)?
+    );
+    res.push(' ');
+    for x in 0..width {
+        res.push(' ');
+        res.push_str(&Coordinates::Kanji.file_name(x, width, PlayerColor::White));
+    }
+    res.push('\n');
+    let border = format!("+{}+\n", "-".repeat(width * 3));
+    res.push_str(&border);
+    for y in (0..height).rev() {
+        res.push('|');
+        for x in 0..width {
+            match position.board().get(x, y).unwrap() {
+                Some((piece, color)) => {
+                    res.push(if color.white() { ' ' } else { 'v' });
+                    res.push_str(&kanji(position, piece, true)?);
+                }
+                None => res.push_str(" ・"),
+            }
+        }
+        res.push('|');
+        res.push_str(&Coordinates::Kanji.rank_name(y, height, PlayerColor::White));
+        res.push('\n');
+    }
+    res.push_str(&border);
+    res.push_str(&format!(
+        "先手の持駒：{}\n",
+        hand_names(position, PlayerColor::White)?
+    ));
+    if position.current_player().map(|p| p.color) == Some(PlayerColor::Black) {
+        res.push_str("後手番\n");
+    }
+    Ok(res)
+}
+
+fn hand_names(position: &Game, color: PlayerColor) -> KifResult<String> {
+    let hand = match position.player(color) {
+        Some(player) => &player.hand,
+        None => return Ok(String::from("なし")),
+    };
+    let mut names = vec![];
+    for piece in 0..position.pieces().len() {
+        let count = hand.iter().filter(|&&p| p == piece).count();
+        if count > 0 {
+            let mut name = kanji(position, piece, true)?;
+            if count > 1 {
+                name.push_str(&kanji_number(count));
+            }
+            names.push(name);
+        }
+    }
+    Ok(if names.is_empty() {
+        String::from("なし")
+    } else {
+        names.join("　")
+    })
+}
+
+impl fmt::Display for KifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnknownHandicap(h) => write!(f, "unknown handicap {}", h),
+            InvalidBoard(b) => write!(f, "invalid board {}", b),
+            InvalidHand(h) => write!(f, "invalid pieces in hand {}", h),
+            InvalidMove(m) => write!(f, "invalid move {}", m),
+            IllegalMove(m) => write!(f, "illegal move {}", m),
+            NoName(id) => write!(f, "piece {} has no kanji name", id),
+            Sfen(e) => write!(f, "invalid position: {}", e),
+        }
+    }
+}
+
+impl Error for KifError {}
@@ -1,8 +1,29 @@
 // The ~engine~ (the thing that handles rules & stuff)
 
+pub mod adapter;
+pub mod attack;
+pub mod betza;
+pub mod bitboard;
 pub mod board;
+pub mod coordinates;
+pub mod csa;
+pub mod driver;
+pub mod engine;
+pub mod fen;
 pub mod game;
+pub mod kif;
 pub mod movement;
+pub mod perft;
+pub mod pgn;
 pub mod piece;
 pub mod player;
+pub mod protocol;
+pub mod record;
 pub mod rule;
+pub mod sfen;
+pub mod storage;
+pub mod uci;
+pub mod usi;
+pub mod value;
+pub mod variant;
+pub mod xboard;
@@ -84,11 +84,101 @@ pub enum MovementCondition {
     AsWhite,
     /// If the current player is black
     AsBlack,
+    /// If the piece stands on the given rank, counted from its owner's side of the board
+    Rank(usize),
     /// A custom condition
-    Custom(&'static (dyn Fn(&Board, &Player, usize, usize, isize, isize) -> bool + 'static)),
+    Custom(&'static CustomCondition),
 }
 
-pub type RawMovement = (isize, isize);
+/// A condition given by a function of the board, the player, the piece's square and the movement
+pub type CustomCondition = dyn Fn(&Board, &Player, usize, usize, isize, isize) -> bool + Sync;
+
+/// A displacement (dx, dy) of a piece, before it is turned into a `Move`
+pub(crate) type RawMovement = (isize, isize);
+
+/// A move of a piece on the board, or a drop from a player's hand
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    /// Starting square of the piece, or `None` if it is dropped from the player's hand
+    pub from: Option<(usize, usize)>,
+    pub to: (usize, usize),
+    /// Index of the moved or dropped piece
+    pub piece: usize,
+    /// Index of the captured piece, if any
+    pub captured: Option<usize>,
+    /// Index of the piece that the moved piece promotes into
+    pub promotion: Option<usize>,
+    pub kind: MoveKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MoveKind {
+    Normal,
+    Drop,
+    /// Castling with the rook on the given file; `to` is then the king's destination
+    Castling(usize),
+    /// En passant capture of the piece standing on the given square
+    EnPassant((usize, usize)),
+}
+
+impl Move {
+    /// A move from `from` to `to`, capturing whatever stands on `to`
+    pub fn new(
+        from: (usize, usize),
+        to: (usize, usize),
+        piece: usize,
+        captured: Option<usize>,
+    ) -> Self {
+        Move {
+            from: Some(from),
+            to,
+            piece,
+            captured,
+            promotion: None,
+            kind: MoveKind::Normal,
+        }
+    }
+
+    pub fn drop(piece: usize, to: (usize, usize)) -> Self {
+        Move {
+            from: None,
+            to,
+            piece,
+            captured: None,
+            promotion: None,
+            kind: MoveKind::Drop,
+        }
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    pub fn is_drop(&self) -> bool {
+        self.kind == MoveKind::Drop
+    }
+
+    /// File of the rook that the king castles with, if the move is castling
+    pub fn castling(&self) -> Option<usize> {
+        match self.kind {
+            MoveKind::Castling(file) => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Square of the piece captured en passant, if the move is an en passant capture
+    pub fn en_passant(&self) -> Option<(usize, usize)> {
+        match self.kind {
+            MoveKind::EnPassant(square) => Some(square),
+            _ => None,
+        }
+    }
+
+    /// The piece standing on `to` after the move
+    pub fn final_piece(&self) -> usize {
+        self.promotion.unwrap_or(self.piece)
+    }
+}
 
 impl Copy for MovementCondition {}
 
@@ -99,6 +189,7 @@ impl Clone for MovementCondition {
             MovementCondition::NoCapture => MovementCondition::NoCapture,
             MovementCondition::AsWhite => MovementCondition::AsWhite,
             MovementCondition::AsBlack => MovementCondition::AsBlack,
+            MovementCondition::Rank(rank) => MovementCondition::Rank(*rank),
             MovementCondition::Custom(f) => MovementCondition::Custom(*f),
         }
     }
@@ -111,6 +202,7 @@ impl fmt::Debug for MovementCondition {
             MovementCondition::NoCapture => write!(f, "NoCapture"),
             MovementCondition::AsWhite => write!(f, "AsWhite"),
             MovementCondition::AsBlack => write!(f, "AsBlack"),
+            MovementCondition::Rank(rank) => write!(f, "Rank({})", rank),
             MovementCondition::Custom(_) => write!(f, "Custom(<fn>)"),
         }
     }
@@ -139,16 +231,39 @@ impl MovementCondition {
                 .is_none(),
             MovementCondition::AsWhite => player.color.white(),
             MovementCondition::AsBlack => player.color.black(),
+            MovementCondition::Rank(rank) => {
+                if player.color.white() {
+                    y == *rank
+                } else {
+                    board.height.get() - 1 - y == *rank
+                }
+            }
             MovementCondition::Custom(f) => f(board, player, x, y, dx, dy),
         }
     }
 }
 
 impl MovementType {
+    /**
+    Generates the moves that this movement gives to `piece` standing on (x, y), without promotions or special moves.
+    Squares held by the player's own pieces are left out, and the moves are sorted by target square.
+    **/
+    pub fn moves(
+        &self,
+        board: &Board,
+        player: &Player,
+        piece: usize,
+        x: usize,
+        y: usize,
+    ) -> Vec<Move> {
+        let raw = self.flatten(board, player, x, y).unwrap_or_default();
+        moves_from(board, player, piece, x, y, raw)
+    }
+
     /**
     Evaluates a MovementType's branches down into a set of possible, raw movements (dx, dy).
     **/
-    pub fn flatten(
+    pub(crate) fn flatten(
         &self,
         board: &Board,
         player: &Player,
@@ -282,3 +397,25 @@ impl MovementType {
 fn is_within_bounds(board: &Board, x: isize, y: isize) -> bool {
     x >= 0 && x < board.width.get() as isize && y >= 0 && y < board.height.get() as isize
 }
+
+/// Turns the raw movements of `piece` standing on (x, y) into moves, sorted by target square
+pub(crate) fn moves_from(
+    board: &Board,
+    player: &Player,
+    piece: usize,
+    x: usize,
+    y: usize,
+    raw: impl IntoIterator<Item = RawMovement>,
+) -> Vec<Move> {
+    let mut res: Vec<Move> = raw
+        .into_iter()
+        .map(|(dx, dy)| ((x as isize + dx) as usize, (y as isize + dy) as usize))
+        .filter_map(|to| match board.get(to.0, to.1).ok()? {
+            Some((_, color)) if color == player.color => None,
+            target => Some(Move::new((x, y), to, piece, target.map(|(p, _)| p))),
+        })
+        .collect();
+    res.sort_unstable_by_key(|mv| mv.to);
+    res.dedup();
+    res
+}
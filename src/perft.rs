@@ -0,0 +1,43 @@
+use crate::game::Game;
+use crate::movement::Move;
+
+impl Game {
+    /// Counts the leaf nodes of the legal move tree, `depth` plies deep
+    pub fn perft(&self, depth: usize) -> u64 {
+        self.clone().perft_nodes(depth)
+    }
+
+    /// Counts the leaf nodes under each legal move, `depth` plies deep from the current position
+    pub fn divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return vec![];
+        }
+        let mut game = self.clone();
+        game.legal_moves()
+            .into_iter()
+            .map(|mv| {
+                game.make_move(mv.clone());
+                let nodes = game.perft_nodes(depth - 1);
+                game.undo();
+                (mv, nodes)
+            })
+            .collect()
+    }
+
+    fn perft_nodes(&mut self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for mv in moves {
+            self.make_move(mv);
+            nodes += self.perft_nodes(depth - 1);
+            self.undo();
+        }
+        nodes
+    }
+}
@@ -0,0 +1,706 @@
+use self::PgnError::*;
+use crate::coordinates::Coordinates;
+use crate::fen::FenError;
+use crate::game::{Game, Outcome};
+use crate::movement::Move;
+use crate::player::PlayerColor;
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// FEN of the orthodox chess starting position; games that start elsewhere are written with a `FEN` tag
+pub const STANDARD_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// A game in Portable Game Notation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pgn {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    /// `1-0`, `0-1`, `1/2-1/2` or `*`
+    pub result: String,
+}
+
+/// A move of the movetext, along with its annotations
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PgnMove {
+    pub san: String,
+    /// Numeric Annotation Glyphs; `!`, `?` and the like are stored as their NAG equivalent
+    pub nags: Vec<usize>,
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    /// Lines that could have been played instead of this move
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PgnError {
+    Empty,
+    InvalidTag(String),
+    UnterminatedComment,
+    UnbalancedVariation,
+    UnexpectedToken(String),
+    InvalidSan(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
+    /// The piece with the given id has no letter and can't be written in SAN
+    NoLetter(String),
+    Fen(FenError),
+}
+
+pub type PgnResult<T> = Result<T, PgnError>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Open,
+    Close,
+    Nag(usize),
+    Result(String),
+    San(String),
+}
+
+impl Pgn {
+    /// Parses every game of a PGN file
+    pub fn parse_all(text: &str) -> PgnResult<Vec<Pgn>> {
+        let tokens = tokenize(text)?;
+        let mut res = vec![];
+        let mut pgn = Pgn::default();
+        let mut lines: Vec<Vec<PgnMove>> = vec![vec![]];
+        let mut pending_comment: Option<String> = None;
+        let mut in_movetext = false;
+
+        for token in tokens {
+            match token {
+                Token::Tag(name, value) => {
+                    if in_movetext {
+                        if lines.len() > 1 {
+                            return Err(UnbalancedVariation);
+                        }
+                        pgn.moves = lines.pop().unwrap();
+                        pgn.result = String::from("*");
+                        res.push(pgn);
+                        pgn = Pgn::default();
+                        lines = vec![vec![]];
+                        in_movetext = false;
+                    }
+                    pgn.tags.push((name, value));
+                }
+                Token::Result(result) => {
+                    if lines.len() > 1 {
+                        return Err(UnbalancedVariation);
+                    }
+                    pgn.moves = lines.pop().unwrap();
+                    pgn.result = result;
+                    res.push(pgn);
+                    pgn = Pgn::default();
+                    lines = vec![vec![]];
+                    in_movetext = false;
+                }
+                token => {
+                    in_movetext = true;
+                    let line = lines.last_mut().unwrap();
+                    match token {
+                        Token::San(san) => line.push(PgnMove {
+                            san,
+                            comment_before: pending_comment.take(),
+                            ..PgnMove::default()
+                        }),
+                        Token::Nag(nag) => line
+                            .last_mut()
+                            .ok_or_else(|| UnexpectedToken(format!("${}", nag)))?
+                            .nags
+                            .push(nag),
+                        Token::Comment(comment) => match line.last_mut() {
+                            Some(mv) if pending_comment.is_none() => {
+                                mv.comment = Some(match mv.comment.take() {
+                                    Some(previous) => format!("{} {}", previous, comment),
+                                    None => comment,
+                                });
+                            }
+                            _ => {
+                                pending_comment = Some(match pending_comment.take() {
+                                    Some(previous) => format!("{} {}", previous, comment),
+                                    None => comment,
+                                });
+                            }
+                        },
+                        Token::Open => {
+                            if line.is_empty() {
+                                return Err(UnbalancedVariation);
+                            }
+                            lines.push(vec![]);
+                        }
+                        Token::Close => {
+                            if lines.len() < 2 {
+                                return Err(UnbalancedVariation);
+                            }
+                            let variation = lines.pop().unwrap();
+                            lines
+                                .last_mut()
+                                .unwrap()
+                                .last_mut()
+                                .unwrap()
+                                .variations
+                                .push(variation);
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+
+        if in_movetext || !pgn.tags.is_empty() {
+            if lines.len() > 1 {
+                return Err(UnbalancedVariation);
+            }
+            pgn.moves = lines.pop().unwrap();
+            pgn.result = String::from("*");
+            res.push(pgn);
+        }
+        Ok(res)
+    }
+
+    /// Builds the PGN of a game, from its initial position and its history.
+    /// The seven tags of the Seven Tag Roster are filled with `?`, and may be changed with `set_tag`.
+    pub fn from_game(game: &Game) -> PgnResult<Pgn> {
+        let mut position = game.clone();
+        while position.undo().is_some() {}
+
+        let mut pgn = Pgn::default();
+        for (name, value) in &[
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", "*"),
+        ] {
+            pgn.set_tag(name, value);
+        }
+        let fen = position.fen()?;
+        if fen != STANDARD_FEN {
+            pgn.set_tag("SetUp", "1");
+            pgn.set_tag("FEN", &fen);
+        }
+
+        for mv in game.history() {
+            pgn.moves.push(PgnMove {
+                san: position.san(mv)?,
+                ..PgnMove::default()
+            });
+            position.make_move(mv.clone());
+        }
+
+        pgn.result = String::from(match position.result() {
+            None => "*",
+            Some(Outcome::Draw) => "1/2-1/2",
+            Some(Outcome::Win(color)) if color.white() => "1-0",
+            Some(Outcome::Win(_)) => "0-1",
+        });
+        let result = pgn.result.clone();
+        pgn.set_tag("Result", &result);
+        Ok(pgn)
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of a tag, adding it if it doesn't exist yet
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /**
+    Plays the main line onto `game`, starting from the position of the `FEN` tag if there is one.
+    Variations are resolved too, but aren't kept in the game's history.
+    **/
+    pub fn play(&self, game: &mut Game) -> PgnResult<()> {
+        if let Some(fen) = self.tag("FEN") {
+            game.load_fen(fen)?;
+        }
+        play_line(game, &self.moves)
+    }
+}
+
+fn play_line(game: &mut Game, moves: &[PgnMove]) -> PgnResult<()> {
+    for mv in moves {
+        for variation in &mv.variations {
+            play_line(&mut game.clone(), variation)?;
+        }
+        let parsed = game.parse_san(&mv.san)?;
+        game.make_move(parsed);
+    }
+    Ok(())
+}
+
+impl FromStr for Pgn {
+    type Err = PgnError;
+
+    /// Parses the first game of a PGN file
+    fn from_str(s: &str) -> PgnResult<Self> {
+        Pgn::parse_all(s)?.into_iter().next().ok_or(Empty)
+    }
+}
+
+impl fmt::Display for Pgn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(
+                f,
+                "[{} \"{}\"]",
+                name,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f)?;
+        }
+
+        let (white, number) = match self.tag("FEN").map(|fen| fen.split_whitespace()) {
+            Some(mut fields) => (
+                fields.nth(1) != Some("b"),
+                fields.nth(3).and_then(|n| n.parse().ok()).unwrap_or(1),
+            ),
+            None => (true, 1),
+        };
+        let mut tokens = vec![];
+        write_line(&mut tokens, &self.moves, white, number);
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() && !line.ends_with('(') && !token.starts_with(')') {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+fn write_line(tokens: &mut Vec<String>, moves: &[PgnMove], mut white: bool, mut number: usize) {
+    let mut need_number = true;
+    for mv in moves {
+        if let Some(comment) = &mv.comment_before {
+            tokens.push(format!("{{{}}}", comment));
+            need_number = true;
+        }
+        if white {
+            tokens.push(format!("{}.", number));
+        } else if need_number {
+            tokens.push(format!("{}...", number));
+        }
+        need_number = false;
+        tokens.push(mv.san.clone());
+        for nag in &mv.nags {
+            tokens.push(format!("${}", nag));
+        }
+        if let Some(comment) = &mv.comment {
+            tokens.push(format!("{{{}}}", comment));
+            need_number = true;
+        }
+        for variation in &mv.variations {
+            tokens.push(String::from("("));
+            write_line(tokens, variation, white, number);
+            tokens.push(String::from(")"));
+            need_number = true;
+        }
+        if !white {
+            number += 1;
+        }
+        white = !white;
+    }
+}
+
+fn tokenize(text: &str) -> PgnResult<Vec<Token>> {
+    let mut res = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(&c) = chars.peek() {
+        if c == '%' && line_start {
+            // escaped line
+            skip_line(&mut chars);
+            continue;
+        }
+        line_start = c == '\n';
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        match c {
+            '[' => {
+                chars.next();
+                res.push(tag(&mut chars)?);
+            }
+            '{' => {
+                chars.next();
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(UnterminatedComment),
+                    }
+                }
+                res.push(Token::Comment(
+                    comment.split_whitespace().collect::<Vec<_>>().join(" "),
+                ));
+            }
+            ';' => {
+                chars.next();
+                let comment = skip_line(&mut chars);
+                line_start = true;
+                res.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => {
+                chars.next();
+                res.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                res.push(Token::Close);
+            }
+            '$' => {
+                chars.next();
+                let word = word(&mut chars);
+                res.push(Token::Nag(
+                    word.parse()
+                        .map_err(|_| UnexpectedToken(format!("${}", word)))?,
+                ));
+            }
+            _ => {
+                let word = word(&mut chars);
+                if word.is_empty() {
+                    return Err(UnexpectedToken(c.to_string()));
+                }
+                match word.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => res.push(Token::Result(word)),
+                    _ => {
+                        // move numbers, possibly glued to the move: `12.`, `12...`, `1.e4`
+                        let san = word.trim_start_matches(|c: char| c.is_ascii_digit());
+                        let san = if san.starts_with('.') {
+                            san.trim_start_matches('.')
+                        } else {
+                            word.as_str()
+                        };
+                        let annotation_start = san.find(['!', '?']).unwrap_or(san.len());
+                        let (san, annotation) = san.split_at(annotation_start);
+                        if !san.is_empty() {
+                            res.push(Token::San(san.to_string()));
+                        }
+                        if !annotation.is_empty() {
+                            res.push(Token::Nag(match annotation {
+                                "!" => 1,
+                                "?" => 2,
+                                "!!" => 3,
+                                "??" => 4,
+                                "!?" => 5,
+                                "?!" => 6,
+                                _ => return Err(UnexpectedToken(annotation.to_string())),
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+fn skip_line(chars: &mut Peekable<Chars>) -> String {
+    let mut res = String::new();
+    for c in chars {
+        if c == '\n' {
+            break;
+        }
+        res.push(c);
+    }
+    res
+}
+
+fn word(chars: &mut Peekable<Chars>) -> String {
+    let mut res = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || "{}()[];$".contains(c) {
+            break;
+        }
+        res.push(c);
+        chars.next();
+    }
+    res
+}
+
+/// Parses a tag pair, after its opening bracket
+fn tag(chars: &mut Peekable<Chars>) -> PgnResult<Token> {
+    let mut name = String::new();
+    let mut value = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_alphanumeric() || c == '_' {
+            name.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    while chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+        chars.next();
+    }
+    if name.is_empty() || chars.next() != Some('"') {
+        return Err(InvalidTag(name));
+    }
+    loop {
+        match chars.next() {
+            Some('\\') => value.push(chars.next().ok_or_else(|| InvalidTag(name.clone()))?),
+            Some('"') => break,
+            Some(c) => value.push(c),
+            None => return Err(InvalidTag(name)),
+        }
+    }
+    while chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+        chars.next();
+    }
+    if chars.next() != Some(']') {
+        return Err(InvalidTag(name));
+    }
+    Ok(Token::Tag(name, value))
+}
+
+impl Game {
+    /// Writes a legal move in Standard Algebraic Notation
+    pub fn san(&self, mv: &Move) -> PgnResult<String> {
+        let mut res = String::new();
+        let piece = &self.pieces()[mv.piece];
+        let letter = piece
+            .letter()
+            .ok_or_else(|| NoLetter(piece.id().to_string()))?;
+
+        match mv.from {
+            _ if mv.castling().is_some() => {
+                if mv.castling() > mv.from.map(|(x, _)| x) {
+                    res.push_str("O-O");
+                } else {
+                    res.push_str("O-O-O");
+                }
+            }
+            None => {
+                res.push(letter);
+                res.push('@');
+                res.push_str(&self.algebraic_name(mv.to));
+            }
+            Some((x, y)) => {
+                if self.is_pawn(mv.piece) {
+                    if mv.is_capture() {
+                        res.push_str(&self.algebraic_file(x));
+                    }
+                } else {
+                    res.push(letter);
+                    let others: Vec<(usize, usize)> = self
+                        .legal_moves()
+                        .into_iter()
+                        .filter(|other| {
+                            other.piece == mv.piece
+                                && other.to == mv.to
+                                && other.castling().is_none()
+                                && other.from.is_some()
+                                && other.from != mv.from
+                        })
+                        .filter_map(|other| other.from)
+                        .collect();
+                    if !others.is_empty() {
+                        if others.iter().all(|&(ox, _)| ox != x) {
+                            res.push_str(&self.algebraic_file(x));
+                        } else if others.iter().all(|&(_, oy)| oy != y) {
+                            res.push_str(&(y + 1).to_string());
+                        } else {
+                            res.push_str(&self.algebraic_name((x, y)));
+                        }
+                    }
+                }
+                if mv.is_capture() {
+                    res.push('x');
+                }
+                res.push_str(&self.algebraic_name(mv.to));
+                if let Some(promotion) = mv.promotion {
+                    let promotion = &self.pieces()[promotion];
+                    res.push('=');
+                    res.push(
+                        promotion
+                            .letter()
+                            .ok_or_else(|| NoLetter(promotion.id().to_string()))?,
+                    );
+                }
+            }
+        }
+
+        let mut after = self.clone();
+        after.make_move(mv.clone());
+        if after.is_check() {
+            res.push(if after.legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        Ok(res)
+    }
+
+    /// Finds the legal move described by a move in Standard Algebraic Notation
+    pub fn parse_san(&self, san: &str) -> PgnResult<Move> {
+        let width = self.board().width.get();
+        let height = self.board().height.get();
+        let body = san.trim_end_matches(|c| "+#!?".contains(c));
+        // squares are sliced by byte below
+        if !body.is_ascii() {
+            return Err(InvalidSan(san.to_string()));
+        }
+        let legal_moves = self.legal_moves();
+
+        let candidates: Vec<Move> = match body {
+            "O-O" | "0-0" | "O-O-O" | "0-0-0" => {
+                let kingside = body.len() == 3;
+                legal_moves
+                    .into_iter()
+                    .filter(|mv| match (mv.castling(), mv.from) {
+                        (Some(rook), Some((x, _))) => (rook > x) == kingside,
+                        _ => false,
+                    })
+                    .collect()
+            }
+            _ if body.contains('@') => {
+                let mut parts = body.splitn(2, '@');
+                let letter = match parts.next().unwrap() {
+                    "" => 'P',
+                    letter if letter.len() == 1 => letter.chars().next().unwrap(),
+                    _ => return Err(InvalidSan(san.to_string())),
+                };
+                let to = Coordinates::Algebraic
+                    .parse_square(parts.next().unwrap(), width, height, PlayerColor::White)
+                    .ok_or_else(|| InvalidSan(san.to_string()))?;
+                legal_moves
+                    .into_iter()
+                    .filter(|mv| {
+                        mv.from.is_none()
+                            && mv.to == to
+                            && self.pieces()[mv.piece].letter() == Some(letter)
+                    })
+                    .collect()
+            }
+            _ => {
+                let mut body = body.replace(['x', '-'], "");
+                let mut promotion = None;
+                if let Some(index) = body.find('=') {
+                    promotion = body[index + 1..].chars().next();
+                    body.truncate(index);
+                } else if body.len() > 2
+                    && body.ends_with(|c: char| c.is_ascii_uppercase())
+                    && body[..body.len() - 1].ends_with(|c: char| c.is_ascii_digit())
+                {
+                    promotion = body.pop();
+                }
+
+                let letter = match body.chars().next() {
+                    Some(c) if c.is_ascii_uppercase() => {
+                        body.remove(0);
+                        c
+                    }
+                    _ => 'P',
+                };
+                let rank_start = body
+                    .rfind(|c: char| !c.is_ascii_digit())
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                let to_start = body[..rank_start]
+                    .rfind(|c: char| !c.is_ascii_lowercase())
+                    .map(|i| i + 1)
+                    .unwrap_or(0)
+                    .max(rank_start.saturating_sub(1));
+                let to = Coordinates::Algebraic
+                    .parse_square(&body[to_start..], width, height, PlayerColor::White)
+                    .ok_or_else(|| InvalidSan(san.to_string()))?;
+                let from = &body[..to_start];
+                let rank = from.trim_start_matches(|c: char| c.is_ascii_lowercase());
+                let from_file = Coordinates::Algebraic.parse_file(
+                    &from[..from.len() - rank.len()],
+                    width,
+                    PlayerColor::White,
+                );
+                let from_rank = Coordinates::Algebraic.parse_rank(rank, height, PlayerColor::White);
+
+                legal_moves
+                    .into_iter()
+                    .filter(|mv| {
+                        let (x, y) = match mv.from {
+                            Some(from) if mv.castling().is_none() => from,
+                            _ => return false,
+                        };
+                        mv.to == to
+                            && self.pieces()[mv.piece].letter() == Some(letter)
+                            && from_file.map(|f| f == x).unwrap_or(true)
+                            && from_rank.map(|r| r == y).unwrap_or(true)
+                            && mv.promotion.and_then(|p| self.pieces()[p].letter()) == promotion
+                    })
+                    .collect()
+            }
+        };
+
+        match candidates.len() {
+            0 => Err(IllegalMove(san.to_string())),
+            1 => Ok(candidates.into_iter().next().unwrap()),
+            _ => Err(AmbiguousMove(san.to_string())),
+        }
+    }
+
+    /// SAN names squares algebraically, whatever the game's coordinates
+    fn algebraic_name(&self, square: (usize, usize)) -> String {
+        Coordinates::Algebraic.square_name(
+            square,
+            self.board().width.get(),
+            self.board().height.get(),
+            PlayerColor::White,
+        )
+    }
+
+    fn algebraic_file(&self, x: usize) -> String {
+        Coordinates::Algebraic.file_name(x, self.board().width.get(), PlayerColor::White)
+    }
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Empty => write!(f, "no game found"),
+            InvalidTag(name) => write!(f, "invalid tag {}", name),
+            UnterminatedComment => write!(f, "unterminated comment"),
+            UnbalancedVariation => write!(f, "unbalanced variation"),
+            UnexpectedToken(token) => write!(f, "unexpected token {}", token),
+            InvalidSan(san) => write!(f, "invalid move {}", san),
+            IllegalMove(san) => write!(f, "illegal move {}", san),
+            AmbiguousMove(san) => write!(f, "ambiguous move {}", san),
+            NoLetter(id) => write!(f, "piece {} has no letter", id),
+            Fen(e) => write!(f, "invalid FEN tag: {}", e),
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+impl From<FenError> for PgnError {
+    fn from(e: FenError) -> Self {
+        Fen(e)
+    }
+}
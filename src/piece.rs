@@ -25,6 +25,17 @@ impl Piece {
         &self.alias
     }
 
+    /// The piece's letter, as used in FEN: its first alias made of a single uppercase letter
+    pub fn letter(&self) -> Option<char> {
+        self.alias.iter().find_map(|alias| {
+            let mut chars = alias.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+                _ => None,
+            }
+        })
+    }
+
     pub fn display_white(&self) -> &str {
         if self.display_white == 0 || self.alias.len() < self.display_white {
             &self.id
@@ -48,6 +59,10 @@ impl Piece {
     pub fn movement_type(&self) -> &Vec<MovementType> {
         &self.movement_type
     }
+
+    pub fn set_movement_type(&mut self, movement_type: Vec<MovementType>) {
+        self.movement_type = movement_type;
+    }
 }
 
 pub struct PieceBuilder {
@@ -1,12 +1,16 @@
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum PlayerColor {
     White,
     Black,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Player {
     pub color: PlayerColor,
+    /// Indices of the pieces in the player's hand, which they may drop onto the board
+    pub hand: Vec<usize>,
 }
 
 impl PlayerColor {
@@ -17,10 +21,20 @@ impl PlayerColor {
     pub fn black(&self) -> bool {
         *self == PlayerColor::Black
     }
+
+    pub fn opponent(&self) -> PlayerColor {
+        match self {
+            PlayerColor::White => PlayerColor::Black,
+            PlayerColor::Black => PlayerColor::White,
+        }
+    }
 }
 
 impl Player {
     pub fn new(color: PlayerColor) -> Self {
-        Self { color }
+        Self {
+            color,
+            hand: vec![],
+        }
     }
 }
@@ -0,0 +1,506 @@
+use self::ProtocolError::*;
+use crate::game::Game;
+use crate::player::PlayerColor;
+use crate::storage::{ExportFormat, GameQuery};
+use crate::variant::Variant;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/**
+Version of the protocol, which client and server exchange in their handshake and must agree on.
+It is bumped with each change of the messages or of what the server accepts:
+1 hosted games, with the lobby, spectators and stored games; 2 resumed sessions; 3 clocks; 4 chat;
+5 time controls limited to `MAX_BASE` and `MAX_INCREMENT`; 6 history queries answered by pages.
+**/
+pub const PROTOCOL_VERSION: u32 = 6;
+/// Size of the largest frame accepted, in bytes
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+/// Length of the longest chat message accepted, in characters
+pub const MAX_CHAT_LENGTH: usize = 300;
+/// Longest time accepted for a game, per player
+pub const MAX_BASE: Duration = Duration::from_secs(24 * 60 * 60);
+/// Longest time accepted to be added after each move
+pub const MAX_INCREMENT: Duration = Duration::from_secs(10 * 60);
+/// Size of the length prefix of a frame, in bytes
+const PREFIX_SIZE: usize = 4;
+
+/// A message sent by a client to the server
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// Opens the connection; the server answers with `Welcome`, or an `Error` if the versions differ
+    Hello {
+        version: u32,
+        name: String,
+    },
+    /**
+    Opens a connection in place of one that was lost, with the token given by `Welcome`.
+    The server answers with `Welcome`, then the snapshot of the game the client plays in, if any.
+    **/
+    Resume {
+        version: u32,
+        token: String,
+    },
+    /// Opens a game for anyone to join
+    CreateGame {
+        variant: String,
+        color: ColorChoice,
+        time: Option<TimeControl>,
+    },
+    JoinGame {
+        game: u64,
+    },
+    /// Plays a move, written like in UCI
+    Move {
+        game: u64,
+        mv: String,
+    },
+    Resign {
+        game: u64,
+    },
+    /// Asks for the games waiting for an opponent
+    ListGames,
+    /// Asks for the lobby: the users, the games waiting for an opponent and the client's challenges
+    Lobby,
+    /// Challenges another user to a game, with the given color for the challenger
+    Challenge {
+        to: u64,
+        variant: String,
+        color: ColorChoice,
+        time: Option<TimeControl>,
+    },
+    AcceptChallenge {
+        challenge: u64,
+    },
+    /// Declines a challenge received, or withdraws one sent
+    DeclineChallenge {
+        challenge: u64,
+    },
+    /// Watches a running game; the server answers with its snapshot, then sends its moves
+    Watch {
+        game: u64,
+    },
+    StopWatching {
+        game: u64,
+    },
+    /// Looks up the games stored by the server
+    History(GameQuery),
+    /// Asks for a stored game, written in the given format
+    Export {
+        game: u64,
+        format: ExportFormat,
+    },
+    /// Answers a `Ping` from the server
+    Pong {
+        ping: u64,
+    },
+    /// Says something on a chat channel the client is in
+    Chat {
+        channel: ChatChannel,
+        text: String,
+    },
+    /// Stops receiving the chat messages of the users with the given name
+    Mute {
+        name: String,
+    },
+    Unmute {
+        name: String,
+    },
+}
+
+/// A message sent by the server to a client
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome {
+        version: u32,
+        client: u64,
+        /// Secret with which the client can resume its session after losing the connection
+        token: String,
+    },
+    GameCreated {
+        game: u64,
+    },
+    /// The whole state of a game, sent when it starts, is watched, or a player resumes their session
+    Snapshot(Snapshot),
+    /// A move was played, leading to a position with the given checksum
+    Move {
+        game: u64,
+        mv: String,
+        checksum: u64,
+    },
+    MoveRejected {
+        game: u64,
+        mv: String,
+        reason: String,
+    },
+    /// The players' clocks, sent after each move
+    Clock(ClockUpdate),
+    GameOver {
+        game: u64,
+        result: GameResult,
+        reason: String,
+    },
+    Error(String),
+    GameList(Vec<OpenGame>),
+    Lobby(Lobby),
+    /// A challenge sent to the client, or by it
+    Challenge(Challenge),
+    /// A challenge was declined, withdrawn, or dropped because a player left
+    ChallengeClosed {
+        challenge: u64,
+        reason: String,
+    },
+    /// Number of clients watching a game, sent when it changes
+    Spectators {
+        game: u64,
+        count: u32,
+    },
+    /// A player lost their connection, and forfeits unless they come back within the grace period
+    PlayerAway {
+        game: u64,
+        color: PlayerColor,
+        grace: Duration,
+    },
+    PlayerBack {
+        game: u64,
+        color: PlayerColor,
+    },
+    /// The stored games matching a query, at most `MAX_FOUND` of them
+    History(Vec<GameSummary>),
+    Export {
+        game: u64,
+        format: ExportFormat,
+        text: String,
+    },
+    /// Asks the client to answer with `Pong` right away, which measures the network latency
+    Ping {
+        ping: u64,
+    },
+    Chat(ChatMessage),
+    /// The last messages of a chat channel, sent when the client joins it
+    ChatHistory {
+        channel: ChatChannel,
+        messages: Vec<ChatMessage>,
+    },
+    /// The names muted by the client, sent when they change
+    Muted(Vec<String>),
+}
+
+/// Where chat messages are said and heard
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChatChannel {
+    /// Everyone connected
+    Lobby,
+    /// The players of a game
+    Players(u64),
+    /// The spectators of a game, which its players don't hear
+    Spectators(u64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub channel: ChatChannel,
+    pub from: u64,
+    pub from_name: String,
+    pub text: String,
+    pub sent: SystemTime,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorChoice {
+    White,
+    Black,
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+/// A game waiting for an opponent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenGame {
+    pub game: u64,
+    pub variant: String,
+    /// Name of the player who created the game
+    pub host: String,
+    /// The color the opponent would play
+    pub color: PlayerColor,
+    pub time: Option<TimeControl>,
+}
+
+/// A game being played, which can be watched
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunningGame {
+    pub game: u64,
+    pub variant: String,
+    /// Names of white and black
+    pub players: [String; 2],
+    pub spectators: u32,
+}
+
+/// A game stored by the server, without its moves
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub game: u64,
+    pub variant: String,
+    /// Names of white and black
+    pub players: [String; 2],
+    /// Number of moves played
+    pub moves: u32,
+    pub started: SystemTime,
+    pub ended: Option<SystemTime>,
+    /// The result, or `None` if the game isn't over
+    pub result: Option<GameResult>,
+    pub reason: Option<String>,
+    pub time: Option<TimeControl>,
+}
+
+/// A user connected to the server
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LobbyUser {
+    pub id: u64,
+    pub name: String,
+    /// The game the user plays or waits in, if any
+    pub game: Option<u64>,
+}
+
+/// An offer to play a game against a given user
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    pub id: u64,
+    pub from: u64,
+    pub from_name: String,
+    pub to: u64,
+    pub to_name: String,
+    pub variant: String,
+    /// The color chosen by the challenger
+    pub color: ColorChoice,
+    pub time: Option<TimeControl>,
+}
+
+/// What a client needs to show the lobby
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lobby {
+    pub users: Vec<LobbyUser>,
+    pub games: Vec<OpenGame>,
+    pub running: Vec<RunningGame>,
+    /// The challenges sent to the client or by it
+    pub challenges: Vec<Challenge>,
+}
+
+/// Time given to each player for the game, and added after each of their moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+/// Time left on the players' clocks when the update was sent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockUpdate {
+    pub game: u64,
+    pub white: Duration,
+    pub black: Duration,
+    /// The player whose clock is running, if any
+    pub running: Option<PlayerColor>,
+}
+
+/// Everything a client needs to know about a game to display and play it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub game: u64,
+    /// Name of the variant
+    pub variant: String,
+    /// The variant's definition, as written in its TOML file
+    pub definition: String,
+    /// Names of the players of white and black, if they joined
+    pub players: [Option<String>; 2],
+    /// The color played by the client receiving the snapshot, or `None` if it watches the game
+    pub color: Option<PlayerColor>,
+    /// Moves played since the variant's starting position, written like in UCI
+    pub moves: Vec<String>,
+    pub time: Option<TimeControl>,
+    pub clock: Option<ClockUpdate>,
+    /// Checksum of the current position
+    pub checksum: u64,
+}
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(io::Error),
+    /// A message couldn't be encoded or decoded
+    Encoding(String),
+    FrameTooLarge(usize),
+    InvalidTimeControl(String),
+    InvalidVariant(String),
+    IllegalMove(String),
+    /// The position differs from the one on the other side
+    Desync,
+}
+
+pub type ProtocolResult<T> = Result<T, ProtocolError>;
+
+impl Snapshot {
+    /// Builds the game described by the snapshot, checking that it ends in the same position as on the server
+    pub fn game(&self) -> ProtocolResult<Game> {
+        let mut game = self
+            .definition
+            .parse::<Variant>()
+            .and_then(|variant| variant.game())
+            .map_err(|e| InvalidVariant(e.to_string()))?;
+        for text in &self.moves {
+            let mv = game
+                .parse_uci_move(text)
+                .map_err(|_| IllegalMove(text.clone()))?;
+            game.play(&mv);
+        }
+        if checksum(&game) != self.checksum {
+            return Err(Desync);
+        }
+        Ok(game)
+    }
+}
+
+impl TimeControl {
+    pub fn new(base: Duration, increment: Duration) -> Self {
+        TimeControl { base, increment }
+    }
+
+    /// Checks that the game has some time, and no more than `MAX_BASE` and `MAX_INCREMENT`
+    pub fn validate(&self) -> ProtocolResult<()> {
+        if self.base.is_zero() || self.base > MAX_BASE || self.increment > MAX_INCREMENT {
+            return Err(InvalidTimeControl(self.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Written like `5+3`: minutes for the game, then seconds added after each move
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = self.base.as_secs_f64() / 60.0;
+        write!(f, "{}+{}", minutes, self.increment.as_secs())
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = ProtocolError;
+
+    fn from_str(text: &str) -> ProtocolResult<Self> {
+        let invalid = || InvalidTimeControl(text.to_string());
+        let (minutes, seconds) = match text.trim().split_once('+') {
+            Some((minutes, seconds)) => (minutes, seconds),
+            None => (text.trim(), "0"),
+        };
+        let minutes: f64 = minutes.trim().parse().map_err(|_| invalid())?;
+        let seconds: u64 = seconds.trim().parse().map_err(|_| invalid())?;
+        if minutes <= 0.0 {
+            return Err(invalid());
+        }
+        let base = Duration::try_from_secs_f64(minutes * 60.0).map_err(|_| invalid())?;
+        let time = TimeControl::new(base, Duration::from_secs(seconds));
+        time.validate().map_err(|_| invalid())?;
+        Ok(time)
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::WhiteWins => write!(f, "1-0"),
+            GameResult::BlackWins => write!(f, "0-1"),
+            GameResult::Draw => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
+/**
+Checksum of a position, which client and server compare after each move to detect desynchronisation.
+It covers the pieces on the board and in hand, the player to move, and the castling and en passant rights.
+**/
+pub fn checksum(game: &Game) -> u64 {
+    game.position_key()
+}
+
+/// Encodes a message into a frame: its length as a big-endian `u32`, followed by the message
+pub fn encode<T: Serialize>(message: &T) -> ProtocolResult<Vec<u8>> {
+    let body = bincode::serialize(message).map_err(|e| Encoding(e.to_string()))?;
+    if body.len() > MAX_FRAME_SIZE {
+        return Err(FrameTooLarge(body.len()));
+    }
+    let mut frame = (body.len() as u32).to_be_bytes().to_vec();
+    frame.extend(body);
+    Ok(frame)
+}
+
+/**
+Decodes the first frame of `buffer`, returning the message and the number of bytes it took,
+or `None` if the frame isn't complete yet.
+**/
+pub fn decode<T: DeserializeOwned>(buffer: &[u8]) -> ProtocolResult<Option<(T, usize)>> {
+    if buffer.len() < PREFIX_SIZE {
+        return Ok(None);
+    }
+    let mut prefix = [0; PREFIX_SIZE];
+    prefix.copy_from_slice(&buffer[..PREFIX_SIZE]);
+    let size = u32::from_be_bytes(prefix) as usize;
+    if size > MAX_FRAME_SIZE {
+        return Err(FrameTooLarge(size));
+    }
+    if buffer.len() < PREFIX_SIZE + size {
+        return Ok(None);
+    }
+    let message = bincode::deserialize(&buffer[PREFIX_SIZE..PREFIX_SIZE + size])
+        .map_err(|e| Encoding(e.to_string()))?;
+    Ok(Some((message, PREFIX_SIZE + size)))
+}
+
+/// Writes a message as a frame
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, message: &T) -> ProtocolResult<()> {
+    writer.write_all(&encode(message)?)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a frame, waiting until it is complete
+pub fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> ProtocolResult<T> {
+    let mut prefix = [0; PREFIX_SIZE];
+    reader.read_exact(&mut prefix)?;
+    let size = u32::from_be_bytes(prefix) as usize;
+    if size > MAX_FRAME_SIZE {
+        return Err(FrameTooLarge(size));
+    }
+    let mut body = vec![0; size];
+    reader.read_exact(&mut body)?;
+    bincode::deserialize(&body).map_err(|e| Encoding(e.to_string()))
+}
+
+impl From<io::Error> for ProtocolError {
+    fn from(e: io::Error) -> Self {
+        Io(e)
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Io(e) => write!(f, "{}", e),
+            Encoding(e) => write!(f, "invalid message: {}", e),
+            FrameTooLarge(size) => write!(f, "frame of {} bytes is too large", size),
+            InvalidTimeControl(text) => write!(f, "invalid time control {}", text),
+            InvalidVariant(e) => write!(f, "invalid variant: {}", e),
+            IllegalMove(mv) => write!(f, "illegal move {}", mv),
+            Desync => write!(f, "the position differs from the server's"),
+        }
+    }
+}
+
+impl Error for ProtocolError {}
@@ -0,0 +1,126 @@
+use crate::game::Game;
+use crate::movement::Move;
+use crate::player::PlayerColor;
+use std::time::Duration;
+
+/// SFEN of the even (平手) shogi starting position
+pub const HIRATE_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+/// A shogi game record, as read from or written to the KIF and CSA formats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    pub sente: Option<String>,
+    pub gote: Option<String>,
+    /// Other header fields, kept as they were written in the file they were read from
+    pub headers: Vec<(String, String)>,
+    /// Starting position, or `None` for the even starting position
+    pub sfen: Option<String>,
+    pub moves: Vec<RecordMove>,
+    pub end: Option<GameEnd>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordMove {
+    pub mv: Move,
+    /// Time spent on the move
+    pub time: Option<Duration>,
+}
+
+/// How a game record ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameEnd {
+    /// The player to move resigned
+    Resignation,
+    /// The player to move is checkmated
+    Checkmate,
+    /// Fourfold repetition
+    Sennichite,
+    /// Impasse, with both kings in their promotion zone
+    Jishogi,
+    /// The player to move declared a win by entering king
+    EnteringKing,
+    /// The player to move ran out of time
+    TimeUp,
+    /// The last move was illegal
+    IllegalMove,
+    /// The game was interrupted
+    Interruption,
+}
+
+impl Record {
+    /// Builds the record of a game, from its initial position and its history
+    pub fn from_game(game: &Game) -> Record {
+        let mut position = game.clone();
+        while position.undo().is_some() {}
+        let sfen = position.sfen().ok().filter(|sfen| sfen != HIRATE_SFEN);
+
+        Record {
+            sfen,
+            moves: game
+                .history()
+                .iter()
+                .map(|mv| RecordMove {
+                    mv: mv.clone(),
+                    time: None,
+                })
+                .collect(),
+            end: if game.legal_moves().is_empty() {
+                Some(GameEnd::Checkmate)
+            } else {
+                None
+            },
+            ..Record::default()
+        }
+    }
+
+    /// Sets up the starting position on `game` and plays the record's moves
+    pub fn play(&self, game: &mut Game) -> Option<()> {
+        game.load_sfen(self.sfen.as_deref().unwrap_or(HIRATE_SFEN))
+            .ok()?;
+        for mv in &self.moves {
+            game.play(&mv.mv)?;
+        }
+        Some(())
+    }
+
+    /// Returns the game in the record's starting position
+    pub(crate) fn start(&self, game: &Game) -> Option<Game> {
+        let mut position = game.clone();
+        position
+            .load_sfen(self.sfen.as_deref().unwrap_or(HIRATE_SFEN))
+            .ok()?;
+        Some(position)
+    }
+}
+
+/// Converts board coordinates to a shogi square: files are numbered from the right, ranks from the top
+pub(crate) fn shogi_square(game: &Game, (x, y): (usize, usize)) -> (usize, usize) {
+    (game.board().width.get() - x, game.board().height.get() - y)
+}
+
+pub(crate) fn board_square(game: &Game, file: usize, rank: usize) -> Option<(usize, usize)> {
+    let width = game.board().width.get();
+    let height = game.board().height.get();
+    if file == 0 || rank == 0 || file > width || rank > height {
+        None
+    } else {
+        Some((width - file, height - rank))
+    }
+}
+
+/// Returns the game with an empty board of the same size, and empty hands
+pub(crate) fn empty_position(game: &Game, color: PlayerColor) -> Game {
+    let mut position = game.clone();
+    position.board_mut().clear();
+    position.clear_history();
+    for player in position.players.iter_mut() {
+        player.hand.clear();
+    }
+    position.set_current_player(color);
+    position.set_castling(vec![]);
+    position.set_en_passant(None);
+    position.set_halfmove_clock(0);
+    position.set_fullmove_number(1);
+    position.set_ply_number(1);
+    position
+}
@@ -1,8 +1,24 @@
-#[derive(Debug)]
-pub struct Rule;
-
-impl Rule {
-    pub fn new() -> Self {
-        Rule
-    }
+/// A rule of the game, on top of what the pieces' movements describe
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// The piece with the given id is royal: losing it loses the game
+    Royal(String),
+    /// Pieces with the id `piece` may promote into any of the `to` pieces when moving into, within or out of the last `zone` ranks.
+    /// If `optional` is false, the promotion must happen as soon as the piece reaches that zone.
+    Promotion {
+        piece: String,
+        to: Vec<String>,
+        zone: usize,
+        optional: bool,
+    },
+    /// The `king` piece may castle with the `rook` piece, as in orthodox chess
+    Castling { king: String, rook: String },
+    /// The piece with the given id may be captured en passant after moving two squares straight ahead
+    EnPassant(String),
+    /// Captured pieces go into the capturer's hand and may be dropped back onto the board
+    Drops,
+    /// The piece with the given id may not be dropped on a file where its owner already has one
+    OnePerFile(String),
+    /// The piece with the given id may not be dropped to checkmate
+    NoDropMate(String),
 }
@@ -0,0 +1,278 @@
+use self::SfenError::*;
+use crate::game::Game;
+use crate::piece::Piece;
+use crate::player::PlayerColor;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SfenError {
+    MissingField(&'static str),
+    InvalidPlacement(String),
+    UnknownPiece(String),
+    /// The piece with the given id has no SFEN symbol
+    NoSymbol(String),
+    InvalidColor(String),
+    InvalidHand(String),
+    InvalidNumber(String),
+}
+
+pub type SfenResult<T> = Result<T, SfenError>;
+
+impl Game {
+    /**
+    Loads the position described by an SFEN string, replacing the current one.
+    Pieces are found through their SFEN symbol: their letter (see `Piece::letter`), or an alias made of `+` and a letter for promoted pieces.
+    Sente (`b`, uppercase) is the white player and gote (`w`, lowercase) the black player.
+    The move number may be omitted.
+    **/
+    pub fn load_sfen(&mut self, sfen: &str) -> SfenResult<()> {
+        let mut fields = sfen.split_whitespace();
+        let placement = fields.next().ok_or(MissingField("placement"))?;
+        let color = fields.next().ok_or(MissingField("color"))?;
+        let hands = fields.next().unwrap_or("-");
+        let ply_number = parse_number(fields.next().unwrap_or("1"))?;
+
+        let mut board = self.board().clone();
+        board.clear();
+        let width = board.width.get();
+        let height = board.height.get();
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != height {
+            return Err(InvalidPlacement(format!(
+                "expected {} ranks, got {}",
+                height,
+                ranks.len()
+            )));
+        }
+        for (i, rank) in ranks.into_iter().enumerate() {
+            let y = height - 1 - i;
+            let mut x = 0;
+            let mut empty = 0;
+            let mut promoted = false;
+            for c in rank.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    empty = empty * 10 + digit as usize;
+                    if x + empty > width {
+                        return Err(InvalidPlacement(format!("rank {} is too long", rank)));
+                    }
+                    continue;
+                }
+                x += empty;
+                empty = 0;
+                if c == '+' {
+                    promoted = true;
+                    continue;
+                }
+                let (piece, color) = self.find_symbol(c, promoted)?;
+                promoted = false;
+                if x >= width {
+                    return Err(InvalidPlacement(format!("rank {} is too long", rank)));
+                }
+                board.set(x, y, Some((piece, color))).unwrap();
+                x += 1;
+            }
+            x += empty;
+            if x != width || promoted {
+                return Err(InvalidPlacement(format!(
+                    "rank {} has {} squares instead of {}",
+                    rank, x, width
+                )));
+            }
+        }
+
+        let color = match color {
+            "b" => PlayerColor::White,
+            "w" => PlayerColor::Black,
+            _ => return Err(InvalidColor(color.to_string())),
+        };
+        if self.player(color).is_none() {
+            return Err(InvalidColor(color_char(color).to_string()));
+        }
+
+        let mut white_hand = vec![];
+        let mut black_hand = vec![];
+        if hands != "-" {
+            let mut count = 0;
+            for c in hands.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    count = count * 10 + digit as usize;
+                    // no more pieces than squares can be in hand
+                    if count > width * height {
+                        return Err(InvalidHand(hands.to_string()));
+                    }
+                    continue;
+                }
+                let (piece, color) = self
+                    .find_symbol(c, false)
+                    .map_err(|_| InvalidHand(hands.to_string()))?;
+                let hand = if color.white() {
+                    &mut white_hand
+                } else {
+                    &mut black_hand
+                };
+                for _ in 0..count.max(1) {
+                    hand.push(piece);
+                }
+                count = 0;
+            }
+            if count > 0 {
+                return Err(InvalidHand(hands.to_string()));
+            }
+        }
+
+        *self.board_mut() = board;
+        self.clear_history();
+        for player in self.players.iter_mut() {
+            player.hand = if player.color.white() {
+                white_hand.clone()
+            } else {
+                black_hand.clone()
+            };
+        }
+        self.set_current_player(color).unwrap();
+        self.set_castling(vec![]);
+        self.set_en_passant(None);
+        self.set_halfmove_clock(0);
+        self.set_ply_number(ply_number);
+        self.set_fullmove_number(ply_number.max(1).div_ceil(2));
+        Ok(())
+    }
+
+    /// Returns the current position as an SFEN string
+    pub fn sfen(&self) -> SfenResult<String> {
+        let board = self.board();
+        let width = board.width.get();
+        let height = board.height.get();
+        let mut res = String::new();
+
+        for y in (0..height).rev() {
+            let mut empty = 0;
+            for x in 0..width {
+                match board.get(x, y).unwrap() {
+                    Some((piece, color)) => {
+                        if empty > 0 {
+                            res.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        res.push_str(&self.piece_symbol(piece, color)?);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                res.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                res.push('/');
+            }
+        }
+
+        res.push(' ');
+        res.push(color_char(
+            self.current_player()
+                .map(|p| p.color)
+                .unwrap_or(PlayerColor::White),
+        ));
+
+        res.push(' ');
+        let mut hands = String::new();
+        for color in &[PlayerColor::White, PlayerColor::Black] {
+            let hand = match self.player(*color) {
+                Some(player) => &player.hand,
+                None => continue,
+            };
+            for piece in 0..self.pieces().len() {
+                let count = hand.iter().filter(|&&p| p == piece).count();
+                if count > 1 {
+                    hands.push_str(&count.to_string());
+                }
+                if count > 0 {
+                    hands.push_str(&self.piece_symbol(piece, *color)?);
+                }
+            }
+        }
+        if hands.is_empty() {
+            hands.push('-');
+        }
+        res.push_str(&hands);
+
+        res.push_str(&format!(" {}", self.ply_number()));
+        Ok(res)
+    }
+
+    /// Finds the piece whose symbol is the letter `c`, optionally prefixed by `+`
+    fn find_symbol(&self, c: char, promoted: bool) -> SfenResult<(usize, PlayerColor)> {
+        let symbol = if promoted {
+            format!("+{}", c.to_ascii_uppercase())
+        } else {
+            c.to_ascii_uppercase().to_string()
+        };
+        let piece = self
+            .pieces()
+            .iter()
+            .position(|p| sfen_symbol(p).as_ref() == Some(&symbol))
+            .ok_or(UnknownPiece(symbol))?;
+        let color = if c.is_ascii_uppercase() {
+            PlayerColor::White
+        } else {
+            PlayerColor::Black
+        };
+        Ok((piece, color))
+    }
+
+    fn piece_symbol(&self, piece: usize, color: PlayerColor) -> SfenResult<String> {
+        let piece = &self.pieces()[piece];
+        let symbol = sfen_symbol(piece).ok_or_else(|| NoSymbol(piece.id().to_string()))?;
+        Ok(if color.white() {
+            symbol
+        } else {
+            symbol.to_ascii_lowercase()
+        })
+    }
+}
+
+/// The piece's letter, or its first alias made of `+` and an uppercase letter
+fn sfen_symbol(piece: &Piece) -> Option<String> {
+    piece.letter().map(|c| c.to_string()).or_else(|| {
+        piece
+            .alias_list()
+            .iter()
+            .find(|alias| {
+                let mut chars = alias.chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some('+'), Some(c), None) => c.is_ascii_uppercase(),
+                    _ => false,
+                }
+            })
+            .cloned()
+    })
+}
+
+fn color_char(color: PlayerColor) -> char {
+    if color.white() {
+        'b'
+    } else {
+        'w'
+    }
+}
+
+fn parse_number(raw: &str) -> SfenResult<usize> {
+    raw.parse().map_err(|_| InvalidNumber(raw.to_string()))
+}
+
+impl fmt::Display for SfenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingField(field) => write!(f, "missing {} field", field),
+            InvalidPlacement(e) => write!(f, "invalid piece placement: {}", e),
+            UnknownPiece(s) => write!(f, "unknown piece {}", s),
+            NoSymbol(id) => write!(f, "piece {} has no SFEN symbol", id),
+            InvalidColor(c) => write!(f, "invalid color {}", c),
+            InvalidHand(h) => write!(f, "invalid pieces in hand {}", h),
+            InvalidNumber(n) => write!(f, "invalid number {}", n),
+        }
+    }
+}
+
+impl Error for SfenError {}
@@ -0,0 +1,292 @@
+use self::StorageError::*;
+use crate::game::Game;
+use crate::pgn::Pgn;
+use crate::protocol::{GameResult, GameSummary, TimeControl};
+use crate::record::{GameEnd, Record};
+use crate::variant::Variant;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Most games given for a query; the next ones are asked for with `GameQuery::after`
+pub const MAX_FOUND: usize = 100;
+
+/// A game hosted by the server, finished or still being played
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredGame {
+    pub id: u64,
+    /// Name of the variant
+    pub variant: String,
+    /// The variant's definition, as written in its TOML file
+    pub definition: String,
+    /// Names of white and black
+    pub players: [String; 2],
+    /// Moves played since the variant's starting position, written like in UCI
+    pub moves: Vec<String>,
+    pub result: Option<GameResult>,
+    /// Why the game ended, like `checkmate` or `forfeit`
+    pub reason: Option<String>,
+    // TOML tables come last, after the plain values
+    pub started: SystemTime,
+    /// When the game ended, or `None` if it is still being played
+    pub ended: Option<SystemTime>,
+    pub time: Option<TimeControl>,
+}
+
+/// What a stored game is looked up by; fields left to `None` match every game
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameQuery {
+    /// Name of a player of the game, with either color
+    pub player: Option<String>,
+    /// Earliest start of the game
+    pub since: Option<SystemTime>,
+    /// Latest start of the game
+    pub until: Option<SystemTime>,
+    /// Only games with a larger id, to go on from the last game of a previous answer
+    pub after: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Pgn,
+    Kif,
+}
+
+/// Games kept as files in a directory, one TOML file per game named after its id
+#[derive(Clone, Debug)]
+pub struct GameStore {
+    dir: PathBuf,
+    /// Summaries of the readable games, which queries look through without reading the files
+    index: BTreeMap<u64, GameSummary>,
+    /// Games whose files couldn't be read when the store was opened, with why
+    unreadable: Vec<(u64, String)>,
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    /// A game file couldn't be written or read back
+    Encoding(String),
+    UnknownGame(u64),
+    InvalidVariant(String),
+    IllegalMove(String),
+    /// The game can't be written in the requested format
+    Export(String),
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+impl StoredGame {
+    /// Builds the game, with its moves played from the variant's starting position
+    pub fn game(&self) -> StorageResult<Game> {
+        let mut game = self
+            .definition
+            .parse::<Variant>()
+            .and_then(|variant| variant.game())
+            .map_err(|e| InvalidVariant(e.to_string()))?;
+        for text in &self.moves {
+            let mv = game
+                .parse_uci_move(text)
+                .map_err(|_| IllegalMove(text.clone()))?;
+            game.play(&mv);
+        }
+        Ok(game)
+    }
+
+    pub fn summary(&self) -> GameSummary {
+        GameSummary {
+            game: self.id,
+            variant: self.variant.clone(),
+            players: self.players.clone(),
+            moves: self.moves.len() as u32,
+            started: self.started,
+            ended: self.ended,
+            result: self.result,
+            reason: self.reason.clone(),
+            time: self.time,
+        }
+    }
+
+    pub fn export(&self, format: ExportFormat) -> StorageResult<String> {
+        match format {
+            ExportFormat::Pgn => self.pgn(),
+            ExportFormat::Kif => self.kif(),
+        }
+    }
+
+    /// Writes the game in PGN, with its players, date and result as tags
+    pub fn pgn(&self) -> StorageResult<String> {
+        let game = self.game()?;
+        let mut pgn = Pgn::from_game(&game).map_err(|e| Export(e.to_string()))?;
+        let started = DateTime::<Utc>::from(self.started);
+        pgn.set_tag("Event", &format!("Game {}", self.id));
+        pgn.set_tag("Date", &started.format("%Y.%m.%d").to_string());
+        pgn.set_tag("White", &self.players[0]);
+        pgn.set_tag("Black", &self.players[1]);
+        pgn.result = match self.result {
+            Some(result) => result.to_string(),
+            None => String::from("*"),
+        };
+        let result = pgn.result.clone();
+        pgn.set_tag("Result", &result);
+        pgn.set_tag("Variant", &self.variant);
+        if let Some(time) = self.time {
+            let control = format!("{}+{}", time.base.as_secs(), time.increment.as_secs());
+            pgn.set_tag("TimeControl", &control);
+        }
+        if let Some(reason) = &self.reason {
+            pgn.set_tag("Termination", reason);
+        }
+        Ok(pgn.to_string())
+    }
+
+    /// Writes the game in KIF, which only holds shogi games
+    pub fn kif(&self) -> StorageResult<String> {
+        let game = self.game()?;
+        let mut record = Record::from_game(&game);
+        let format = |time: SystemTime| {
+            DateTime::<Utc>::from(time)
+                .format("%Y/%m/%d %H:%M:%S")
+                .to_string()
+        };
+        record
+            .headers
+            .push((String::from("開始日時"), format(self.started)));
+        if let Some(ended) = self.ended {
+            record
+                .headers
+                .push((String::from("終了日時"), format(ended)));
+        }
+        record.sente = Some(self.players[0].clone());
+        record.gote = Some(self.players[1].clone());
+        record.end = match self.reason.as_deref() {
+            None => None,
+            Some("checkmate") => Some(GameEnd::Checkmate),
+            Some("forfeit") => Some(GameEnd::Resignation),
+            Some("time forfeit") => Some(GameEnd::TimeUp),
+            Some(_) => Some(GameEnd::Interruption),
+        };
+        record.kif(&game).map_err(|e| Export(e.to_string()))
+    }
+}
+
+impl GameQuery {
+    /// Whether the game is one of those looked up by the query
+    pub fn matches(&self, game: &GameSummary) -> bool {
+        self.player
+            .iter()
+            .all(|player| game.players.contains(player))
+            && self.since.iter().all(|&since| game.started >= since)
+            && self.until.iter().all(|&until| game.started <= until)
+            && self.after.iter().all(|&after| game.game > after)
+    }
+}
+
+impl GameStore {
+    /**
+    Opens the store kept in the given directory, creating the directory if needed.
+    Games that can't be read are left out, and listed by `unreadable`.
+    **/
+    pub fn open<P: AsRef<Path>>(dir: P) -> StorageResult<GameStore> {
+        fs::create_dir_all(&dir)?;
+        let mut store = GameStore {
+            dir: dir.as_ref().to_path_buf(),
+            index: BTreeMap::new(),
+            unreadable: Vec::new(),
+        };
+        for id in store.ids()? {
+            match store.load(id) {
+                Ok(game) => {
+                    store.index.insert(id, game.summary());
+                }
+                Err(e) => store.unreadable.push((id, e.to_string())),
+            }
+        }
+        Ok(store)
+    }
+
+    /// Writes a game, replacing its previous version
+    pub fn save(&mut self, game: &StoredGame) -> StorageResult<()> {
+        let text = toml::to_string(game).map_err(|e| Encoding(e.to_string()))?;
+        // written aside first, so that a crash doesn't leave half a file
+        let temporary = self.dir.join(format!("{}.toml.tmp", game.id));
+        fs::write(&temporary, text)?;
+        fs::rename(&temporary, self.path(game.id))?;
+        self.index.insert(game.id, game.summary());
+        Ok(())
+    }
+
+    pub fn load(&self, id: u64) -> StorageResult<StoredGame> {
+        let text = fs::read_to_string(self.path(id)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => UnknownGame(id),
+            _ => Io(e),
+        })?;
+        toml::from_str(&text).map_err(|e| Encoding(e.to_string()))
+    }
+
+    /// Ids of the stored games, in increasing order
+    pub fn ids(&self) -> StorageResult<Vec<u64>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)?.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+                continue;
+            }
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// The first `MAX_FOUND` games matching the query, by increasing id
+    pub fn find(&self, query: &GameQuery) -> Vec<GameSummary> {
+        self.index
+            .values()
+            .filter(|game| query.matches(game))
+            .take(MAX_FOUND)
+            .cloned()
+            .collect()
+    }
+
+    /// Games whose files couldn't be read when the store was opened, with why
+    pub fn unreadable(&self) -> &[(u64, String)] {
+        &self.unreadable
+    }
+
+    fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.toml", id))
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> Self {
+        Io(e)
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Io(e) => write!(f, "{}", e),
+            Encoding(e) => write!(f, "invalid game file: {}", e),
+            UnknownGame(id) => write!(f, "unknown game {}", id),
+            InvalidVariant(e) => write!(f, "invalid variant: {}", e),
+            IllegalMove(mv) => write!(f, "illegal move {}", mv),
+            Export(e) => write!(f, "couldn't export the game: {}", e),
+        }
+    }
+}
+
+impl Error for StorageError {}
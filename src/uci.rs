@@ -0,0 +1,244 @@
+use self::UciError::*;
+use crate::coordinates::Coordinates;
+use crate::engine::{Clock, SearchInfo, SearchLimits};
+use crate::game::Game;
+use crate::movement::Move;
+use crate::player::PlayerColor;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A command sent by a GUI to an engine, in the Universal Chess Interface
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UciCommand {
+    Uci,
+    Debug(bool),
+    IsReady,
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    UciNewGame,
+    /// Sets up the position from a FEN, or the starting position if `None`, and plays the given moves
+    Position {
+        fen: Option<String>,
+        moves: Vec<String>,
+    },
+    Go(Go),
+    Stop,
+    PonderHit,
+    Quit,
+}
+
+/// Parameters of the `go` command
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Go {
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub infinite: bool,
+    pub ponder: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UciError {
+    Empty,
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidNumber(String),
+    IllegalMove(String),
+}
+
+pub type UciResult<T> = Result<T, UciError>;
+
+impl Go {
+    /// Search limits for `color`, whose clock is used
+    pub fn limits(&self, color: PlayerColor) -> SearchLimits {
+        let (time, increment) = match color {
+            PlayerColor::White => (self.wtime, self.winc),
+            PlayerColor::Black => (self.btime, self.binc),
+        };
+        SearchLimits {
+            depth: self.depth,
+            nodes: self.nodes,
+            movetime: self.movetime,
+            clock: time.map(|remaining| Clock {
+                remaining,
+                increment: increment.unwrap_or_default(),
+                moves_to_go: self.movestogo,
+            }),
+            ponder: self.ponder,
+        }
+    }
+}
+
+impl FromStr for UciCommand {
+    type Err = UciError;
+
+    fn from_str(line: &str) -> UciResult<Self> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or(Empty)?;
+        let args: Vec<&str> = words.collect();
+        Ok(match command {
+            "uci" => UciCommand::Uci,
+            "debug" => UciCommand::Debug(args.first() != Some(&"off")),
+            "isready" => UciCommand::IsReady,
+            "setoption" => {
+                let value_index = args.iter().position(|&word| word == "value");
+                let name_end = value_index.unwrap_or(args.len());
+                match args.first() {
+                    Some(&"name") if name_end > 1 => {}
+                    _ => return Err(MissingArgument("name")),
+                }
+                UciCommand::SetOption {
+                    name: args[1..name_end].join(" "),
+                    value: value_index.map(|index| args[index + 1..].join(" ")),
+                }
+            }
+            "ucinewgame" => UciCommand::UciNewGame,
+            "position" => {
+                let moves_index = args.iter().position(|&word| word == "moves");
+                let setup_end = moves_index.unwrap_or(args.len());
+                let fen = match args.first() {
+                    Some(&"startpos") => None,
+                    Some(&"fen") if setup_end > 1 => Some(args[1..setup_end].join(" ")),
+                    _ => return Err(MissingArgument("startpos or fen")),
+                };
+                UciCommand::Position {
+                    fen,
+                    moves: moves_index
+                        .map(|index| args[index + 1..].iter().map(|mv| mv.to_string()).collect())
+                        .unwrap_or_default(),
+                }
+            }
+            "go" => UciCommand::Go(parse_go(&args)?),
+            "stop" => UciCommand::Stop,
+            "ponderhit" => UciCommand::PonderHit,
+            "quit" => UciCommand::Quit,
+            _ => return Err(UnknownCommand(command.to_string())),
+        })
+    }
+}
+
+fn parse_go(args: &[&str]) -> UciResult<Go> {
+    let mut go = Go::default();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let mut number = || -> UciResult<u64> {
+            let raw = args.next().ok_or(MissingArgument("number"))?;
+            // some GUIs send negative times when the clock runs out
+            raw.parse::<i64>()
+                .map(|n| n.max(0) as u64)
+                .map_err(|_| InvalidNumber(raw.to_string()))
+        };
+        match arg {
+            "wtime" => go.wtime = Some(Duration::from_millis(number()?)),
+            "btime" => go.btime = Some(Duration::from_millis(number()?)),
+            "winc" => go.winc = Some(Duration::from_millis(number()?)),
+            "binc" => go.binc = Some(Duration::from_millis(number()?)),
+            "movestogo" => go.movestogo = Some(number()? as u32),
+            "depth" => go.depth = Some(number()? as usize),
+            "nodes" => go.nodes = Some(number()?),
+            "movetime" => go.movetime = Some(Duration::from_millis(number()?)),
+            "infinite" => go.infinite = true,
+            "ponder" => go.ponder = true,
+            _ => {}
+        }
+    }
+    Ok(go)
+}
+
+impl Game {
+    /**
+    Writes a move in UCI's long algebraic notation, like `e2e4` or `e7e8q`.
+    Promotions are followed by the lowercase letter of the promoted piece, or `+` if it has none,
+    and drops are written like `P@e4`.
+    **/
+    pub fn uci_move(&self, mv: &Move) -> String {
+        let mut res = match mv.from {
+            Some(from) => format!(
+                "{}{}",
+                self.algebraic_square(from),
+                self.algebraic_square(mv.to)
+            ),
+            None => format!(
+                "{}@{}",
+                self.pieces()[mv.piece].letter().unwrap_or('?'),
+                self.algebraic_square(mv.to)
+            ),
+        };
+        if let Some(promotion) = mv.promotion {
+            match self.pieces()[promotion].letter() {
+                Some(letter) => res.push(letter.to_ascii_lowercase()),
+                None => res.push('+'),
+            }
+        }
+        res
+    }
+
+    /// Finds the legal move written in UCI's long algebraic notation
+    pub fn parse_uci_move(&self, text: &str) -> UciResult<Move> {
+        self.legal_moves()
+            .into_iter()
+            .find(|mv| self.uci_move(mv) == text)
+            .ok_or_else(|| IllegalMove(text.to_string()))
+    }
+
+    fn algebraic_square(&self, square: (usize, usize)) -> String {
+        Coordinates::Algebraic.square_name(
+            square,
+            self.board().width.get(),
+            self.board().height.get(),
+            PlayerColor::White,
+        )
+    }
+}
+
+/// Writes the `info` line reporting a search's progress in the position `game`
+pub fn info_line(game: &Game, info: &SearchInfo) -> String {
+    let score = match info.mate() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", info.score),
+    };
+    let millis = info.time.as_millis() as u64;
+    let mut res = format!(
+        "info depth {} score {} nodes {} nps {} time {}",
+        info.depth,
+        score,
+        info.nodes,
+        info.nodes * 1000 / millis.max(1),
+        millis
+    );
+    if !info.pv.is_empty() {
+        res.push_str(" pv");
+        let mut game = game.clone();
+        for mv in &info.pv {
+            res.push(' ');
+            res.push_str(&game.uci_move(mv));
+            if game.play(mv).is_none() {
+                break;
+            }
+        }
+    }
+    res
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Empty => write!(f, "empty command"),
+            UnknownCommand(command) => write!(f, "unknown command {}", command),
+            MissingArgument(argument) => write!(f, "missing argument: {}", argument),
+            InvalidNumber(raw) => write!(f, "invalid number {}", raw),
+            IllegalMove(mv) => write!(f, "illegal move {}", mv),
+        }
+    }
+}
+
+impl Error for UciError {}
@@ -0,0 +1,242 @@
+use self::UsiError::*;
+use crate::engine::{Clock, SearchInfo, SearchLimits, MATE};
+use crate::game::Game;
+use crate::movement::Move;
+use crate::player::PlayerColor;
+use crate::record::shogi_square;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A command sent by a GUI to an engine, in the Universal Shogi Interface
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UsiCommand {
+    Usi,
+    IsReady,
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    UsiNewGame,
+    /// Sets up the position from an SFEN, or the starting position if `None`, and plays the given moves
+    Position {
+        sfen: Option<String>,
+        moves: Vec<String>,
+    },
+    Go(Go),
+    Stop,
+    PonderHit,
+    /// The game ended, with `win`, `lose` or `draw` for the engine
+    GameOver(String),
+    Quit,
+}
+
+/// Parameters of the `go` command; `b` stands for sente and `w` for gote
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Go {
+    pub btime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub winc: Option<Duration>,
+    /// Time to spend on each move once the main time is over
+    pub byoyomi: Option<Duration>,
+    pub infinite: bool,
+    pub ponder: bool,
+    /// Asks for a checkmate search, which the engine doesn't support
+    pub mate: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UsiError {
+    Empty,
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidNumber(String),
+    IllegalMove(String),
+}
+
+pub type UsiResult<T> = Result<T, UsiError>;
+
+impl Go {
+    /**
+    Search limits for `color`, whose clock is used.
+    The byoyomi counts as an increment, which may be spent along with the main time.
+    **/
+    pub fn limits(&self, color: PlayerColor) -> SearchLimits {
+        let (time, increment) = match color {
+            PlayerColor::White => (self.btime, self.binc),
+            PlayerColor::Black => (self.wtime, self.winc),
+        };
+        let byoyomi = self.byoyomi.unwrap_or_default();
+        let clock = match (time, byoyomi > Duration::default()) {
+            (None, false) => None,
+            (time, _) => Some(Clock {
+                remaining: time.unwrap_or_default() + byoyomi,
+                increment: increment.unwrap_or_default() + byoyomi,
+                moves_to_go: None,
+            }),
+        };
+        SearchLimits {
+            clock,
+            ponder: self.ponder,
+            ..SearchLimits::default()
+        }
+    }
+}
+
+impl FromStr for UsiCommand {
+    type Err = UsiError;
+
+    fn from_str(line: &str) -> UsiResult<Self> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or(Empty)?;
+        let args: Vec<&str> = words.collect();
+        Ok(match command {
+            "usi" => UsiCommand::Usi,
+            "isready" => UsiCommand::IsReady,
+            "setoption" => {
+                let value_index = args.iter().position(|&word| word == "value");
+                let name_end = value_index.unwrap_or(args.len());
+                match args.first() {
+                    Some(&"name") if name_end > 1 => {}
+                    _ => return Err(MissingArgument("name")),
+                }
+                UsiCommand::SetOption {
+                    name: args[1..name_end].join(" "),
+                    value: value_index.map(|index| args[index + 1..].join(" ")),
+                }
+            }
+            "usinewgame" => UsiCommand::UsiNewGame,
+            "position" => {
+                let moves_index = args.iter().position(|&word| word == "moves");
+                let setup_end = moves_index.unwrap_or(args.len());
+                let sfen = match args.first() {
+                    Some(&"startpos") => None,
+                    Some(&"sfen") if setup_end > 1 => Some(args[1..setup_end].join(" ")),
+                    _ => return Err(MissingArgument("startpos or sfen")),
+                };
+                UsiCommand::Position {
+                    sfen,
+                    moves: moves_index
+                        .map(|index| args[index + 1..].iter().map(|mv| mv.to_string()).collect())
+                        .unwrap_or_default(),
+                }
+            }
+            "go" => UsiCommand::Go(parse_go(&args)?),
+            "stop" => UsiCommand::Stop,
+            "ponderhit" => UsiCommand::PonderHit,
+            "gameover" => {
+                UsiCommand::GameOver(args.first().ok_or(MissingArgument("result"))?.to_string())
+            }
+            "quit" => UsiCommand::Quit,
+            _ => return Err(UnknownCommand(command.to_string())),
+        })
+    }
+}
+
+fn parse_go(args: &[&str]) -> UsiResult<Go> {
+    let mut go = Go::default();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let mut millis = || -> UsiResult<Duration> {
+            let raw = args.next().ok_or(MissingArgument("number"))?;
+            raw.parse::<i64>()
+                .map(|n| Duration::from_millis(n.max(0) as u64))
+                .map_err(|_| InvalidNumber(raw.to_string()))
+        };
+        match arg {
+            "btime" => go.btime = Some(millis()?),
+            "wtime" => go.wtime = Some(millis()?),
+            "binc" => go.binc = Some(millis()?),
+            "winc" => go.winc = Some(millis()?),
+            "byoyomi" => go.byoyomi = Some(millis()?),
+            "infinite" => go.infinite = true,
+            "ponder" => go.ponder = true,
+            "mate" => {
+                go.mate = true;
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(go)
+}
+
+impl Game {
+    /**
+    Writes a move in USI notation, like `7g7f`, `8h2b+` or `P*5e`.
+    Files are numbered from sente's right and ranks lettered from gote's side, as in SFEN.
+    **/
+    pub fn usi_move(&self, mv: &Move) -> String {
+        let mut res = match mv.from {
+            Some(from) => format!("{}{}", self.usi_square(from), self.usi_square(mv.to)),
+            None => format!(
+                "{}*{}",
+                self.pieces()[mv.piece].letter().unwrap_or('?'),
+                self.usi_square(mv.to)
+            ),
+        };
+        if mv.promotion.is_some() {
+            res.push('+');
+        }
+        res
+    }
+
+    /// Finds the legal move written in USI notation
+    pub fn parse_usi_move(&self, text: &str) -> UsiResult<Move> {
+        self.legal_moves()
+            .into_iter()
+            .find(|mv| self.usi_move(mv) == text)
+            .ok_or_else(|| IllegalMove(text.to_string()))
+    }
+
+    fn usi_square(&self, square: (usize, usize)) -> String {
+        let (file, rank) = shogi_square(self, square);
+        format!("{}{}", file, (b'a' + rank as u8 - 1) as char)
+    }
+}
+
+/// Writes the `info` line reporting a search's progress in the position `game`; mates are counted in plies
+pub fn info_line(game: &Game, info: &SearchInfo) -> String {
+    let score = match info.mate() {
+        Some(moves) if moves > 0 => format!("mate {}", MATE - info.score),
+        Some(_) => format!("mate -{}", MATE + info.score),
+        None => format!("cp {}", info.score),
+    };
+    let millis = info.time.as_millis() as u64;
+    let mut res = format!(
+        "info depth {} score {} nodes {} nps {} time {}",
+        info.depth,
+        score,
+        info.nodes,
+        info.nodes * 1000 / millis.max(1),
+        millis
+    );
+    if !info.pv.is_empty() {
+        res.push_str(" pv");
+        let mut game = game.clone();
+        for mv in &info.pv {
+            res.push(' ');
+            res.push_str(&game.usi_move(mv));
+            if game.play(mv).is_none() {
+                break;
+            }
+        }
+    }
+    res
+}
+
+impl fmt::Display for UsiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Empty => write!(f, "empty command"),
+            UnknownCommand(command) => write!(f, "unknown command {}", command),
+            MissingArgument(argument) => write!(f, "missing argument: {}", argument),
+            InvalidNumber(raw) => write!(f, "invalid number {}", raw),
+            IllegalMove(mv) => write!(f, "illegal move {}", mv),
+        }
+    }
+}
+
+impl Error for UsiError {}
@@ -0,0 +1,217 @@
+use crate::board::Board;
+use crate::game::Game;
+use crate::movement::RawMovement;
+use crate::piece::Piece;
+use crate::player::{Player, PlayerColor};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::num::NonZeroUsize;
+
+const DEFAULT_SAMPLES: usize = 300;
+const DEFAULT_DENSITY: f64 = 0.25;
+const DEFAULT_SEED: u64 = 0x0053_4841_524d_4154;
+
+/// Mobility statistics of a piece and the value suggested from them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PieceEstimate {
+    /// Average number of squares the piece may move to
+    pub mobility: f64,
+    /// Average number of those squares that are not adjacent to the piece,
+    /// from which it could check a king without being captured by it
+    pub safe_checks: f64,
+    /// Share of forward moves minus the share of backward moves, from -1 to 1
+    pub forward_bias: f64,
+    /// Whether the piece always stays on squares of the same color, like the bishop
+    pub colorbound: bool,
+    /// Suggested value, in centipawns
+    pub value: i32,
+}
+
+/// Estimates the value of pieces by sampling their mobility over random positions
+#[derive(Clone, Debug)]
+pub struct Estimator {
+    samples: usize,
+    density: f64,
+    seed: u64,
+}
+
+impl Estimator {
+    /**
+    Estimates the value of `piece` on a board of the given size, or gives a neutral estimate if there is no such board.
+    Each sample places the piece on a random square of a board filled with random pieces of both colors,
+    and counts the squares it may move to.
+    **/
+    pub fn estimate(&self, piece: &Piece, width: usize, height: usize) -> PieceEstimate {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut board = match (NonZeroUsize::new(width), NonZeroUsize::new(height)) {
+            (Some(width), Some(height)) => Board::new(width, height),
+            _ => {
+                return PieceEstimate {
+                    mobility: 0.0,
+                    safe_checks: 0.0,
+                    forward_bias: 0.0,
+                    colorbound: false,
+                    value: 0,
+                }
+            }
+        };
+        let player = Player::new(PlayerColor::White);
+
+        let (mut moves, mut safe, mut forward, mut backward, mut odd) = (0, 0, 0, 0, 0);
+        for _ in 0..self.samples {
+            board.clear();
+            let (x, y) = (rng.gen_range(0, width), rng.gen_range(0, height));
+            for sy in 0..height {
+                for sx in 0..width {
+                    if (sx, sy) != (x, y) && rng.gen_bool(self.density) {
+                        let color = if rng.gen() {
+                            PlayerColor::White
+                        } else {
+                            PlayerColor::Black
+                        };
+                        board.set(sx, sy, Some((0, color))).unwrap();
+                    }
+                }
+            }
+            board.set(x, y, Some((0, PlayerColor::White))).unwrap();
+
+            for (dx, dy) in movements(piece, &board, &player, x, y) {
+                moves += 1;
+                if dx.abs() > 1 || dy.abs() > 1 {
+                    safe += 1;
+                }
+                if dy > 0 {
+                    forward += 1;
+                } else if dy < 0 {
+                    backward += 1;
+                }
+                if (dx + dy) % 2 != 0 {
+                    odd += 1;
+                }
+            }
+        }
+
+        let samples = self.samples.max(1) as f64;
+        let mobility = moves as f64 / samples;
+        let safe_checks = safe as f64 / samples;
+        let forward_bias = if moves == 0 {
+            0.0
+        } else {
+            (forward as f64 - backward as f64) / moves as f64
+        };
+        let colorbound = moves > 0 && odd == 0;
+        PieceEstimate {
+            mobility,
+            safe_checks,
+            forward_bias,
+            colorbound,
+            value: value(mobility, safe_checks, forward_bias, colorbound),
+        }
+    }
+}
+
+impl Game {
+    /**
+    Suggested values of the pieces, in centipawns, estimated from their movement on this game's board.
+    Royal pieces are worth nothing, and the values are scaled for pawns to be worth 100.
+    **/
+    pub fn estimate_values(&self, estimator: &Estimator) -> Vec<i32> {
+        let (width, height) = (self.board().width.get(), self.board().height.get());
+        let mut values: Vec<i32> = self
+            .pieces()
+            .iter()
+            .map(|piece| estimator.estimate(piece, width, height).value)
+            .collect();
+        let pawn = (0..values.len()).find(|&piece| self.is_pawn(piece) && values[piece] > 0);
+        if let Some(pawn) = pawn {
+            let pawn_value = values[pawn];
+            for value in values.iter_mut() {
+                *value = (*value as i64 * 100 / pawn_value as i64) as i32;
+            }
+        }
+        for (piece, value) in values.iter_mut().enumerate() {
+            if self.is_royal(piece) {
+                *value = 0;
+            }
+        }
+        values
+    }
+}
+
+/// Moves of a piece from (x, y), without duplicates
+fn movements(
+    piece: &Piece,
+    board: &Board,
+    player: &Player,
+    x: usize,
+    y: usize,
+) -> Vec<RawMovement> {
+    let mut res: Vec<RawMovement> = piece
+        .movement_type()
+        .iter()
+        .filter_map(|mt| mt.flatten(board, player, x, y))
+        .flatten()
+        .collect();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+/**
+Combines the mobility statistics into a value.
+Pieces which only move forward, and colorbound pieces, lose up to a tenth of their value.
+**/
+fn value(mobility: f64, safe_checks: f64, forward_bias: f64, colorbound: bool) -> i32 {
+    let base = 60.0 + 40.0 * mobility + 10.0 * safe_checks + mobility * mobility;
+    let direction = 1.0 - forward_bias.abs() / 10.0;
+    let colorbound = if colorbound { 0.9 } else { 1.0 };
+    (base * direction * colorbound).round() as i32
+}
+
+pub struct EstimatorBuilder {
+    estimator_samples: usize,
+    estimator_density: f64,
+    estimator_seed: u64,
+}
+
+impl Default for EstimatorBuilder {
+    fn default() -> Self {
+        EstimatorBuilder {
+            estimator_samples: DEFAULT_SAMPLES,
+            estimator_density: DEFAULT_DENSITY,
+            estimator_seed: DEFAULT_SEED,
+        }
+    }
+}
+
+impl EstimatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of random positions to sample
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.estimator_samples = samples;
+        self
+    }
+
+    /// Probability for each square of the random positions to hold a piece
+    pub fn density(mut self, density: f64) -> Self {
+        self.estimator_density = density.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seed of the random positions; the same seed gives the same estimates
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.estimator_seed = seed;
+        self
+    }
+
+    pub fn build(self) -> Estimator {
+        Estimator {
+            samples: self.estimator_samples,
+            density: self.estimator_density,
+            seed: self.estimator_seed,
+        }
+    }
+}
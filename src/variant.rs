@@ -0,0 +1,452 @@
+use self::VariantError::*;
+use crate::board::Board;
+use crate::coordinates::Coordinates;
+use crate::fen::FenError;
+use crate::game::{Game, GameBuilder};
+use crate::movement::{MovementCondition, MovementType};
+use crate::piece::{Piece, PieceBuilder};
+use crate::player::{Player, PlayerColor};
+use crate::rule::Rule;
+use crate::sfen::SfenError;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A variant definition, as read from a TOML file.
+///
+/// ## Example:
+///
+/// ```toml
+/// name = "Tiny"
+/// width = 3
+/// height = 3
+///
+/// [[piece]]
+/// id = "tiny.king"
+/// alias = ["king", "K"]
+/// movement = { union = [{ undirected = [1, 0] }, { undirected = [1, 1] }] }
+///
+/// [[setup]]
+/// piece = "king"
+/// color = "white"
+/// squares = [[1, 0]]
+///
+/// [[setup]]
+/// piece = "king"
+/// color = "black"
+/// squares = ["b3"]
+///
+/// [[rule]]
+/// type = "royal"
+/// piece = "tiny.king"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Variant {
+    pub name: String,
+    #[serde(default)]
+    pub desc: String,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default = "default_players")]
+    pub players: Vec<ColorDef>,
+    #[serde(rename = "piece", default)]
+    pub pieces: Vec<PieceDef>,
+    #[serde(rename = "setup", default)]
+    pub setup: Vec<SetupDef>,
+    /// Starting position as a FEN string, used instead of `setup`
+    pub fen: Option<String>,
+    /// Starting position as an SFEN string, used instead of `setup`
+    pub sfen: Option<String>,
+    #[serde(rename = "rule", default)]
+    pub rules: Vec<RuleDef>,
+    /// How squares are named, in setups and notations
+    #[serde(default)]
+    pub coordinates: Coordinates,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorDef {
+    White,
+    Black,
+}
+
+/// A piece, as described in a variant file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PieceDef {
+    pub id: String,
+    #[serde(default)]
+    pub alias: Vec<String>,
+    pub display_white: Option<String>,
+    pub display_black: Option<String>,
+    #[serde(default)]
+    pub desc: String,
+    pub movement: MovementDef,
+}
+
+/// A movement type, as described in a variant file.
+/// Exactly one of `stay`, `undirected`, `directed`, `union`, `range_any` and `range` must be given;
+/// `when` and `rank` add conditions on top of it.
+///
+/// ## Example:
+///
+/// ```toml
+/// movement = { directed = [0, 2], when = ["as_white", "no_capture"], rank = 1 }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MovementDef {
+    #[serde(default)]
+    pub stay: bool,
+    pub undirected: Option<(usize, usize)>,
+    pub directed: Option<(isize, isize)>,
+    pub union: Option<Vec<MovementDef>>,
+    pub range_any: Option<Box<MovementDef>>,
+    pub range: Option<Box<MovementDef>>,
+    /// Maximum range of a `range` movement
+    pub max: Option<usize>,
+    #[serde(default)]
+    pub when: Vec<ConditionDef>,
+    pub rank: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionDef {
+    Capture,
+    NoCapture,
+    AsWhite,
+    AsBlack,
+}
+
+/// Pieces to put on the board when the game starts
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SetupDef {
+    /// Id or alias of the piece
+    pub piece: String,
+    pub color: ColorDef,
+    pub squares: Vec<SquareDef>,
+}
+
+/// A square, either as `[x, y]` or named in the variant's coordinates, like `"e1"`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SquareDef {
+    Position(usize, usize),
+    Name(String),
+}
+
+/// A rule, as described in a variant file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RuleDef {
+    Royal {
+        piece: String,
+    },
+    Promotion {
+        piece: String,
+        to: Vec<String>,
+        #[serde(default = "default_zone")]
+        zone: usize,
+        #[serde(default)]
+        optional: bool,
+    },
+    Castling {
+        king: String,
+        rook: String,
+    },
+    EnPassant {
+        piece: String,
+    },
+    Drops,
+    OnePerFile {
+        piece: String,
+    },
+    NoDropMate {
+        piece: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum VariantError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    InvalidSize(usize, usize),
+    InvalidMovement(String),
+    UnknownPiece(String),
+    OutOfBounds(usize, usize),
+    InvalidSquare(String),
+    Fen(FenError),
+    Sfen(SfenError),
+}
+
+pub type VariantResult<T> = Result<T, VariantError>;
+
+fn default_players() -> Vec<ColorDef> {
+    vec![ColorDef::White, ColorDef::Black]
+}
+
+fn default_zone() -> usize {
+    1
+}
+
+impl Variant {
+    /// Reads and parses the variant file at `path`
+    pub fn load<P: AsRef<Path>>(path: P) -> VariantResult<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Builds a new game, set up in the variant's starting position
+    pub fn game(&self) -> VariantResult<Game> {
+        let width = NonZeroUsize::new(self.width).ok_or(InvalidSize(self.width, self.height))?;
+        let height = NonZeroUsize::new(self.height).ok_or(InvalidSize(self.width, self.height))?;
+        let mut board = Board::new(width, height);
+        board.set_name(&self.name);
+
+        let mut builder = GameBuilder::new()
+            .board(board)
+            .coordinates(self.coordinates);
+        for piece in &self.pieces {
+            builder = builder.piece(piece.build()?);
+        }
+        for color in &self.players {
+            builder = builder.player(Player::new(color.color()));
+        }
+        for rule in &self.rules {
+            for id in rule.piece_ids() {
+                if !self.pieces.iter().any(|p| p.id == *id) {
+                    return Err(UnknownPiece(id.clone()));
+                }
+            }
+            builder = builder.rule(rule.build());
+        }
+
+        let mut game = builder.build();
+        for setup in &self.setup {
+            if game.search_piece(&setup.piece).is_none()
+                && game.search_piece_alias(&setup.piece).is_empty()
+            {
+                return Err(UnknownPiece(setup.piece.clone()));
+            }
+            for square in &setup.squares {
+                let (x, y) = match square {
+                    SquareDef::Position(x, y) => (*x, *y),
+                    SquareDef::Name(name) => game
+                        .parse_square(name)
+                        .ok_or_else(|| InvalidSquare(name.clone()))?,
+                };
+                game.set(x, y, &setup.piece, setup.color.color())
+                    .ok_or(OutOfBounds(x, y))?;
+            }
+        }
+        if let Some(fen) = &self.fen {
+            game.load_fen(fen)?;
+        }
+        if let Some(sfen) = &self.sfen {
+            game.load_sfen(sfen)?;
+        }
+
+        Ok(game)
+    }
+}
+
+impl FromStr for Variant {
+    type Err = VariantError;
+
+    fn from_str(s: &str) -> VariantResult<Self> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+impl ColorDef {
+    pub fn color(self) -> PlayerColor {
+        match self {
+            ColorDef::White => PlayerColor::White,
+            ColorDef::Black => PlayerColor::Black,
+        }
+    }
+}
+
+impl PieceDef {
+    pub fn build(&self) -> VariantResult<Piece> {
+        let mut builder = PieceBuilder::new().id(&self.id);
+        for alias in &self.alias {
+            builder = builder.alias(alias);
+        }
+        if let Some(display) = &self.display_white {
+            builder = builder.display_white(display);
+        }
+        if let Some(display) = &self.display_black {
+            builder = builder.display_black(display);
+        }
+        if !self.desc.is_empty() {
+            builder = builder.desc(&self.desc);
+        }
+        Ok(builder.movement(vec![self.movement.build()?]).build())
+    }
+}
+
+impl MovementDef {
+    pub fn build(&self) -> VariantResult<MovementType> {
+        let kinds = [
+            self.stay,
+            self.undirected.is_some(),
+            self.directed.is_some(),
+            self.union.is_some(),
+            self.range_any.is_some(),
+            self.range.is_some(),
+        ];
+        if kinds.iter().filter(|k| **k).count() != 1 {
+            return Err(InvalidMovement(format!(
+                "expected exactly one movement kind in {:?}",
+                self
+            )));
+        }
+        if self.max.is_some() && self.range.is_none() {
+            return Err(InvalidMovement(String::from("`max` given without `range`")));
+        }
+
+        let movement = if let Some((dx, dy)) = self.undirected {
+            MovementType::Undirected(dx, dy)
+        } else if let Some((dx, dy)) = self.directed {
+            MovementType::Directed(dx, dy)
+        } else if let Some(union) = &self.union {
+            MovementType::Union(
+                union
+                    .iter()
+                    .map(|m| m.build())
+                    .collect::<VariantResult<Vec<_>>>()?,
+            )
+        } else if let Some(mv) = &self.range_any {
+            MovementType::RangeAny(Box::new(mv.build()?))
+        } else if let Some(mv) = &self.range {
+            let max = self
+                .max
+                .ok_or_else(|| InvalidMovement(String::from("`range` given without `max`")))?;
+            MovementType::Range(Box::new(mv.build()?), max)
+        } else {
+            MovementType::Stay
+        };
+
+        let mut conditions: Vec<MovementCondition> =
+            self.when.iter().map(|c| c.condition()).collect();
+        if let Some(rank) = self.rank {
+            conditions.push(MovementCondition::Rank(rank));
+        }
+
+        if conditions.is_empty() {
+            Ok(movement)
+        } else {
+            Ok(MovementType::Condition(Box::new(movement), conditions))
+        }
+    }
+}
+
+impl ConditionDef {
+    pub fn condition(self) -> MovementCondition {
+        match self {
+            ConditionDef::Capture => MovementCondition::Capture,
+            ConditionDef::NoCapture => MovementCondition::NoCapture,
+            ConditionDef::AsWhite => MovementCondition::AsWhite,
+            ConditionDef::AsBlack => MovementCondition::AsBlack,
+        }
+    }
+}
+
+impl RuleDef {
+    /// Ids of the pieces that this rule refers to
+    pub fn piece_ids(&self) -> Vec<&String> {
+        match self {
+            RuleDef::Royal { piece } => vec![piece],
+            RuleDef::Promotion { piece, to, .. } => {
+                let mut res = vec![piece];
+                res.extend(to.iter());
+                res
+            }
+            RuleDef::Castling { king, rook } => vec![king, rook],
+            RuleDef::EnPassant { piece }
+            | RuleDef::OnePerFile { piece }
+            | RuleDef::NoDropMate { piece } => vec![piece],
+            RuleDef::Drops => vec![],
+        }
+    }
+
+    pub fn build(&self) -> Rule {
+        match self {
+            RuleDef::Royal { piece } => Rule::Royal(piece.clone()),
+            RuleDef::Promotion {
+                piece,
+                to,
+                zone,
+                optional,
+            } => Rule::Promotion {
+                piece: piece.clone(),
+                to: to.clone(),
+                zone: *zone,
+                optional: *optional,
+            },
+            RuleDef::Castling { king, rook } => Rule::Castling {
+                king: king.clone(),
+                rook: rook.clone(),
+            },
+            RuleDef::EnPassant { piece } => Rule::EnPassant(piece.clone()),
+            RuleDef::Drops => Rule::Drops,
+            RuleDef::OnePerFile { piece } => Rule::OnePerFile(piece.clone()),
+            RuleDef::NoDropMate { piece } => Rule::NoDropMate(piece.clone()),
+        }
+    }
+}
+
+impl fmt::Display for VariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Io(e) => write!(f, "couldn't read variant file: {}", e),
+            Parse(e) => write!(f, "couldn't parse variant file: {}", e),
+            InvalidSize(w, h) => write!(f, "invalid board size {}x{}", w, h),
+            InvalidMovement(e) => write!(f, "invalid movement: {}", e),
+            UnknownPiece(p) => write!(f, "unknown piece {}", p),
+            OutOfBounds(x, y) => write!(
+                f,
+                "square {} is out of the board",
+                Coordinates::Algebraic.square_name((*x, *y), 0, 0, PlayerColor::White)
+            ),
+            InvalidSquare(name) => write!(f, "invalid square {}", name),
+            Fen(e) => write!(f, "invalid starting position: {}", e),
+            Sfen(e) => write!(f, "invalid starting position: {}", e),
+        }
+    }
+}
+
+impl Error for VariantError {}
+
+impl From<io::Error> for VariantError {
+    fn from(e: io::Error) -> Self {
+        Io(e)
+    }
+}
+
+impl From<FenError> for VariantError {
+    fn from(e: FenError) -> Self {
+        Fen(e)
+    }
+}
+
+impl From<SfenError> for VariantError {
+    fn from(e: SfenError) -> Self {
+        Sfen(e)
+    }
+}
+
+impl From<toml::de::Error> for VariantError {
+    fn from(e: toml::de::Error) -> Self {
+        Parse(e)
+    }
+}
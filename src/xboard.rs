@@ -0,0 +1,309 @@
+use self::XboardError::*;
+use crate::engine::{Clock, SearchInfo, SearchLimits};
+use crate::game::{Game, Outcome};
+use crate::player::PlayerColor;
+use crate::rule::Rule;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Piece types known to XBoard, in the order of the piece-to-char table of the `setup` command
+const PIECE_TYPES: &str = "PNBRQFEACWMOHIJGDVLSUK";
+
+/// Time given to a move when the GUI sets neither a clock, a move time nor a depth
+pub const UNLIMITED_MOVE_TIME: Duration = Duration::from_secs(10);
+
+/// A command sent by a GUI to an engine, in the Chess Engine Communication Protocol
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XboardCommand {
+    Xboard,
+    Protover(u32),
+    New,
+    Variant(String),
+    /// Stops the engine from playing either side
+    Force,
+    /// Makes the engine play the side to move
+    Go,
+    /// Makes the engine play the side not to move
+    PlayOther,
+    UserMove(String),
+    /// Sets up a time control of `moves` moves, or the whole game if 0, in `base` time with `increment` per move
+    Level {
+        moves: u32,
+        base: Duration,
+        increment: Duration,
+    },
+    /// Sets a fixed time per move
+    St(Duration),
+    /// Limits the search depth
+    Sd(usize),
+    /// Time left on the engine's clock
+    Time(Duration),
+    /// Time left on the opponent's clock
+    Otim(Duration),
+    /// Asks the engine to move now
+    MoveNow,
+    Ping(String),
+    /// The game ended, with the result and an optional comment
+    Result(String),
+    SetBoard(String),
+    Undo,
+    Remove,
+    /// Turns thinking output on or off
+    Post(bool),
+    /// Memory the engine may use, in MB
+    Memory(usize),
+    /// Redefines the movement of the piece with the given letter, in Betza's notation
+    Piece {
+        letter: char,
+        betza: String,
+    },
+    Quit,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum XboardError {
+    Empty,
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidNumber(String),
+}
+
+pub type XboardResult<T> = Result<T, XboardError>;
+
+/// Time control set by the `level`, `st` and `sd` commands, and the engine's clock from `time`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeControl {
+    /// Moves per session, or 0 for the whole game
+    pub moves: u32,
+    pub base: Duration,
+    pub increment: Duration,
+    pub move_time: Option<Duration>,
+    pub depth: Option<usize>,
+    /// Time left on the engine's clock, if the GUI sent it
+    pub remaining: Option<Duration>,
+}
+
+impl TimeControl {
+    /// Search limits for the engine's next move, after it played `moves_played` moves
+    pub fn limits(&self, moves_played: usize) -> SearchLimits {
+        let clock = if self.move_time.is_some() {
+            None
+        } else if self.remaining.is_some() || self.base > Duration::default() {
+            Some(Clock {
+                remaining: self.remaining.unwrap_or(self.base),
+                increment: self.increment,
+                moves_to_go: match self.moves {
+                    0 => None,
+                    moves => Some(moves - moves_played as u32 % moves),
+                },
+            })
+        } else {
+            None
+        };
+        // a search must end for its move to be played, and for pings to be answered
+        let movetime = match (self.move_time, &clock, self.depth) {
+            (None, None, None) => Some(UNLIMITED_MOVE_TIME),
+            _ => self.move_time,
+        };
+        SearchLimits {
+            depth: self.depth,
+            movetime,
+            clock,
+            ..SearchLimits::default()
+        }
+    }
+}
+
+impl FromStr for XboardCommand {
+    type Err = XboardError;
+
+    fn from_str(line: &str) -> XboardResult<Self> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or(Empty)?;
+        let args: Vec<&str> = words.collect();
+        let arg = |index: usize, name: &'static str| args.get(index).ok_or(MissingArgument(name));
+        Ok(match command {
+            "xboard" => XboardCommand::Xboard,
+            "protover" => XboardCommand::Protover(number(arg(0, "version")?)?),
+            "new" => XboardCommand::New,
+            "variant" => XboardCommand::Variant(arg(0, "variant")?.to_string()),
+            "force" => XboardCommand::Force,
+            "go" => XboardCommand::Go,
+            "playother" => XboardCommand::PlayOther,
+            "usermove" => XboardCommand::UserMove(arg(0, "move")?.to_string()),
+            "level" => XboardCommand::Level {
+                moves: number(arg(0, "moves")?)?,
+                base: parse_base(arg(1, "base")?)?,
+                increment: seconds(arg(2, "increment")?)?,
+            },
+            "st" => XboardCommand::St(seconds(arg(0, "time")?)?),
+            "sd" => XboardCommand::Sd(number(arg(0, "depth")?)?),
+            "time" => XboardCommand::Time(centiseconds(arg(0, "time")?)?),
+            "otim" => XboardCommand::Otim(centiseconds(arg(0, "time")?)?),
+            "?" => XboardCommand::MoveNow,
+            "ping" => XboardCommand::Ping(arg(0, "number")?.to_string()),
+            "result" => XboardCommand::Result(args.join(" ")),
+            "setboard" => XboardCommand::SetBoard(args.join(" ")),
+            "undo" => XboardCommand::Undo,
+            "remove" => XboardCommand::Remove,
+            "post" => XboardCommand::Post(true),
+            "nopost" => XboardCommand::Post(false),
+            "memory" => XboardCommand::Memory(number(arg(0, "size")?)?),
+            "piece" => {
+                // the letter may be followed by `&`, for pieces shared by both colors
+                let letter = arg(0, "piece")?
+                    .chars()
+                    .next()
+                    .ok_or(MissingArgument("piece"))?;
+                if args.len() < 2 {
+                    return Err(MissingArgument("movement"));
+                }
+                XboardCommand::Piece {
+                    letter,
+                    betza: args[1..].concat(),
+                }
+            }
+            "quit" => XboardCommand::Quit,
+            _ => return Err(UnknownCommand(command.to_string())),
+        })
+    }
+}
+
+fn number<T: FromStr>(raw: &str) -> XboardResult<T> {
+    raw.parse().map_err(|_| InvalidNumber(raw.to_string()))
+}
+
+fn seconds(raw: &str) -> XboardResult<Duration> {
+    raw.parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite())
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds.max(0.0)).ok())
+        .ok_or_else(|| InvalidNumber(raw.to_string()))
+}
+
+/// Parses the base time of `level`, given in minutes like `5` or `0:30`
+fn parse_base(raw: &str) -> XboardResult<Duration> {
+    let mut parts = raw.splitn(2, ':');
+    let minutes: u64 = number(parts.next().unwrap_or_default())?;
+    let seconds: u64 = parts.next().map(number).transpose()?.unwrap_or(0);
+    minutes
+        .checked_mul(60)
+        .and_then(|minutes| minutes.checked_add(seconds))
+        .map(Duration::from_secs)
+        .ok_or_else(|| InvalidNumber(raw.to_string()))
+}
+
+/// Parses the clocks of `time` and `otim`, which are in centiseconds and may be negative once the time is up
+fn centiseconds(raw: &str) -> XboardResult<Duration> {
+    let n: i64 = number(raw)?;
+    (n.max(0) as u64)
+        .checked_mul(10)
+        .map(Duration::from_millis)
+        .ok_or_else(|| InvalidNumber(raw.to_string()))
+}
+
+/**
+Writes the `setup` command describing the variant of `game` to the GUI:
+its pieces' letters, its size and holdings, the variant it derives from and its starting position.
+Variants with drops derive from shogi, and the others from fairy chess.
+**/
+pub fn setup_line(game: &Game) -> String {
+    let letters: String = PIECE_TYPES
+        .chars()
+        .map(|c| {
+            if game.pieces().iter().any(|piece| piece.letter() == Some(c)) {
+                c
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    let drops = game.rules().contains(&Rule::Drops);
+    let holdings = if drops {
+        game.pieces()
+            .iter()
+            .enumerate()
+            .filter(|&(index, piece)| {
+                piece.letter().is_some() && game.demoted(index) == index && !game.is_royal(index)
+            })
+            .count()
+    } else {
+        0
+    };
+    let fen = if drops {
+        game.sfen().ok()
+    } else {
+        game.fen().ok()
+    };
+    format!(
+        "setup ({}{}) {}x{}+{}_{} {}",
+        letters,
+        letters.to_lowercase(),
+        game.board().width.get(),
+        game.board().height.get(),
+        holdings,
+        if drops { "shogi" } else { "fairy" },
+        fen.unwrap_or_default()
+    )
+}
+
+/// Writes the `piece` commands telling the GUI how each piece with a letter moves, in Betza's notation
+pub fn piece_lines(game: &Game) -> Vec<String> {
+    game.pieces()
+        .iter()
+        .filter_map(|piece| {
+            let betza: String = piece.movement_type().iter().map(|mv| mv.betza()).collect();
+            Some(format!("piece {}& {}", piece.letter()?, betza))
+        })
+        .collect()
+}
+
+/// Writes the line reporting a search's progress: depth, score, time in centiseconds, nodes and principal variation
+pub fn thinking_line(game: &Game, info: &SearchInfo) -> String {
+    // mates are reported as 100000 plus the number of moves
+    let score = match info.mate() {
+        Some(moves) if moves > 0 => 100000 + moves,
+        Some(moves) => -100000 + moves,
+        None => info.score,
+    };
+    let mut res = format!(
+        "{} {} {} {}",
+        info.depth,
+        score,
+        info.time.as_millis() / 10,
+        info.nodes
+    );
+    let mut game = game.clone();
+    for mv in &info.pv {
+        res.push(' ');
+        res.push_str(&game.uci_move(mv));
+        if game.play(mv).is_none() {
+            break;
+        }
+    }
+    res
+}
+
+/// Writes the result of the game if it is over, like `1-0 {White mates}`
+pub fn result_line(game: &Game) -> Option<String> {
+    Some(match game.result()? {
+        Outcome::Win(PlayerColor::White) => "1-0 {White mates}".to_string(),
+        Outcome::Win(PlayerColor::Black) => "0-1 {Black mates}".to_string(),
+        Outcome::Draw => "1/2-1/2 {Stalemate}".to_string(),
+    })
+}
+
+impl fmt::Display for XboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Empty => write!(f, "empty command"),
+            UnknownCommand(command) => write!(f, "unknown command {}", command),
+            MissingArgument(argument) => write!(f, "missing argument: {}", argument),
+            InvalidNumber(raw) => write!(f, "invalid number {}", raw),
+        }
+    }
+}
+
+impl Error for XboardError {}
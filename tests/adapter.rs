@@ -0,0 +1,100 @@
+use sharmat::adapter::*;
+use sharmat::engine::*;
+use sharmat::game::*;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn variants_dir() -> PathBuf {
+    PathBuf::from(format!("{}/variants", env!("CARGO_MANIFEST_DIR")))
+}
+
+/// Starts a search of the starting position, which sends its best move through the returned channel
+fn start(limits: SearchLimits, released: bool) -> (Search, mpsc::Receiver<Option<String>>) {
+    let game = load_variant(&variants_dir(), "standard").unwrap();
+    let engine = build_engine(&game, 1, DEFAULT_MOBILITY);
+    let (sender, receiver) = mpsc::channel();
+    let search = Search::start(
+        engine,
+        game,
+        limits,
+        released,
+        |_: &Game, _: &SearchInfo| {},
+        move |game: &Game, info: &SearchInfo| {
+            let _ = sender.send(info.best_move().map(|mv| game.uci_move(mv)));
+        },
+    );
+    (search, receiver)
+}
+
+#[test]
+fn adapter_variants() {
+    assert_eq!(
+        variant_names(&variants_dir()),
+        vec!["shogi".to_string(), "standard".to_string()]
+    );
+    assert_eq!(
+        load_variant(&variants_dir(), "shogi")
+            .unwrap()
+            .board()
+            .name(),
+        "Shogi"
+    );
+    assert!(load_variant(&variants_dir(), "missing").is_err());
+    assert!(variant_names(&variants_dir().join("missing")).is_empty());
+}
+
+#[test]
+fn adapter_search() {
+    let (search, moves) = start(
+        SearchLimits {
+            depth: Some(2),
+            ..SearchLimits::default()
+        },
+        true,
+    );
+    assert!(moves.recv().unwrap().is_some());
+    let (_, info) = search.join().unwrap();
+    assert_eq!(info.depth, 2);
+
+    // an infinite search waits to be stopped
+    let (search, moves) = start(SearchLimits::default(), false);
+    thread::sleep(Duration::from_millis(100));
+    assert!(moves.try_recv().is_err());
+    search.stop();
+    assert!(moves.recv().unwrap().is_some());
+    assert!(search.join().is_some());
+}
+
+#[test]
+fn adapter_ponder() {
+    let clock = Clock {
+        remaining: Duration::from_secs(3),
+        ..Clock::default()
+    };
+    let (search, moves) = start(
+        SearchLimits {
+            clock: Some(clock),
+            ponder: true,
+            ..SearchLimits::default()
+        },
+        false,
+    );
+    // about 100ms are spent on the move, but only from the ponder hit
+    thread::sleep(Duration::from_millis(400));
+    assert!(moves.try_recv().is_err());
+    let hit = Instant::now();
+    search.ponder_hit();
+    assert!(moves
+        .recv_timeout(Duration::from_secs(2))
+        .unwrap()
+        .is_some());
+    assert!(hit.elapsed() >= Duration::from_millis(20));
+    let (engine, _) = search.join().unwrap();
+
+    // the flags don't outlive the search
+    assert!(!engine.stop_handle().load(Ordering::Relaxed));
+    assert!(!engine.ponder_handle().load(Ordering::Relaxed));
+}
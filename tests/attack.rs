@@ -0,0 +1,100 @@
+use sharmat::attack::*;
+use sharmat::game::*;
+use sharmat::player::*;
+use sharmat::variant::*;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+#[test]
+fn attack_start_position() {
+    let game = load("standard");
+    let map = game.attack_map();
+    // d2 and f2 pawns
+    assert_eq!(map.count(4, 2, PlayerColor::White), 2);
+    // e2 and g2 pawns, g1 knight
+    assert_eq!(map.count(5, 2, PlayerColor::White), 3);
+    // pawn pushes don't attack
+    assert!(!map.is_attacked(4, 3, PlayerColor::White));
+    assert!(map.quiet_targets(PlayerColor::White).get(4, 3));
+    assert!(!map.quiet_targets(PlayerColor::White).get(4, 4));
+    assert_eq!(map.attacked_by(PlayerColor::White).count(), 22);
+    assert_eq!(map.quiet_targets(PlayerColor::White).count(), 16);
+
+    // f7 is only defended by the king
+    let king = game.piece_index("standard.king").unwrap();
+    let defenders: Vec<&Attacker> = map.attackers_by(5, 6, PlayerColor::Black).collect();
+    assert_eq!(
+        defenders,
+        vec![&Attacker {
+            from: (4, 7),
+            piece: king,
+            color: PlayerColor::Black,
+        }]
+    );
+    assert!(map.attackers_by(5, 6, PlayerColor::White).next().is_none());
+}
+
+#[test]
+fn attack_sliders_stop_at_blockers() {
+    let mut game = load("standard");
+    game.load_fen("4k3/8/8/8/1p1Q2P1/8/8/4K3 w - - 0 1")
+        .unwrap();
+    let map = game.attack_map();
+    let queen = game.piece_index("standard.queen").unwrap();
+    // the blockers are attacked, whatever their color, but not what stands behind them
+    for &(x, y, attacked) in &[(1, 3, true), (0, 3, false), (6, 3, true), (7, 3, false)] {
+        assert_eq!(
+            map.attackers(x, y).iter().any(|a| a.piece == queen),
+            attacked,
+            "{:?}",
+            (x, y)
+        );
+    }
+    // the black king is not in check
+    assert!(!map.is_attacked(4, 7, PlayerColor::White));
+    assert!(map.is_attacked(3, 7, PlayerColor::White));
+    assert!(map.is_attacked(0, 2, PlayerColor::Black));
+}
+
+#[test]
+fn attack_map_matches_checks() {
+    let mut game = load("standard");
+    game.load_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+        .unwrap();
+    let map = game.attack_map();
+    for y in 0..8 {
+        for x in 0..8 {
+            for &color in &[PlayerColor::White, PlayerColor::Black] {
+                assert_eq!(
+                    map.is_attacked(x, y, color),
+                    game.is_attacked(x, y, color),
+                    "{:?} by {:?}",
+                    (x, y),
+                    color
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn attack_shogi() {
+    let game = load("shogi");
+    let map = game.attack_map();
+    // shogi pawns capture the way they move
+    assert!(map.is_attacked(4, 3, PlayerColor::White));
+    assert!(map.quiet_targets(PlayerColor::White).is_empty());
+    // the rooks defend the pawns in front of them, on 2g and 8c
+    assert_eq!(map.count(7, 2, PlayerColor::White), 1);
+    assert_eq!(map.count(1, 6, PlayerColor::Black), 1);
+    assert!(!map.is_attacked(7, 4, PlayerColor::White));
+}
@@ -0,0 +1,117 @@
+use sharmat::betza::*;
+use sharmat::board::*;
+use sharmat::game::*;
+use sharmat::movement::*;
+use sharmat::player::*;
+use sharmat::variant::*;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn betza(game: &Game, piece: &str) -> String {
+    game.pieces()[game.piece_index(piece).unwrap()].movement_type()[0].betza()
+}
+
+/// Checks that two movements reach the same squares from every square of an empty board, for both colors
+fn assert_same_moves(game: &Game, a: &MovementType, b: &MovementType) {
+    let board = &Board::new(game.board().width, game.board().height);
+    for &color in &[PlayerColor::White, PlayerColor::Black] {
+        let player = Player::new(color);
+        for y in 0..board.height.get() {
+            for x in 0..board.width.get() {
+                let expected = a.moves(board, &player, 0, x, y);
+                let actual = b.moves(board, &player, 0, x, y);
+                assert_eq!(actual, expected, "{:?} on {:?}", color, (x, y));
+            }
+        }
+    }
+}
+
+#[test]
+fn betza_write() {
+    let chess = load("standard");
+    assert_eq!(betza(&chess, "K"), "K");
+    assert_eq!(betza(&chess, "Q"), "Q");
+    assert_eq!(betza(&chess, "R"), "R");
+    assert_eq!(betza(&chess, "B"), "B");
+    assert_eq!(betza(&chess, "N"), "N");
+    assert_eq!(betza(&chess, "P"), "mfWcfFimfW2");
+
+    let shogi = load("shogi");
+    assert_eq!(betza(&shogi, "G"), "WfF");
+    assert_eq!(betza(&shogi, "S"), "fWF");
+    assert_eq!(betza(&shogi, "N"), "ffN");
+    assert_eq!(betza(&shogi, "L"), "fR");
+    assert_eq!(betza(&shogi, "+R"), "RF");
+    assert_eq!(betza(&shogi, "+B"), "WB");
+}
+
+#[test]
+fn betza_round_trip() {
+    for &name in &["standard", "shogi"] {
+        let game = load(name);
+        for piece in game.pieces() {
+            let movement = &piece.movement_type()[0];
+            let parsed = MovementType::from_betza(&movement.betza()).unwrap();
+            assert_same_moves(&game, movement, &parsed);
+        }
+    }
+}
+
+#[test]
+fn betza_parse() {
+    let game = load("standard");
+    let knight = &game.pieces()[game.piece_index("N").unwrap()].movement_type()[0];
+    assert_same_moves(&game, knight, &MovementType::Undirected(2, 1));
+    assert_same_moves(
+        &game,
+        &MovementType::from_betza("fmWfcF").unwrap(),
+        &MovementType::from_betza("cfFmfW").unwrap(),
+    );
+    // the rook, written in several ways
+    let rook = MovementType::RangeAny(Box::new(MovementType::Undirected(1, 0)));
+    for text in &["R", "WW", "W0", "vRsR"] {
+        assert_same_moves(&game, &rook, &MovementType::from_betza(text).unwrap());
+    }
+    assert_same_moves(
+        &game,
+        &MovementType::from_betza("W3").unwrap(),
+        &MovementType::Range(Box::new(MovementType::Undirected(1, 0)), 3),
+    );
+    // the narrow and wide forward knight moves
+    assert_same_moves(
+        &game,
+        &MovementType::from_betza("ffNrrfNllfN").unwrap(),
+        &MovementType::from_betza("fN").unwrap(),
+    );
+    assert_eq!(MovementType::from_betza("fN").unwrap().betza(), "fN");
+}
+
+#[test]
+fn betza_errors() {
+    assert_eq!(MovementType::from_betza("").err(), Some(BetzaError::Empty));
+    assert_eq!(
+        MovementType::from_betza("fm").err(),
+        Some(BetzaError::Empty)
+    );
+    assert_eq!(
+        MovementType::from_betza("X").err(),
+        Some(BetzaError::UnknownAtom('X'))
+    );
+    assert_eq!(
+        MovementType::from_betza("pR").err(),
+        Some(BetzaError::UnsupportedModifier('p'))
+    );
+    assert_eq!(
+        MovementType::from_betza("ffF").err(),
+        Some(BetzaError::InvalidDirection("ff".to_string()))
+    );
+}
@@ -0,0 +1,183 @@
+use sharmat::bitboard::*;
+use sharmat::board::*;
+use sharmat::game::*;
+use sharmat::movement::*;
+use sharmat::player::*;
+use sharmat::variant::*;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+/// Checks that the attack tables of every piece give the same targets as `MovementType::moves`, on every square
+fn check_tables(game: &Game) {
+    let board = game.board();
+    let (width, height) = (board.width.get(), board.height.get());
+    for piece in game.pieces() {
+        for &color in &[PlayerColor::White, PlayerColor::Black] {
+            let table = AttackTable::new(piece.movement_type(), color, width, height);
+            assert!(table.is_exact());
+            let player = Player::new(color);
+            for y in 0..height {
+                for x in 0..width {
+                    let own = board.occupied_by(color);
+                    let expected: Vec<(usize, usize)> = piece.movement_type()[0]
+                        .moves(board, &player, 0, x, y)
+                        .into_iter()
+                        .map(|mv| mv.to)
+                        .collect();
+                    let mut targets: Vec<(usize, usize)> = table
+                        .targets(x, y, board.occupied(), own)
+                        .squares()
+                        .collect();
+                    targets.sort();
+                    assert_eq!(targets, expected, "{} on {:?}", piece.id(), (x, y));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn bitboard_squares() {
+    let mut bb = Bitboard::EMPTY;
+    assert!(bb.is_empty());
+    bb.set(3, 0);
+    bb.set(15, 15);
+    bb.set(0, 4);
+    assert!(bb.get(15, 15));
+    assert!(!bb.get(4, 3));
+    assert_eq!(bb.count(), 3);
+    assert_eq!(bb.first(), Some(3));
+    assert_eq!(bb.last(), Some(255));
+    assert_eq!(
+        bb.squares().collect::<Vec<_>>(),
+        vec![(3, 0), (0, 4), (15, 15)]
+    );
+    bb.clear(3, 0);
+    assert_eq!(bb.first(), Some(64));
+}
+
+#[test]
+fn bitboard_operations() {
+    let a = Bitboard::from_square(1, 1) | Bitboard::from_square(2, 2);
+    let b = Bitboard::from_square(2, 2) | Bitboard::from_square(9, 12);
+    assert_eq!(a & b, Bitboard::from_square(2, 2));
+    assert_eq!((a | b).count(), 3);
+    assert_eq!((a & !b), Bitboard::from_square(1, 1));
+    assert_eq!((!Bitboard::EMPTY).count(), 256);
+}
+
+#[test]
+fn bitboard_board_occupancy() {
+    let mut game = load("standard");
+    assert_eq!(game.board().occupied().count(), 32);
+    assert_eq!(game.board().occupied_by(PlayerColor::White).count(), 16);
+    assert!(game.board().occupied_by(PlayerColor::Black).get(4, 7));
+
+    game.board_mut().move_piece(4, 1, 4, 3).unwrap();
+    assert!(!game.board().occupied().get(4, 1));
+    assert!(game.board().occupied_by(PlayerColor::White).get(4, 3));
+    game.board_mut().clear_pos(4, 7).unwrap();
+    assert_eq!(game.board().occupied_by(PlayerColor::Black).count(), 15);
+    game.board_mut().clear();
+    assert!(game.board().occupied().is_empty());
+}
+
+#[test]
+fn bitboard_tables_chess() {
+    let mut game = load("standard");
+    check_tables(&game);
+    game.load_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+        .unwrap();
+    check_tables(&game);
+}
+
+#[test]
+fn bitboard_tables_shogi() {
+    let mut game = load("shogi");
+    check_tables(&game);
+    game.load_sfen("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1")
+        .unwrap();
+    check_tables(&game);
+}
+
+#[test]
+fn bitboard_large_board() {
+    let variant: Variant = r#"
+        name = "Large"
+        width = 16
+        height = 16
+
+        [[piece]]
+        id = "large.rook"
+        alias = ["rook", "R"]
+        movement = { range_any = { undirected = [1, 0] } }
+
+        [[piece]]
+        id = "large.knight"
+        alias = ["knight", "N"]
+        movement = { undirected = [2, 1] }
+
+        [[setup]]
+        piece = "rook"
+        color = "white"
+        squares = [[15, 15]]
+
+        [[setup]]
+        piece = "knight"
+        color = "black"
+        squares = [[15, 3]]
+    "#
+    .parse()
+    .unwrap();
+    let game = variant.game().unwrap();
+    check_tables(&game);
+    // 15 squares along the rank, and 12 down the file up to the knight
+    assert_eq!(game.legal_moves().len(), 27);
+
+    // larger boards are played from their array of squares
+    let larger = Variant {
+        width: 20,
+        height: 20,
+        ..variant
+    };
+    let mut game = larger.game().unwrap();
+    assert!(!game.board().has_bitboards());
+    assert!(game.board().occupied().is_empty());
+    // 19 squares along the rank, 4 up the file, and 12 down the file up to the knight
+    assert_eq!(game.legal_moves().len(), 35);
+    assert!(game.is_attacked(15, 3, PlayerColor::White));
+    assert_eq!(game.attack_map().count(15, 3, PlayerColor::White), 1);
+    let key = game.position_key();
+    let capture = game
+        .legal_moves()
+        .into_iter()
+        .find(|mv| mv.to == (15, 3))
+        .unwrap();
+    game.play(&capture).unwrap();
+    assert_ne!(game.position_key(), key);
+    assert_eq!(game.board().squares_of(PlayerColor::White), vec![(15, 3)]);
+    assert!(game.board().squares_of(PlayerColor::Black).is_empty());
+}
+
+fn always(_: &Board, _: &Player, _: usize, _: usize, _: isize, _: isize) -> bool {
+    true
+}
+
+#[test]
+fn bitboard_custom_condition() {
+    let movement = MovementType::Condition(
+        Box::new(MovementType::Undirected(1, 0)),
+        vec![MovementCondition::Custom(&always)],
+    );
+    let table = AttackTable::new(&[movement], PlayerColor::White, 8, 8);
+    assert!(!table.is_exact());
+}
@@ -0,0 +1,129 @@
+use sharmat::board::*;
+use sharmat::coordinates::*;
+use sharmat::player::PlayerColor::*;
+use sharmat::variant::*;
+use std::num::NonZeroUsize;
+
+#[test]
+fn coordinates_algebraic() {
+    let c = Coordinates::Algebraic;
+    assert_eq!(c.square_name((4, 3), 8, 8, White), "e4");
+    assert_eq!(c.file_name(25, 30, White), "z");
+    assert_eq!(c.file_name(26, 30, White), "aa");
+    assert_eq!(c.file_name(27, 30, White), "ab");
+    assert_eq!(c.file_name(52, 60, White), "ba");
+    assert_eq!(c.square_name((27, 15), 30, 16, Black), "ab16");
+    assert_eq!(c.parse_square("e4", 8, 8, White), Some((4, 3)));
+    assert_eq!(c.parse_square("ab16", 30, 16, White), Some((27, 15)));
+    assert_eq!(c.parse_square("i1", 8, 8, White), None);
+    assert_eq!(c.parse_square("a9", 8, 8, White), None);
+    assert_eq!(c.parse_square("a0", 8, 8, White), None);
+    assert_eq!(c.parse_square("4e", 8, 8, White), None);
+}
+
+#[test]
+fn coordinates_shogi() {
+    let kanji = Coordinates::Kanji;
+    assert_eq!(kanji.square_name((2, 3), 9, 9, White), "７六");
+    assert_eq!(kanji.square_name((8, 8), 9, 9, Black), "１一");
+    assert_eq!(kanji.parse_square("７六", 9, 9, White), Some((2, 3)));
+    assert_eq!(kanji.parse_square("7六", 9, 9, White), Some((2, 3)));
+    assert_eq!(kanji.square_name((0, 0), 12, 12, White), "１２十二");
+    assert_eq!(kanji.parse_square("１２十二", 12, 12, White), Some((0, 0)));
+    assert_eq!(kanji.parse_square("０一", 9, 9, White), None);
+
+    let letters = Coordinates::Shogi;
+    assert_eq!(letters.square_name((2, 3), 9, 9, White), "7f");
+    assert_eq!(letters.square_name((8, 8), 9, 9, White), "1a");
+    assert_eq!(letters.parse_square("7f", 9, 9, White), Some((2, 3)));
+    assert_eq!(letters.parse_square("7j", 9, 9, White), None);
+}
+
+#[test]
+fn coordinates_xiangqi() {
+    let c = Coordinates::Xiangqi;
+    // the same square, as seen by each player
+    assert_eq!(c.square_name((1, 2), 9, 10, White), "8-3");
+    assert_eq!(c.square_name((1, 2), 9, 10, Black), "2-8");
+    assert_eq!(c.parse_square("8-3", 9, 10, White), Some((1, 2)));
+    assert_eq!(c.parse_square("2-8", 9, 10, Black), Some((1, 2)));
+    assert_eq!(c.parse_square("10-1", 9, 10, White), None);
+}
+
+#[test]
+fn coordinates_round_trip() {
+    for c in &[
+        Coordinates::Algebraic,
+        Coordinates::Kanji,
+        Coordinates::Shogi,
+        Coordinates::Xiangqi,
+    ] {
+        for &color in &[White, Black] {
+            for x in 0..30 {
+                for y in 0..28 {
+                    let name = c.square_name((x, y), 30, 28, color);
+                    assert_eq!(
+                        c.parse_square(&name, 30, 28, color),
+                        Some((x, y)),
+                        "{}",
+                        name
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn coordinates_variant() {
+    let game = Variant::load(format!(
+        "{}/variants/shogi.toml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+    .game()
+    .unwrap();
+    assert_eq!(game.coordinates(), Coordinates::Kanji);
+    assert_eq!(game.square_name((2, 3)), "７六");
+    assert_eq!(game.parse_square("５九"), Some((4, 0)));
+
+    let variant: Variant = r#"
+        name = "Tiny"
+        width = 3
+        height = 3
+        coordinates = "shogi"
+
+        [[piece]]
+        id = "tiny.king"
+        alias = ["K"]
+        movement = { undirected = [1, 0] }
+
+        [[setup]]
+        piece = "K"
+        color = "white"
+        squares = ["2c", [0, 2]]
+    "#
+    .parse()
+    .unwrap();
+    let game = variant.game().unwrap();
+    assert!(game.board().get(1, 0).unwrap().is_some());
+    assert!(game.board().get(0, 2).unwrap().is_some());
+    let mv = game.legal_moves()[0].clone();
+    assert!(game.move_name(&mv).starts_with("2c-"));
+
+    let mut variant = variant;
+    variant.setup[0].squares = vec![SquareDef::Name(String::from("4a"))];
+    match variant.game() {
+        Err(VariantError::InvalidSquare(name)) => assert_eq!(name, "4a"),
+        x => panic!("Expected InvalidSquare, got {:?}", x),
+    }
+}
+
+#[test]
+fn coordinates_error_messages() {
+    let board = Board::new(NonZeroUsize::new(8).unwrap(), NonZeroUsize::new(8).unwrap());
+    assert_eq!(
+        board.get(8, 0).unwrap_err().to_string(),
+        "square i1 is out of the board"
+    );
+}
@@ -0,0 +1,105 @@
+use sharmat::csa::*;
+use sharmat::game::*;
+use sharmat::record::*;
+use sharmat::variant::*;
+
+fn shogi() -> Game {
+    Variant::load(format!(
+        "{}/variants/shogi.toml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn sample(name: &str) -> String {
+    std::fs::read_to_string(format!(
+        "{}/tests/records/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+#[test]
+fn csa_read() {
+    let game = shogi();
+    let record = Record::from_csa(&game, &sample("game.csa")).unwrap();
+    assert_eq!(record.sente.as_deref(), Some("Alice"));
+    assert_eq!(record.gote.as_deref(), Some("Bob"));
+    assert_eq!(
+        record.headers[0],
+        (String::from("EVENT"), String::from("Practice"))
+    );
+    assert_eq!(record.end, Some(GameEnd::Resignation));
+
+    // same game as the KIF sample
+    let kif = Record::from_kif(&game, &sample("game.kif")).unwrap();
+    assert_eq!(record.moves, kif.moves);
+}
+
+#[test]
+fn csa_round_trip() {
+    let game = shogi();
+    let record = Record::from_csa(&game, &sample("game.csa")).unwrap();
+    let csa = record.csa(&game).unwrap();
+    assert!(csa.starts_with("V2.2\nN+Alice\nN-Bob\n$EVENT:Practice\n"));
+    assert!(csa.contains("PI\n+\n+7776FU\nT3\n"));
+    assert!(csa.contains("+8822UM\nT5\n-3122GI\nT61\n+0045KA\nT10\n%TORYO\n"));
+    assert_eq!(Record::from_csa(&game, &csa).unwrap(), record);
+}
+
+#[test]
+fn csa_position() {
+    let game = shogi();
+    let record = Record::from_csa(&game, &sample("position.csa")).unwrap();
+    assert_eq!(
+        record.sfen.as_deref(),
+        Some("ln1gk3l/1r4g2/p1pppps1p/6pp1/1p6P/2PP5/PPS1PPPP1/2G3GR1/LN1K3NL w BSbsnp 1")
+    );
+    assert_eq!(record.moves.len(), 2);
+    assert_eq!(record.moves[0].mv.from, None);
+    assert_eq!(record.end, Some(GameEnd::Interruption));
+
+    let mut played = shogi();
+    record.play(&mut played).unwrap();
+    let csa = record.csa(&game).unwrap();
+    assert!(csa.contains("P1-KY-KE * -KI-OU *  *  * -KY\n"));
+    assert!(csa.contains("P+00KA00GI\n"));
+    assert_eq!(Record::from_csa(&game, &csa).unwrap(), record);
+}
+
+#[test]
+fn csa_handicap() {
+    let game = shogi();
+    let record = Record::from_csa(&game, "PI11KY\n-\n-3334FU\n+7776FU\n").unwrap();
+    let kif = Record::from_kif(&game, &sample("handicap.kif")).unwrap();
+    assert_eq!(record.sfen, kif.sfen);
+    assert_eq!(record.moves, kif.moves);
+}
+
+#[test]
+fn csa_errors() {
+    let game = shogi();
+    assert_eq!(
+        Record::from_csa(&game, "+\n+7775FU\n"),
+        Err(CsaError::IllegalMove(String::from("+7775FU")))
+    );
+    assert_eq!(
+        Record::from_csa(&game, "+\n+7776XX\n"),
+        Err(CsaError::UnknownPiece(String::from("XX")))
+    );
+    assert_eq!(
+        Record::from_csa(&game, "+7776FU\n"),
+        Err(CsaError::InvalidLine(String::from("+7776FU")))
+    );
+    assert_eq!(
+        Record::from_csa(&game, "Q\n"),
+        Err(CsaError::InvalidLine(String::from("Q")))
+    );
+    // truncated or non-ASCII board rows
+    assert!(Record::from_csa(&game, "V2.2\nP\n").is_err());
+    assert!(Record::from_csa(&game, "V2.2\nP一\n").is_err());
+    assert!(Record::from_csa(&game, "V2.2\nP1一FU\n").is_err());
+}
@@ -0,0 +1,191 @@
+use sharmat::driver::*;
+use sharmat::engine::*;
+use sharmat::game::*;
+use sharmat::variant::*;
+use std::time::Duration;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn scripted(protocol: Protocol) -> EngineDriver {
+    DriverBuilder::new()
+        .command("sh")
+        .arg(&format!(
+            "{}/tests/engines/scripted.sh",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .protocol(protocol)
+        .option("Hash", "16")
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap()
+}
+
+fn play(game: &mut Game, moves: &[&str]) {
+    for text in moves {
+        let mv = game.parse_uci_move(text).unwrap();
+        game.play(&mv);
+    }
+}
+
+#[test]
+fn driver_info() {
+    let mut game = load("standard");
+    let info = parse_info(
+        &game,
+        Protocol::Uci,
+        "info depth 12 seldepth 20 score cp 35 nodes 123456 nps 1000 pv e2e4 e7e5 g1f3",
+    )
+    .unwrap();
+    assert_eq!(info.depth, Some(12));
+    assert_eq!(info.score, Some(Score::Centipawns(35)));
+    assert_eq!(info.nodes, Some(123456));
+    let pv: Vec<String> = info.pv.iter().map(|mv| game.uci_move(mv)).collect();
+    assert_eq!(pv, vec!["e2e4", "e7e5", "g1f3"]);
+
+    // the principal variation stops at its first illegal move
+    let info = parse_info(&game, Protocol::Uci, "info score mate -2 pv e2e4 e2e4").unwrap();
+    assert_eq!(info.score, Some(Score::Mate(-2)));
+    assert_eq!(info.pv.len(), 1);
+
+    assert_eq!(
+        parse_info(&game, Protocol::Uci, "info string score cp 10"),
+        None
+    );
+    assert_eq!(
+        parse_info(&game, Protocol::Uci, "info depth 3 nodes 10"),
+        None
+    );
+
+    play(&mut game, &["e2e4"]);
+    let info = parse_info(&game, Protocol::Uci, "info depth 1 pv e7e5").unwrap();
+    assert_eq!(game.uci_move(&info.pv[0]), "e7e5");
+
+    let shogi = load("shogi");
+    let info = parse_info(
+        &shogi,
+        Protocol::Usi,
+        "info depth 5 score mate + pv 7g7f 3c3d",
+    )
+    .unwrap();
+    assert_eq!(info.score, Some(Score::Mate(1)));
+    assert_eq!(info.pv.len(), 2);
+}
+
+#[test]
+fn driver_uci() {
+    let mut driver = scripted(Protocol::Uci);
+    assert_eq!(driver.name(), Some("Scripted"));
+    assert_eq!(driver.author(), Some("Sharmat"));
+    driver.new_game().unwrap();
+
+    let mut game = load("standard");
+    let limits = SearchLimits {
+        depth: Some(2),
+        ..SearchLimits::default()
+    };
+    let mv = driver.best_move(&game, &limits).unwrap().unwrap();
+    assert_eq!(game.uci_move(&mv), "e2e4");
+    let info = driver.info().unwrap();
+    assert_eq!(info.depth, Some(2));
+    assert_eq!(info.score, Some(Score::Centipawns(15)));
+    assert!(!driver.is_searching());
+
+    // the engine is sent the game's moves
+    game.play(&mv);
+    driver.go(&game, &limits).unwrap();
+    match driver.next_event().unwrap() {
+        DriverEvent::Info(info) => assert_eq!(info.pv.len(), 2),
+        event => panic!("unexpected event {:?}", event),
+    }
+    match driver.next_event().unwrap() {
+        DriverEvent::BestMove(Some(mv)) => assert_eq!(game.uci_move(&mv), "e7e5"),
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert!(matches!(
+        driver.next_event(),
+        Err(DriverError::NotSearching)
+    ));
+
+    // the engine doesn't know this position, and answers with nonsense
+    play(&mut game, &["e7e5"]);
+    assert!(matches!(
+        driver.best_move(&game, &limits),
+        Err(DriverError::IllegalMove(_))
+    ));
+}
+
+#[test]
+fn driver_usi() {
+    let mut driver = scripted(Protocol::Usi);
+    assert_eq!(driver.name(), Some("Scripted"));
+    assert_eq!(driver.author(), None);
+
+    let mut game = load("shogi");
+    let mv = driver
+        .best_move(&game, &SearchLimits::default())
+        .unwrap()
+        .unwrap();
+    assert_eq!(game.usi_move(&mv), "7g7f");
+    game.play(&mv);
+    let mv = driver
+        .best_move(&game, &SearchLimits::default())
+        .unwrap()
+        .unwrap();
+    assert_eq!(game.usi_move(&mv), "3c3d");
+    assert_eq!(driver.info().unwrap().score, Some(Score::Mate(-3)));
+}
+
+#[test]
+fn driver_errors() {
+    assert!(matches!(
+        DriverBuilder::new().command("/nonexistent/engine").build(),
+        Err(DriverError::Spawn(_))
+    ));
+    // an engine that never answers
+    assert!(matches!(
+        DriverBuilder::new()
+            .command("sleep")
+            .arg("5")
+            .timeout(Duration::from_millis(100))
+            .build(),
+        Err(DriverError::Timeout)
+    ));
+    // an engine that hangs during a search, even once asked to stop
+    let mut driver = DriverBuilder::new()
+        .command("sh")
+        .arg(&format!(
+            "{}/tests/engines/scripted.sh",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let mut game = load("standard");
+    play(&mut game, &["d2d4"]);
+    let limits = SearchLimits {
+        movetime: Some(Duration::from_millis(100)),
+        ..SearchLimits::default()
+    };
+    assert!(matches!(
+        driver.best_move(&game, &limits),
+        Err(DriverError::Timeout)
+    ));
+    assert!(matches!(
+        driver.go(&game, &SearchLimits::default()),
+        Err(DriverError::Timeout)
+    ));
+    // an engine that quits at once
+    assert!(matches!(
+        DriverBuilder::new().command("true").build(),
+        Err(DriverError::Closed)
+    ));
+}
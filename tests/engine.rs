@@ -0,0 +1,179 @@
+use sharmat::engine::*;
+use sharmat::game::*;
+use sharmat::player::*;
+use sharmat::variant::*;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn depth(depth: usize) -> SearchLimits {
+    SearchLimits {
+        depth: Some(depth),
+        ..SearchLimits::default()
+    }
+}
+
+fn best_move_name(game: &Game, limits: &SearchLimits) -> String {
+    let mut engine = EngineBuilder::new().build(game);
+    let info = engine.search(game, limits);
+    game.move_name(info.best_move().unwrap())
+}
+
+#[test]
+fn engine_mate_in_one() {
+    let mut game = load("standard");
+    game.load_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1")
+        .unwrap();
+    let mut engine = EngineBuilder::new().build(&game);
+    let info = engine.search(&game, &depth(3));
+    assert_eq!(game.move_name(info.best_move().unwrap()), "a1-a8");
+    assert_eq!(info.mate(), Some(1));
+    assert_eq!(info.score, MATE - 1);
+}
+
+#[test]
+fn engine_wins_material() {
+    let mut game = load("standard");
+    game.load_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+    assert_eq!(best_move_name(&game, &depth(2)), "d2-d5");
+}
+
+#[test]
+fn engine_quiescence() {
+    // the pawn on d5 is defended, taking it loses the queen
+    let mut game = load("standard");
+    game.load_fen("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+    assert_ne!(best_move_name(&game, &depth(1)), "d1-d5");
+}
+
+#[test]
+fn engine_evaluation() {
+    for &name in &["standard", "shogi"] {
+        let game = load(name);
+        let engine = EngineBuilder::new().build(&game);
+        assert_eq!(engine.evaluate(&game), 0, "{}", name);
+    }
+
+    let mut game = load("standard");
+    game.load_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+    let engine = EngineBuilder::new().build(&game);
+    assert!(engine.evaluate(&game) < -500);
+}
+
+#[test]
+fn engine_piece_values() {
+    let game = load("standard");
+    let engine = EngineBuilder::new().build(&game);
+    let value = |name: &str| engine.piece_value(game.piece_index(name).unwrap());
+    assert_eq!(value("P"), 100);
+    assert_eq!(value("K"), 0);
+    assert!(value("N") > value("P"));
+    assert!(value("R") > value("B"));
+    assert!(value("Q") > value("R"));
+
+    let engine = EngineBuilder::new().piece_value("knight", 325).build(&game);
+    assert_eq!(engine.piece_value(game.piece_index("N").unwrap()), 325);
+}
+
+#[test]
+fn engine_position_key() {
+    let mut game = load("standard");
+    let start = game.position_key();
+    for &name in &["g1-f3", "g8-f6", "f3-g1", "f6-g8"] {
+        let mv = game
+            .legal_moves()
+            .into_iter()
+            .find(|mv| game.move_name(mv) == name)
+            .unwrap();
+        game.play(&mv).unwrap();
+        if name != "f6-g8" {
+            assert_ne!(game.position_key(), start);
+        }
+    }
+    assert_eq!(game.position_key(), start);
+    game.set_current_player(PlayerColor::Black);
+    assert_ne!(game.position_key(), start);
+
+    let mut shogi = load("shogi");
+    shogi.load_sfen("4k4/9/9/9/9/9/9/9/4K4 b P 1").unwrap();
+    let with_pawn = shogi.position_key();
+    shogi.load_sfen("4k4/9/9/9/9/9/9/9/4K4 b 2P 1").unwrap();
+    assert_ne!(shogi.position_key(), with_pawn);
+}
+
+#[test]
+fn engine_shogi() {
+    let game = load("shogi");
+    let mut engine = EngineBuilder::new().build(&game);
+    let info = engine.search(&game, &depth(2));
+    assert!(game.legal_moves().contains(info.best_move().unwrap()));
+    assert_eq!(info.depth, 2);
+}
+
+#[test]
+fn engine_stalemate_loses_with_drops() {
+    // the king has no move but isn't in check, which loses in shogi and draws in chess
+    let mut shogi = load("shogi");
+    shogi.load_sfen("1r6k/9/9/9/9/9/9/4r4/K8 b - 1").unwrap();
+    assert!(!shogi.is_check() && shogi.legal_moves().is_empty());
+    let mut engine = EngineBuilder::new().build(&shogi);
+    assert_eq!(engine.search(&shogi, &depth(1)).score, -MATE);
+
+    let mut game = load("standard");
+    game.load_fen("1r5k/8/8/8/8/8/4r3/K7 w - - 0 1").unwrap();
+    let mut engine = EngineBuilder::new().build(&game);
+    assert_eq!(engine.search(&game, &depth(1)).score, 0);
+}
+
+#[test]
+fn engine_time_limits() {
+    let game = load("standard");
+    let mut engine = EngineBuilder::new().build(&game);
+    let start = Instant::now();
+    let info = engine.search(
+        &game,
+        &SearchLimits {
+            movetime: Some(Duration::from_millis(100)),
+            ..SearchLimits::default()
+        },
+    );
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(game.legal_moves().contains(info.best_move().unwrap()));
+
+    let info = engine.search(
+        &game,
+        &SearchLimits {
+            nodes: Some(2000),
+            ..SearchLimits::default()
+        },
+    );
+    assert!(info.nodes <= 2000);
+    assert!(info.best_move().is_some());
+}
+
+#[test]
+fn engine_stop() {
+    let game = load("standard");
+    let mut engine = EngineBuilder::new().build(&game);
+    let stop = engine.stop_handle();
+    let stopper = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        stop.store(true, Ordering::Relaxed);
+    });
+    let mut iterations = 0;
+    let info = engine.search_with(&game, &SearchLimits::default(), |_| iterations += 1);
+    stopper.join().unwrap();
+    assert!(iterations >= 1);
+    assert!(info.best_move().is_some());
+}
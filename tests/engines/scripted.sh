@@ -0,0 +1,45 @@
+#!/bin/sh
+# A stand-in engine speaking UCI or USI, which answers the starting positions with scripted moves
+# and hangs after 1. d4
+
+position=""
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name Scripted"
+            echo "id author Sharmat"
+            echo "uciok"
+            ;;
+        usi)
+            echo "id name Scripted"
+            echo "usiok"
+            ;;
+        isready) echo "readyok" ;;
+        "position "*) position="$line" ;;
+        go*)
+            echo "info string $position"
+            case "$position" in
+                "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+                    echo "info depth 1 score cp 20 nodes 21 pv e2e4"
+                    echo "info depth 2 score cp 15 nodes 400 pv e2e4 e7e5"
+                    echo "bestmove e2e4 ponder e7e5"
+                    ;;
+                *" moves d2d4") ;;
+                *" moves e2e4")
+                    echo "info depth 1 score cp -15 nodes 30 pv e7e5 g1f3"
+                    echo "bestmove e7e5"
+                    ;;
+                "position sfen "*" moves 7g7f")
+                    echo "info depth 1 score mate -3 nodes 30 pv 3c3d"
+                    echo "bestmove 3c3d"
+                    ;;
+                "position sfen "*)
+                    echo "info depth 1 score cp 40 nodes 30 pv 7g7f 3c3d"
+                    echo "bestmove 7g7f"
+                    ;;
+                *) echo "bestmove z9z9" ;;
+            esac
+            ;;
+        quit) exit 0 ;;
+    esac
+done
@@ -0,0 +1,194 @@
+use sharmat::fen::*;
+use sharmat::game::*;
+use sharmat::player::*;
+use sharmat::variant::*;
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+fn standard() -> Game {
+    Variant::load(format!(
+        "{}/variants/standard.toml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+/// A 10x8 board with the standard pieces, plus an archbishop and a chancellor
+fn capablanca() -> Game {
+    let mut variant: Variant = std::fs::read_to_string(format!(
+        "{}/variants/standard.toml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+    .parse()
+    .unwrap();
+    variant.width = 10;
+    variant.fen = None;
+    let extra: Variant = r#"
+        name = "Capablanca"
+        width = 10
+        height = 8
+
+        [[piece]]
+        id = "capablanca.archbishop"
+        alias = ["A"]
+        movement = { union = [{ range_any = { undirected = [1, 1] } }, { undirected = [2, 1] }] }
+
+        [[piece]]
+        id = "capablanca.chancellor"
+        alias = ["C"]
+        movement = { union = [{ range_any = { undirected = [1, 0] } }, { undirected = [2, 1] }] }
+    "#
+    .parse()
+    .unwrap();
+    variant.pieces.extend(extra.pieces);
+    variant.game().unwrap()
+}
+
+#[test]
+fn fen_start_position() {
+    let game = standard();
+    assert_eq!(game.fen().unwrap(), START);
+    assert_eq!(
+        game.shredder_fen().unwrap(),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1"
+    );
+    assert_eq!(
+        game.castling(),
+        &vec![
+            (PlayerColor::White, 7),
+            (PlayerColor::White, 0),
+            (PlayerColor::Black, 7),
+            (PlayerColor::Black, 0),
+        ]
+    );
+}
+
+#[test]
+fn fen_round_trip() {
+    let mut game = standard();
+    for fen in &[
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq d6 0 2",
+        "8/8/8/8/8/8/8/4K2k b - - 42 73",
+    ] {
+        game.load_fen(fen).unwrap();
+        assert_eq!(&game.fen().unwrap(), fen);
+    }
+}
+
+#[test]
+fn fen_position() {
+    let mut game = standard();
+    game.load_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b Kq d6 3 12")
+        .unwrap();
+    assert_eq!(game.current_player().unwrap().color, PlayerColor::Black);
+    assert_eq!(game.en_passant(), Some((3, 5)));
+    assert_eq!(game.halfmove_clock(), 3);
+    assert_eq!(game.fullmove_number(), 12);
+    assert_eq!(
+        game.castling(),
+        &vec![(PlayerColor::White, 7), (PlayerColor::Black, 0)]
+    );
+    let pawn = game.board().get(4, 4).unwrap().unwrap();
+    assert_eq!(game.pieces()[pawn.0].id(), "standard.pawn");
+    assert_eq!(pawn.1, PlayerColor::White);
+    assert_eq!(game.board().get(4, 1).unwrap(), None);
+}
+
+#[test]
+fn fen_optional_clocks() {
+    let mut game = standard();
+    game.load_fen("4k3/8/8/8/8/8/8/4K3 w -").unwrap();
+    assert_eq!(game.fen().unwrap(), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+}
+
+#[test]
+fn fen_large_board() {
+    let mut game = capablanca();
+    let fen = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+    game.load_fen(fen).unwrap();
+    assert_eq!(game.fen().unwrap(), fen);
+    assert_eq!(
+        game.shredder_fen().unwrap(),
+        "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w JAja - 0 1"
+    );
+    let archbishop = game.board().get(2, 0).unwrap().unwrap();
+    assert_eq!(game.pieces()[archbishop.0].id(), "capablanca.archbishop");
+
+    game.load_fen("4k5/10/10/10/10/10/10/R3K4R w KQ - 0 1")
+        .unwrap();
+    assert_eq!(
+        game.castling(),
+        &vec![(PlayerColor::White, 9), (PlayerColor::White, 0)]
+    );
+}
+
+#[test]
+fn fen_x_fen_castling() {
+    let mut game = standard();
+    // Chess960 position with an extra rook on the king's side: the inner rook needs its file
+    game.load_fen("4k3/8/8/8/8/8/8/1R2K1RR w GB - 0 1").unwrap();
+    assert_eq!(
+        game.castling(),
+        &vec![(PlayerColor::White, 6), (PlayerColor::White, 1)]
+    );
+    assert_eq!(game.fen().unwrap(), "4k3/8/8/8/8/8/8/1R2K1RR w GQ - 0 1");
+    assert_eq!(
+        game.shredder_fen().unwrap(),
+        "4k3/8/8/8/8/8/8/1R2K1RR w GB - 0 1"
+    );
+}
+
+#[test]
+fn fen_errors() {
+    let mut game = standard();
+    assert_eq!(game.load_fen(""), Err(FenError::MissingField("placement")));
+    assert_eq!(
+        game.load_fen("8/8/8/8/8/8/8/8"),
+        Err(FenError::MissingField("color"))
+    );
+    assert!(matches!(
+        game.load_fen("8/8/8/8/8/8/8 w - - 0 1"),
+        Err(FenError::InvalidPlacement(_))
+    ));
+    assert!(matches!(
+        game.load_fen("9/8/8/8/8/8/8/8 w - - 0 1"),
+        Err(FenError::InvalidPlacement(_))
+    ));
+    assert!(matches!(
+        game.load_fen("12345678901234567890123/8/8/8/8/8/8/8 w - - 0 1"),
+        Err(FenError::InvalidPlacement(_))
+    ));
+    assert_eq!(
+        game.load_fen("7z/8/8/8/8/8/8/8 w - - 0 1"),
+        Err(FenError::UnknownPiece('z'))
+    );
+    assert_eq!(
+        game.load_fen("8/8/8/8/8/8/8/8 x - - 0 1"),
+        Err(FenError::InvalidColor(String::from("x")))
+    );
+    assert_eq!(
+        game.load_fen("8/8/8/8/8/8/8/4K3 w K - 0 1"),
+        Err(FenError::InvalidCastling('K'))
+    );
+    assert_eq!(
+        game.load_fen("8/8/8/8/8/8/8/8 w - z9 0 1"),
+        Err(FenError::InvalidSquare(String::from("z9")))
+    );
+    assert_eq!(
+        game.load_fen("8/8/8/8/8/8/8/8 w - - x 1"),
+        Err(FenError::InvalidNumber(String::from("x")))
+    );
+    assert_eq!(
+        game.load_fen("8/8/8/8/8/8/8/8 w - - 0 18446744073709551615"),
+        Err(FenError::InvalidNumber(String::from(
+            "18446744073709551615"
+        )))
+    );
+    // a failed load leaves the game untouched
+    assert_eq!(game.fen().unwrap(), START);
+}
@@ -0,0 +1,150 @@
+use sharmat::game::*;
+use sharmat::kif::*;
+use sharmat::movement::*;
+use sharmat::record::*;
+use sharmat::variant::*;
+use std::time::Duration;
+
+fn shogi() -> Game {
+    Variant::load(format!(
+        "{}/variants/shogi.toml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn sample(name: &str) -> String {
+    std::fs::read_to_string(format!(
+        "{}/tests/records/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+#[test]
+fn kif_read() {
+    let game = shogi();
+    let record = Record::from_kif(&game, &sample("game.kif")).unwrap();
+    assert_eq!(record.sente.as_deref(), Some("Alice"));
+    assert_eq!(record.gote.as_deref(), Some("Bob"));
+    assert_eq!(
+        record.headers,
+        vec![
+            (
+                String::from("開始日時"),
+                String::from("2020/10/10 10:00:00")
+            ),
+            (String::from("棋戦"), String::from("練習対局")),
+        ]
+    );
+    assert_eq!(record.sfen, None);
+    assert_eq!(record.end, Some(GameEnd::Resignation));
+    assert_eq!(
+        record.moves.iter().map(|mv| mv.time).collect::<Vec<_>>(),
+        [3, 2, 5, 61, 10]
+            .iter()
+            .map(|&s| Some(Duration::from_secs(s)))
+            .collect::<Vec<_>>()
+    );
+
+    let moves: Vec<&Move> = record.moves.iter().map(|mv| &mv.mv).collect();
+    assert_eq!(moves[0].from, Some((2, 2)));
+    assert_eq!(moves[0].to, (2, 3));
+    assert_eq!(moves[2].promotion, game.piece_index("shogi.pbishop"));
+    assert_eq!(moves[3].to, moves[2].to);
+    assert_eq!(moves[3].piece, game.piece_index("shogi.silver").unwrap());
+    assert_eq!(moves[4].from, None);
+
+    let mut played = shogi();
+    record.play(&mut played).unwrap();
+    assert_eq!(
+        played.sfen().unwrap(),
+        "lnsgkg1nl/1r5s1/pppppp1pp/6p2/5B3/2P6/PP1PPPPPP/7R1/LNSGKGSNL w b 6"
+    );
+}
+
+#[test]
+fn kif_round_trip() {
+    let game = shogi();
+    let record = Record::from_kif(&game, &sample("game.kif")).unwrap();
+    let kif = record.kif(&game).unwrap();
+    assert!(kif.contains("手合割：平手\n"));
+    assert!(kif.contains("   3 ２二角成(88)   ( 0:05/00:00:08)\n"));
+    assert!(kif.contains("   4 同　銀(31)   ( 1:01/00:01:03)\n"));
+    assert!(kif.contains("   5 ４五角打   ( 0:10/00:00:18)\n"));
+    assert!(kif.contains("   6 投了\n"));
+    assert_eq!(Record::from_kif(&game, &kif).unwrap(), record);
+}
+
+#[test]
+fn kif_handicap() {
+    let game = shogi();
+    let record = Record::from_kif(&game, &sample("handicap.kif")).unwrap();
+    assert_eq!(
+        record.sfen.as_deref(),
+        Some("lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1")
+    );
+    assert_eq!(record.sente.as_deref(), Some("Student"));
+    assert_eq!(record.gote.as_deref(), Some("Sensei"));
+    assert_eq!(record.moves.len(), 2);
+    assert_eq!(record.moves[0].time, None);
+    assert_eq!(record.end, Some(GameEnd::Interruption));
+    assert!(record.kif(&game).unwrap().contains("手合割：香落ち\n"));
+}
+
+#[test]
+fn kif_board_diagram() {
+    let mut game = shogi();
+    game.load_sfen("lnsgk2nl/1r4gs1/p1pppp1pp/1p4p2/7P1/2P6/PP1PPPP1P/1SG4R1/LN2KGSNL w Bb2p 1")
+        .unwrap();
+    let record = Record::from_game(&game);
+    let kif = record.kif(&game).unwrap();
+    assert!(kif.contains("後手の持駒：角　歩二\n"));
+    assert!(kif.contains("|v香v桂v銀v金v玉 ・ ・v桂v香|一\n"));
+    assert!(kif.contains("先手の持駒：角\n後手番\n"));
+    assert_eq!(Record::from_kif(&game, &kif).unwrap().sfen, record.sfen);
+}
+
+#[test]
+fn kif_from_game() {
+    let mut game = shogi();
+    for _ in 0..10 {
+        let mv = game.legal_moves()[0].clone();
+        game.play(&mv).unwrap();
+    }
+    let record = Record::from_game(&game);
+    assert_eq!(record.sfen, None);
+    assert_eq!(record.moves.len(), 10);
+    let read = Record::from_kif(&game, &record.kif(&game).unwrap()).unwrap();
+    let mut played = shogi();
+    read.play(&mut played).unwrap();
+    assert_eq!(played.sfen().unwrap(), game.sfen().unwrap());
+}
+
+#[test]
+fn kif_errors() {
+    let game = shogi();
+    assert_eq!(
+        Record::from_kif(&game, "手合割：九枚落ち\n"),
+        Err(KifError::UnknownHandicap(String::from("九枚落ち")))
+    );
+    assert_eq!(
+        Record::from_kif(&game, "1 ７五歩(77)\n"),
+        Err(KifError::IllegalMove(String::from("1 ７五歩(77)")))
+    );
+    assert_eq!(
+        Record::from_kif(&game, "1 同　歩(77)\n"),
+        Err(KifError::InvalidMove(String::from("1 同　歩(77)")))
+    );
+    assert!(matches!(
+        Record::from_kif(&game, "|v香|一\n1 ７六歩(77)\n"),
+        Err(KifError::InvalidBoard(_))
+    ));
+    // a time too long to count is left out
+    let record =
+        Record::from_kif(&game, "1 ７六歩(77)   (307445734561825861:00/00:00:00)\n").unwrap();
+    assert_eq!(record.moves[0].time, None);
+}
@@ -1,9 +1,8 @@
 use sharmat::board::*;
 use sharmat::game::*;
 use sharmat::movement::*;
-use sharmat::piece::*;
 use sharmat::player::*;
-use sharmat::rule::*;
+use sharmat::variant::*;
 use std::num::NonZeroUsize;
 
 fn assert_set_equal<T: PartialEq + std::fmt::Debug>(a: Vec<T>, b: Vec<T>) {
@@ -28,13 +27,21 @@ fn set_inclusion<T: PartialEq>(a: &Vec<T>, b: &Vec<T>) -> bool {
             .all(|a_elem| b.iter().find(|b_elem| a_elem == *b_elem).is_some())
 }
 
+/// Displacements of the moves of a piece standing on (x, y)
+fn displacements(moves: Vec<Move>, x: usize, y: usize) -> Vec<(isize, isize)> {
+    moves
+        .into_iter()
+        .map(|mv| (mv.to.0 as isize - x as isize, mv.to.1 as isize - y as isize))
+        .collect()
+}
+
 #[test]
 fn movement_undirected() {
     let knight_movement = MovementType::Undirected(2, 1);
     let board = Board::new(NonZeroUsize::new(8).unwrap(), NonZeroUsize::new(8).unwrap());
-    let player = Player::new(true);
+    let player = Player::new(PlayerColor::White);
     assert_set_equal(
-        knight_movement.flatten(&board, &player, 4, 4).unwrap(),
+        displacements(knight_movement.moves(&board, &player, 0, 4, 4), 4, 4),
         vec![
             (2, 1),
             (1, 2),
@@ -52,9 +59,9 @@ fn movement_undirected() {
 fn movement_directed() {
     let pawn_movement = MovementType::Directed(0, 1);
     let board = Board::new(NonZeroUsize::new(8).unwrap(), NonZeroUsize::new(8).unwrap());
-    let player = Player::new(true);
+    let player = Player::new(PlayerColor::White);
     assert_set_equal(
-        pawn_movement.flatten(&board, &player, 4, 4).unwrap(),
+        displacements(pawn_movement.moves(&board, &player, 0, 4, 4), 4, 4),
         vec![(0, 1)],
     );
 }
@@ -63,11 +70,9 @@ fn movement_directed() {
 fn movement_range() {
     let double_wazir_movement = MovementType::Range(Box::new(MovementType::Undirected(1, 0)), 2);
     let board = Board::new(NonZeroUsize::new(8).unwrap(), NonZeroUsize::new(8).unwrap());
-    let player = Player::new(true);
+    let player = Player::new(PlayerColor::White);
     assert_set_equal(
-        double_wazir_movement
-            .flatten(&board, &player, 4, 4)
-            .unwrap(),
+        displacements(double_wazir_movement.moves(&board, &player, 0, 4, 4), 4, 4),
         vec![
             (1, 0),
             (2, 0),
@@ -85,9 +90,9 @@ fn movement_range() {
 fn movement_range_any() {
     let rook_movement = MovementType::RangeAny(Box::new(MovementType::Undirected(1, 0)));
     let board = Board::new(NonZeroUsize::new(8).unwrap(), NonZeroUsize::new(8).unwrap());
-    let player = Player::new(true);
+    let player = Player::new(PlayerColor::White);
     assert_set_equal(
-        rook_movement.flatten(&board, &player, 4, 3).unwrap(),
+        displacements(rook_movement.moves(&board, &player, 0, 4, 3), 4, 3),
         vec![
             (1, 0),
             (2, 0),
@@ -106,3 +111,95 @@ fn movement_range_any() {
         ],
     );
 }
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn find(game: &Game, name: &str) -> Move {
+    game.legal_moves()
+        .into_iter()
+        .find(|mv| game.move_name(mv) == name)
+        .unwrap()
+}
+
+#[test]
+fn movement_moves() {
+    let game = load("standard");
+    let knight = game.board().get(1, 0).unwrap().unwrap();
+    assert_eq!(
+        game.piece_moves(1, 0),
+        vec![
+            Move::new((1, 0), (0, 2), knight.0, None),
+            Move::new((1, 0), (2, 2), knight.0, None),
+        ]
+    );
+    assert!(game.piece_moves(4, 4).is_empty());
+}
+
+#[test]
+fn movement_game_result() {
+    let mut game = load("standard");
+    assert_eq!(game.result(), None);
+    game.load_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+        .unwrap();
+    assert_eq!(game.result(), Some(Outcome::Win(PlayerColor::Black)));
+    game.load_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    assert_eq!(game.result(), Some(Outcome::Draw));
+}
+
+#[test]
+fn movement_captures() {
+    let mut game = load("standard");
+    game.load_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+    let pawn = game.piece_index("standard.pawn").unwrap();
+    let capture = find(&game, "e4-d5");
+    assert!(capture.is_capture());
+    assert_eq!(capture.captured, Some(pawn));
+    assert_eq!(capture.kind, MoveKind::Normal);
+    assert!(!find(&game, "e4-e5").is_capture());
+}
+
+#[test]
+fn movement_special_moves() {
+    let mut game = load("standard");
+    game.load_fen("4k3/8/8/3pP3/8/8/8/R3K2R w KQ d6 0 1")
+        .unwrap();
+    let en_passant = find(&game, "e5-d6");
+    assert_eq!(en_passant.kind, MoveKind::EnPassant((3, 4)));
+    assert_eq!(en_passant.en_passant(), Some((3, 4)));
+    assert!(en_passant.is_capture());
+    game.play(&en_passant).unwrap();
+    assert_eq!(game.board().get(3, 4).unwrap(), None);
+    game.undo();
+    assert!(game.board().get(3, 4).unwrap().is_some());
+
+    let castling = find(&game, "e1-g1");
+    assert_eq!(castling.kind, MoveKind::Castling(7));
+    assert_eq!(castling.castling(), Some(7));
+    assert!(!castling.is_capture());
+
+    game.load_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let promotion = find(&game, "a7-a8=Q");
+    assert_eq!(promotion.final_piece(), game.piece_index("Q").unwrap());
+}
+
+#[test]
+fn movement_drops() {
+    let mut game = load("shogi");
+    game.load_sfen("4k4/9/9/9/9/9/9/9/4K4 b P 1").unwrap();
+    let drop = game
+        .legal_moves()
+        .into_iter()
+        .find(|mv| mv.from.is_none() && mv.to == (4, 4))
+        .unwrap();
+    assert!(drop.is_drop());
+    assert_eq!(drop, Move::drop(game.piece_index("P").unwrap(), (4, 4)));
+}
@@ -0,0 +1,102 @@
+use sharmat::game::*;
+use sharmat::variant::*;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn chess(fen: &str) -> Game {
+    let mut game = load("standard");
+    game.load_fen(fen).unwrap();
+    game
+}
+
+fn shogi(sfen: &str) -> Game {
+    let mut game = load("shogi");
+    game.load_sfen(sfen).unwrap();
+    game
+}
+
+fn check(game: &Game, counts: &[u64]) {
+    for (depth, &count) in counts.iter().enumerate() {
+        assert_eq!(game.perft(depth + 1), count, "depth {}", depth + 1);
+    }
+}
+
+#[test]
+fn perft_chess_start() {
+    check(&load("standard"), &[20, 400, 8902]);
+}
+
+#[test]
+fn perft_kiwipete() {
+    check(
+        &chess("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+        &[48, 2039],
+    );
+}
+
+#[test]
+fn perft_chess_en_passant_pins() {
+    check(
+        &chess("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+        &[14, 191, 2812],
+    );
+}
+
+#[test]
+fn perft_chess_promotions() {
+    check(
+        &chess("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1"),
+        &[6, 264, 9467],
+    );
+    check(
+        &chess("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8"),
+        &[44, 1486],
+    );
+}
+
+#[test]
+fn perft_shogi_start() {
+    check(&load("shogi"), &[30, 900, 25470]);
+}
+
+#[test]
+fn perft_shogi_drops() {
+    // middle game position with pieces in hand for both players
+    check(
+        &shogi("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1"),
+        &[207, 28684],
+    );
+}
+
+#[test]
+fn perft_divide() {
+    let game = load("standard");
+    let divide = game.divide(2);
+    assert_eq!(divide.len(), 20);
+    assert!(divide.iter().all(|(_, nodes)| *nodes == 20));
+    assert_eq!(
+        divide.iter().map(|(_, nodes)| nodes).sum::<u64>(),
+        game.perft(2)
+    );
+}
+
+/// Deeper counts, too slow for debug builds: run with `cargo test --release -- --ignored`
+#[test]
+#[ignore]
+fn perft_deep() {
+    check(&load("standard"), &[20, 400, 8902, 197281]);
+    check(
+        &chess("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+        &[48, 2039, 97862],
+    );
+    check(&load("shogi"), &[30, 900, 25470, 719731]);
+}
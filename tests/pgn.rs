@@ -0,0 +1,201 @@
+use sharmat::game::*;
+use sharmat::pgn::*;
+use sharmat::variant::*;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn sans(game: &Game) -> Vec<String> {
+    let mut res: Vec<String> = game
+        .legal_moves()
+        .iter()
+        .map(|mv| game.san(mv).unwrap())
+        .collect();
+    res.sort();
+    res
+}
+
+const GAME: &str = r#"[Event "Casual game"]
+[Site "?"]
+[Date "2020.10.10"]
+[Round "?"]
+[White "Alice \"A\""]
+[Black "Bob"]
+[Result "1-0"]
+
+% this line is ignored
+{Scholar's mate} 1. e4 e5 2. Bc4 Nc6 (2... Nf6 3. d3) 3. Qh5!? Nf6?? $4 ; oops
+4. Qxf7# 1-0
+"#;
+
+#[test]
+fn pgn_parse() {
+    let pgn: Pgn = GAME.parse().unwrap();
+    assert_eq!(pgn.tag("White"), Some("Alice \"A\""));
+    assert_eq!(pgn.tag("Date"), Some("2020.10.10"));
+    assert_eq!(pgn.result, "1-0");
+    assert_eq!(pgn.moves.len(), 7);
+    assert_eq!(
+        pgn.moves[0].comment_before,
+        Some(String::from("Scholar's mate"))
+    );
+    assert_eq!(pgn.moves[3].variations.len(), 1);
+    assert_eq!(pgn.moves[3].variations[0][1].san, "d3");
+    assert_eq!(pgn.moves[4].nags, vec![5]);
+    assert_eq!(pgn.moves[5].nags, vec![4, 4]);
+    assert_eq!(pgn.moves[5].comment, Some(String::from("oops")));
+
+    let mut game = load("standard");
+    pgn.play(&mut game).unwrap();
+    assert_eq!(game.history().len(), 7);
+    assert!(game.is_check());
+    assert!(game.legal_moves().is_empty());
+    assert_eq!(Pgn::from_game(&game).unwrap().result, "1-0");
+}
+
+#[test]
+fn pgn_round_trip() {
+    let pgn: Pgn = GAME.parse().unwrap();
+    let written = pgn.to_string();
+    assert!(written.contains("[White \"Alice \\\"A\\\"\"]"));
+    assert!(written.contains("2. Bc4 Nc6 (2... Nf6 3. d3) 3. Qh5 $5"));
+    assert_eq!(written.parse::<Pgn>().unwrap(), pgn);
+
+    let mut game = load("standard");
+    game.load_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+        .unwrap();
+    for san in &["O-O", "O-O-O", "Rf7", "Rd7"] {
+        let mv = game.parse_san(san).unwrap();
+        game.play(&mv).unwrap();
+    }
+    let pgn = Pgn::from_game(&game).unwrap();
+    assert_eq!(pgn.tag("SetUp"), Some("1"));
+    assert_eq!(pgn.result, "*");
+    let mut copy = load("standard");
+    pgn.to_string()
+        .parse::<Pgn>()
+        .unwrap()
+        .play(&mut copy)
+        .unwrap();
+    assert_eq!(copy.fen().unwrap(), game.fen().unwrap());
+}
+
+#[test]
+fn pgn_multiple_games() {
+    let games = Pgn::parse_all(&format!("{}\n1.d4 d5 *\n\n[Event \"?\"]\n1. c4", GAME)).unwrap();
+    assert_eq!(games.len(), 3);
+    assert_eq!(games[1].moves.len(), 2);
+    assert_eq!(games[1].result, "*");
+    assert_eq!(games[2].tag("Event"), Some("?"));
+    assert_eq!(games[2].moves[0].san, "c4");
+}
+
+#[test]
+fn san_special_moves() {
+    let mut game = load("standard");
+    game.load_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1")
+        .unwrap();
+    let moves = sans(&game);
+    for san in &["O-O", "O-O-O", "exd6", "e6", "Rxa8+", "Kf1"] {
+        assert!(
+            moves.contains(&san.to_string()),
+            "{} not in {:?}",
+            san,
+            moves
+        );
+    }
+    assert_eq!(game.parse_san("0-0").unwrap().castling(), Some(7));
+    assert_eq!(game.parse_san("exd6").unwrap().to, (3, 5));
+
+    game.load_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let moves = sans(&game);
+    assert!(moves.contains(&String::from("a8=Q+")));
+    assert!(moves.contains(&String::from("a8=N")));
+    let queen = game.piece_index("standard.queen");
+    assert_eq!(game.parse_san("a8Q").unwrap().promotion, queen);
+}
+
+#[test]
+fn san_disambiguation() {
+    let mut game = load("standard");
+    game.load_fen("4k3/8/6R1/8/8/Q5R1/8/QNQ2N1K w - - 0 1")
+        .unwrap();
+    let moves = sans(&game);
+    for san in &[
+        "Nbd2", "Nfd2", "R3g4", "R6g4", "Rg2", "Qa1b2", "Qcb2", "Q3b2",
+    ] {
+        assert!(
+            moves.contains(&san.to_string()),
+            "{} not in {:?}",
+            san,
+            moves
+        );
+    }
+    assert_eq!(game.parse_san("Nbd2").unwrap().from, Some((1, 0)));
+    assert_eq!(game.parse_san("Qa1xb2").unwrap().from, Some((0, 0)));
+}
+
+#[test]
+fn san_drops() {
+    let mut game = load("shogi");
+    game.load_sfen("4k4/9/9/9/9/9/9/9/4K4 b P 1").unwrap();
+    let mv = game.parse_san("P@e5").unwrap();
+    assert_eq!(mv.from, None);
+    assert_eq!(game.san(&mv).unwrap(), "P@e5");
+    assert_eq!(game.parse_san("@e5").unwrap(), mv);
+}
+
+#[test]
+fn pgn_errors() {
+    let mut game = load("standard");
+    assert_eq!("".parse::<Pgn>(), Err(PgnError::Empty));
+    assert_eq!(
+        "1. e4 {unterminated".parse::<Pgn>(),
+        Err(PgnError::UnterminatedComment)
+    );
+    assert_eq!(
+        "1. e4 (1. d4 *".parse::<Pgn>(),
+        Err(PgnError::UnbalancedVariation)
+    );
+    assert_eq!(
+        "[Event ?]".parse::<Pgn>(),
+        Err(PgnError::InvalidTag(String::from("Event")))
+    );
+    assert_eq!(
+        game.parse_san("e5"),
+        Err(PgnError::IllegalMove(String::from("e5")))
+    );
+    assert_eq!(
+        game.parse_san("Z"),
+        Err(PgnError::InvalidSan(String::from("Z")))
+    );
+    for san in &["é4", "Nié3"] {
+        assert_eq!(
+            game.parse_san(san),
+            Err(PgnError::InvalidSan(san.to_string()))
+        );
+    }
+    let pgn: Pgn = "1. é4 *".parse().unwrap();
+    assert_eq!(
+        pgn.play(&mut load("standard")),
+        Err(PgnError::InvalidSan(String::from("é4")))
+    );
+    game.load_fen("4k3/8/8/8/8/8/8/2N1K1N1 w - - 0 1").unwrap();
+    assert_eq!(
+        game.parse_san("Ne2"),
+        Err(PgnError::AmbiguousMove(String::from("Ne2")))
+    );
+    let pgn: Pgn = "1. e4 e5 2. Ke3 *".parse().unwrap();
+    assert_eq!(
+        pgn.play(&mut load("standard")),
+        Err(PgnError::IllegalMove(String::from("Ke3")))
+    );
+}
@@ -0,0 +1,361 @@
+use sharmat::player::*;
+use sharmat::protocol::*;
+use sharmat::storage::*;
+use sharmat::variant::*;
+use std::fs;
+use std::io::Cursor;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn definition(name: &str) -> String {
+    fs::read_to_string(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+fn snapshot(name: &str, moves: &[&str]) -> Snapshot {
+    Snapshot {
+        game: 3,
+        variant: name.to_string(),
+        definition: definition(name),
+        players: [Some("alice".to_string()), None],
+        color: Some(PlayerColor::White),
+        moves: moves.iter().map(|mv| mv.to_string()).collect(),
+        time: Some(TimeControl::new(
+            Duration::from_secs(300),
+            Duration::from_secs(0),
+        )),
+        clock: Some(ClockUpdate {
+            game: 3,
+            white: Duration::from_millis(299_500),
+            black: Duration::from_secs(300),
+            running: Some(PlayerColor::Black),
+        }),
+        checksum: 0,
+    }
+}
+
+/// A snapshot with the right checksum
+fn synced(name: &str, moves: &[&str]) -> Snapshot {
+    let mut snapshot = snapshot(name, moves);
+    let mut game = definition(name).parse::<Variant>().unwrap().game().unwrap();
+    for mv in moves {
+        game.play(&game.parse_uci_move(mv).unwrap());
+    }
+    snapshot.checksum = checksum(&game);
+    snapshot
+}
+
+#[test]
+fn protocol_round_trip() {
+    let client_messages = vec![
+        ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: "alice".to_string(),
+        },
+        ClientMessage::Resume {
+            version: PROTOCOL_VERSION,
+            token: "0123456789abcdef".to_string(),
+        },
+        ClientMessage::CreateGame {
+            variant: "shogi".to_string(),
+            color: ColorChoice::Random,
+            time: None,
+        },
+        ClientMessage::JoinGame { game: 3 },
+        ClientMessage::Move {
+            game: 3,
+            mv: "P@e5".to_string(),
+        },
+        ClientMessage::Resign { game: 3 },
+        ClientMessage::ListGames,
+        ClientMessage::Lobby,
+        ClientMessage::Challenge {
+            to: 2,
+            variant: "standard".to_string(),
+            color: ColorChoice::White,
+            time: Some("3+2".parse().unwrap()),
+        },
+        ClientMessage::AcceptChallenge { challenge: 5 },
+        ClientMessage::DeclineChallenge { challenge: 5 },
+        ClientMessage::Watch { game: 3 },
+        ClientMessage::StopWatching { game: 3 },
+        ClientMessage::History(GameQuery {
+            player: Some("alice".to_string()),
+            since: Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)),
+            until: None,
+            after: Some(12),
+        }),
+        ClientMessage::Export {
+            game: 3,
+            format: ExportFormat::Kif,
+        },
+        ClientMessage::Pong { ping: 12 },
+        ClientMessage::Chat {
+            channel: ChatChannel::Players(3),
+            text: "good luck".to_string(),
+        },
+        ClientMessage::Mute {
+            name: "bob".to_string(),
+        },
+        ClientMessage::Unmute {
+            name: "bob".to_string(),
+        },
+    ];
+    let server_messages = vec![
+        ServerMessage::Welcome {
+            version: PROTOCOL_VERSION,
+            client: 7,
+            token: "0123456789abcdef".to_string(),
+        },
+        ServerMessage::GameCreated { game: 3 },
+        ServerMessage::Snapshot(synced("standard", &["e2e4"])),
+        ServerMessage::Move {
+            game: 3,
+            mv: "e7e5".to_string(),
+            checksum: 42,
+        },
+        ServerMessage::MoveRejected {
+            game: 3,
+            mv: "e2e5".to_string(),
+            reason: "illegal move".to_string(),
+        },
+        ServerMessage::Clock(ClockUpdate {
+            game: 3,
+            white: Duration::from_secs(60),
+            black: Duration::from_millis(1),
+            running: None,
+        }),
+        ServerMessage::GameOver {
+            game: 3,
+            result: GameResult::Draw,
+            reason: "stalemate".to_string(),
+        },
+        ServerMessage::Error("unknown game 4".to_string()),
+        ServerMessage::GameList(vec![OpenGame {
+            game: 3,
+            variant: "shogi".to_string(),
+            host: "alice".to_string(),
+            color: PlayerColor::Black,
+            time: None,
+        }]),
+        ServerMessage::Lobby(Lobby {
+            users: vec![LobbyUser {
+                id: 1,
+                name: "bob".to_string(),
+                game: Some(3),
+            }],
+            games: vec![],
+            running: vec![RunningGame {
+                game: 3,
+                variant: "standard".to_string(),
+                players: ["bob".to_string(), "carol".to_string()],
+                spectators: 2,
+            }],
+            challenges: vec![Challenge {
+                id: 5,
+                from: 1,
+                from_name: "bob".to_string(),
+                to: 7,
+                to_name: "alice".to_string(),
+                variant: "shogi".to_string(),
+                color: ColorChoice::Black,
+                time: Some("10".parse().unwrap()),
+            }],
+        }),
+        ServerMessage::ChallengeClosed {
+            challenge: 5,
+            reason: "declined".to_string(),
+        },
+        ServerMessage::Spectators { game: 3, count: 2 },
+        ServerMessage::PlayerAway {
+            game: 3,
+            color: PlayerColor::Black,
+            grace: Duration::from_secs(60),
+        },
+        ServerMessage::PlayerBack {
+            game: 3,
+            color: PlayerColor::Black,
+        },
+        ServerMessage::History(vec![GameSummary {
+            game: 3,
+            variant: "standard".to_string(),
+            players: ["alice".to_string(), "bob".to_string()],
+            moves: 4,
+            started: UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            ended: Some(UNIX_EPOCH + Duration::from_millis(1_600_000_300_500)),
+            result: Some(GameResult::BlackWins),
+            reason: Some("checkmate".to_string()),
+            time: Some("5+3".parse().unwrap()),
+        }]),
+        ServerMessage::Export {
+            game: 3,
+            format: ExportFormat::Pgn,
+            text: "1. f3 e5 2. g4 Qh4# 0-1".to_string(),
+        },
+        ServerMessage::Ping { ping: 12 },
+        ServerMessage::Chat(ChatMessage {
+            channel: ChatChannel::Lobby,
+            from: 7,
+            from_name: "alice".to_string(),
+            text: "hello".to_string(),
+            sent: UNIX_EPOCH + Duration::from_secs(1_555_200_000),
+        }),
+        ServerMessage::ChatHistory {
+            channel: ChatChannel::Spectators(3),
+            messages: vec![],
+        },
+        ServerMessage::Muted(vec!["bob".to_string()]),
+    ];
+
+    // through a stream
+    let mut stream = Vec::new();
+    for message in &client_messages {
+        write_frame(&mut stream, message).unwrap();
+    }
+    let mut reader = Cursor::new(stream);
+    for message in &client_messages {
+        assert_eq!(
+            &read_frame::<_, ClientMessage>(&mut reader).unwrap(),
+            message
+        );
+    }
+    assert!(matches!(
+        read_frame::<_, ClientMessage>(&mut reader),
+        Err(ProtocolError::Io(_))
+    ));
+
+    // through a buffer
+    for message in &server_messages {
+        let frame = encode(message).unwrap();
+        assert_eq!(
+            decode(&frame).unwrap(),
+            Some((message.clone(), frame.len()))
+        );
+    }
+
+    assert_eq!(GameResult::WhiteWins.to_string(), "1-0");
+    assert_eq!(GameResult::BlackWins.to_string(), "0-1");
+    assert_eq!(GameResult::Draw.to_string(), "1/2-1/2");
+}
+
+#[test]
+fn protocol_time_control() {
+    let blitz: TimeControl = "3+2".parse().unwrap();
+    assert_eq!(blitz.base, Duration::from_secs(180));
+    assert_eq!(blitz.increment, Duration::from_secs(2));
+    assert_eq!(blitz.to_string(), "3+2");
+    let bullet: TimeControl = " 0.5 ".parse().unwrap();
+    assert_eq!(
+        bullet,
+        TimeControl::new(Duration::from_secs(30), Duration::from_secs(0))
+    );
+    assert_eq!(bullet.to_string(), "0.5+0");
+    assert!(
+        TimeControl::new(Duration::from_secs(0), Duration::from_secs(5))
+            .validate()
+            .is_err()
+    );
+    assert!(TimeControl::new(MAX_BASE, MAX_INCREMENT).validate().is_ok());
+    for text in &[
+        "", "+2", "3+", "0+5", "-1+0", "3+-2", "1e300+0", "five", "2000+0", "3+700",
+    ] {
+        assert!(
+            matches!(
+                text.parse::<TimeControl>(),
+                Err(ProtocolError::InvalidTimeControl(_))
+            ),
+            "{}",
+            text
+        );
+    }
+}
+
+#[test]
+fn protocol_framing() {
+    let first = ClientMessage::JoinGame { game: 1 };
+    let second = ClientMessage::Resign { game: 1 };
+    let mut buffer = encode(&first).unwrap();
+    buffer.extend(encode(&second).unwrap());
+
+    // incomplete frames are waited for
+    assert_eq!(decode::<ClientMessage>(&buffer[..3]).unwrap(), None);
+    assert_eq!(decode::<ClientMessage>(&buffer[..6]).unwrap(), None);
+    let (message, size) = decode::<ClientMessage>(&buffer).unwrap().unwrap();
+    assert_eq!(message, first);
+    let (message, rest) = decode::<ClientMessage>(&buffer[size..]).unwrap().unwrap();
+    assert_eq!(message, second);
+    assert_eq!(size + rest, buffer.len());
+
+    // the length prefix is big-endian
+    let frame = encode(&first).unwrap();
+    assert_eq!(frame[..4], ((frame.len() - 4) as u32).to_be_bytes());
+
+    let huge = ((MAX_FRAME_SIZE + 1) as u32).to_be_bytes();
+    assert!(matches!(
+        decode::<ClientMessage>(&huge),
+        Err(ProtocolError::FrameTooLarge(_))
+    ));
+    assert!(matches!(
+        read_frame::<_, ClientMessage>(&mut Cursor::new(huge)),
+        Err(ProtocolError::FrameTooLarge(_))
+    ));
+    assert!(matches!(
+        encode(&ServerMessage::Error("x".repeat(MAX_FRAME_SIZE))),
+        Err(ProtocolError::FrameTooLarge(_))
+    ));
+
+    // a frame holding something else than a message
+    let mut garbage = 2u32.to_be_bytes().to_vec();
+    garbage.extend(&[0xff, 0xff]);
+    assert!(matches!(
+        decode::<ClientMessage>(&garbage),
+        Err(ProtocolError::Encoding(_))
+    ));
+}
+
+#[test]
+fn protocol_checksum() {
+    let standard = synced("standard", &["e2e4", "e7e5", "g1f3"]);
+    let game = standard.game().unwrap();
+    assert_eq!(game.ply_number(), 4);
+    assert_eq!(checksum(&game), standard.checksum);
+
+    let shogi = synced("shogi", &["c3c4", "g7g6", "b2h8+", "g9h8", "B@e5"]);
+    assert_eq!(checksum(&shogi.game().unwrap()), shogi.checksum);
+
+    // the same position reached by another move order has the same checksum
+    let knights_last = synced("standard", &["e2e4", "e7e5", "g1f3", "b8c6", "b1c3"]);
+    let transposed = synced("standard", &["g1f3", "e7e5", "e2e4", "b8c6", "b1c3"]);
+    assert_eq!(transposed.checksum, knights_last.checksum);
+    // unless the en passant rights differ
+    let pawns_last = synced("standard", &["g1f3", "b8c6", "b1c3", "e7e5", "e2e4"]);
+    assert_ne!(pawns_last.checksum, knights_last.checksum);
+
+    // but different positions don't
+    let other = synced("standard", &["e2e4", "e7e5", "b1c3"]);
+    assert_ne!(other.checksum, standard.checksum);
+    assert!(matches!(
+        Snapshot {
+            checksum: other.checksum,
+            ..standard.clone()
+        }
+        .game(),
+        Err(ProtocolError::Desync)
+    ));
+
+    assert!(matches!(
+        snapshot("standard", &["e2e5"]).game(),
+        Err(ProtocolError::IllegalMove(_))
+    ));
+    assert!(matches!(
+        Snapshot {
+            definition: "name = 3".to_string(),
+            ..standard
+        }
+        .game(),
+        Err(ProtocolError::InvalidVariant(_))
+    ));
+}
@@ -0,0 +1,939 @@
+use sharmat::player::PlayerColor;
+use sharmat::protocol::*;
+use sharmat::storage::*;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const GRACE: u64 = 60;
+const MAX_LAG: u64 = 500;
+
+/// A server running on a free port, killed when dropped
+struct Server {
+    child: Child,
+    address: String,
+    /// The directory of its games, if they are thrown away with the server
+    games: Option<GamesDir>,
+}
+
+/// A directory of games, removed when dropped
+struct GamesDir(PathBuf);
+
+impl GamesDir {
+    fn new() -> GamesDir {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = env::temp_dir().join(format!(
+            "sharmat-games-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        GamesDir(dir)
+    }
+}
+
+impl Drop for GamesDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+impl Server {
+    fn start() -> Server {
+        Server::with_grace(GRACE)
+    }
+
+    /// Starts a server giving players who lose their connection the given number of seconds to come back
+    fn with_grace(grace: u64) -> Server {
+        Server::with_options(grace, MAX_LAG)
+    }
+
+    /// Starts a server taking at most the given milliseconds of latency off the players' clocks
+    fn with_options(grace: u64, max_lag: u64) -> Server {
+        let games = GamesDir::new();
+        let mut server = Server::start_in(&games.0, grace, max_lag);
+        server.games = Some(games);
+        server
+    }
+
+    /// Starts a server keeping its games in the given directory
+    fn start_in(games: &Path, grace: u64, max_lag: u64) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_server"))
+            .arg(format!("{}/variants", env!("CARGO_MANIFEST_DIR")))
+            .arg("127.0.0.1:0")
+            .arg(games)
+            .arg(grace.to_string())
+            .arg(max_lag.to_string())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let address = line.trim().trim_start_matches("Listening on ").to_string();
+        Server {
+            child,
+            address,
+            games: None,
+        }
+    }
+
+    fn connect(&self, name: &str) -> Client {
+        let mut client = Client::new(&self.address);
+        client.send(ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: name.to_string(),
+        });
+        match client.receive() {
+            ServerMessage::Welcome { token, .. } => client.token = token,
+            message => panic!("expected a welcome, got {:?}", message),
+        }
+        assert_eq!(client.history().0, ChatChannel::Lobby);
+        client
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    /// The token to resume the session with
+    token: String,
+    /// How long the client takes to answer pings
+    delay: Duration,
+}
+
+impl Client {
+    fn new(address: &str) -> Client {
+        Client {
+            stream: TcpStream::connect(address).unwrap(),
+            token: String::new(),
+            delay: Duration::from_secs(0),
+        }
+    }
+
+    fn send(&mut self, message: ClientMessage) {
+        write_frame(&mut self.stream, &message).unwrap();
+    }
+
+    /// Receives the next message other than a ping, answering the pings on the way
+    fn receive(&mut self) -> ServerMessage {
+        loop {
+            match read_frame(&mut self.stream).unwrap() {
+                ServerMessage::Ping { ping } => {
+                    thread::sleep(self.delay);
+                    self.send(ClientMessage::Pong { ping });
+                }
+                message => return message,
+            }
+        }
+    }
+
+    fn error(&mut self) -> String {
+        match self.receive() {
+            ServerMessage::Error(e) => e,
+            message => panic!("expected an error, got {:?}", message),
+        }
+    }
+
+    fn history(&mut self) -> (ChatChannel, Vec<ChatMessage>) {
+        match self.receive() {
+            ServerMessage::ChatHistory { channel, messages } => (channel, messages),
+            message => panic!("expected a chat history, got {:?}", message),
+        }
+    }
+
+    fn chat(&mut self) -> ChatMessage {
+        match self.receive() {
+            ServerMessage::Chat(message) => message,
+            message => panic!("expected a chat message, got {:?}", message),
+        }
+    }
+
+    fn snapshot(&mut self) -> Snapshot {
+        match self.receive() {
+            ServerMessage::Snapshot(snapshot) => snapshot,
+            message => panic!("expected a snapshot, got {:?}", message),
+        }
+    }
+}
+
+fn create(variant: &str, color: ColorChoice) -> ClientMessage {
+    ClientMessage::CreateGame {
+        variant: variant.to_string(),
+        color,
+        time: None,
+    }
+}
+
+fn play(mv: &str) -> ClientMessage {
+    ClientMessage::Move {
+        game: 1,
+        mv: mv.to_string(),
+    }
+}
+
+#[test]
+fn server_game() {
+    let server = Server::start();
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+
+    alice.send(create("standard", ColorChoice::Black));
+    assert_eq!(alice.receive(), ServerMessage::GameCreated { game: 1 });
+    bob.send(ClientMessage::ListGames);
+    assert_eq!(
+        bob.receive(),
+        ServerMessage::GameList(vec![OpenGame {
+            game: 1,
+            variant: "standard".to_string(),
+            host: "alice".to_string(),
+            color: PlayerColor::White,
+            time: None,
+        }])
+    );
+    bob.send(ClientMessage::JoinGame { game: 1 });
+    let snapshot = alice.snapshot();
+    assert_eq!(snapshot.variant, "standard");
+    assert_eq!(snapshot.color, Some(PlayerColor::Black));
+    assert_eq!(
+        snapshot.players,
+        [Some("bob".to_string()), Some("alice".to_string())]
+    );
+    let mut game = snapshot.game().unwrap();
+    assert_eq!(
+        game.fen().unwrap(),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    );
+    assert_eq!(bob.snapshot().color, Some(PlayerColor::White));
+    bob.send(ClientMessage::ListGames);
+    assert_eq!(bob.receive(), ServerMessage::GameList(vec![]));
+
+    // moves are checked by the server
+    alice.send(play("e7e5"));
+    assert_eq!(
+        alice.receive(),
+        ServerMessage::MoveRejected {
+            game: 1,
+            mv: "e7e5".to_string(),
+            reason: "not your turn".to_string()
+        }
+    );
+    bob.send(play("e2e5"));
+    assert!(matches!(bob.receive(), ServerMessage::MoveRejected { .. }));
+
+    for mv in &["f2f3", "e7e5", "g2g4", "d8h4"] {
+        let mover = if game.current_player().unwrap().color == PlayerColor::White {
+            &mut bob
+        } else {
+            &mut alice
+        };
+        mover.send(play(mv));
+        game.play(&game.parse_uci_move(mv).unwrap());
+        let expected = ServerMessage::Move {
+            game: 1,
+            mv: mv.to_string(),
+            checksum: checksum(&game),
+        };
+        assert_eq!(alice.receive(), expected);
+        assert_eq!(bob.receive(), expected);
+    }
+    let over = ServerMessage::GameOver {
+        game: 1,
+        result: GameResult::BlackWins,
+        reason: "checkmate".to_string(),
+    };
+    assert_eq!(alice.receive(), over);
+    assert_eq!(bob.receive(), over);
+
+    bob.send(play("a2a3"));
+    assert_eq!(bob.error(), "not in this game");
+}
+
+#[test]
+fn server_forfeit() {
+    let server = Server::with_grace(0);
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+
+    alice.send(create("shogi", ColorChoice::White));
+    assert_eq!(alice.receive(), ServerMessage::GameCreated { game: 1 });
+    alice.send(create("shogi", ColorChoice::White));
+    assert_eq!(alice.error(), "already in a game");
+    bob.send(ClientMessage::JoinGame { game: 1 });
+    let snapshot = alice.snapshot();
+    assert_eq!(snapshot.color, Some(PlayerColor::White));
+    assert!(snapshot
+        .game()
+        .unwrap()
+        .sfen()
+        .unwrap()
+        .starts_with("lnsgkgsnl/"));
+    assert_eq!(bob.snapshot().color, Some(PlayerColor::Black));
+
+    // a player who leaves and doesn't come back loses
+    drop(bob);
+    assert_eq!(
+        alice.receive(),
+        ServerMessage::PlayerAway {
+            game: 1,
+            color: PlayerColor::Black,
+            grace: Duration::from_secs(0)
+        }
+    );
+    assert_eq!(
+        alice.receive(),
+        ServerMessage::GameOver {
+            game: 1,
+            result: GameResult::WhiteWins,
+            reason: "forfeit".to_string()
+        }
+    );
+}
+
+#[test]
+fn server_errors() {
+    let server = Server::start();
+    let mut alice = server.connect("alice");
+    alice.send(create("../standard", ColorChoice::Random));
+    assert_eq!(alice.error(), "unknown variant ../standard");
+    alice.send(ClientMessage::JoinGame { game: 4 });
+    assert_eq!(alice.error(), "unknown game 4");
+    alice.send(play("e2e4"));
+    assert_eq!(alice.error(), "not in this game");
+    for (base, increment) in &[(0, 5), (MAX_BASE.as_secs() + 1, 0)] {
+        alice.send(ClientMessage::CreateGame {
+            variant: "standard".to_string(),
+            color: ColorChoice::White,
+            time: Some(TimeControl::new(
+                Duration::from_secs(*base),
+                Duration::from_secs(*increment),
+            )),
+        });
+        assert!(alice.error().starts_with("invalid time control"));
+    }
+
+    let mut anonymous = Client::new(&server.address);
+    anonymous.send(create("standard", ColorChoice::White));
+    assert_eq!(anonymous.error(), "say hello first");
+    anonymous.send(ClientMessage::Hello {
+        version: PROTOCOL_VERSION + 1,
+        name: "mallory".to_string(),
+    });
+    assert!(anonymous
+        .error()
+        .starts_with("unsupported protocol version"));
+}
+
+#[test]
+fn server_lobby() {
+    let server = Server::start();
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+    let mut carol = server.connect("carol");
+    let blitz = TimeControl::new(Duration::from_secs(180), Duration::from_secs(2));
+
+    carol.send(ClientMessage::CreateGame {
+        variant: "shogi".to_string(),
+        color: ColorChoice::White,
+        time: Some(blitz),
+    });
+    assert_eq!(carol.receive(), ServerMessage::GameCreated { game: 1 });
+
+    alice.send(ClientMessage::Lobby);
+    let lobby = match alice.receive() {
+        ServerMessage::Lobby(lobby) => lobby,
+        message => panic!("expected the lobby, got {:?}", message),
+    };
+    let names: Vec<&str> = lobby.users.iter().map(|user| user.name.as_str()).collect();
+    assert_eq!(names, ["alice", "bob", "carol"]);
+    assert_eq!(lobby.users[2].game, Some(1));
+    assert_eq!(lobby.games.len(), 1);
+    assert_eq!(lobby.games[0].time, Some(blitz));
+    assert!(lobby.challenges.is_empty());
+    let bob_id = lobby.users[1].id;
+
+    // a declined challenge
+    let challenge = |to| ClientMessage::Challenge {
+        to,
+        variant: "standard".to_string(),
+        color: ColorChoice::Black,
+        time: Some(blitz),
+    };
+    alice.send(challenge(bob_id));
+    let sent = match alice.receive() {
+        ServerMessage::Challenge(challenge) => challenge,
+        message => panic!("expected a challenge, got {:?}", message),
+    };
+    assert_eq!(bob.receive(), ServerMessage::Challenge(sent.clone()));
+    assert_eq!(sent.from_name, "alice");
+    assert_eq!(sent.to_name, "bob");
+    bob.send(ClientMessage::Lobby);
+    match bob.receive() {
+        ServerMessage::Lobby(lobby) => assert_eq!(lobby.challenges, vec![sent.clone()]),
+        message => panic!("expected the lobby, got {:?}", message),
+    }
+    bob.send(ClientMessage::DeclineChallenge { challenge: sent.id });
+    let declined = ServerMessage::ChallengeClosed {
+        challenge: sent.id,
+        reason: "declined".to_string(),
+    };
+    assert_eq!(alice.receive(), declined);
+    assert_eq!(bob.receive(), declined);
+    bob.send(ClientMessage::AcceptChallenge { challenge: sent.id });
+    assert_eq!(bob.error(), format!("unknown challenge {}", sent.id));
+
+    // an accepted one starts the game
+    alice.send(challenge(bob_id));
+    let sent = match alice.receive() {
+        ServerMessage::Challenge(challenge) => challenge,
+        message => panic!("expected a challenge, got {:?}", message),
+    };
+    bob.receive();
+    bob.send(ClientMessage::AcceptChallenge { challenge: sent.id });
+    let snapshot = alice.snapshot();
+    assert_eq!(snapshot.game, 2);
+    assert_eq!(snapshot.color, Some(PlayerColor::Black));
+    assert_eq!(snapshot.time, Some(blitz));
+    // white's clock runs from the start
+    let clock = snapshot.clock.unwrap();
+    assert_eq!(clock.running, Some(PlayerColor::White));
+    assert!(clock.white <= Duration::from_secs(180));
+    assert_eq!(clock.black, Duration::from_secs(180));
+    assert_eq!(bob.snapshot().color, Some(PlayerColor::White));
+    alice.send(challenge(bob_id));
+    assert_eq!(alice.error(), "already in a game");
+
+    // challenges need someone to challenge and a variant to play
+    carol.send(ClientMessage::Resign { game: 1 });
+    carol.send(ClientMessage::Challenge {
+        to: 99,
+        variant: "standard".to_string(),
+        color: ColorChoice::Random,
+        time: None,
+    });
+    assert_eq!(carol.error(), "unknown user 99");
+    carol.send(ClientMessage::Challenge {
+        to: bob_id,
+        variant: "checkers".to_string(),
+        color: ColorChoice::Random,
+        time: None,
+    });
+    assert_eq!(carol.error(), "unknown variant checkers");
+
+    // challenges are dropped when a player leaves
+    let dave_id = bob_id + 2;
+    let mut dave = server.connect("dave");
+    carol.send(challenge(dave_id));
+    let sent = match carol.receive() {
+        ServerMessage::Challenge(challenge) => challenge,
+        message => panic!("expected a challenge, got {:?}", message),
+    };
+    assert_eq!(dave.receive(), ServerMessage::Challenge(sent.clone()));
+    drop(dave);
+    assert_eq!(
+        carol.receive(),
+        ServerMessage::ChallengeClosed {
+            challenge: sent.id,
+            reason: "the player left".to_string()
+        }
+    );
+}
+
+#[test]
+fn server_spectators() {
+    let server = Server::start();
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+    let mut carol = server.connect("carol");
+
+    alice.send(create("shogi", ColorChoice::White));
+    alice.receive();
+    carol.send(ClientMessage::Watch { game: 1 });
+    assert_eq!(carol.error(), "the game hasn't started");
+    bob.send(ClientMessage::JoinGame { game: 1 });
+    alice.snapshot();
+    bob.snapshot();
+    for mv in &["c3c4", "g7g6"] {
+        let mover = if *mv == "c3c4" { &mut alice } else { &mut bob };
+        mover.send(play(mv));
+        alice.receive();
+        bob.receive();
+    }
+
+    carol.send(ClientMessage::Lobby);
+    match carol.receive() {
+        ServerMessage::Lobby(lobby) => assert_eq!(
+            lobby.running,
+            vec![RunningGame {
+                game: 1,
+                variant: "shogi".to_string(),
+                players: ["alice".to_string(), "bob".to_string()],
+                spectators: 0,
+            }]
+        ),
+        message => panic!("expected the lobby, got {:?}", message),
+    }
+
+    // spectators get the game so far, then its moves
+    carol.send(ClientMessage::Watch { game: 1 });
+    let snapshot = carol.snapshot();
+    assert_eq!(carol.history(), (ChatChannel::Spectators(1), vec![]));
+    assert_eq!(snapshot.color, None);
+    assert_eq!(snapshot.moves, ["c3c4", "g7g6"]);
+    let mut game = snapshot.game().unwrap();
+    let watched = ServerMessage::Spectators { game: 1, count: 1 };
+    assert_eq!(alice.receive(), watched);
+    assert_eq!(bob.receive(), watched);
+    assert_eq!(carol.receive(), watched);
+    carol.send(ClientMessage::Watch { game: 1 });
+    assert_eq!(carol.error(), "already watching this game");
+    alice.send(ClientMessage::Watch { game: 1 });
+    assert_eq!(alice.error(), "already playing this game");
+
+    alice.send(play("b2h8+"));
+    game.play(&game.parse_uci_move("b2h8+").unwrap());
+    let expected = ServerMessage::Move {
+        game: 1,
+        mv: "b2h8+".to_string(),
+        checksum: checksum(&game),
+    };
+    assert_eq!(alice.receive(), expected);
+    assert_eq!(bob.receive(), expected);
+    assert_eq!(carol.receive(), expected);
+
+    // but can't play them
+    carol.send(play("g9h8"));
+    assert_eq!(carol.error(), "spectators can't move");
+
+    carol.send(ClientMessage::StopWatching { game: 1 });
+    let left = ServerMessage::Spectators { game: 1, count: 0 };
+    assert_eq!(alice.receive(), left);
+    assert_eq!(bob.receive(), left);
+    carol.send(ClientMessage::StopWatching { game: 1 });
+    assert_eq!(carol.error(), "not watching this game");
+
+    // and are told the result
+    carol.send(ClientMessage::Watch { game: 1 });
+    carol.snapshot();
+    carol.history();
+    carol.receive();
+    alice.receive();
+    bob.receive();
+    bob.send(ClientMessage::Resign { game: 1 });
+    assert_eq!(
+        carol.receive(),
+        ServerMessage::GameOver {
+            game: 1,
+            result: GameResult::WhiteWins,
+            reason: "forfeit".to_string()
+        }
+    );
+}
+
+#[test]
+fn server_history() {
+    let games = GamesDir::new();
+    let before = SystemTime::now();
+    {
+        let server = Server::start_in(&games.0, GRACE, MAX_LAG);
+        let mut alice = server.connect("alice");
+        let mut bob = server.connect("bob");
+        alice.send(create("standard", ColorChoice::White));
+        assert_eq!(alice.receive(), ServerMessage::GameCreated { game: 1 });
+        bob.send(ClientMessage::JoinGame { game: 1 });
+        alice.snapshot();
+        bob.snapshot();
+        for (i, mv) in ["f2f3", "e7e5", "g2g4", "d8h4"].iter().enumerate() {
+            let player = if i % 2 == 0 { &mut alice } else { &mut bob };
+            player.send(play(mv));
+            assert!(matches!(alice.receive(), ServerMessage::Move { .. }));
+            assert!(matches!(bob.receive(), ServerMessage::Move { .. }));
+        }
+        assert!(matches!(alice.receive(), ServerMessage::GameOver { .. }));
+
+        // games that are still being played are stored too
+        alice.send(create("shogi", ColorChoice::Black));
+        assert_eq!(alice.receive(), ServerMessage::GameCreated { game: 2 });
+        bob.send(ClientMessage::JoinGame { game: 2 });
+        alice.snapshot();
+        bob.send(ClientMessage::Move {
+            game: 2,
+            mv: "c3c4".to_string(),
+        });
+        assert!(matches!(alice.receive(), ServerMessage::Move { .. }));
+        // stopped before the players leave, who would forfeit
+        drop(server);
+    }
+
+    // the games outlive the server
+    let server = Server::start_in(&games.0, GRACE, MAX_LAG);
+    let mut carol = server.connect("carol");
+    carol.send(ClientMessage::History(GameQuery {
+        player: Some("alice".to_string()),
+        since: Some(before),
+        until: None,
+        after: None,
+    }));
+    let history = match carol.receive() {
+        ServerMessage::History(history) => history,
+        message => panic!("expected the history, got {:?}", message),
+    };
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].game, 1);
+    assert_eq!(history[0].players, ["alice".to_string(), "bob".to_string()]);
+    assert_eq!(history[0].moves, 4);
+    assert_eq!(history[0].result, Some(GameResult::BlackWins));
+    assert_eq!(history[0].reason.as_deref(), Some("checkmate"));
+    assert!(history[0].ended.is_some());
+    assert_eq!(history[1].players, ["bob".to_string(), "alice".to_string()]);
+    assert_eq!(history[1].moves, 1);
+    assert_eq!(history[1].result, None);
+
+    carol.send(ClientMessage::History(GameQuery {
+        player: Some("carol".to_string()),
+        ..GameQuery::default()
+    }));
+    assert_eq!(carol.receive(), ServerMessage::History(vec![]));
+    carol.send(ClientMessage::History(GameQuery {
+        until: Some(before),
+        ..GameQuery::default()
+    }));
+    assert_eq!(carol.receive(), ServerMessage::History(vec![]));
+
+    carol.send(ClientMessage::Export {
+        game: 1,
+        format: ExportFormat::Pgn,
+    });
+    match carol.receive() {
+        ServerMessage::Export { game: 1, text, .. } => {
+            assert!(text.contains("[White \"alice\"]"));
+            assert!(text.contains("[Result \"0-1\"]"));
+            assert!(text.contains("1. f3 e5 2. g4 Qh4# 0-1"));
+        }
+        message => panic!("expected an export, got {:?}", message),
+    }
+    carol.send(ClientMessage::Export {
+        game: 2,
+        format: ExportFormat::Kif,
+    });
+    match carol.receive() {
+        ServerMessage::Export { game: 2, text, .. } => assert!(text.contains("先手：bob")),
+        message => panic!("expected an export, got {:?}", message),
+    }
+    carol.send(ClientMessage::Export {
+        game: 5,
+        format: ExportFormat::Pgn,
+    });
+    assert_eq!(carol.error(), "unknown game 5");
+
+    // new games don't take the ids of stored ones
+    carol.send(create("standard", ColorChoice::White));
+    assert_eq!(carol.receive(), ServerMessage::GameCreated { game: 3 });
+}
+
+#[test]
+fn server_resume() {
+    let server = Server::start();
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+    assert_ne!(alice.token, bob.token);
+    alice.send(create("standard", ColorChoice::White));
+    assert_eq!(alice.receive(), ServerMessage::GameCreated { game: 1 });
+    bob.send(ClientMessage::JoinGame { game: 1 });
+    alice.snapshot();
+    bob.snapshot();
+    alice.send(play("e2e4"));
+    assert!(matches!(alice.receive(), ServerMessage::Move { .. }));
+    assert!(matches!(bob.receive(), ServerMessage::Move { .. }));
+
+    // the game goes on while alice is away
+    let token = alice.token.clone();
+    drop(alice);
+    assert_eq!(
+        bob.receive(),
+        ServerMessage::PlayerAway {
+            game: 1,
+            color: PlayerColor::White,
+            grace: Duration::from_secs(GRACE)
+        }
+    );
+    bob.send(play("e7e5"));
+    assert!(matches!(bob.receive(), ServerMessage::Move { .. }));
+
+    let mut mallory = Client::new(&server.address);
+    mallory.send(ClientMessage::Resume {
+        version: PROTOCOL_VERSION,
+        token: "guessed".to_string(),
+    });
+    assert_eq!(mallory.error(), "unknown session");
+
+    // alice comes back to her seat, and catches up with the moves she missed
+    let mut alice = Client::new(&server.address);
+    alice.send(ClientMessage::Resume {
+        version: PROTOCOL_VERSION,
+        token: token.clone(),
+    });
+    match alice.receive() {
+        ServerMessage::Welcome { token: resumed, .. } => assert_eq!(resumed, token),
+        message => panic!("expected a welcome, got {:?}", message),
+    }
+    assert_eq!(alice.history().0, ChatChannel::Lobby);
+    let snapshot = alice.snapshot();
+    assert_eq!(alice.history().0, ChatChannel::Players(1));
+    assert_eq!(snapshot.color, Some(PlayerColor::White));
+    assert_eq!(snapshot.moves, vec!["e2e4".to_string(), "e7e5".to_string()]);
+    assert_eq!(checksum(&snapshot.game().unwrap()), snapshot.checksum);
+    assert_eq!(
+        bob.receive(),
+        ServerMessage::PlayerBack {
+            game: 1,
+            color: PlayerColor::White
+        }
+    );
+    alice.send(play("g1f3"));
+    assert!(matches!(alice.receive(), ServerMessage::Move { .. }));
+    assert!(matches!(bob.receive(), ServerMessage::Move { .. }));
+
+    // a session can't be taken over by a client which already said hello
+    bob.send(ClientMessage::Resume {
+        version: PROTOCOL_VERSION,
+        token,
+    });
+    assert_eq!(bob.error(), "already introduced");
+}
+
+#[test]
+fn server_grace_period() {
+    let server = Server::with_grace(1);
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+    alice.send(create("standard", ColorChoice::White));
+    assert_eq!(alice.receive(), ServerMessage::GameCreated { game: 1 });
+    bob.send(ClientMessage::JoinGame { game: 1 });
+    alice.snapshot();
+    bob.snapshot();
+
+    let token = bob.token.clone();
+    let left = Instant::now();
+    drop(bob);
+    assert!(matches!(alice.receive(), ServerMessage::PlayerAway { .. }));
+    assert_eq!(
+        alice.receive(),
+        ServerMessage::GameOver {
+            game: 1,
+            result: GameResult::WhiteWins,
+            reason: "forfeit".to_string()
+        }
+    );
+    assert!(left.elapsed() >= Duration::from_secs(1));
+
+    // the session is gone with the game
+    let mut bob = Client::new(&server.address);
+    bob.send(ClientMessage::Resume {
+        version: PROTOCOL_VERSION,
+        token,
+    });
+    assert_eq!(bob.error(), "unknown session");
+}
+
+/// Starts a timed game between alice, with white, and bob
+fn timed(server: &Server, time: TimeControl) -> (Client, Client) {
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+    alice.send(ClientMessage::CreateGame {
+        variant: "standard".to_string(),
+        color: ColorChoice::White,
+        time: Some(time),
+    });
+    assert_eq!(alice.receive(), ServerMessage::GameCreated { game: 1 });
+    bob.send(ClientMessage::JoinGame { game: 1 });
+    (alice, bob)
+}
+
+fn clock(client: &mut Client) -> ClockUpdate {
+    match client.receive() {
+        ServerMessage::Clock(clock) => clock,
+        message => panic!("expected a clock, got {:?}", message),
+    }
+}
+
+#[test]
+fn server_clock() {
+    let server = Server::start();
+    let time = TimeControl::new(Duration::from_secs(1), Duration::from_secs(2));
+    let (mut alice, mut bob) = timed(&server, time);
+    let snapshot = alice.snapshot();
+    assert_eq!(snapshot.time, Some(time));
+    let start = snapshot.clock.unwrap();
+    assert_eq!(start.running, Some(PlayerColor::White));
+    assert!(start.white <= time.base && start.white > Duration::from_millis(500));
+    assert_eq!(start.black, time.base);
+    bob.snapshot();
+
+    // the mover's clock stops and gets the increment, the other one starts
+    alice.send(play("e2e4"));
+    for client in &mut [&mut alice, &mut bob] {
+        assert!(matches!(client.receive(), ServerMessage::Move { .. }));
+        let clock = clock(client);
+        assert_eq!(clock.running, Some(PlayerColor::Black));
+        assert!(
+            clock.white > Duration::from_millis(2500) && clock.white <= time.base + time.increment
+        );
+        assert!(clock.black <= time.base);
+    }
+
+    // bob doesn't move in time
+    let waited = Instant::now();
+    for client in &mut [&mut alice, &mut bob] {
+        assert_eq!(
+            client.receive(),
+            ServerMessage::GameOver {
+                game: 1,
+                result: GameResult::WhiteWins,
+                reason: "time forfeit".to_string()
+            }
+        );
+    }
+    assert!(waited.elapsed() >= Duration::from_millis(500));
+}
+
+/// What becomes of a move played `late` milliseconds after the player's clock started, on a connection that is slow to answer
+fn late_move(max_lag: u64, late: u64) -> ServerMessage {
+    let server = Server::with_options(GRACE, max_lag);
+    // at most 200ms of latency are taken off a player's clock over the game
+    let time = TimeControl::new(Duration::from_secs(2), Duration::from_secs(0));
+    let (mut alice, mut bob) = timed(&server, time);
+    // bob's answers to pings take long, which the server takes for latency
+    bob.delay = Duration::from_millis(800);
+    bob.snapshot();
+    alice.snapshot();
+    alice.send(play("e2e4"));
+    for client in &mut [&mut alice, &mut bob] {
+        assert!(matches!(client.receive(), ServerMessage::Move { .. }));
+        assert_eq!(clock(client).running, Some(PlayerColor::Black));
+    }
+    thread::sleep(Duration::from_millis(late));
+    bob.send(play("e7e5"));
+    alice.receive()
+}
+
+#[test]
+fn server_lag_compensation() {
+    let forfeit = ServerMessage::GameOver {
+        game: 1,
+        result: GameResult::WhiteWins,
+        reason: "time forfeit".to_string(),
+    };
+    // some latency is taken off bob's move
+    assert!(matches!(
+        late_move(MAX_LAG, 2100),
+        ServerMessage::Move { .. }
+    ));
+    // unless the server doesn't compensate latency
+    assert_eq!(late_move(0, 2100), forfeit);
+    // and slow answers to pings don't buy more than the game allows, though bob seems 400ms away
+    assert_eq!(late_move(MAX_LAG, 2300), forfeit);
+}
+
+#[test]
+fn server_chat() {
+    let server = Server::start();
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+    let mut carol = server.connect("carol");
+    let say = |channel: ChatChannel, text: &str| ClientMessage::Chat {
+        channel,
+        text: text.to_string(),
+    };
+
+    // everyone connected hears the lobby
+    alice.send(say(ChatChannel::Lobby, " hello "));
+    for client in &mut [&mut alice, &mut bob, &mut carol] {
+        let message = client.chat();
+        assert_eq!(message.channel, ChatChannel::Lobby);
+        assert_eq!(message.from_name, "alice");
+        assert_eq!(message.text, "hello");
+    }
+    alice.send(say(ChatChannel::Lobby, "   "));
+    assert_eq!(alice.error(), "empty message");
+    alice.send(say(ChatChannel::Lobby, &"a".repeat(MAX_CHAT_LENGTH + 1)));
+    assert_eq!(
+        alice.error(),
+        format!("messages are limited to {} characters", MAX_CHAT_LENGTH)
+    );
+    // and those who join get what was said
+    let mut dave = Client::new(&server.address);
+    dave.send(ClientMessage::Hello {
+        version: PROTOCOL_VERSION,
+        name: "dave".to_string(),
+    });
+    assert!(matches!(dave.receive(), ServerMessage::Welcome { .. }));
+    let (channel, history) = dave.history();
+    assert_eq!(channel, ChatChannel::Lobby);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].text, "hello");
+
+    // players and spectators of a game don't hear each other
+    alice.send(create("standard", ColorChoice::White));
+    alice.receive();
+    bob.send(say(ChatChannel::Players(1), "hi"));
+    assert_eq!(bob.error(), "not in this chat");
+    bob.send(ClientMessage::JoinGame { game: 1 });
+    alice.snapshot();
+    bob.snapshot();
+    carol.send(ClientMessage::Watch { game: 1 });
+    carol.snapshot();
+    carol.history();
+    for client in &mut [&mut alice, &mut bob, &mut carol] {
+        assert!(matches!(client.receive(), ServerMessage::Spectators { .. }));
+    }
+    alice.send(say(ChatChannel::Players(1), "good luck"));
+    assert_eq!(alice.chat().text, "good luck");
+    assert_eq!(bob.chat().text, "good luck");
+    carol.send(say(ChatChannel::Spectators(1), "white is winning"));
+    assert_eq!(carol.chat().text, "white is winning");
+    carol.send(say(ChatChannel::Players(1), "play e4"));
+    assert_eq!(carol.error(), "not in this chat");
+    alice.send(say(ChatChannel::Spectators(1), "thanks"));
+    assert_eq!(alice.error(), "not in this chat");
+
+    // muted users aren't heard
+    bob.send(ClientMessage::Mute {
+        name: "alice".to_string(),
+    });
+    assert_eq!(
+        bob.receive(),
+        ServerMessage::Muted(vec!["alice".to_string()])
+    );
+    alice.send(say(ChatChannel::Players(1), "resign already"));
+    assert_eq!(alice.chat().text, "resign already");
+    bob.send(ClientMessage::Unmute {
+        name: "alice".to_string(),
+    });
+    assert_eq!(bob.receive(), ServerMessage::Muted(vec![]));
+    alice.send(say(ChatChannel::Players(1), "sorry"));
+    assert_eq!(alice.chat().text, "sorry");
+    assert_eq!(bob.chat().text, "sorry");
+
+    // nor in the history
+    dave.send(ClientMessage::Mute {
+        name: "alice".to_string(),
+    });
+    dave.receive();
+    dave.send(ClientMessage::Watch { game: 1 });
+    dave.snapshot();
+    assert_eq!(dave.history().1.len(), 1);
+}
@@ -0,0 +1,148 @@
+use sharmat::game::*;
+use sharmat::player::*;
+use sharmat::sfen::*;
+use sharmat::variant::*;
+
+const START: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+fn shogi() -> Game {
+    Variant::load(format!(
+        "{}/variants/shogi.toml",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+fn hand(game: &Game, color: PlayerColor) -> Vec<&str> {
+    let mut res: Vec<&str> = game
+        .player(color)
+        .unwrap()
+        .hand
+        .iter()
+        .map(|&p| game.pieces()[p].id())
+        .collect();
+    res.sort();
+    res
+}
+
+#[test]
+fn sfen_start_position() {
+    let game = shogi();
+    assert_eq!(game.sfen().unwrap(), START);
+    assert_eq!(game.current_player().unwrap().color, PlayerColor::White);
+    let rook = game.board().get(7, 1).unwrap().unwrap();
+    assert_eq!(game.pieces()[rook.0].id(), "shogi.rook");
+    assert_eq!(rook.1, PlayerColor::White);
+}
+
+#[test]
+fn sfen_handicaps() {
+    let mut game = shogi();
+    for sfen in &[
+        // lance
+        "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+        // bishop
+        "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+        // rook
+        "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+        // two pieces
+        "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+        // six pieces
+        "2sgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ] {
+        game.load_sfen(sfen).unwrap();
+        assert_eq!(game.current_player().unwrap().color, PlayerColor::Black);
+        assert_eq!(game.ply_number(), 1);
+        assert_eq!(&game.sfen().unwrap(), sfen);
+    }
+}
+
+#[test]
+fn sfen_hands_and_promotions() {
+    let mut game = shogi();
+    let sfen = "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124";
+    game.load_sfen(sfen).unwrap();
+    assert_eq!(game.sfen().unwrap(), sfen);
+    assert_eq!(game.ply_number(), 124);
+    assert_eq!(game.fullmove_number(), 62);
+    assert_eq!(hand(&game, PlayerColor::White), vec!["shogi.silver"]);
+    assert_eq!(
+        hand(&game, PlayerColor::Black),
+        vec![
+            "shogi.bishop",
+            "shogi.gold",
+            "shogi.knight",
+            "shogi.pawn",
+            "shogi.pawn",
+            "shogi.pawn"
+        ]
+    );
+
+    let dragon = game.board().get(2, 7).unwrap().unwrap();
+    assert_eq!(game.pieces()[dragon.0].id(), "shogi.prook");
+    assert_eq!(dragon.1, PlayerColor::White);
+    let tokin = game.board().get(4, 0).unwrap().unwrap();
+    assert_eq!(game.pieces()[tokin.0].id(), "shogi.ppawn");
+    assert_eq!(tokin.1, PlayerColor::Black);
+}
+
+#[test]
+fn sfen_large_hand_counts() {
+    let mut game = shogi();
+    let sfen = "4k4/9/9/9/9/9/9/9/4K4 b 2R2B4G4S4N4L9P9p 1";
+    game.load_sfen(sfen).unwrap();
+    assert_eq!(game.player(PlayerColor::White).unwrap().hand.len(), 29);
+    assert_eq!(game.sfen().unwrap(), sfen);
+    game.load_sfen("4k4/9/9/9/9/9/9/9/4K4 b 18P 1").unwrap();
+    assert_eq!(game.player(PlayerColor::White).unwrap().hand.len(), 18);
+}
+
+#[test]
+fn sfen_errors() {
+    let mut game = shogi();
+    assert_eq!(
+        game.load_sfen(""),
+        Err(SfenError::MissingField("placement"))
+    );
+    assert!(matches!(
+        game.load_sfen("9/9/9 b - 1"),
+        Err(SfenError::InvalidPlacement(_))
+    ));
+    assert!(matches!(
+        game.load_sfen("8+/9/9/9/9/9/9/9/9 b - 1"),
+        Err(SfenError::InvalidPlacement(_))
+    ));
+    assert_eq!(
+        game.load_sfen("+k8/9/9/9/9/9/9/9/9 b - 1"),
+        Err(SfenError::UnknownPiece(String::from("+K")))
+    );
+    assert_eq!(
+        game.load_sfen("9/9/9/9/9/9/9/9/9 x - 1"),
+        Err(SfenError::InvalidColor(String::from("x")))
+    );
+    assert_eq!(
+        game.load_sfen("9/9/9/9/9/9/9/9/9 b 2X 1"),
+        Err(SfenError::InvalidHand(String::from("2X")))
+    );
+    assert_eq!(
+        game.load_sfen("9/9/9/9/9/9/9/9/9 b P2 1"),
+        Err(SfenError::InvalidHand(String::from("P2")))
+    );
+    assert!(matches!(
+        game.load_sfen("99999999999999999999999/9/9/9/9/9/9/9/9 b - 1"),
+        Err(SfenError::InvalidPlacement(_))
+    ));
+    assert_eq!(
+        game.load_sfen("9/9/9/9/9/9/9/9/9 b 100000000000P 1"),
+        Err(SfenError::InvalidHand(String::from("100000000000P")))
+    );
+    assert_eq!(
+        game.load_sfen("9/9/9/9/9/9/9/9/9 b 99999999999999999999999P 1"),
+        Err(SfenError::InvalidHand(String::from(
+            "99999999999999999999999P"
+        )))
+    );
+    assert_eq!(game.sfen().unwrap(), START);
+}
@@ -0,0 +1,216 @@
+use sharmat::protocol::*;
+use sharmat::storage::*;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A fresh directory, removed when dropped
+struct Dir(PathBuf);
+
+impl Dir {
+    fn new(name: &str) -> Dir {
+        let dir = env::temp_dir().join(format!("sharmat-storage-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Dir(dir)
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn stored(id: u64, variant: &str, players: [&str; 2], moves: &[&str], day: u64) -> StoredGame {
+    StoredGame {
+        id,
+        variant: variant.to_string(),
+        definition: fs::read_to_string(format!(
+            "{}/variants/{}.toml",
+            env!("CARGO_MANIFEST_DIR"),
+            variant
+        ))
+        .unwrap(),
+        players: [players[0].to_string(), players[1].to_string()],
+        moves: moves.iter().map(|mv| mv.to_string()).collect(),
+        result: None,
+        reason: None,
+        started: UNIX_EPOCH + Duration::from_secs(day * 86400 + 3600),
+        ended: None,
+        time: None,
+    }
+}
+
+#[test]
+fn storage_save_load() {
+    let dir = Dir::new("save");
+    let mut store = GameStore::open(&dir.0).unwrap();
+    assert_eq!(store.ids().unwrap(), Vec::<u64>::new());
+
+    let mut game = stored(7, "standard", ["alice", "bob"], &["e2e4"], 0);
+    store.save(&game).unwrap();
+    assert_eq!(store.load(7).unwrap(), game);
+
+    // saving again replaces the game
+    game.moves.push("e7e5".to_string());
+    game.result = Some(GameResult::Draw);
+    game.reason = Some("stalemate".to_string());
+    game.ended = Some(SystemTime::now());
+    game.time = Some("3+2".parse().unwrap());
+    store.save(&game).unwrap();
+    assert_eq!(store.load(7).unwrap(), game);
+    assert_eq!(game.game().unwrap().ply_number(), 3);
+
+    store
+        .save(&stored(12, "shogi", ["carol", "dave"], &[], 0))
+        .unwrap();
+    fs::write(dir.0.join("notes.txt"), "not a game").unwrap();
+    assert_eq!(store.ids().unwrap(), vec![7, 12]);
+    // a store opened again finds the same games
+    assert_eq!(
+        GameStore::open(&dir.0).unwrap().load(12).unwrap().players[0],
+        "carol"
+    );
+
+    assert!(matches!(store.load(3), Err(StorageError::UnknownGame(3))));
+    fs::write(dir.0.join("3.toml"), "id = \"three\"").unwrap();
+    assert!(matches!(store.load(3), Err(StorageError::Encoding(_))));
+}
+
+#[test]
+fn storage_find() {
+    let dir = Dir::new("find");
+    let mut store = GameStore::open(&dir.0).unwrap();
+    store
+        .save(&stored(1, "standard", ["alice", "bob"], &[], 1))
+        .unwrap();
+    store
+        .save(&stored(2, "standard", ["bob", "carol"], &[], 2))
+        .unwrap();
+    store
+        .save(&stored(3, "shogi", ["carol", "alice"], &[], 3))
+        .unwrap();
+    let day = |day: u64| Some(UNIX_EPOCH + Duration::from_secs(day * 86400));
+    let ids = |query: GameQuery| -> Vec<u64> {
+        store.find(&query).iter().map(|game| game.game).collect()
+    };
+
+    assert_eq!(ids(GameQuery::default()), vec![1, 2, 3]);
+    let alice = GameQuery {
+        player: Some("alice".to_string()),
+        ..GameQuery::default()
+    };
+    assert_eq!(ids(alice.clone()), vec![1, 3]);
+    assert_eq!(
+        ids(GameQuery {
+            since: day(2),
+            ..GameQuery::default()
+        }),
+        vec![2, 3]
+    );
+    assert_eq!(
+        ids(GameQuery {
+            since: day(2),
+            until: day(3),
+            ..GameQuery::default()
+        }),
+        vec![2]
+    );
+    assert_eq!(
+        ids(GameQuery {
+            until: day(2),
+            ..alice
+        }),
+        vec![1]
+    );
+    assert_eq!(
+        ids(GameQuery {
+            player: Some("dave".to_string()),
+            ..GameQuery::default()
+        }),
+        Vec::<u64>::new()
+    );
+}
+
+#[test]
+fn storage_pages() {
+    let dir = Dir::new("pages");
+    let mut store = GameStore::open(&dir.0).unwrap();
+    let count = MAX_FOUND as u64 + 5;
+    for id in 1..=count {
+        store
+            .save(&stored(id, "standard", ["alice", "bob"], &[], 1))
+            .unwrap();
+    }
+    let first = store.find(&GameQuery::default());
+    assert_eq!(first.len(), MAX_FOUND);
+    let next = store.find(&GameQuery {
+        after: first.last().map(|game| game.game),
+        ..GameQuery::default()
+    });
+    let ids: Vec<u64> = next.iter().map(|game| game.game).collect();
+    assert_eq!(ids, (MAX_FOUND as u64 + 1..=count).collect::<Vec<u64>>());
+
+    // an unreadable game is left out of queries, but its id stays taken
+    fs::write(dir.0.join("3.toml"), "id = \"three\"").unwrap();
+    let store = GameStore::open(&dir.0).unwrap();
+    assert_eq!(store.unreadable().len(), 1);
+    assert_eq!(store.unreadable()[0].0, 3);
+    assert!(store
+        .find(&GameQuery::default())
+        .iter()
+        .all(|game| game.game != 3));
+    assert_eq!(store.ids().unwrap().len() as u64, count);
+}
+
+#[test]
+fn storage_export() {
+    let mut game = stored(
+        4,
+        "standard",
+        ["alice", "bob"],
+        &["f2f3", "e7e5", "g2g4", "d8h4"],
+        18000,
+    );
+    game.result = Some(GameResult::BlackWins);
+    game.reason = Some("checkmate".to_string());
+    game.time = Some("5+3".parse().unwrap());
+    let pgn = game.export(ExportFormat::Pgn).unwrap();
+    for tag in &[
+        "[Event \"Game 4\"]",
+        "[Date \"2019.04.14\"]",
+        "[White \"alice\"]",
+        "[Black \"bob\"]",
+        "[Result \"0-1\"]",
+        "[Variant \"standard\"]",
+        "[TimeControl \"300+3\"]",
+        "[Termination \"checkmate\"]",
+    ] {
+        assert!(pgn.contains(tag), "{} in {}", tag, pgn);
+    }
+    assert!(pgn.contains("1. f3 e5 2. g4 Qh4# 0-1"));
+
+    // games still being played have no result
+    game.result = None;
+    game.reason = None;
+    assert!(game.pgn().unwrap().contains("[Result \"*\"]"));
+
+    let mut shogi = stored(5, "shogi", ["carol", "dave"], &["c3c4", "g7g6"], 18000);
+    shogi.reason = Some("forfeit".to_string());
+    shogi.result = Some(GameResult::BlackWins);
+    let kif = shogi.export(ExportFormat::Kif).unwrap();
+    for line in &[
+        "開始日時：2019/04/14 01:00:00",
+        "先手：carol",
+        "後手：dave",
+        "投了",
+    ] {
+        assert!(kif.contains(line), "{} in {}", line, kif);
+    }
+    assert!(matches!(game.kif(), Err(StorageError::Export(_))));
+
+    shogi.moves.push("e2e4".to_string());
+    assert!(matches!(shogi.pgn(), Err(StorageError::IllegalMove(_))));
+}
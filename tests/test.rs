@@ -27,6 +27,6 @@ mod rule {
 
     #[test]
     fn rule_create() {
-        let _rule = Rule::new(/* UNKNOWN */);
+        let _rule = Rule::Drops;
     }
 }
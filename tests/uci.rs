@@ -0,0 +1,145 @@
+use sharmat::engine::*;
+use sharmat::game::*;
+use sharmat::player::*;
+use sharmat::uci::*;
+use sharmat::variant::*;
+use std::time::Duration;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+#[test]
+fn uci_commands() {
+    assert_eq!("uci".parse(), Ok(UciCommand::Uci));
+    assert_eq!("  isready ".parse(), Ok(UciCommand::IsReady));
+    assert_eq!(
+        "setoption name UCI_Variant value shogi".parse(),
+        Ok(UciCommand::SetOption {
+            name: "UCI_Variant".to_string(),
+            value: Some("shogi".to_string()),
+        })
+    );
+    assert_eq!(
+        "setoption name Clear Hash".parse(),
+        Ok(UciCommand::SetOption {
+            name: "Clear Hash".to_string(),
+            value: None,
+        })
+    );
+    assert_eq!(
+        "position startpos moves e2e4 e7e5".parse(),
+        Ok(UciCommand::Position {
+            fen: None,
+            moves: vec!["e2e4".to_string(), "e7e5".to_string()],
+        })
+    );
+    assert_eq!(
+        "position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1".parse(),
+        Ok(UciCommand::Position {
+            fen: Some("4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string()),
+            moves: vec![],
+        })
+    );
+    assert_eq!("".parse::<UciCommand>(), Err(UciError::Empty));
+    assert_eq!(
+        "position".parse::<UciCommand>(),
+        Err(UciError::MissingArgument("startpos or fen"))
+    );
+    assert_eq!(
+        "hello".parse::<UciCommand>(),
+        Err(UciError::UnknownCommand("hello".to_string()))
+    );
+}
+
+#[test]
+fn uci_go() {
+    let go = match "go wtime 60000 btime -10 winc 1000 movestogo 20".parse() {
+        Ok(UciCommand::Go(go)) => go,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(go.btime, Some(Duration::from_millis(0)));
+    assert_eq!(
+        go.limits(PlayerColor::White),
+        SearchLimits {
+            clock: Some(Clock {
+                remaining: Duration::from_secs(60),
+                increment: Duration::from_secs(1),
+                moves_to_go: Some(20),
+            }),
+            ..SearchLimits::default()
+        }
+    );
+    assert_eq!(
+        go.limits(PlayerColor::Black).clock.unwrap().increment,
+        Duration::default()
+    );
+
+    let go = match "go depth 5 infinite".parse() {
+        Ok(UciCommand::Go(go)) => go,
+        other => panic!("{:?}", other),
+    };
+    assert!(go.infinite);
+    assert_eq!(go.limits(PlayerColor::White).depth, Some(5));
+    assert_eq!(
+        "go movetime x".parse::<UciCommand>(),
+        Err(UciError::InvalidNumber("x".to_string()))
+    );
+}
+
+#[test]
+fn uci_moves() {
+    let mut game = load("standard");
+    let mv = game.parse_uci_move("g1f3").unwrap();
+    assert_eq!(game.move_name(&mv), "g1-f3");
+    assert_eq!(
+        game.parse_uci_move("e2e5"),
+        Err(UciError::IllegalMove("e2e5".to_string()))
+    );
+
+    game.load_fen("4k3/P7/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    let castling = game.parse_uci_move("e1g1").unwrap();
+    assert_eq!(castling.castling(), Some(7));
+    let promotion = game.parse_uci_move("a7a8n").unwrap();
+    assert_eq!(promotion.promotion, game.piece_index("N"));
+    assert_eq!(game.uci_move(&promotion), "a7a8n");
+
+    let mut shogi = load("shogi");
+    shogi.load_sfen("4k4/9/9/9/9/2P6/9/9/4K4 b P 1").unwrap();
+    let drop = shogi.parse_uci_move("P@e5").unwrap();
+    assert!(drop.is_drop());
+    assert_eq!(drop.to, (4, 4));
+    shogi.load_sfen("4k4/9/2P6/9/9/9/9/9/4K4 b - 1").unwrap();
+    let promotion = shogi.parse_uci_move("c7c8+").unwrap();
+    assert_eq!(promotion.promotion, shogi.piece_index("+P"));
+    assert!(shogi.parse_uci_move("c7c8").is_ok());
+}
+
+#[test]
+fn uci_info() {
+    let mut game = load("standard");
+    game.load_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1")
+        .unwrap();
+    let mut engine = EngineBuilder::new().build(&game);
+    let info = engine.search(
+        &game,
+        &SearchLimits {
+            depth: Some(2),
+            ..SearchLimits::default()
+        },
+    );
+    let line = info_line(&game, &info);
+    assert!(
+        line.starts_with("info depth 2 score mate 1 nodes "),
+        "{}",
+        line
+    );
+    assert!(line.ends_with(" pv a1a8"), "{}", line);
+}
@@ -0,0 +1,135 @@
+use sharmat::engine::*;
+use sharmat::game::*;
+use sharmat::player::*;
+use sharmat::usi::*;
+use sharmat::variant::*;
+use std::time::Duration;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+#[test]
+fn usi_commands() {
+    assert_eq!("usi".parse(), Ok(UsiCommand::Usi));
+    assert_eq!(
+        "setoption name USI_Hash value 64".parse(),
+        Ok(UsiCommand::SetOption {
+            name: "USI_Hash".to_string(),
+            value: Some("64".to_string()),
+        })
+    );
+    assert_eq!(
+        "position startpos moves 7g7f 3c3d".parse(),
+        Ok(UsiCommand::Position {
+            sfen: None,
+            moves: vec!["7g7f".to_string(), "3c3d".to_string()],
+        })
+    );
+    assert_eq!(
+        "position sfen 4k4/9/9/9/9/9/9/9/4K4 b P 1 moves P*5e".parse(),
+        Ok(UsiCommand::Position {
+            sfen: Some("4k4/9/9/9/9/9/9/9/4K4 b P 1".to_string()),
+            moves: vec!["P*5e".to_string()],
+        })
+    );
+    assert_eq!(
+        "gameover win".parse(),
+        Ok(UsiCommand::GameOver("win".to_string()))
+    );
+    assert_eq!(
+        "position".parse::<UsiCommand>(),
+        Err(UsiError::MissingArgument("startpos or sfen"))
+    );
+}
+
+#[test]
+fn usi_go() {
+    let go = match "go btime 60000 wtime 30000 byoyomi 10000".parse() {
+        Ok(UsiCommand::Go(go)) => go,
+        other => panic!("{:?}", other),
+    };
+    // sente plays white
+    assert_eq!(
+        go.limits(PlayerColor::White).clock,
+        Some(Clock {
+            remaining: Duration::from_secs(70),
+            increment: Duration::from_secs(10),
+            moves_to_go: None,
+        })
+    );
+    assert_eq!(
+        go.limits(PlayerColor::Black).clock.unwrap().remaining,
+        Duration::from_secs(40)
+    );
+
+    let go = match "go btime 0 wtime 0 byoyomi 0 infinite".parse() {
+        Ok(UsiCommand::Go(go)) => go,
+        other => panic!("{:?}", other),
+    };
+    assert!(go.infinite);
+    let go = match "go ponder".parse() {
+        Ok(UsiCommand::Go(go)) => go,
+        other => panic!("{:?}", other),
+    };
+    assert!(go.ponder);
+    assert_eq!(
+        go.limits(PlayerColor::White),
+        SearchLimits {
+            ponder: true,
+            ..SearchLimits::default()
+        }
+    );
+}
+
+#[test]
+fn usi_moves() {
+    let mut game = load("shogi");
+    let mv = game.parse_usi_move("7g7f").unwrap();
+    assert_eq!((mv.from, mv.to), (Some((2, 2)), (2, 3)));
+    assert_eq!(game.usi_move(&mv), "7g7f");
+    game.play(&mv).unwrap();
+    game.play(&game.parse_usi_move("3c3d").unwrap()).unwrap();
+    let capture = game.parse_usi_move("8h2b+").unwrap();
+    assert!(capture.is_capture());
+    assert_eq!(capture.promotion, game.piece_index("+B"));
+    assert!(game.parse_usi_move("8h2b").is_ok());
+    assert_eq!(
+        game.parse_usi_move("5e5d"),
+        Err(UsiError::IllegalMove("5e5d".to_string()))
+    );
+
+    game.load_sfen("4k4/9/9/9/9/9/9/9/4K4 b P 1").unwrap();
+    let drop = game.parse_usi_move("P*5e").unwrap();
+    assert!(drop.is_drop());
+    assert_eq!(drop.to, (4, 4));
+}
+
+#[test]
+fn usi_info() {
+    let mut game = load("shogi");
+    // the gold drop on 5b mates
+    game.load_sfen("4k4/9/4P4/9/9/9/9/9/4K4 b G 1").unwrap();
+    let mut engine = EngineBuilder::new().build(&game);
+    let info = engine.search(
+        &game,
+        &SearchLimits {
+            depth: Some(2),
+            ..SearchLimits::default()
+        },
+    );
+    let line = info_line(&game, &info);
+    assert!(
+        line.starts_with("info depth 2 score mate 1 nodes "),
+        "{}",
+        line
+    );
+    assert!(line.ends_with(" pv G*5b"), "{}", line);
+}
@@ -0,0 +1,95 @@
+use sharmat::game::*;
+use sharmat::movement::*;
+use sharmat::piece::*;
+use sharmat::value::*;
+use sharmat::variant::*;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+#[test]
+fn value_chess() {
+    let game = load("standard");
+    let values = game.estimate_values(&EstimatorBuilder::new().build());
+    let value = |name: &str| values[game.piece_index(name).unwrap()];
+    assert_eq!(value("P"), 100);
+    assert_eq!(value("K"), 0);
+    assert!(value("N") > 2 * value("P"));
+    assert!(value("R") > value("B"));
+    assert!(value("R") > value("N"));
+    assert!(value("Q") > value("R") + value("B"));
+}
+
+#[test]
+fn value_shogi() {
+    let game = load("shogi");
+    let values = game.estimate_values(&EstimatorBuilder::new().build());
+    let value = |name: &str| values[game.piece_index(name).unwrap()];
+    let order = ["P", "N", "L", "S", "G", "B", "R"];
+    for pair in order.windows(2) {
+        assert!(value(pair[0]) < value(pair[1]), "{:?}", pair);
+    }
+    assert!(value("+B") > value("B"));
+    assert!(value("+R") > value("R"));
+}
+
+#[test]
+fn value_statistics() {
+    let game = load("standard");
+    let estimator = EstimatorBuilder::new().samples(200).build();
+    let estimate =
+        |name: &str| estimator.estimate(&game.pieces()[game.piece_index(name).unwrap()], 8, 8);
+
+    let knight = estimate("N");
+    assert!(knight.mobility > 3.0 && knight.mobility < 8.0);
+    assert_eq!(knight.safe_checks, knight.mobility);
+    assert!(knight.forward_bias.abs() < 0.1);
+    assert!(!knight.colorbound);
+
+    let bishop = estimate("B");
+    assert!(bishop.colorbound);
+    assert!(bishop.safe_checks < bishop.mobility);
+
+    let pawn = estimate("P");
+    assert_eq!(pawn.forward_bias, 1.0);
+    assert!(!pawn.colorbound);
+
+    // the same seed gives the same estimates
+    assert_eq!(estimate("Q"), estimate("Q"));
+
+    // boards that can't be built give a neutral estimate, and larger boards a real one
+    let queen = &game.pieces()[game.piece_index("Q").unwrap()];
+    for &(width, height) in &[(0, 8), (8, 0)] {
+        let neutral = estimator.estimate(queen, width, height);
+        assert_eq!((neutral.mobility, neutral.value), (0.0, 0));
+    }
+    assert!(estimator.estimate(queen, 20, 20).mobility > estimate("Q").mobility);
+}
+
+#[test]
+fn value_fairy_piece() {
+    let ferz = PieceBuilder::new()
+        .id("fairy.ferz")
+        .movement(vec![MovementType::Undirected(1, 1)])
+        .build();
+    let wazir = PieceBuilder::new()
+        .id("fairy.wazir")
+        .movement(vec![MovementType::Undirected(1, 0)])
+        .build();
+    let estimator = EstimatorBuilder::new().density(0.0).samples(100).build();
+    let ferz = estimator.estimate(&ferz, 8, 8);
+    let wazir = estimator.estimate(&wazir, 8, 8);
+    // both have 3.5 moves on average on an empty board, but the ferz is colorbound
+    assert!((ferz.mobility - wazir.mobility).abs() < 0.5);
+    assert_eq!(ferz.safe_checks, 0.0);
+    assert!(ferz.colorbound);
+    assert!(ferz.value < wazir.value);
+}
@@ -0,0 +1,168 @@
+use sharmat::movement::*;
+use sharmat::player::*;
+use sharmat::rule::*;
+use sharmat::variant::*;
+
+fn variant_path(name: &str) -> String {
+    format!("{}/variants/{}.toml", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn variant_load_standard() {
+    let variant = Variant::load(variant_path("standard")).unwrap();
+    assert_eq!(variant.name, "Standard chess");
+    let game = variant.game().unwrap();
+    assert_eq!(game.board().width.get(), 8);
+    assert_eq!(game.board().height.get(), 8);
+    assert_eq!(game.pieces().len(), 6);
+    assert_eq!(game.players.len(), 2);
+    assert_eq!(game.board().name(), "Standard chess");
+    assert!(game
+        .rules()
+        .contains(&Rule::Royal(String::from("standard.king"))));
+
+    let king = game.board().get(4, 0).unwrap().unwrap();
+    assert_eq!(game.pieces()[king.0].id(), "standard.king");
+    assert_eq!(king.1, PlayerColor::White);
+    let queen = game.board().get(3, 7).unwrap().unwrap();
+    assert_eq!(game.pieces()[queen.0].id(), "standard.queen");
+    assert_eq!(queen.1, PlayerColor::Black);
+    assert_eq!(game.board().get(4, 4).unwrap(), None);
+}
+
+#[test]
+fn variant_load_shogi() {
+    let game = Variant::load(variant_path("shogi"))
+        .unwrap()
+        .game()
+        .unwrap();
+    assert_eq!(game.board().width.get(), 9);
+    assert_eq!(game.pieces().len(), 14);
+    assert!(game.rules().contains(&Rule::Drops));
+    let bishop = game.board().get(1, 1).unwrap().unwrap();
+    assert_eq!(
+        game.pieces()[bishop.0].display_white(),
+        "shogi.sente_bishop"
+    );
+}
+
+#[test]
+fn variant_pawn_double_step() {
+    let game = Variant::load(variant_path("standard"))
+        .unwrap()
+        .game()
+        .unwrap();
+    let pawn = game.search_piece("standard.pawn").unwrap();
+    let white = game.player(PlayerColor::White).unwrap();
+    let black = game.player(PlayerColor::Black).unwrap();
+    let index = game.piece_index("standard.pawn").unwrap();
+    let targets = |moves: Vec<Move>| moves.into_iter().map(|mv| mv.to).collect::<Vec<_>>();
+    let white_moves = pawn.movement_type()[0].moves(game.board(), white, index, 4, 1);
+    assert_eq!(targets(white_moves), vec![(4, 2), (4, 3)]);
+    let black_moves = pawn.movement_type()[0].moves(game.board(), black, index, 4, 6);
+    assert_eq!(targets(black_moves), vec![(4, 4), (4, 5)]);
+
+    // the double step cannot jump over a piece
+    let mut board = game.board().clone();
+    board.set(4, 2, board.get(6, 0).unwrap()).unwrap();
+    let blocked = pawn.movement_type()[0].moves(&board, white, index, 4, 1);
+    assert!(blocked.is_empty());
+}
+
+#[test]
+fn variant_inline() {
+    let variant: Variant = r#"
+        name = "Tiny"
+        width = 3
+        height = 3
+
+        [[piece]]
+        id = "tiny.king"
+        alias = ["king"]
+        movement = { union = [{ undirected = [1, 0] }, { undirected = [1, 1] }] }
+
+        [[setup]]
+        piece = "king"
+        color = "black"
+        squares = [[1, 2]]
+    "#
+    .parse()
+    .unwrap();
+    let game = variant.game().unwrap();
+    assert_eq!(game.players.len(), 2);
+    assert_eq!(
+        game.board().get(1, 2).unwrap(),
+        Some((0, PlayerColor::Black))
+    );
+}
+
+#[test]
+fn variant_unknown_piece() {
+    let variant: Variant = r#"
+        name = "Broken"
+        width = 3
+        height = 3
+
+        [[setup]]
+        piece = "king"
+        color = "white"
+        squares = [[0, 0]]
+    "#
+    .parse()
+    .unwrap();
+    match variant.game() {
+        Err(VariantError::UnknownPiece(p)) => assert_eq!(p, "king"),
+        x => panic!("Expected UnknownPiece, got {:?}", x),
+    }
+}
+
+#[test]
+fn variant_out_of_bounds() {
+    let variant: Variant = r#"
+        name = "Broken"
+        width = 3
+        height = 3
+
+        [[piece]]
+        id = "king"
+        movement = { stay = true }
+
+        [[setup]]
+        piece = "king"
+        color = "white"
+        squares = [[3, 0]]
+    "#
+    .parse()
+    .unwrap();
+    match variant.game() {
+        Err(VariantError::OutOfBounds(3, 0)) => {}
+        x => panic!("Expected OutOfBounds, got {:?}", x),
+    }
+}
+
+#[test]
+fn variant_invalid_movement() {
+    let variant: Variant = r#"
+        name = "Broken"
+        width = 3
+        height = 3
+
+        [[piece]]
+        id = "king"
+        movement = { undirected = [1, 0], directed = [0, 1] }
+    "#
+    .parse()
+    .unwrap();
+    match variant.game() {
+        Err(VariantError::InvalidMovement(_)) => {}
+        x => panic!("Expected InvalidMovement, got {:?}", x),
+    }
+}
+
+#[test]
+fn variant_parse_error() {
+    match "name = 3".parse::<Variant>() {
+        Err(VariantError::Parse(_)) => {}
+        x => panic!("Expected Parse, got {:?}", x),
+    }
+}
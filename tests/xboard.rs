@@ -0,0 +1,195 @@
+use sharmat::betza::*;
+use sharmat::engine::*;
+use sharmat::game::*;
+use sharmat::movement::*;
+use sharmat::variant::*;
+use sharmat::xboard::*;
+use std::time::Duration;
+
+fn load(name: &str) -> Game {
+    Variant::load(format!(
+        "{}/variants/{}.toml",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+    .game()
+    .unwrap()
+}
+
+#[test]
+fn xboard_commands() {
+    assert_eq!("protover 2".parse(), Ok(XboardCommand::Protover(2)));
+    assert_eq!(
+        "variant shogi".parse(),
+        Ok(XboardCommand::Variant("shogi".to_string()))
+    );
+    assert_eq!(
+        "usermove e2e4".parse(),
+        Ok(XboardCommand::UserMove("e2e4".to_string()))
+    );
+    assert_eq!(
+        "level 40 0:30 2.5".parse(),
+        Ok(XboardCommand::Level {
+            moves: 40,
+            base: Duration::from_secs(30),
+            increment: Duration::from_millis(2500),
+        })
+    );
+    assert_eq!(
+        "time -150".parse(),
+        Ok(XboardCommand::Time(Duration::default()))
+    );
+    assert_eq!(
+        "otim 6000".parse(),
+        Ok(XboardCommand::Otim(Duration::from_secs(60)))
+    );
+    assert_eq!("?".parse(), Ok(XboardCommand::MoveNow));
+    assert_eq!(
+        "piece N& fN".parse(),
+        Ok(XboardCommand::Piece {
+            letter: 'N',
+            betza: "fN".to_string(),
+        })
+    );
+    assert_eq!(
+        "setboard 8/8/8/8/8/8/8/K6k w - - 0 1".parse(),
+        Ok(XboardCommand::SetBoard(
+            "8/8/8/8/8/8/8/K6k w - - 0 1".to_string()
+        ))
+    );
+
+    assert_eq!("".parse::<XboardCommand>(), Err(XboardError::Empty));
+    assert_eq!(
+        "sd".parse::<XboardCommand>(),
+        Err(XboardError::MissingArgument("depth"))
+    );
+    assert_eq!(
+        "level 40 x 0".parse::<XboardCommand>(),
+        Err(XboardError::InvalidNumber("x".to_string()))
+    );
+    // numbers too large for a duration
+    assert_eq!(
+        "st 1e30".parse::<XboardCommand>(),
+        Err(XboardError::InvalidNumber("1e30".to_string()))
+    );
+    assert_eq!(
+        "level 40 400000000000000000 0".parse::<XboardCommand>(),
+        Err(XboardError::InvalidNumber("400000000000000000".to_string()))
+    );
+    assert_eq!(
+        "time 4000000000000000000".parse::<XboardCommand>(),
+        Err(XboardError::InvalidNumber(
+            "4000000000000000000".to_string()
+        ))
+    );
+    assert_eq!(
+        "accepted done".parse::<XboardCommand>(),
+        Err(XboardError::UnknownCommand("accepted".to_string()))
+    );
+}
+
+#[test]
+fn xboard_time_control() {
+    let time = TimeControl {
+        moves: 40,
+        base: Duration::from_secs(300),
+        increment: Duration::from_secs(2),
+        ..TimeControl::default()
+    };
+    let limits = time.limits(45);
+    let clock = limits.clock.unwrap();
+    assert_eq!(clock.remaining, Duration::from_secs(300));
+    assert_eq!(clock.increment, Duration::from_secs(2));
+    assert_eq!(clock.moves_to_go, Some(35));
+
+    let time = TimeControl {
+        remaining: Some(Duration::from_secs(10)),
+        depth: Some(4),
+        ..time
+    };
+    let limits = time.limits(0);
+    assert_eq!(limits.clock.unwrap().remaining, Duration::from_secs(10));
+    assert_eq!(limits.depth, Some(4));
+
+    let time = TimeControl {
+        move_time: Some(Duration::from_secs(1)),
+        ..time
+    };
+    let limits = time.limits(0);
+    assert_eq!(limits.clock, None);
+    assert_eq!(limits.movetime, Some(Duration::from_secs(1)));
+    // searches are never left without a limit
+    assert_eq!(
+        TimeControl::default().limits(0),
+        SearchLimits {
+            movetime: Some(UNLIMITED_MOVE_TIME),
+            ..SearchLimits::default()
+        }
+    );
+}
+
+#[test]
+fn xboard_setup() {
+    let shogi = load("shogi");
+    assert_eq!(
+        setup_line(&shogi),
+        "setup (PNBR...........G..LS.Kpnbr...........g..ls.k) 9x9+7_shogi \
+         lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1"
+    );
+    let lines = piece_lines(&shogi);
+    assert!(lines.contains(&"piece L& fR".to_string()));
+    assert!(lines.contains(&"piece G& WfF".to_string()));
+
+    let chess = load("standard");
+    assert!(setup_line(&chess)
+        .ends_with("8x8+0_fairy rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+    assert!(piece_lines(&chess).contains(&"piece N& N".to_string()));
+}
+
+#[test]
+fn xboard_results() {
+    let mut game = load("standard");
+    assert_eq!(result_line(&game), None);
+    for text in &["f2f3", "e7e5", "g2g4", "d8h4"] {
+        let mv = game.parse_uci_move(text).unwrap();
+        game.play(&mv);
+    }
+    assert_eq!(result_line(&game), Some("0-1 {Black mates}".to_string()));
+
+    game.load_fen("k7/8/1Q6/8/8/8/8/7K b - - 0 1").unwrap();
+    assert_eq!(result_line(&game), Some("1/2-1/2 {Stalemate}".to_string()));
+}
+
+#[test]
+fn xboard_custom_piece() {
+    let mut game = load("standard");
+    game.load_fen("4k3/8/8/8/8/8/8/4K1N1 w - - 0 1").unwrap();
+    let knight = game.piece_index("N").unwrap();
+    // a knight that only jumps forward
+    game.set_movement(knight, vec![MovementType::from_betza("fN").unwrap()]);
+    let mut moves: Vec<String> = game
+        .legal_moves()
+        .iter()
+        .filter(|mv| mv.piece == knight)
+        .map(|mv| game.uci_move(mv))
+        .collect();
+    moves.sort();
+    assert_eq!(moves, vec!["g1e2", "g1f3", "g1h3"]);
+    assert_eq!(
+        MovementType::from_betza("fX").err(),
+        Some(BetzaError::UnknownAtom('X'))
+    );
+
+    let mut engine = EngineBuilder::new().build(&game);
+    let limits = SearchLimits {
+        depth: Some(2),
+        ..SearchLimits::default()
+    };
+    let info = engine.search(&game, &limits);
+    let line = thinking_line(&game, &info);
+    let fields: Vec<&str> = line.split(' ').collect();
+    assert_eq!(fields[0], "2");
+    assert_eq!(fields[3], info.nodes.to_string());
+    assert_eq!(fields.len(), 4 + info.pv.len());
+}